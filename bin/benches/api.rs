@@ -22,9 +22,9 @@ full_bench_impl!({
         .unwrap();
     let exec = LocalPool::new();
     let spawner = exec.spawner();
-    let f = server::run(addr, indradb::MemoryDatastore::default(), exec.spawner());
+    let f = server::run(addr, indradb::MemoryDatastore::default(), exec.spawner(), None, None);
     spawner
         .spawn_local_obj(Box::pin(f.map_err(|err| panic!(err)).map(|_| ())).into())
         .unwrap();
-    ClientDatastore::new(TEST_PORT, exec)
+    ClientDatastore::new(addr, exec)
 });
@@ -0,0 +1,101 @@
+//! Token-based authentication for the server.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error as IoError, ErrorKind};
+use std::path::Path;
+
+/// What an authenticated client is allowed to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    /// Only reads are allowed.
+    ReadOnly,
+    /// Both reads and writes are allowed.
+    ReadWrite,
+}
+
+/// Compares two byte strings in time that only depends on their lengths, so
+/// that tokens can't be guessed a byte at a time.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The tokens that clients can authenticate with.
+#[derive(Clone, Debug, Default)]
+pub struct AuthConfig {
+    tokens: Vec<(String, Access)>,
+}
+
+impl AuthConfig {
+    /// Adds a token.
+    ///
+    /// # Arguments
+    /// * `token` - The token, which clients pass to `Service.authenticate`.
+    /// * `access` - What clients authenticated with the token can do.
+    pub fn add_token<S: Into<String>>(&mut self, token: S, access: Access) {
+        self.tokens.push((token.into(), access));
+    }
+
+    /// Gets the access granted by a token, or `None` if the token is
+    /// unknown.
+    pub fn access(&self, token: &str) -> Option<Access> {
+        let mut access = None;
+
+        // Every token is checked, so that the time taken doesn't reveal
+        // which one matched
+        for (candidate, candidate_access) in &self.tokens {
+            if constant_time_eq(candidate.as_bytes(), token.as_bytes()) {
+                access = Some(*candidate_access);
+            }
+        }
+
+        access
+    }
+
+    /// Reads tokens from a file. Each line holds an access level, either
+    /// `read-only` or `read-write`, followed by whitespace and the token.
+    /// Blank lines and lines starting with `#` are ignored.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the file.
+    pub fn read_from<P: AsRef<Path>>(path: P) -> Result<Self, IoError> {
+        let mut config = Self::default();
+
+        for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, char::is_whitespace);
+
+            let access = match parts.next() {
+                Some("read-only") => Access::ReadOnly,
+                Some("read-write") => Access::ReadWrite,
+                _ => {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        format!("invalid access level on line {}", i + 1),
+                    ))
+                }
+            };
+
+            match parts.next().map(|token| token.trim()) {
+                Some(token) if !token.is_empty() => config.add_token(token, access),
+                _ => {
+                    return Err(IoError::new(
+                        ErrorKind::InvalidData,
+                        format!("missing token on line {}", i + 1),
+                    ))
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
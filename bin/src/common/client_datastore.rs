@@ -1,26 +1,34 @@
 use std::cell::RefCell;
-use std::net::ToSocketAddrs;
+use std::net::SocketAddr;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
 
 use crate::autogen;
 use crate::converters;
 
+use async_tls::TlsConnector;
+use capnp::capability::Promise;
 use capnp::Error as CapnpError;
 use capnp_rpc::rpc_twoparty_capnp::Side;
-use capnp_rpc::{twoparty, RpcSystem};
+use capnp_rpc::{twoparty, RpcSystem, Server};
 use futures::executor::{LocalPool, LocalSpawner};
 use futures::prelude::*;
 use futures::task::LocalSpawn;
 use indradb;
+use rustls::ClientConfig;
 use serde_json::value::Value as JsonValue;
 use uuid::Uuid;
 
-async fn build_client(port: u16, spawner: &LocalSpawner) -> Result<autogen::service::Client, CapnpError> {
-    let addr = format!("127.0.0.1:{}", port).to_socket_addrs().unwrap().next().unwrap();
-    let stream = async_std::net::TcpStream::connect(&addr).await?;
-    stream.set_nodelay(true)?;
+/// The number of items to ask the server for in each chunk when streaming
+/// results.
+const ITER_CHUNK_SIZE: u32 = 100;
+
+async fn init_client<S>(stream: S, spawner: &LocalSpawner) -> Result<autogen::service::Client, CapnpError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + 'static,
+{
     let (reader, writer) = stream.split();
 
     let rpc_network = Box::new(twoparty::VatNetwork::new(
@@ -46,17 +54,70 @@ async fn build_client(port: u16, spawner: &LocalSpawner) -> Result<autogen::serv
     }
 }
 
+async fn build_client(
+    addr: SocketAddr,
+    tls: Option<&(TlsConnector, String)>,
+    spawner: &LocalSpawner,
+) -> Result<autogen::service::Client, CapnpError> {
+    let stream = async_std::net::TcpStream::connect(&addr).await?;
+    stream.set_nodelay(true)?;
+
+    match tls {
+        Some((connector, domain)) => {
+            let stream = connector.connect(domain, stream).await?;
+            init_client(stream, spawner).await
+        }
+        None => init_client(stream, spawner).await,
+    }
+}
+
 pub struct ClientDatastore {
     client: autogen::service::Client,
     exec: Rc<RefCell<LocalPool>>,
 }
 
 impl ClientDatastore {
-    pub fn new(port: u16, mut exec: LocalPool) -> Self {
+    /// Creates a client.
+    ///
+    /// # Arguments
+    /// * `addr` - The address the server is listening on.
+    /// * `exec` - The executor to run requests on.
+    pub fn new(addr: SocketAddr, exec: LocalPool) -> Self {
+        Self::connect(addr, None, exec)
+    }
+
+    /// Creates a client that connects to the server over TLS.
+    ///
+    /// # Arguments
+    /// * `addr` - The address the server is listening on.
+    /// * `tls_config` - The TLS configuration, which determines the
+    ///   certificates that are trusted.
+    /// * `domain` - The domain name to verify the server's certificate
+    ///   against.
+    /// * `exec` - The executor to run requests on.
+    pub fn new_with_tls(addr: SocketAddr, tls_config: ClientConfig, domain: &str, exec: LocalPool) -> Self {
+        let connector = TlsConnector::from(Arc::new(tls_config));
+        Self::connect(addr, Some((connector, domain.to_string())), exec)
+    }
+
+    /// Authenticates with the server. Subsequent requests are scoped to
+    /// what the token allows.
+    ///
+    /// # Arguments
+    /// * `token` - The token to authenticate with.
+    pub fn authenticate(&mut self, token: &str) -> Result<(), CapnpError> {
+        let mut req = self.client.authenticate_request();
+        req.get().set_token(token);
+        let res = self.exec.borrow_mut().run_until(req.send().promise)?;
+        self.client = res.get()?.get_service()?;
+        Ok(())
+    }
+
+    fn connect(addr: SocketAddr, tls: Option<(TlsConnector, String)>, mut exec: LocalPool) -> Self {
         let spawner = exec.spawner();
 
         for _ in 0..10 {
-            if let Ok(client) = exec.run_until(build_client(port, &spawner)) {
+            if let Ok(client) = exec.run_until(build_client(addr, tls.as_ref(), &spawner)) {
                 return Self {
                     client,
                     exec: Rc::new(RefCell::new(exec)),
@@ -84,6 +145,39 @@ impl ClientDatastore {
         res.get()?;
         Ok(())
     }
+
+    async fn async_run_algorithm(
+        &self,
+        algorithm: &indradb::algorithms::Algorithm,
+        t: Option<&indradb::Type>,
+        name: &str,
+    ) -> Result<u64, CapnpError> {
+        let mut req = self.client.run_algorithm_request();
+        converters::from_algorithm(algorithm, req.get().init_algorithm());
+        req.get().set_name(name);
+
+        if let Some(t) = t {
+            req.get().set_t(&t.0);
+        }
+
+        let res = req.send().promise.await?;
+        Ok(res.get()?.get_result())
+    }
+
+    /// Runs an algorithm on the server, writing its results to a vertex
+    /// property. See `indradb::algorithms::run`.
+    pub fn run_algorithm(
+        &self,
+        algorithm: &indradb::algorithms::Algorithm,
+        t: Option<&indradb::Type>,
+        name: &str,
+    ) -> Result<u64, indradb::Error> {
+        Ok(self
+            .exec
+            .borrow_mut()
+            .run_until(self.async_run_algorithm(algorithm, t, name))
+            .unwrap())
+    }
 }
 
 impl indradb::Datastore for ClientDatastore {
@@ -103,6 +197,110 @@ impl indradb::Datastore for ClientDatastore {
     }
 }
 
+impl ClientDatastore {
+    async fn async_graph(&self, name: &str) -> Result<Option<autogen::service::Client>, CapnpError> {
+        let mut req = self.client.graph_request();
+        req.get().set_name(name);
+        let res = req.send().promise.await?;
+        let res = res.get()?;
+
+        if res.get_found() {
+            Ok(Some(res.get_service()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn async_create_graph(&self, name: &str) -> Result<bool, CapnpError> {
+        let mut req = self.client.create_graph_request();
+        req.get().set_name(name);
+        let res = req.send().promise.await?;
+        Ok(res.get()?.get_result())
+    }
+
+    async fn async_drop_graph(&self, name: &str) -> Result<bool, CapnpError> {
+        let mut req = self.client.drop_graph_request();
+        req.get().set_name(name);
+        let res = req.send().promise.await?;
+        Ok(res.get()?.get_result())
+    }
+
+    async fn async_list_graphs(&self) -> Result<Vec<String>, CapnpError> {
+        let req = self.client.list_graphs_request();
+        let res = req.send().promise.await?;
+        let list = res.get()?.get_result()?;
+        list.into_iter().map(|name| Ok(name?.to_string())).collect()
+    }
+}
+
+impl indradb::NamedGraphs for ClientDatastore {
+    fn create_graph(&self, name: &str) -> Result<bool, indradb::Error> {
+        indradb::util::validate_graph_name(name)?;
+        Ok(self.exec.borrow_mut().run_until(self.async_create_graph(name)).unwrap())
+    }
+
+    fn drop_graph(&self, name: &str) -> Result<bool, indradb::Error> {
+        indradb::util::validate_graph_name(name)?;
+        Ok(self.exec.borrow_mut().run_until(self.async_drop_graph(name)).unwrap())
+    }
+
+    fn list_graphs(&self) -> Result<Vec<String>, indradb::Error> {
+        Ok(self.exec.borrow_mut().run_until(self.async_list_graphs()).unwrap())
+    }
+
+    fn graph(&self, name: &str) -> Result<Option<Self>, indradb::Error> {
+        indradb::util::validate_graph_name(name)?;
+        let client = self.exec.borrow_mut().run_until(self.async_graph(name)).unwrap();
+
+        Ok(client.map(|client| Self {
+            client,
+            exec: self.exec.clone(),
+        }))
+    }
+}
+
+struct VertexReceiver {
+    vertices: Rc<RefCell<Vec<indradb::Vertex>>>,
+}
+
+impl autogen::vertex_receiver::Server for VertexReceiver {
+    fn receive(
+        &mut self,
+        req: autogen::vertex_receiver::ReceiveParams,
+        _: autogen::vertex_receiver::ReceiveResults,
+    ) -> Promise<(), CapnpError> {
+        let list = pry!(pry!(req.get()).get_vertices());
+        let mut vertices = self.vertices.borrow_mut();
+
+        for reader in list.into_iter() {
+            vertices.push(pry!(converters::to_vertex(&reader)));
+        }
+
+        Promise::ok(())
+    }
+}
+
+struct EdgeReceiver {
+    edges: Rc<RefCell<Vec<indradb::Edge>>>,
+}
+
+impl autogen::edge_receiver::Server for EdgeReceiver {
+    fn receive(
+        &mut self,
+        req: autogen::edge_receiver::ReceiveParams,
+        _: autogen::edge_receiver::ReceiveResults,
+    ) -> Promise<(), CapnpError> {
+        let list = pry!(pry!(req.get()).get_edges());
+        let mut edges = self.edges.borrow_mut();
+
+        for reader in list.into_iter() {
+            edges.push(pry!(converters::to_edge(&reader)));
+        }
+
+        Promise::ok(())
+    }
+}
+
 pub struct ClientTransaction {
     trans: RefCell<autogen::transaction::Client>,
     exec: Rc<RefCell<LocalPool>>,
@@ -149,6 +347,27 @@ impl ClientTransaction {
         list
     }
 
+    async fn async_iter_vertices<Q: Into<indradb::VertexQuery>>(
+        &self,
+        q: Q,
+    ) -> Result<Vec<indradb::Vertex>, CapnpError> {
+        let trans = self.trans.borrow_mut();
+        let vertices = Rc::new(RefCell::new(Vec::new()));
+        let receiver = autogen::vertex_receiver::ToClient::new(VertexReceiver {
+            vertices: vertices.clone(),
+        })
+        .into_client::<Server>();
+
+        let mut req = trans.iter_vertices_request();
+        converters::from_vertex_query(&q.into(), req.get().init_q());
+        req.get().set_receiver(receiver);
+        req.get().set_chunk_size(ITER_CHUNK_SIZE);
+
+        let res = req.send().promise.await?;
+        res.get()?;
+        Ok(vertices.replace(Vec::new()))
+    }
+
     async fn async_delete_vertices<Q: Into<indradb::VertexQuery>>(&self, q: Q) -> Result<(), CapnpError> {
         let trans = self.trans.borrow_mut();
         let mut req = trans.delete_vertices_request();
@@ -158,9 +377,22 @@ impl ClientTransaction {
         Ok(())
     }
 
-    async fn async_get_vertex_count(&self) -> Result<u64, CapnpError> {
+    async fn async_get_vertex_count(&self, t: Option<&indradb::Type>) -> Result<u64, CapnpError> {
         let trans = self.trans.borrow_mut();
-        let req = trans.get_vertex_count_request();
+        let mut req = trans.get_vertex_count_request();
+
+        if let Some(t) = t {
+            req.get().set_t(&t.0);
+        }
+
+        let res = req.send().promise.await?;
+        Ok(res.get()?.get_result())
+    }
+
+    async fn async_get_vertex_count_for_query<Q: Into<indradb::VertexQuery>>(&self, q: Q) -> Result<u64, CapnpError> {
+        let trans = self.trans.borrow_mut();
+        let mut req = trans.get_vertex_count_for_query_request();
+        converters::from_vertex_query(&q.into(), req.get().init_q());
         let res = req.send().promise.await?;
         Ok(res.get()?.get_result())
     }
@@ -185,6 +417,22 @@ impl ClientTransaction {
         list
     }
 
+    async fn async_iter_edges<Q: Into<indradb::EdgeQuery>>(&self, q: Q) -> Result<Vec<indradb::Edge>, CapnpError> {
+        let trans = self.trans.borrow_mut();
+        let edges = Rc::new(RefCell::new(Vec::new()));
+        let receiver =
+            autogen::edge_receiver::ToClient::new(EdgeReceiver { edges: edges.clone() }).into_client::<Server>();
+
+        let mut req = trans.iter_edges_request();
+        converters::from_edge_query(&q.into(), req.get().init_q());
+        req.get().set_receiver(receiver);
+        req.get().set_chunk_size(ITER_CHUNK_SIZE);
+
+        let res = req.send().promise.await?;
+        res.get()?;
+        Ok(edges.replace(Vec::new()))
+    }
+
     async fn async_delete_edges<Q: Into<indradb::EdgeQuery>>(&self, q: Q) -> Result<(), CapnpError> {
         let trans = self.trans.borrow_mut();
         let mut req = trans.delete_edges_request();
@@ -214,6 +462,39 @@ impl ClientTransaction {
         Ok(res.get()?.get_result())
     }
 
+    async fn async_traverse(&self, q: indradb::TraversalQuery) -> Result<Vec<indradb::Vertex>, CapnpError> {
+        let trans = self.trans.borrow_mut();
+        let mut req = trans.traverse_request();
+        converters::from_traversal_query(&q, req.get().init_q());
+        let res = req.send().promise.await?;
+        let list = res.get()?.get_result()?;
+        let list: Result<Vec<indradb::Vertex>, CapnpError> =
+            list.into_iter().map(|reader| converters::to_vertex(&reader)).collect();
+        list
+    }
+
+    async fn async_shortest_path(
+        &self,
+        q: indradb::ShortestPathQuery,
+    ) -> Result<Option<Vec<indradb::EdgeKey>>, CapnpError> {
+        let trans = self.trans.borrow_mut();
+        let mut req = trans.shortest_path_request();
+        converters::from_shortest_path_query(&q, req.get().init_q());
+        let res = req.send().promise.await?;
+        let res = res.get()?;
+
+        if !res.get_found() {
+            return Ok(None);
+        }
+
+        let path: Result<Vec<indradb::EdgeKey>, CapnpError> = res
+            .get_result()?
+            .into_iter()
+            .map(|reader| converters::to_edge_key(&reader))
+            .collect();
+        Ok(Some(path?))
+    }
+
     async fn async_get_vertex_properties(
         &self,
         q: indradb::VertexPropertyQuery,
@@ -307,6 +588,23 @@ impl ClientTransaction {
         list
     }
 
+    async fn async_get_edges_by_indexed_property(
+        &self,
+        name: &str,
+        value: &JsonValue,
+    ) -> Result<Vec<indradb::Edge>, CapnpError> {
+        let trans = self.trans.borrow_mut();
+        let mut req = trans.get_edges_by_indexed_property_request();
+        req.get().set_name(name);
+        req.get().set_value(&value.to_string());
+
+        let res = req.send().promise.await?;
+        let list = res.get()?.get_result()?;
+        let list: Result<Vec<indradb::Edge>, CapnpError> =
+            list.into_iter().map(|reader| converters::to_edge(&reader)).collect();
+        list
+    }
+
     async fn async_set_edge_properties(
         &self,
         q: indradb::EdgePropertyQuery,
@@ -331,6 +629,22 @@ impl ClientTransaction {
         res.get()?;
         Ok(())
     }
+
+    async fn async_commit(&self) -> Result<(), CapnpError> {
+        let trans = self.trans.borrow_mut();
+        let req = trans.commit_request();
+        let res = req.send().promise.await?;
+        res.get()?;
+        Ok(())
+    }
+
+    async fn async_rollback(&self) -> Result<(), CapnpError> {
+        let trans = self.trans.borrow_mut();
+        let req = trans.rollback_request();
+        let res = req.send().promise.await?;
+        res.get()?;
+        Ok(())
+    }
 }
 
 impl indradb::Transaction for ClientTransaction {
@@ -350,13 +664,35 @@ impl indradb::Transaction for ClientTransaction {
         Ok(self.exec.borrow_mut().run_until(self.async_get_vertices(q)).unwrap())
     }
 
+    fn iter_vertices<'a, Q: Into<indradb::VertexQuery>>(
+        &'a self,
+        q: Q,
+    ) -> Result<Box<dyn Iterator<Item = Result<indradb::Vertex, indradb::Error>> + 'a>, indradb::Error> {
+        // Results are streamed from the server in chunks, but buffered here
+        // since the client is driven by a single-threaded executor
+        let vertices = self.exec.borrow_mut().run_until(self.async_iter_vertices(q)).unwrap();
+        Ok(Box::new(vertices.into_iter().map(Ok)))
+    }
+
     fn delete_vertices<Q: Into<indradb::VertexQuery>>(&self, q: Q) -> Result<(), indradb::Error> {
         self.exec.borrow_mut().run_until(self.async_delete_vertices(q)).unwrap();
         Ok(())
     }
 
-    fn get_vertex_count(&self) -> Result<u64, indradb::Error> {
-        Ok(self.exec.borrow_mut().run_until(self.async_get_vertex_count()).unwrap())
+    fn get_vertex_count(&self, t: Option<&indradb::Type>) -> Result<u64, indradb::Error> {
+        Ok(self
+            .exec
+            .borrow_mut()
+            .run_until(self.async_get_vertex_count(t))
+            .unwrap())
+    }
+
+    fn get_vertex_count_for_query<Q: Into<indradb::VertexQuery>>(&self, q: Q) -> Result<u64, indradb::Error> {
+        Ok(self
+            .exec
+            .borrow_mut()
+            .run_until(self.async_get_vertex_count_for_query(q))
+            .unwrap())
     }
 
     fn create_edge(&self, e: &indradb::EdgeKey) -> Result<bool, indradb::Error> {
@@ -367,6 +703,14 @@ impl indradb::Transaction for ClientTransaction {
         Ok(self.exec.borrow_mut().run_until(self.async_get_edges(q)).unwrap())
     }
 
+    fn iter_edges<'a, Q: Into<indradb::EdgeQuery>>(
+        &'a self,
+        q: Q,
+    ) -> Result<Box<dyn Iterator<Item = Result<indradb::Edge, indradb::Error>> + 'a>, indradb::Error> {
+        let edges = self.exec.borrow_mut().run_until(self.async_iter_edges(q)).unwrap();
+        Ok(Box::new(edges.into_iter().map(Ok)))
+    }
+
     fn delete_edges<Q: Into<indradb::EdgeQuery>>(&self, q: Q) -> Result<(), indradb::Error> {
         self.exec.borrow_mut().run_until(self.async_delete_edges(q)).unwrap();
         Ok(())
@@ -385,6 +729,14 @@ impl indradb::Transaction for ClientTransaction {
             .unwrap())
     }
 
+    fn traverse(&self, q: indradb::TraversalQuery) -> Result<Vec<indradb::Vertex>, indradb::Error> {
+        Ok(self.exec.borrow_mut().run_until(self.async_traverse(q)).unwrap())
+    }
+
+    fn shortest_path(&self, q: indradb::ShortestPathQuery) -> Result<Option<Vec<indradb::EdgeKey>>, indradb::Error> {
+        Ok(self.exec.borrow_mut().run_until(self.async_shortest_path(q)).unwrap())
+    }
+
     fn get_vertex_properties(
         &self,
         q: indradb::VertexPropertyQuery,
@@ -442,6 +794,18 @@ impl indradb::Transaction for ClientTransaction {
             .unwrap())
     }
 
+    fn get_edges_by_indexed_property(
+        &self,
+        name: &str,
+        value: &JsonValue,
+    ) -> Result<Vec<indradb::Edge>, indradb::Error> {
+        Ok(self
+            .exec
+            .borrow_mut()
+            .run_until(self.async_get_edges_by_indexed_property(name, value))
+            .unwrap())
+    }
+
     fn set_edge_properties(&self, q: indradb::EdgePropertyQuery, value: &JsonValue) -> Result<(), indradb::Error> {
         self.exec
             .borrow_mut()
@@ -457,4 +821,14 @@ impl indradb::Transaction for ClientTransaction {
             .unwrap();
         Ok(())
     }
+
+    fn commit(&self) -> Result<(), indradb::Error> {
+        self.exec.borrow_mut().run_until(self.async_commit()).unwrap();
+        Ok(())
+    }
+
+    fn rollback(&self) -> Result<(), indradb::Error> {
+        self.exec.borrow_mut().run_until(self.async_rollback()).unwrap();
+        Ok(())
+    }
 }
@@ -130,6 +130,71 @@ pub fn to_edge_property<'a>(reader: &autogen::edge_property::Reader<'a>) -> Resu
     Ok(indradb::EdgeProperty::new(key, value))
 }
 
+pub fn from_edge_cursor<'a>(cursor: &indradb::EdgeCursor, mut builder: autogen::edge_cursor::Builder<'a>) {
+    builder.set_update_datetime(cursor.update_datetime.timestamp_nanos() as u64);
+    from_edge_key(&cursor.key, builder.init_key());
+}
+
+pub fn to_edge_cursor<'a>(reader: &autogen::edge_cursor::Reader<'a>) -> Result<indradb::EdgeCursor, CapnpError> {
+    let key = to_edge_key(&reader.get_key()?)?;
+    let timestamp = reader.get_update_datetime();
+    let update_datetime = Utc.timestamp((timestamp / NANOS_PER_SEC) as i64, (timestamp % NANOS_PER_SEC) as u32);
+    Ok(indradb::EdgeCursor::new(key, update_datetime))
+}
+
+pub fn from_property_filter<'a>(filter: &indradb::PropertyFilter, mut builder: autogen::property_filter::Builder<'a>) {
+    builder.set_name(&filter.name);
+
+    match &filter.predicate {
+        indradb::PropertyPredicate::Exists => builder.set_exists(()),
+        indradb::PropertyPredicate::Equals(value) => builder.set_equals(&value.to_string()),
+        indradb::PropertyPredicate::GreaterThan(value) => builder.set_greater_than(&value.to_string()),
+        indradb::PropertyPredicate::GreaterThanOrEqual(value) => builder.set_greater_than_or_equal(&value.to_string()),
+        indradb::PropertyPredicate::LessThan(value) => builder.set_less_than(&value.to_string()),
+        indradb::PropertyPredicate::LessThanOrEqual(value) => builder.set_less_than_or_equal(&value.to_string()),
+    }
+}
+
+pub fn to_property_filter(reader: autogen::property_filter::Reader) -> Result<indradb::PropertyFilter, CapnpError> {
+    let name = reader.get_name()?;
+
+    let predicate = match reader.which()? {
+        autogen::property_filter::Exists(()) => indradb::PropertyPredicate::Exists,
+        autogen::property_filter::Equals(value) => {
+            indradb::PropertyPredicate::Equals(map_capnp_err(serde_json::from_str(value?))?)
+        }
+        autogen::property_filter::GreaterThan(value) => {
+            indradb::PropertyPredicate::GreaterThan(map_capnp_err(serde_json::from_str(value?))?)
+        }
+        autogen::property_filter::GreaterThanOrEqual(value) => {
+            indradb::PropertyPredicate::GreaterThanOrEqual(map_capnp_err(serde_json::from_str(value?))?)
+        }
+        autogen::property_filter::LessThan(value) => {
+            indradb::PropertyPredicate::LessThan(map_capnp_err(serde_json::from_str(value?))?)
+        }
+        autogen::property_filter::LessThanOrEqual(value) => {
+            indradb::PropertyPredicate::LessThanOrEqual(map_capnp_err(serde_json::from_str(value?))?)
+        }
+    };
+
+    Ok(indradb::PropertyFilter::new(name, predicate))
+}
+
+fn from_property_filters<'a>(
+    filters: &[indradb::PropertyFilter],
+    mut builder: capnp::struct_list::Builder<'a, autogen::property_filter::Owned>,
+) {
+    for (i, filter) in filters.iter().enumerate() {
+        from_property_filter(filter, builder.reborrow().get(i as u32));
+    }
+}
+
+fn to_property_filters<'a>(
+    reader: capnp::struct_list::Reader<'a, autogen::property_filter::Owned>,
+) -> Result<Vec<indradb::PropertyFilter>, CapnpError> {
+    reader.into_iter().map(to_property_filter).collect()
+}
+
 pub fn from_vertex_query<'a>(q: &indradb::VertexQuery, builder: autogen::vertex_query::Builder<'a>) {
     match q {
         indradb::VertexQuery::Range(q) => {
@@ -144,6 +209,7 @@ pub fn from_vertex_query<'a>(q: &indradb::VertexQuery, builder: autogen::vertex_
             }
 
             builder.set_limit(q.limit);
+            from_property_filters(&q.filters, builder.init_filters(q.filters.len() as u32));
         }
         indradb::VertexQuery::Specific(q) => {
             let mut builder = builder.init_specific().init_ids(q.ids.len() as u32);
@@ -161,6 +227,11 @@ pub fn from_vertex_query<'a>(q: &indradb::VertexQuery, builder: autogen::vertex_
                 builder.set_t(&t.0);
             }
 
+            if let Some(ref cursor) = q.cursor {
+                from_edge_cursor(cursor, builder.reborrow().init_cursor());
+            }
+
+            from_property_filters(&q.filters, builder.reborrow().init_filters(q.filters.len() as u32));
             from_edge_query(&q.inner, builder.init_inner());
         }
     }
@@ -181,6 +252,10 @@ pub fn to_vertex_query<'a>(reader: &autogen::vertex_query::Reader<'a>) -> Result
                 range = range.t(map_capnp_err(indradb::Type::new(t_str))?);
             }
 
+            for filter in to_property_filters(params.get_filters()?)? {
+                range = range.filter(filter);
+            }
+
             Ok(range.into())
         }
         autogen::vertex_query::Specific(params) => {
@@ -202,11 +277,93 @@ pub fn to_vertex_query<'a>(reader: &autogen::vertex_query::Reader<'a>) -> Result
                 pipe = pipe.t(map_capnp_err(indradb::Type::new(t_str))?);
             }
 
+            for filter in to_property_filters(params.get_filters()?)? {
+                pipe = pipe.filter(filter);
+            }
+
+            if params.has_cursor() {
+                pipe = pipe.cursor(to_edge_cursor(&params.get_cursor()?)?);
+            }
+
             Ok(pipe.into())
         }
     }
 }
 
+pub fn from_traversal_query<'a>(q: &indradb::TraversalQuery, mut builder: autogen::traversal_query::Builder<'a>) {
+    builder.set_direction(from_edge_direction(q.direction));
+    builder.set_limit(q.limit);
+    builder.set_min_depth(q.min_depth);
+    builder.set_max_depth(q.max_depth);
+
+    if let Some(ref t) = q.t {
+        builder.set_t(&t.0);
+    }
+
+    from_vertex_query(&q.start, builder.init_start());
+}
+
+pub fn to_traversal_query<'a>(
+    reader: &autogen::traversal_query::Reader<'a>,
+) -> Result<indradb::TraversalQuery, CapnpError> {
+    let start = Box::new(to_vertex_query(&reader.get_start()?)?);
+    let direction = to_edge_direction(reader.get_direction()?);
+    let mut q = indradb::TraversalQuery::new(start, direction, reader.get_max_depth(), reader.get_limit())
+        .min_depth(reader.get_min_depth());
+
+    let t = reader.get_t()?;
+    if t != "" {
+        q = q.t(map_capnp_err(indradb::Type::new(t))?);
+    }
+
+    Ok(q)
+}
+
+pub fn from_shortest_path_query<'a>(
+    q: &indradb::ShortestPathQuery,
+    mut builder: autogen::shortest_path_query::Builder<'a>,
+) {
+    builder.set_start_id(q.start_id.as_bytes());
+    builder.set_end_id(q.end_id.as_bytes());
+    builder.set_max_depth(q.max_depth);
+
+    if let Some(ref t) = q.t {
+        builder.set_t(&t.0);
+    }
+
+    match &q.algorithm {
+        indradb::ShortestPathAlgorithm::BreadthFirst => builder.set_breadth_first(()),
+        indradb::ShortestPathAlgorithm::BidirectionalBreadthFirst => builder.set_bidirectional_breadth_first(()),
+        indradb::ShortestPathAlgorithm::Weighted(name) => builder.set_weighted(name),
+    }
+}
+
+pub fn to_shortest_path_query<'a>(
+    reader: &autogen::shortest_path_query::Reader<'a>,
+) -> Result<indradb::ShortestPathQuery, CapnpError> {
+    let start_id = map_capnp_err(Uuid::from_slice(reader.get_start_id()?))?;
+    let end_id = map_capnp_err(Uuid::from_slice(reader.get_end_id()?))?;
+
+    let algorithm = match reader.which()? {
+        autogen::shortest_path_query::BreadthFirst(()) => indradb::ShortestPathAlgorithm::BreadthFirst,
+        autogen::shortest_path_query::BidirectionalBreadthFirst(()) => {
+            indradb::ShortestPathAlgorithm::BidirectionalBreadthFirst
+        }
+        autogen::shortest_path_query::Weighted(name) => indradb::ShortestPathAlgorithm::Weighted(name?.to_string()),
+    };
+
+    let mut q = indradb::ShortestPathQuery::new(start_id, end_id)
+        .max_depth(reader.get_max_depth())
+        .algorithm(algorithm);
+
+    let t = reader.get_t()?;
+    if t != "" {
+        q = q.t(map_capnp_err(indradb::Type::new(t))?);
+    }
+
+    Ok(q)
+}
+
 pub fn from_vertex_property_query<'a>(
     q: &indradb::VertexPropertyQuery,
     mut builder: autogen::vertex_property_query::Builder<'a>,
@@ -248,7 +405,15 @@ pub fn from_edge_query<'a>(q: &indradb::EdgeQuery, builder: autogen::edge_query:
                 builder.set_low(low.timestamp_nanos() as u64);
             }
 
+            if let Some(ref cursor) = pipe.cursor {
+                from_edge_cursor(cursor, builder.reborrow().init_cursor());
+            }
+
             builder.set_limit(pipe.limit);
+            from_property_filters(
+                &pipe.filters,
+                builder.reborrow().init_filters(pipe.filters.len() as u32),
+            );
             from_vertex_query(&pipe.inner, builder.init_inner());
         }
     }
@@ -283,6 +448,14 @@ pub fn to_edge_query<'a>(reader: &autogen::edge_query::Reader<'a>) -> Result<ind
                 pipe = pipe.low(low);
             }
 
+            for filter in to_property_filters(params.get_filters()?)? {
+                pipe = pipe.filter(filter);
+            }
+
+            if params.has_cursor() {
+                pipe = pipe.cursor(to_edge_cursor(&params.get_cursor()?)?);
+            }
+
             Ok(indradb::EdgeQuery::Pipe(pipe))
         }
     }
@@ -320,6 +493,10 @@ pub fn from_bulk_insert_items<'a>(
                 let builder = builder.init_edge();
                 from_edge_key(edge, builder.get_key()?);
             }
+            indradb::BulkInsertItem::EdgeWithDatetime(edge) => {
+                let builder = builder.init_edge_with_datetime();
+                from_edge(edge, builder.get_edge()?)?;
+            }
             indradb::BulkInsertItem::VertexProperty(id, name, value) => {
                 let mut builder = builder.init_vertex_property();
                 builder.set_id(id.as_bytes());
@@ -352,6 +529,10 @@ pub fn to_bulk_insert_items<'a>(
                 let edge_key = to_edge_key(&params.get_key()?)?;
                 Ok(indradb::BulkInsertItem::Edge(edge_key))
             }
+            autogen::bulk_insert_item::EdgeWithDatetime(params) => {
+                let edge = to_edge(&params.get_edge()?)?;
+                Ok(indradb::BulkInsertItem::EdgeWithDatetime(edge))
+            }
             autogen::bulk_insert_item::VertexProperty(params) => {
                 let id = map_capnp_err(Uuid::from_slice(params.get_id()?))?;
                 let name = params.get_name()?.to_string();
@@ -369,6 +550,34 @@ pub fn to_bulk_insert_items<'a>(
     Ok(items?.into_iter())
 }
 
+pub fn from_algorithm<'a>(algorithm: &indradb::algorithms::Algorithm, mut builder: autogen::algorithm::Builder<'a>) {
+    match *algorithm {
+        indradb::algorithms::Algorithm::PageRank {
+            damping_factor,
+            iterations,
+        } => {
+            let mut builder = builder.init_page_rank();
+            builder.set_damping_factor(damping_factor);
+            builder.set_iterations(iterations);
+        }
+        indradb::algorithms::Algorithm::WeaklyConnectedComponents => builder.set_weakly_connected_components(()),
+        indradb::algorithms::Algorithm::TriangleCount => builder.set_triangle_count(()),
+    }
+}
+
+pub fn to_algorithm<'a>(reader: &autogen::algorithm::Reader<'a>) -> Result<indradb::algorithms::Algorithm, CapnpError> {
+    let algorithm = match reader.which()? {
+        autogen::algorithm::PageRank(params) => indradb::algorithms::Algorithm::PageRank {
+            damping_factor: params.get_damping_factor(),
+            iterations: params.get_iterations(),
+        },
+        autogen::algorithm::WeaklyConnectedComponents(()) => indradb::algorithms::Algorithm::WeaklyConnectedComponents,
+        autogen::algorithm::TriangleCount(()) => indradb::algorithms::Algorithm::TriangleCount,
+    };
+
+    Ok(algorithm)
+}
+
 pub fn from_edge_direction(direction: indradb::EdgeDirection) -> autogen::EdgeDirection {
     match direction {
         indradb::EdgeDirection::Outbound => autogen::EdgeDirection::Outbound,
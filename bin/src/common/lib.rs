@@ -10,11 +10,13 @@ extern crate indradb;
 #[cfg(not(test))]
 extern crate indradb;
 
+pub mod auth;
 pub mod autogen;
 #[macro_use]
 pub mod converters;
 pub mod client_datastore;
 pub mod server;
+pub mod tls;
 
 #[cfg(test)]
 mod tests;
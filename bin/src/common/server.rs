@@ -1,40 +1,68 @@
+use crate::auth::{Access, AuthConfig};
 use crate::autogen;
 use crate::converters;
 
+use std::cmp::max;
+use std::mem;
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
 
 use async_std::io::Error as AsyncIoError;
 use async_std::net::TcpListener;
 use async_std::task::spawn_blocking;
+use async_tls::TlsAcceptor;
 use capnp::capability::Promise;
 use capnp::Error as CapnpError;
 use capnp_rpc::rpc_twoparty_capnp::Side;
 use capnp_rpc::twoparty::VatNetwork;
 use capnp_rpc::{RpcSystem, Server};
-use futures::executor::LocalSpawner;
+use futures::channel::mpsc;
+use futures::executor::{block_on, LocalSpawner};
 use futures::prelude::*;
 use futures::task::LocalSpawn;
 use indradb;
-use indradb::{Datastore as IndraDbDatastore, Transaction as IndraDbTransaction, Type};
+use indradb::{Datastore as IndraDbDatastore, NamedGraphs, Transaction as IndraDbTransaction, Type};
+use rustls::ServerConfig;
 use serde_json;
 use uuid::Uuid;
 
-struct Service<D: IndraDbDatastore<Trans = T> + Send + Sync + 'static, T: IndraDbTransaction + Send + Sync + 'static> {
+struct Service<D: NamedGraphs<Trans = T> + Send + Sync + 'static, T: IndraDbTransaction + Send + Sync + 'static> {
     datastore: Arc<D>,
+    auth_config: Option<Arc<AuthConfig>>,
+    /// What this capability allows, or `None` if it has yet to be
+    /// authenticated.
+    access: Option<Access>,
 }
 
-impl<D: IndraDbDatastore<Trans = T> + Send + Sync + 'static, T: IndraDbTransaction + Send + Sync + 'static>
-    Service<D, T>
-{
-    fn new(datastore: D) -> Self {
+impl<D: NamedGraphs<Trans = T> + Send + Sync + 'static, T: IndraDbTransaction + Send + Sync + 'static> Service<D, T> {
+    fn new(datastore: D, auth_config: Option<AuthConfig>) -> Self {
+        // Without any tokens configured, there's nothing to authenticate
+        // against, so everyone gets full access
+        let access = match auth_config {
+            Some(_) => None,
+            None => Some(Access::ReadWrite),
+        };
+
         Self {
             datastore: Arc::new(datastore),
+            auth_config: auth_config.map(Arc::new),
+            access,
+        }
+    }
+
+    fn check_access(&self, required: Access) -> Result<(), CapnpError> {
+        match self.access {
+            None => Err(CapnpError::failed("not authenticated".to_string())),
+            Some(access) if access < required => Err(CapnpError::failed(
+                "permission denied: this capability is read-only".to_string(),
+            )),
+            Some(_) => Ok(()),
         }
     }
 }
 
-impl<D: IndraDbDatastore<Trans = T> + Send + Sync + 'static, T: IndraDbTransaction + Send + Sync + 'static>
+impl<D: NamedGraphs<Trans = T> + Send + Sync + 'static, T: IndraDbTransaction + Send + Sync + 'static>
     autogen::service::Server for Service<D, T>
 {
     fn ping(
@@ -51,6 +79,7 @@ impl<D: IndraDbDatastore<Trans = T> + Send + Sync + 'static, T: IndraDbTransacti
         req: autogen::service::BulkInsertParams,
         mut res: autogen::service::BulkInsertResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_access(Access::ReadWrite));
         let datastore = self.datastore.clone();
         let cnp_items = pry!(pry!(req.get()).get_items());
         let items = pry!(converters::to_bulk_insert_items(&cnp_items));
@@ -62,26 +91,210 @@ impl<D: IndraDbDatastore<Trans = T> + Send + Sync + 'static, T: IndraDbTransacti
         })
     }
 
+    fn run_algorithm(
+        &mut self,
+        req: autogen::service::RunAlgorithmParams,
+        mut res: autogen::service::RunAlgorithmResults,
+    ) -> Promise<(), CapnpError> {
+        pry!(self.check_access(Access::ReadWrite));
+        let datastore = self.datastore.clone();
+        let params = pry!(req.get());
+        let algorithm = pry!(converters::to_algorithm(&pry!(params.get_algorithm())));
+        let name = pry!(params.get_name()).to_string();
+
+        let t = pry!(params.get_t());
+        let t = if t != "" {
+            Some(pry!(converters::map_capnp_err(Type::new(t))))
+        } else {
+            None
+        };
+
+        Promise::from_future(async move {
+            let count = spawn_blocking(move || {
+                converters::map_capnp_err(indradb::algorithms::run(&*datastore, &algorithm, t.as_ref(), &name))
+            })
+            .await?;
+            res.get().set_result(count);
+            Ok(())
+        })
+    }
+
     fn transaction(
         &mut self,
         _: autogen::service::TransactionParams,
         mut res: autogen::service::TransactionResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_access(Access::ReadOnly));
         let trans = pry!(converters::map_capnp_err(self.datastore.transaction()));
-        let trans_server = Transaction::new(trans);
+        let trans_server = Transaction::new(trans, self.access == Some(Access::ReadOnly));
         let trans_client = autogen::transaction::ToClient::new(trans_server).into_client::<Server>();
         res.get().set_transaction(trans_client);
         Promise::ok(())
     }
+
+    fn authenticate(
+        &mut self,
+        req: autogen::service::AuthenticateParams,
+        mut res: autogen::service::AuthenticateResults,
+    ) -> Promise<(), CapnpError> {
+        let token = pry!(pry!(req.get()).get_token());
+
+        let access = match self.auth_config {
+            Some(ref auth_config) => auth_config.access(token),
+            None => Some(Access::ReadWrite),
+        };
+
+        let access = match access {
+            Some(access) => access,
+            None => return Promise::err(CapnpError::failed("invalid token".to_string())),
+        };
+
+        let service = Service {
+            datastore: self.datastore.clone(),
+            auth_config: self.auth_config.clone(),
+            access: Some(access),
+        };
+
+        res.get()
+            .set_service(autogen::service::ToClient::new(service).into_client::<Server>());
+        Promise::ok(())
+    }
+
+    fn graph(
+        &mut self,
+        req: autogen::service::GraphParams,
+        mut res: autogen::service::GraphResults,
+    ) -> Promise<(), CapnpError> {
+        pry!(self.check_access(Access::ReadOnly));
+        let datastore = self.datastore.clone();
+        let auth_config = self.auth_config.clone();
+        let access = self.access;
+        let name = pry!(pry!(req.get()).get_name()).to_string();
+
+        Promise::from_future(async move {
+            let graph = spawn_blocking(move || converters::map_capnp_err(datastore.graph(&name))).await?;
+
+            if let Some(graph) = graph {
+                let service = Service {
+                    datastore: Arc::new(graph),
+                    auth_config,
+                    access,
+                };
+
+                res.get()
+                    .set_service(autogen::service::ToClient::new(service).into_client::<Server>());
+                res.get().set_found(true);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn create_graph(
+        &mut self,
+        req: autogen::service::CreateGraphParams,
+        mut res: autogen::service::CreateGraphResults,
+    ) -> Promise<(), CapnpError> {
+        pry!(self.check_access(Access::ReadWrite));
+        let datastore = self.datastore.clone();
+        let name = pry!(pry!(req.get()).get_name()).to_string();
+
+        Promise::from_future(async move {
+            let created = spawn_blocking(move || converters::map_capnp_err(datastore.create_graph(&name))).await?;
+            res.get().set_result(created);
+            Ok(())
+        })
+    }
+
+    fn drop_graph(
+        &mut self,
+        req: autogen::service::DropGraphParams,
+        mut res: autogen::service::DropGraphResults,
+    ) -> Promise<(), CapnpError> {
+        pry!(self.check_access(Access::ReadWrite));
+        let datastore = self.datastore.clone();
+        let name = pry!(pry!(req.get()).get_name()).to_string();
+
+        Promise::from_future(async move {
+            let dropped = spawn_blocking(move || converters::map_capnp_err(datastore.drop_graph(&name))).await?;
+            res.get().set_result(dropped);
+            Ok(())
+        })
+    }
+
+    fn list_graphs(
+        &mut self,
+        _: autogen::service::ListGraphsParams,
+        mut res: autogen::service::ListGraphsResults,
+    ) -> Promise<(), CapnpError> {
+        pry!(self.check_access(Access::ReadOnly));
+        let datastore = self.datastore.clone();
+
+        Promise::from_future(async move {
+            let names = spawn_blocking(move || converters::map_capnp_err(datastore.list_graphs())).await?;
+            let mut res = res.get().init_result(names.len() as u32);
+
+            for (i, name) in names.iter().enumerate() {
+                res.set(i as u32, name);
+            }
+
+            Ok(())
+        })
+    }
 }
 
 struct Transaction<T: IndraDbTransaction + Send + Sync + 'static> {
     trans: Arc<T>,
+    read_only: bool,
+}
+
+/// Splits the results of an iterator into chunks and sends them to a
+/// channel, stopping early if the receiving end has gone away. This is meant
+/// to run on a blocking thread, so that results are read lazily as the
+/// client consumes them.
+fn send_chunks<I, V>(iter: I, chunk_size: usize, mut sender: mpsc::Sender<Vec<V>>) -> Result<(), CapnpError>
+where
+    I: Iterator<Item = Result<V, indradb::Error>>,
+{
+    let mut chunk = Vec::with_capacity(chunk_size);
+
+    for item in iter {
+        chunk.push(converters::map_capnp_err(item)?);
+
+        if chunk.len() == chunk_size {
+            let full_chunk = mem::replace(&mut chunk, Vec::with_capacity(chunk_size));
+
+            if block_on(sender.send(full_chunk)).is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    if !chunk.is_empty() {
+        // An error here just means the receiving end has gone away, in which
+        // case there's nothing left to do
+        let _ = block_on(sender.send(chunk));
+    }
+
+    Ok(())
 }
 
 impl<T: IndraDbTransaction + Send + Sync + 'static> Transaction<T> {
-    fn new(trans: T) -> Self {
-        Self { trans: Arc::new(trans) }
+    fn new(trans: T, read_only: bool) -> Self {
+        Self {
+            trans: Arc::new(trans),
+            read_only,
+        }
+    }
+
+    fn check_writable(&self) -> Result<(), CapnpError> {
+        if self.read_only {
+            Err(CapnpError::failed(
+                "permission denied: this transaction is read-only".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -91,6 +304,7 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         req: autogen::transaction::CreateVertexParams,
         mut res: autogen::transaction::CreateVertexResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_writable());
         let trans = self.trans.clone();
         let cnp_vertex = pry!(pry!(req.get()).get_vertex());
         let vertex = pry!(converters::to_vertex(&cnp_vertex));
@@ -107,6 +321,7 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         req: autogen::transaction::CreateVertexFromTypeParams,
         mut res: autogen::transaction::CreateVertexFromTypeResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_writable());
         let trans = self.trans.clone();
         let cnp_t = pry!(pry!(req.get()).get_t());
         let t = pry!(converters::map_capnp_err(indradb::Type::new(cnp_t)));
@@ -145,6 +360,7 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         req: autogen::transaction::DeleteVerticesParams,
         mut res: autogen::transaction::DeleteVerticesResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_writable());
         let trans = self.trans.clone();
         let cnp_q = pry!(pry!(req.get()).get_q());
         let q = pry!(converters::to_vertex_query(&cnp_q));
@@ -158,13 +374,33 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
 
     fn get_vertex_count(
         &mut self,
-        _: autogen::transaction::GetVertexCountParams,
+        req: autogen::transaction::GetVertexCountParams,
         mut res: autogen::transaction::GetVertexCountResults,
     ) -> Promise<(), CapnpError> {
         let trans = self.trans.clone();
+        let t = match pry!(pry!(req.get()).get_t()) {
+            "" => None,
+            value => Some(pry!(converters::map_capnp_err(Type::new(value)))),
+        };
 
         Promise::from_future(async move {
-            let count = spawn_blocking(move || converters::map_capnp_err(trans.get_vertex_count())).await?;
+            let count = spawn_blocking(move || converters::map_capnp_err(trans.get_vertex_count(t.as_ref()))).await?;
+            res.get().set_result(count);
+            Ok(())
+        })
+    }
+
+    fn get_vertex_count_for_query(
+        &mut self,
+        req: autogen::transaction::GetVertexCountForQueryParams,
+        mut res: autogen::transaction::GetVertexCountForQueryResults,
+    ) -> Promise<(), CapnpError> {
+        let trans = self.trans.clone();
+        let cnp_q = pry!(pry!(req.get()).get_q());
+        let q = pry!(converters::to_vertex_query(&cnp_q));
+
+        Promise::from_future(async move {
+            let count = spawn_blocking(move || converters::map_capnp_err(trans.get_vertex_count_for_query(q))).await?;
             res.get().set_result(count);
             Ok(())
         })
@@ -175,6 +411,7 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         req: autogen::transaction::CreateEdgeParams,
         mut res: autogen::transaction::CreateEdgeResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_writable());
         let trans = self.trans.clone();
         let cnp_edge_key = pry!(pry!(req.get()).get_key());
         let edge_key = pry!(converters::to_edge_key(&cnp_edge_key));
@@ -213,6 +450,7 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         req: autogen::transaction::DeleteEdgesParams,
         mut res: autogen::transaction::DeleteEdgesResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_writable());
         let trans = self.trans.clone();
         let cnp_q = pry!(pry!(req.get()).get_q());
         let q = pry!(converters::to_edge_query(&cnp_q));
@@ -247,6 +485,54 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         })
     }
 
+    fn traverse(
+        &mut self,
+        req: autogen::transaction::TraverseParams,
+        mut res: autogen::transaction::TraverseResults,
+    ) -> Promise<(), CapnpError> {
+        let trans = self.trans.clone();
+        let cnp_q = pry!(pry!(req.get()).get_q());
+        let q = pry!(converters::to_traversal_query(&cnp_q));
+
+        Promise::from_future(async move {
+            let vertices = spawn_blocking(move || converters::map_capnp_err(trans.traverse(q))).await?;
+
+            let mut res = res.get().init_result(vertices.len() as u32);
+
+            for (i, vertex) in vertices.into_iter().enumerate() {
+                converters::from_vertex(&vertex, res.reborrow().get(i as u32));
+            }
+
+            Ok(())
+        })
+    }
+
+    fn shortest_path(
+        &mut self,
+        req: autogen::transaction::ShortestPathParams,
+        mut res: autogen::transaction::ShortestPathResults,
+    ) -> Promise<(), CapnpError> {
+        let trans = self.trans.clone();
+        let cnp_q = pry!(pry!(req.get()).get_q());
+        let q = pry!(converters::to_shortest_path_query(&cnp_q));
+
+        Promise::from_future(async move {
+            let path = spawn_blocking(move || converters::map_capnp_err(trans.shortest_path(q))).await?;
+            let mut res = res.get();
+            res.set_found(path.is_some());
+
+            if let Some(path) = path {
+                let mut res = res.init_result(path.len() as u32);
+
+                for (i, key) in path.iter().enumerate() {
+                    converters::from_edge_key(key, res.reborrow().get(i as u32));
+                }
+            }
+
+            Ok(())
+        })
+    }
+
     fn get_vertex_properties(
         &mut self,
         req: autogen::transaction::GetVertexPropertiesParams,
@@ -297,6 +583,7 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         req: autogen::transaction::SetVertexPropertiesParams,
         mut res: autogen::transaction::SetVertexPropertiesResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_writable());
         let trans = self.trans.clone();
         let params = pry!(req.get());
         let cnp_q = pry!(params.get_q());
@@ -316,6 +603,7 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         req: autogen::transaction::DeleteVertexPropertiesParams,
         mut res: autogen::transaction::DeleteVertexPropertiesResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_writable());
         let trans = self.trans.clone();
         let params = pry!(req.get());
         let cnp_q = pry!(params.get_q());
@@ -374,11 +662,38 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         })
     }
 
+    fn get_edges_by_indexed_property(
+        &mut self,
+        req: autogen::transaction::GetEdgesByIndexedPropertyParams,
+        mut res: autogen::transaction::GetEdgesByIndexedPropertyResults,
+    ) -> Promise<(), CapnpError> {
+        let trans = self.trans.clone();
+        let params = pry!(req.get());
+        let name = pry!(params.get_name()).to_string();
+        let cnp_value = pry!(params.get_value());
+        let value = pry!(converters::map_capnp_err(serde_json::from_str(cnp_value)));
+
+        Promise::from_future(async move {
+            let edges =
+                spawn_blocking(move || converters::map_capnp_err(trans.get_edges_by_indexed_property(&name, &value)))
+                    .await?;
+
+            let mut res = res.get().init_result(edges.len() as u32);
+
+            for (i, edge) in edges.into_iter().enumerate() {
+                converters::from_edge(&edge, res.reborrow().get(i as u32))?;
+            }
+
+            Ok(())
+        })
+    }
+
     fn set_edge_properties(
         &mut self,
         req: autogen::transaction::SetEdgePropertiesParams,
         mut res: autogen::transaction::SetEdgePropertiesResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_writable());
         let trans = self.trans.clone();
         let params = pry!(req.get());
         let cnp_q = pry!(params.get_q());
@@ -398,6 +713,7 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
         req: autogen::transaction::DeleteEdgePropertiesParams,
         mut res: autogen::transaction::DeleteEdgePropertiesResults,
     ) -> Promise<(), CapnpError> {
+        pry!(self.check_writable());
         let trans = self.trans.clone();
         let params = pry!(req.get());
         let cnp_q = pry!(params.get_q());
@@ -409,16 +725,143 @@ impl<T: IndraDbTransaction + Send + Sync + 'static> autogen::transaction::Server
             Ok(())
         })
     }
+
+    fn commit(
+        &mut self,
+        _: autogen::transaction::CommitParams,
+        mut res: autogen::transaction::CommitResults,
+    ) -> Promise<(), CapnpError> {
+        let trans = self.trans.clone();
+
+        Promise::from_future(async move {
+            spawn_blocking(move || converters::map_capnp_err(trans.commit())).await?;
+            res.get().set_result(());
+            Ok(())
+        })
+    }
+
+    fn rollback(
+        &mut self,
+        _: autogen::transaction::RollbackParams,
+        mut res: autogen::transaction::RollbackResults,
+    ) -> Promise<(), CapnpError> {
+        let trans = self.trans.clone();
+
+        Promise::from_future(async move {
+            spawn_blocking(move || converters::map_capnp_err(trans.rollback())).await?;
+            res.get().set_result(());
+            Ok(())
+        })
+    }
+
+    fn iter_vertices(
+        &mut self,
+        req: autogen::transaction::IterVerticesParams,
+        mut res: autogen::transaction::IterVerticesResults,
+    ) -> Promise<(), CapnpError> {
+        let trans = self.trans.clone();
+        let params = pry!(req.get());
+        let cnp_q = pry!(params.get_q());
+        let q = pry!(converters::to_vertex_query(&cnp_q));
+        let receiver = pry!(params.get_receiver());
+        let chunk_size = max(params.get_chunk_size(), 1) as usize;
+        let (sender, mut chunks) = mpsc::channel(1);
+
+        Promise::from_future(async move {
+            let producer = spawn_blocking(move || {
+                let iter = converters::map_capnp_err(trans.iter_vertices(q))?;
+                send_chunks(iter, chunk_size, sender)
+            });
+
+            while let Some(vertices) = chunks.next().await {
+                let mut req = receiver.receive_request();
+                let mut list = req.get().init_vertices(vertices.len() as u32);
+
+                for (i, vertex) in vertices.iter().enumerate() {
+                    converters::from_vertex(vertex, list.reborrow().get(i as u32));
+                }
+
+                req.send().promise.await?;
+            }
+
+            producer.await?;
+            res.get().set_result(());
+            Ok(())
+        })
+    }
+
+    fn iter_edges(
+        &mut self,
+        req: autogen::transaction::IterEdgesParams,
+        mut res: autogen::transaction::IterEdgesResults,
+    ) -> Promise<(), CapnpError> {
+        let trans = self.trans.clone();
+        let params = pry!(req.get());
+        let cnp_q = pry!(params.get_q());
+        let q = pry!(converters::to_edge_query(&cnp_q));
+        let receiver = pry!(params.get_receiver());
+        let chunk_size = max(params.get_chunk_size(), 1) as usize;
+        let (sender, mut chunks) = mpsc::channel(1);
+
+        Promise::from_future(async move {
+            let producer = spawn_blocking(move || {
+                let iter = converters::map_capnp_err(trans.iter_edges(q))?;
+                send_chunks(iter, chunk_size, sender)
+            });
+
+            while let Some(edges) = chunks.next().await {
+                let mut req = receiver.receive_request();
+                let mut list = req.get().init_edges(edges.len() as u32);
+
+                for (i, edge) in edges.iter().enumerate() {
+                    converters::from_edge(edge, list.reborrow().get(i as u32))?;
+                }
+
+                req.send().promise.await?;
+            }
+
+            producer.await?;
+            res.get().set_result(());
+            Ok(())
+        })
+    }
+}
+
+/// Runs the Cap'n Proto RPC system for a single connection.
+async fn serve_connection<S>(stream: S, service: autogen::service::Client) -> Result<(), CapnpError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let (reader, writer) = stream.split();
+    let rpc_network = VatNetwork::new(reader, writer, Side::Server, Default::default());
+    RpcSystem::new(Box::new(rpc_network), Some(service.client)).await
 }
 
-pub async fn run<D, T>(addr: SocketAddr, datastore: D, spawner: LocalSpawner) -> Result<(), AsyncIoError>
+/// Runs the server.
+///
+/// # Arguments
+/// * `addr` - The address to listen on.
+/// * `datastore` - The datastore to serve.
+/// * `spawner` - Spawns the tasks that handle each connection.
+/// * `tls_config` - If set, connections are required to use TLS with this
+///   configuration.
+/// * `auth_config` - If set, clients are required to authenticate with one
+///   of its tokens before using the datastore.
+pub async fn run<D, T>(
+    addr: SocketAddr,
+    datastore: D,
+    spawner: LocalSpawner,
+    tls_config: Option<ServerConfig>,
+    auth_config: Option<AuthConfig>,
+) -> Result<(), AsyncIoError>
 where
-    D: IndraDbDatastore<Trans = T> + Send + Sync + 'static,
+    D: NamedGraphs<Trans = T> + Send + Sync + 'static,
     T: IndraDbTransaction + Send + Sync + 'static,
 {
     let listener = TcpListener::bind(&addr).await?;
+    let acceptor = tls_config.map(|config| TlsAcceptor::from(Arc::new(config)));
 
-    let service = autogen::service::ToClient::new(Service::new(datastore)).into_client::<Server>();
+    let service = autogen::service::ToClient::new(Service::new(datastore, auth_config)).into_client::<Server>();
 
     let mut incoming = listener.incoming();
 
@@ -428,19 +871,34 @@ where
                 eprintln!("could not set nodelay on client");
             }
 
-            let (reader, writer) = socket.split();
-            let rpc_network = VatNetwork::new(reader, writer, Side::Server, Default::default());
-            let rpc_system = RpcSystem::new(Box::new(rpc_network), Some(service.clone().client));
+            let service = service.clone();
+
+            let f: Pin<Box<dyn Future<Output = ()>>> = match acceptor {
+                // The handshake happens on the connection's own task, so that
+                // a slow client can't hold up accepting other connections
+                Some(ref acceptor) => {
+                    let accept = acceptor.accept(socket);
+
+                    Box::pin(async move {
+                        match accept.await {
+                            Ok(stream) => {
+                                if let Err(err) = serve_connection(stream, service).await {
+                                    eprintln!("error handling request: {:?}", err);
+                                }
+                            }
+                            Err(err) => eprintln!("TLS handshake failed: {}", err),
+                        }
+                    })
+                }
+                None => Box::pin(
+                    serve_connection(socket, service)
+                        .map_err(|err| eprintln!("error handling request: {:?}", err))
+                        .map(|_| ()),
+                ),
+            };
 
             spawner
-                .spawn_local_obj(
-                    Box::pin(
-                        rpc_system
-                            .map_err(|err| eprintln!("error handling request: {:?}", err))
-                            .map(|_| ()),
-                    )
-                    .into(),
-                )
+                .spawn_local_obj(f.into())
                 .expect("Expected to be able to spawn a request handler")
         } else {
             eprintln!("connection setup failed");
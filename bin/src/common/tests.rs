@@ -2,6 +2,7 @@ use std::net::ToSocketAddrs;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 
+use crate::auth::{Access, AuthConfig};
 use crate::client_datastore::ClientDatastore;
 use crate::server;
 
@@ -9,7 +10,7 @@ use futures::executor::LocalPool;
 use futures::prelude::*;
 use futures::task::LocalSpawn;
 use indradb::util::generate_temporary_path;
-use indradb::{Datastore, Transaction};
+use indradb::{Datastore, Transaction, VertexQueryExt};
 
 const START_PORT: u16 = 27616;
 
@@ -17,18 +18,21 @@ lazy_static! {
     static ref CURRENT_PORT: AtomicUsize = AtomicUsize::new(START_PORT as usize);
 }
 
-full_test_impl!({
+fn run_server() -> ClientDatastore {
     let port = (*CURRENT_PORT).fetch_add(1, Ordering::SeqCst);
     let addr = format!("127.0.0.1:{}", port).to_socket_addrs().unwrap().next().unwrap();
 
     let exec = LocalPool::new();
     let spawner = exec.spawner();
-    let f = server::run(addr, indradb::MemoryDatastore::default(), exec.spawner());
+    let f = server::run(addr, indradb::MemoryDatastore::default(), exec.spawner(), None, None);
     spawner
         .spawn_local_obj(Box::pin(f.map_err(|err| panic!(err)).map(|_| ())).into())
         .unwrap();
-    ClientDatastore::new(port as u16, exec)
-});
+    ClientDatastore::new(addr, exec)
+}
+
+full_test_impl!(run_server());
+named_graphs_test_impl!(run_server());
 
 #[test]
 fn should_create_rocksdb_datastore() {
@@ -38,15 +42,159 @@ fn should_create_rocksdb_datastore() {
     let exec = LocalPool::new();
     let spawner = exec.spawner();
     let datastore = indradb::RocksdbDatastore::new(&generate_temporary_path(), None, false).unwrap();
-    let f = server::run(addr, datastore, exec.spawner());
+    let f = server::run(addr, datastore, exec.spawner(), None, None);
     spawner
         .spawn_local_obj(Box::pin(f.map_err(|err| panic!(err)).map(|_| ())).into())
         .unwrap();
 
     // Just make sure we can run a command
-    let datastore = ClientDatastore::new(port as u16, exec);
+    let datastore = ClientDatastore::new(addr, exec);
     let trans = datastore.transaction().unwrap();
-    let count = trans.get_vertex_count().unwrap();
+    let count = trans.get_vertex_count(None).unwrap();
 
     assert_eq!(count, 0);
 }
+
+#[test]
+fn should_run_algorithm() {
+    let port = (*CURRENT_PORT).fetch_add(1, Ordering::SeqCst);
+    let addr = format!("127.0.0.1:{}", port).to_socket_addrs().unwrap().next().unwrap();
+
+    let exec = LocalPool::new();
+    let spawner = exec.spawner();
+    let f = server::run(addr, indradb::MemoryDatastore::default(), exec.spawner(), None, None);
+    spawner
+        .spawn_local_obj(Box::pin(f.map_err(|err| panic!(err)).map(|_| ())).into())
+        .unwrap();
+
+    let datastore = ClientDatastore::new(addr, exec);
+    let trans = datastore.transaction().unwrap();
+    let t = indradb::Type::new("test_vertex_type").unwrap();
+    let id = trans.create_vertex_from_type(t).unwrap();
+
+    let count = datastore
+        .run_algorithm(
+            &indradb::algorithms::Algorithm::WeaklyConnectedComponents,
+            None,
+            "component",
+        )
+        .unwrap();
+    assert_eq!(count, 1);
+
+    let q = indradb::SpecificVertexQuery::single(id).property("component");
+    let properties = trans.get_vertex_properties(q).unwrap();
+    assert_eq!(properties[0].value, serde_json::json!(id.to_string()));
+}
+
+#[test]
+fn should_serve_over_tls() {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::tls;
+
+    let port = (*CURRENT_PORT).fetch_add(1, Ordering::SeqCst);
+    let addr = format!("127.0.0.1:{}", port).to_socket_addrs().unwrap().next().unwrap();
+
+    // Generate a self-signed certificate, and trust only it on the client
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let dir = generate_temporary_path();
+    fs::create_dir_all(&dir).unwrap();
+    let cert_path = Path::new(&dir).join("cert.pem");
+    let key_path = Path::new(&dir).join("key.pem");
+    fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+    fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+
+    let server_config = tls::load_server_config(&cert_path, &key_path).unwrap();
+    let client_config = tls::load_client_config(&cert_path).unwrap();
+
+    let exec = LocalPool::new();
+    let spawner = exec.spawner();
+    let f = server::run(
+        addr,
+        indradb::MemoryDatastore::default(),
+        exec.spawner(),
+        Some(server_config),
+        None,
+    );
+    spawner
+        .spawn_local_obj(Box::pin(f.map_err(|err| panic!(err)).map(|_| ())).into())
+        .unwrap();
+
+    let datastore = ClientDatastore::new_with_tls(addr, client_config, "localhost", exec);
+    let trans = datastore.transaction().unwrap();
+    let t = indradb::Type::new("test_vertex_type").unwrap();
+    trans.create_vertex_from_type(t).unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 1);
+}
+
+fn run_authenticated_server() -> ClientDatastore {
+    let port = (*CURRENT_PORT).fetch_add(1, Ordering::SeqCst);
+    let addr = format!("127.0.0.1:{}", port).to_socket_addrs().unwrap().next().unwrap();
+
+    let mut auth_config = AuthConfig::default();
+    auth_config.add_token("read-write-token", Access::ReadWrite);
+    auth_config.add_token("read-only-token", Access::ReadOnly);
+
+    let exec = LocalPool::new();
+    let spawner = exec.spawner();
+    let f = server::run(
+        addr,
+        indradb::MemoryDatastore::default(),
+        exec.spawner(),
+        None,
+        Some(auth_config),
+    );
+    spawner
+        .spawn_local_obj(Box::pin(f.map_err(|err| panic!(err)).map(|_| ())).into())
+        .unwrap();
+    ClientDatastore::new(addr, exec)
+}
+
+#[test]
+fn should_authenticate() {
+    let mut datastore = run_authenticated_server();
+    assert!(datastore.authenticate("invalid-token").is_err());
+    datastore.authenticate("read-write-token").unwrap();
+
+    let trans = datastore.transaction().unwrap();
+    let t = indradb::Type::new("test_vertex_type").unwrap();
+    trans.create_vertex_from_type(t).unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 1);
+}
+
+#[test]
+#[should_panic(expected = "not authenticated")]
+fn should_not_allow_unauthenticated_transactions() {
+    let datastore = run_authenticated_server();
+    let trans = datastore.transaction().unwrap();
+    trans.get_vertex_count(None).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "read-only")]
+fn should_not_allow_writes_when_read_only() {
+    let mut datastore = run_authenticated_server();
+    datastore.authenticate("read-only-token").unwrap();
+
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 0);
+    let t = indradb::Type::new("test_vertex_type").unwrap();
+    trans.create_vertex_from_type(t).unwrap();
+}
+
+#[test]
+fn should_read_auth_config() {
+    use std::fs;
+
+    let path = generate_temporary_path();
+    fs::write(&path, "# tokens\nread-write  secret-a\n\nread-only secret-b\n").unwrap();
+
+    let auth_config = AuthConfig::read_from(&path).unwrap();
+    assert_eq!(auth_config.access("secret-a"), Some(Access::ReadWrite));
+    assert_eq!(auth_config.access("secret-b"), Some(Access::ReadOnly));
+    assert_eq!(auth_config.access("secret-c"), None);
+
+    fs::write(&path, "admin secret-a\n").unwrap();
+    assert!(AuthConfig::read_from(&path).is_err());
+}
@@ -0,0 +1,74 @@
+//! Loading of TLS configuration for the server and client, from PEM-encoded
+//! certificate and key files.
+
+use std::fs::File;
+use std::io::{BufReader, Error as IoError, ErrorKind};
+use std::path::Path;
+
+use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use rustls::{ClientConfig, NoClientAuth, PrivateKey, ServerConfig};
+
+fn invalid_data(message: String) -> IoError {
+    IoError::new(ErrorKind::InvalidData, message)
+}
+
+fn read_private_key(path: &Path) -> Result<PrivateKey, IoError> {
+    let err = || invalid_data(format!("could not read a private key from `{}`", path.display()));
+
+    // Keys may be PKCS#8 or, as generated by older versions of openssl,
+    // PKCS#1 RSA keys
+    let mut keys = pkcs8_private_keys(&mut BufReader::new(File::open(path)?)).map_err(|_| err())?;
+
+    if keys.is_empty() {
+        keys = rsa_private_keys(&mut BufReader::new(File::open(path)?)).map_err(|_| err())?;
+    }
+
+    keys.into_iter().next().ok_or_else(err)
+}
+
+/// Creates a server TLS configuration.
+///
+/// # Arguments
+/// * `cert_path` - Path to the PEM-encoded certificate chain, starting with
+///   the server's certificate.
+/// * `key_path` - Path to the PEM-encoded private key for the certificate.
+pub fn load_server_config(cert_path: &Path, key_path: &Path) -> Result<ServerConfig, IoError> {
+    let cert_chain = certs(&mut BufReader::new(File::open(cert_path)?))
+        .map_err(|_| invalid_data(format!("could not read certificates from `{}`", cert_path.display())))?;
+
+    if cert_chain.is_empty() {
+        return Err(invalid_data(format!(
+            "no certificates found in `{}`",
+            cert_path.display()
+        )));
+    }
+
+    let key = read_private_key(key_path)?;
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config
+        .set_single_cert(cert_chain, key)
+        .map_err(|err| invalid_data(format!("invalid certificate or key: {}", err)))?;
+    Ok(config)
+}
+
+/// Creates a client TLS configuration that trusts the given certificates.
+///
+/// # Arguments
+/// * `ca_path` - Path to the PEM-encoded certificates to trust. For a server
+///   using a self-signed certificate, this is just that certificate.
+pub fn load_client_config(ca_path: &Path) -> Result<ClientConfig, IoError> {
+    let mut config = ClientConfig::new();
+    let (valid, _) = config
+        .root_store
+        .add_pem_file(&mut BufReader::new(File::open(ca_path)?))
+        .map_err(|_| invalid_data(format!("could not read certificates from `{}`", ca_path.display())))?;
+
+    if valid == 0 {
+        return Err(invalid_data(format!(
+            "no valid certificates found in `{}`",
+            ca_path.display()
+        )));
+    }
+
+    Ok(config)
+}
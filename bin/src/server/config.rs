@@ -0,0 +1,599 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::errors::Error;
+
+use serde::Deserialize;
+
+const DEFAULT_DATABASE_URL: &str = "memory://";
+const DEFAULT_HOST: &str = "127.0.0.1";
+const DEFAULT_PORT: u16 = 27615;
+const DEFAULT_MEMORY_SYNC_INTERVAL: u64 = 60;
+const DEFAULT_ROCKSDB_MAX_OPEN_FILES: i32 = 512;
+
+/// The server's configuration. Settings are read from a TOML file, then
+/// overridden by environment variables, then by command line arguments.
+/// Anything left unset falls back to a default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database_url: Option<String>,
+    pub server: ServerConfig,
+    pub memory: MemoryConfig,
+    pub rocksdb: RocksdbConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    pub auth_tokens_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemoryConfig {
+    /// How often to persist the datastore to disk, in seconds.
+    pub sync_interval: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BloomFilterConfig {
+    pub column_family: indradb::ColumnFamily,
+    pub bits_per_key: i32,
+}
+
+/// Tuning for the rocksdb datastore. See `indradb::RocksdbOptions` for what
+/// each setting does.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RocksdbConfig {
+    pub max_open_files: Option<i32>,
+    pub bulk_load_optimized: Option<bool>,
+    pub write_buffer_size: Option<usize>,
+    pub max_write_buffer_number: Option<i32>,
+    pub target_file_size_base: Option<u64>,
+    pub compaction_style: Option<indradb::CompactionStyle>,
+    pub max_background_compactions: Option<i32>,
+    pub level_zero_file_num_compaction_trigger: Option<i32>,
+    pub level_zero_slowdown_writes_trigger: Option<i32>,
+    pub level_zero_stop_writes_trigger: Option<i32>,
+    pub num_levels: Option<i32>,
+    pub max_bytes_for_level_base: Option<u64>,
+    pub max_bytes_for_level_multiplier: Option<f64>,
+    pub block_cache_size: Option<usize>,
+    pub bloom_filters: Vec<BloomFilterConfig>,
+    pub compression: Option<indradb::Compression>,
+    pub compression_per_level: Option<Vec<indradb::Compression>>,
+    pub prefix_extractors: Option<bool>,
+    pub wal_enabled: Option<bool>,
+    pub sync_writes: Option<bool>,
+    pub use_fsync: Option<bool>,
+    pub bytes_per_sync: Option<u64>,
+}
+
+/// Parses an environment variable, if it's set.
+fn parse_env_var<T: FromStr>(name: &'static str) -> Result<Option<T>, Error> {
+    match env::var(name) {
+        Ok(value) => value.parse().map(Some).map_err(|_| Error::CouldNotParseEnvVar { name }),
+        Err(_) => Ok(None),
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidConfig {
+        message: message.to_string(),
+    }
+}
+
+impl Config {
+    /// Reads the configuration from a TOML file.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the file.
+    pub fn read_from<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|err| Error::CouldNotParseConfig { inner: err })
+    }
+
+    /// Overrides settings with any that are set through environment
+    /// variables.
+    pub fn apply_env(&mut self) -> Result<(), Error> {
+        if let Some(value) = parse_env_var("DATABASE_URL")? {
+            self.database_url = Some(value);
+        }
+        if let Some(value) = parse_env_var("HOST")? {
+            self.server.host = Some(value);
+        }
+        if let Some(value) = parse_env_var("PORT")? {
+            self.server.port = Some(value);
+        }
+        if let Some(value) = parse_env_var("TLS_CERT_PATH")? {
+            self.server.tls_cert_path = Some(value);
+        }
+        if let Some(value) = parse_env_var("TLS_KEY_PATH")? {
+            self.server.tls_key_path = Some(value);
+        }
+        if let Some(value) = parse_env_var("AUTH_TOKENS_PATH")? {
+            self.server.auth_tokens_path = Some(value);
+        }
+        if let Some(value) = parse_env_var("MEMORY_SYNC_INTERVAL")? {
+            self.memory.sync_interval = Some(value);
+        }
+        if let Some(value) = parse_env_var("ROCKSDB_MAX_OPEN_FILES")? {
+            self.rocksdb.max_open_files = Some(value);
+        }
+        if let Some(value) = parse_env_var::<String>("ROCKSDB_BULK_LOAD_OPTIMIZED")? {
+            self.rocksdb.bulk_load_optimized = Some(value == "true");
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the settings are usable, so that mistakes are reported
+    /// up front rather than as failures from deep within rocksdb.
+    pub fn validate(&self) -> Result<(), Error> {
+        let database_url = self.database_url();
+
+        if !database_url.starts_with("memory://") && !database_url.starts_with("rocksdb://") {
+            return Err(Error::CouldNotParseDatabaseURL);
+        }
+
+        if self.server.tls_cert_path.is_some() != self.server.tls_key_path.is_some() {
+            return Err(Error::IncompleteTlsConfig);
+        }
+
+        if self.memory.sync_interval == Some(0) {
+            return Err(invalid("`memory.sync_interval` must be greater than 0"));
+        }
+
+        self.rocksdb.validate()
+    }
+
+    pub fn database_url(&self) -> &str {
+        self.database_url.as_deref().unwrap_or(DEFAULT_DATABASE_URL)
+    }
+
+    pub fn host(&self) -> &str {
+        self.server.host.as_deref().unwrap_or(DEFAULT_HOST)
+    }
+
+    pub fn port(&self) -> u16 {
+        self.server.port.unwrap_or(DEFAULT_PORT)
+    }
+
+    pub fn memory_sync_interval(&self) -> u64 {
+        self.memory.sync_interval.unwrap_or(DEFAULT_MEMORY_SYNC_INTERVAL)
+    }
+}
+
+impl RocksdbConfig {
+    fn validate(&self) -> Result<(), Error> {
+        match self.max_open_files {
+            Some(value) if value < 1 && value != -1 => {
+                return Err(invalid(
+                    "`rocksdb.max_open_files` must be greater than 0, or -1 for no limit",
+                ))
+            }
+            _ => (),
+        }
+
+        let positive = [
+            ("max_write_buffer_number", self.max_write_buffer_number),
+            ("max_background_compactions", self.max_background_compactions),
+            (
+                "level_zero_file_num_compaction_trigger",
+                self.level_zero_file_num_compaction_trigger,
+            ),
+            (
+                "level_zero_slowdown_writes_trigger",
+                self.level_zero_slowdown_writes_trigger,
+            ),
+            ("level_zero_stop_writes_trigger", self.level_zero_stop_writes_trigger),
+            ("num_levels", self.num_levels),
+        ];
+
+        for (name, value) in positive.iter() {
+            match value {
+                Some(value) if *value < 1 => {
+                    return Err(invalid(&format!("`rocksdb.{}` must be greater than 0", name)))
+                }
+                _ => (),
+            }
+        }
+
+        let sizes = [
+            ("write_buffer_size", self.write_buffer_size.map(|v| v as u64)),
+            ("target_file_size_base", self.target_file_size_base),
+            ("max_bytes_for_level_base", self.max_bytes_for_level_base),
+            ("block_cache_size", self.block_cache_size.map(|v| v as u64)),
+            ("bytes_per_sync", self.bytes_per_sync),
+        ];
+
+        for (name, value) in sizes.iter() {
+            if *value == Some(0) {
+                return Err(invalid(&format!("`rocksdb.{}` must be greater than 0", name)));
+            }
+        }
+
+        for bloom_filter in &self.bloom_filters {
+            if bloom_filter.bits_per_key < 1 {
+                return Err(invalid("`rocksdb.bloom_filters.bits_per_key` must be greater than 0"));
+            }
+        }
+
+        if let Some(ref value) = self.compression_per_level {
+            if value.is_empty() {
+                return Err(invalid("`rocksdb.compression_per_level` must not be empty"));
+            }
+        }
+
+        if let Some(value) = self.max_bytes_for_level_multiplier {
+            if value.is_nan() || value < 1.0 {
+                return Err(invalid("`rocksdb.max_bytes_for_level_multiplier` must be at least 1"));
+            }
+        }
+
+        // Writes are slowed down, then stopped, as level zero files pile up,
+        // which only makes sense if the triggers are in that order
+        if let (Some(slowdown), Some(stop)) = (
+            self.level_zero_slowdown_writes_trigger,
+            self.level_zero_stop_writes_trigger,
+        ) {
+            if slowdown > stop {
+                return Err(invalid(
+                    "`rocksdb.level_zero_slowdown_writes_trigger` must not be greater than \
+                     `rocksdb.level_zero_stop_writes_trigger`",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn max_open_files(&self) -> i32 {
+        self.max_open_files.unwrap_or(DEFAULT_ROCKSDB_MAX_OPEN_FILES)
+    }
+
+    /// Converts the settings into options for the datastore, using the
+    /// datastore's defaults for anything that isn't set.
+    pub fn to_options(&self) -> indradb::RocksdbOptions {
+        let mut options = indradb::RocksdbOptions::default()
+            .max_open_files(Some(self.max_open_files()))
+            .bulk_load_optimized(self.bulk_load_optimized.unwrap_or(false));
+
+        if let Some(value) = self.write_buffer_size {
+            options = options.write_buffer_size(value);
+        }
+        if let Some(value) = self.max_write_buffer_number {
+            options = options.max_write_buffer_number(value);
+        }
+        if let Some(value) = self.target_file_size_base {
+            options = options.target_file_size_base(value);
+        }
+        if let Some(value) = self.compaction_style {
+            options = options.compaction_style(value);
+        }
+        if let Some(value) = self.max_background_compactions {
+            options = options.max_background_compactions(value);
+        }
+        if let Some(value) = self.level_zero_file_num_compaction_trigger {
+            options = options.level_zero_file_num_compaction_trigger(value);
+        }
+        if let Some(value) = self.level_zero_slowdown_writes_trigger {
+            options = options.level_zero_slowdown_writes_trigger(value);
+        }
+        if let Some(value) = self.level_zero_stop_writes_trigger {
+            options = options.level_zero_stop_writes_trigger(value);
+        }
+        if let Some(value) = self.num_levels {
+            options = options.num_levels(value);
+        }
+        if let Some(value) = self.max_bytes_for_level_base {
+            options = options.max_bytes_for_level_base(value);
+        }
+        if let Some(value) = self.max_bytes_for_level_multiplier {
+            options = options.max_bytes_for_level_multiplier(value);
+        }
+        if self.block_cache_size.is_some() {
+            options = options.block_cache_size(self.block_cache_size);
+        }
+        for bloom_filter in &self.bloom_filters {
+            options = options.bloom_filter(bloom_filter.column_family, bloom_filter.bits_per_key);
+        }
+        if let Some(value) = self.compression {
+            options = options.compression(value);
+        }
+        if let Some(ref value) = self.compression_per_level {
+            options = options.compression_per_level(value.clone());
+        }
+        if let Some(value) = self.prefix_extractors {
+            options = options.prefix_extractors(value);
+        }
+        if let Some(value) = self.wal_enabled {
+            options = options.wal_enabled(value);
+        }
+        if let Some(value) = self.sync_writes {
+            options = options.sync_writes(value);
+        }
+        if let Some(value) = self.use_fsync {
+            options = options.use_fsync(value);
+        }
+        if self.bytes_per_sync.is_some() {
+            options = options.bytes_per_sync(self.bytes_per_sync);
+        }
+
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::Config;
+    use crate::errors::Error;
+
+    use indradb::util::generate_temporary_path;
+
+    fn write_config(contents: &str) -> PathBuf {
+        let dir = generate_temporary_path();
+        fs::create_dir_all(&dir).unwrap();
+        let path = Path::new(&dir).join("config.toml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn parse(contents: &str) -> Config {
+        Config::read_from(write_config(contents)).unwrap()
+    }
+
+    fn assert_invalid(contents: &str, expected_message: &str) {
+        match parse(contents).validate() {
+            Err(Error::InvalidConfig { message }) => assert_eq!(message, expected_message),
+            other => panic!("expected an invalid config error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_read_from_file() {
+        let config = parse(
+            r#"
+            database_url = "rocksdb:///tmp/indradb"
+
+            [server]
+            host = "0.0.0.0"
+            port = 8000
+            tls_cert_path = "cert.pem"
+            tls_key_path = "key.pem"
+
+            [memory]
+            sync_interval = 10
+
+            [rocksdb]
+            max_open_files = -1
+            compaction_style = "universal"
+            compression_per_level = ["none", "lz4"]
+
+            [[rocksdb.bloom_filters]]
+            column_family = "edge_ranges"
+            bits_per_key = 10
+            "#,
+        );
+
+        config.validate().unwrap();
+        assert_eq!(config.database_url(), "rocksdb:///tmp/indradb");
+        assert_eq!(config.host(), "0.0.0.0");
+        assert_eq!(config.port(), 8000);
+        assert_eq!(config.server.tls_cert_path, Some(PathBuf::from("cert.pem")));
+        assert_eq!(config.server.tls_key_path, Some(PathBuf::from("key.pem")));
+        assert_eq!(config.memory_sync_interval(), 10);
+        assert_eq!(config.rocksdb.max_open_files(), -1);
+        assert_eq!(
+            config.rocksdb.compaction_style,
+            Some(indradb::CompactionStyle::Universal)
+        );
+        assert_eq!(
+            config.rocksdb.compression_per_level,
+            Some(vec![indradb::Compression::None, indradb::Compression::Lz4])
+        );
+        assert_eq!(config.rocksdb.bloom_filters.len(), 1);
+        assert_eq!(
+            config.rocksdb.bloom_filters[0].column_family,
+            indradb::ColumnFamily::EdgeRanges
+        );
+        assert_eq!(config.rocksdb.bloom_filters[0].bits_per_key, 10);
+    }
+
+    #[test]
+    fn should_default_missing_settings() {
+        let config = parse("");
+        config.validate().unwrap();
+        assert_eq!(config.database_url(), "memory://");
+        assert_eq!(config.host(), "127.0.0.1");
+        assert_eq!(config.port(), 27615);
+        assert_eq!(config.memory_sync_interval(), 60);
+        assert_eq!(config.rocksdb.max_open_files(), 512);
+    }
+
+    #[test]
+    fn should_not_read_invalid_files() {
+        for contents in &[
+            "unknown = 1",
+            "[server]\nunknown = 1",
+            "[memory]\nunknown = 1",
+            "[rocksdb]\nunknown = 1",
+            "[[rocksdb.bloom_filters]]\ncolumn_family = \"vertices\"\nbits_per_key = 10\nunknown = 1",
+            "[[rocksdb.bloom_filters]]\ncolumn_family = \"unknown\"\nbits_per_key = 10",
+            "[server]\nport = \"not a port\"",
+            "not toml",
+        ] {
+            match Config::read_from(write_config(contents)) {
+                Err(Error::CouldNotParseConfig { .. }) => (),
+                other => panic!("expected a parse error for {:?}, got {:?}", contents, other),
+            }
+        }
+
+        match Config::read_from(Path::new(&generate_temporary_path()).join("missing.toml")) {
+            Err(Error::Io { .. }) => (),
+            other => panic!("expected an i/o error, got {:?}", other),
+        }
+    }
+
+    // Environment variables are shared by the whole process, so everything
+    // that reads them is tested here, rather than in tests that may run in
+    // parallel
+    #[test]
+    fn should_override_settings_in_order() {
+        let path = write_config(
+            r#"
+            database_url = "rocksdb:///from/file"
+
+            [server]
+            host = "10.0.0.1"
+            port = 1000
+
+            [memory]
+            sync_interval = 10
+
+            [rocksdb]
+            max_open_files = 100
+            bulk_load_optimized = false
+            "#,
+        );
+
+        // Environment variables override the file
+        env::set_var("HOST", "10.0.0.2");
+        env::set_var("PORT", "2000");
+        env::set_var("MEMORY_SYNC_INTERVAL", "20");
+        env::set_var("ROCKSDB_MAX_OPEN_FILES", "200");
+        env::set_var("ROCKSDB_BULK_LOAD_OPTIMIZED", "true");
+        let mut config = Config::read_from(&path).unwrap();
+        config.apply_env().unwrap();
+        assert_eq!(config.database_url(), "rocksdb:///from/file");
+        assert_eq!(config.host(), "10.0.0.2");
+        assert_eq!(config.port(), 2000);
+        assert_eq!(config.memory_sync_interval(), 20);
+        assert_eq!(config.rocksdb.max_open_files(), 200);
+        assert_eq!(config.rocksdb.bulk_load_optimized, Some(true));
+
+        // Command line arguments override both
+        let matches = crate::app().get_matches_from(vec![
+            "indradb",
+            "--config",
+            path.to_str().unwrap(),
+            "--database-url",
+            "memory://",
+            "server",
+            "--port",
+            "3000",
+        ]);
+        let config = crate::load_config(&matches).unwrap();
+        assert_eq!(config.database_url(), "memory://");
+        assert_eq!(config.host(), "10.0.0.2");
+        assert_eq!(config.port(), 3000);
+
+        // Unparseable environment variables are errors
+        env::set_var("PORT", "not a port");
+        match Config::default().apply_env() {
+            Err(Error::CouldNotParseEnvVar { name }) => assert_eq!(name, "PORT"),
+            other => panic!("expected an environment variable error, got {:?}", other),
+        }
+
+        for name in &[
+            "HOST",
+            "PORT",
+            "MEMORY_SYNC_INTERVAL",
+            "ROCKSDB_MAX_OPEN_FILES",
+            "ROCKSDB_BULK_LOAD_OPTIMIZED",
+        ] {
+            env::remove_var(name);
+        }
+
+        // As are unparseable command line arguments
+        let matches = crate::app().get_matches_from(vec!["indradb", "server", "--port", "not a port"]);
+        match crate::load_config(&matches) {
+            Err(Error::CouldNotParseArgument { name }) => assert_eq!(name, "--port"),
+            other => panic!("expected an argument error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_validate() {
+        match parse("database_url = \"postgres://\"").validate() {
+            Err(Error::CouldNotParseDatabaseURL) => (),
+            other => panic!("expected a database URL error, got {:?}", other),
+        }
+
+        match parse("[server]\ntls_cert_path = \"cert.pem\"").validate() {
+            Err(Error::IncompleteTlsConfig) => (),
+            other => panic!("expected a TLS config error, got {:?}", other),
+        }
+
+        assert_invalid(
+            "[memory]\nsync_interval = 0",
+            "`memory.sync_interval` must be greater than 0",
+        );
+        assert_invalid(
+            "[rocksdb]\nmax_open_files = 0",
+            "`rocksdb.max_open_files` must be greater than 0, or -1 for no limit",
+        );
+
+        for name in &[
+            "max_write_buffer_number",
+            "max_background_compactions",
+            "level_zero_file_num_compaction_trigger",
+            "level_zero_slowdown_writes_trigger",
+            "level_zero_stop_writes_trigger",
+            "num_levels",
+        ] {
+            assert_invalid(
+                &format!("[rocksdb]\n{} = 0", name),
+                &format!("`rocksdb.{}` must be greater than 0", name),
+            );
+        }
+
+        for name in &[
+            "write_buffer_size",
+            "target_file_size_base",
+            "max_bytes_for_level_base",
+            "block_cache_size",
+            "bytes_per_sync",
+        ] {
+            assert_invalid(
+                &format!("[rocksdb]\n{} = 0", name),
+                &format!("`rocksdb.{}` must be greater than 0", name),
+            );
+        }
+
+        assert_invalid(
+            "[[rocksdb.bloom_filters]]\ncolumn_family = \"vertices\"\nbits_per_key = 0",
+            "`rocksdb.bloom_filters.bits_per_key` must be greater than 0",
+        );
+        assert_invalid(
+            "[rocksdb]\ncompression_per_level = []",
+            "`rocksdb.compression_per_level` must not be empty",
+        );
+
+        for value in &["0.5", "nan"] {
+            assert_invalid(
+                &format!("[rocksdb]\nmax_bytes_for_level_multiplier = {}", value),
+                "`rocksdb.max_bytes_for_level_multiplier` must be at least 1",
+            );
+        }
+
+        assert_invalid(
+            "[rocksdb]\nlevel_zero_slowdown_writes_trigger = 20\nlevel_zero_stop_writes_trigger = 10",
+            "`rocksdb.level_zero_slowdown_writes_trigger` must not be greater than \
+             `rocksdb.level_zero_stop_writes_trigger`",
+        );
+    }
+}
@@ -4,10 +4,26 @@ use std::io;
 pub enum Error {
     #[fail(display = "i/o error: {}", inner)]
     Io { inner: io::Error },
+    #[fail(display = "datastore error: {}", inner)]
+    Datastore { inner: indradb::Error },
     #[fail(display = "could not parse address binding")]
     CouldNotParseBinding,
+    #[fail(display = "both a TLS certificate path and key path must be set to enable TLS")]
+    IncompleteTlsConfig,
     #[fail(display = "could not parse database URL")]
     CouldNotParseDatabaseURL,
+    #[fail(display = "could not parse argument `{}`", name)]
+    CouldNotParseArgument { name: &'static str },
+    #[fail(display = "could not parse environment variable `{}`", name)]
+    CouldNotParseEnvVar { name: &'static str },
+    #[fail(display = "could not parse config file: {}", inner)]
+    CouldNotParseConfig { inner: toml::de::Error },
+    #[fail(display = "invalid config: {}", message)]
+    InvalidConfig { message: String },
+    #[fail(display = "command is not supported by this datastore")]
+    UnsupportedCommand,
+    #[fail(display = "could not set the signal handler: {}", inner)]
+    SignalHandler { inner: ctrlc::Error },
 }
 
 impl From<io::Error> for Error {
@@ -15,3 +31,15 @@ impl From<io::Error> for Error {
         Error::Io { inner: err }
     }
 }
+
+impl From<indradb::Error> for Error {
+    fn from(err: indradb::Error) -> Self {
+        Error::Datastore { inner: err }
+    }
+}
+
+impl From<ctrlc::Error> for Error {
+    fn from(err: ctrlc::Error) -> Self {
+        Error::SignalHandler { inner: err }
+    }
+}
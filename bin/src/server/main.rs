@@ -1,53 +1,293 @@
 #[macro_use]
 extern crate failure;
 
+mod config;
 mod errors;
 
-use std::env;
+use std::fs::File;
+use std::io::{stdin, stdout, BufReader, BufWriter};
 use std::net::ToSocketAddrs;
+use std::path::Path;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use futures::executor::LocalPool;
+use indradb::{NamedGraphs, Transaction};
 
-const DEFAULT_PORT: u16 = 27615;
+/// What the binary should do, as specified by its command line arguments.
+enum Command {
+    /// Runs the server. This is the default.
+    Serve,
+    /// Dumps the datastore to a file, or stdout if no path is given.
+    Dump(Option<String>),
+    /// Restores a dump into the datastore from a file, or stdin if no path is
+    /// given.
+    Restore(Option<String>),
+    /// Checks a rocksdb datastore for dangling references, repairing them if
+    /// `--repair` is given.
+    Check(bool),
+    /// Runs rocksdb's repair on a rocksdb datastore, recovering what it can
+    /// from corrupted files.
+    Repair,
+    /// Compacts a rocksdb datastore.
+    Compact,
+}
 
-fn main() -> Result<(), errors::Error> {
-    let mut exec = LocalPool::new();
+impl Command {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        match matches.subcommand() {
+            ("dump", Some(matches)) => Command::Dump(matches.value_of("path").map(|s| s.to_string())),
+            ("restore", Some(matches)) => Command::Restore(matches.value_of("path").map(|s| s.to_string())),
+            ("check", Some(matches)) => Command::Check(matches.is_present("repair")),
+            ("repair", _) => Command::Repair,
+            ("compact", _) => Command::Compact,
+            _ => Command::Serve,
+        }
+    }
+}
+
+fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new("indradb")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("A graph database server")
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("PATH")
+                .help("Path to a TOML config file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("database-url")
+                .long("database-url")
+                .value_name("URL")
+                .help("The datastore to use, e.g. `memory://` or `rocksdb:///path/to/db`")
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("server")
+                .about("Runs the server (the default)")
+                .arg(
+                    Arg::with_name("host")
+                        .long("host")
+                        .value_name("HOST")
+                        .help("The address to listen on")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("port")
+                        .long("port")
+                        .value_name("PORT")
+                        .help("The port to listen on")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dump")
+                .about("Dumps the datastore to a file, or stdout")
+                .arg(Arg::with_name("path").value_name("PATH")),
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+                .about("Restores a dump into the datastore from a file, or stdin")
+                .arg(Arg::with_name("path").value_name("PATH")),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks a rocksdb datastore for dangling references")
+                .arg(
+                    Arg::with_name("repair")
+                        .long("repair")
+                        .help("Also repairs any issues that are found"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("repair").about("Recovers what it can from a corrupted rocksdb datastore"))
+        .subcommand(SubCommand::with_name("compact").about("Compacts a rocksdb datastore"))
+}
 
-    let port = match env::var("PORT") {
-        Ok(value) => value
-            .parse::<u16>()
-            .expect("Could not parse environment variable `PORT`"),
-        Err(_) => DEFAULT_PORT,
+/// Builds the configuration from the config file, environment variables and
+/// command line arguments, in increasing order of precedence.
+fn load_config(matches: &ArgMatches) -> Result<config::Config, errors::Error> {
+    let mut config = match matches.value_of("config") {
+        Some(path) => config::Config::read_from(path)?,
+        None => config::Config::default(),
     };
 
-    let addr = format!("127.0.0.1:{}", port)
-        .to_socket_addrs()?
-        .next()
-        .ok_or_else(|| -> errors::Error { errors::Error::CouldNotParseBinding })?;
+    config.apply_env()?;
+
+    if let Some(value) = matches.value_of("database-url") {
+        config.database_url = Some(value.to_string());
+    }
+
+    if let ("server", Some(matches)) = matches.subcommand() {
+        if let Some(value) = matches.value_of("host") {
+            config.server.host = Some(value.to_string());
+        }
+
+        if let Some(value) = matches.value_of("port") {
+            let port = value
+                .parse::<u16>()
+                .map_err(|_| errors::Error::CouldNotParseArgument { name: "--port" })?;
+            config.server.port = Some(port);
+        }
+    }
+
+    config.validate()?;
+    Ok(config)
+}
+
+fn run<D, T>(datastore: D, command: Command, config: &config::Config) -> Result<(), errors::Error>
+where
+    D: NamedGraphs<Trans = T> + Send + Sync + 'static,
+    T: Transaction + Send + Sync + 'static,
+{
+    match command {
+        Command::Serve => {
+            let mut exec = LocalPool::new();
+
+            let addr = (config.host(), config.port())
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| -> errors::Error { errors::Error::CouldNotParseBinding })?;
+
+            let tls_config = match (&config.server.tls_cert_path, &config.server.tls_key_path) {
+                (Some(cert_path), Some(key_path)) => Some(common::tls::load_server_config(cert_path, key_path)?),
+                (None, None) => None,
+                _ => return Err(errors::Error::IncompleteTlsConfig),
+            };
+
+            let auth_config = match &config.server.auth_tokens_path {
+                Some(path) => Some(common::auth::AuthConfig::read_from(path)?),
+                None => None,
+            };
+
+            exec.run_until(common::server::run(
+                addr,
+                datastore,
+                exec.spawner(),
+                tls_config,
+                auth_config,
+            ))?;
+        }
+        Command::Dump(path) => {
+            let trans = datastore.transaction()?;
+
+            match path {
+                Some(path) => indradb::dump::dump(&trans, BufWriter::new(File::create(path)?))?,
+                None => indradb::dump::dump(&trans, BufWriter::new(stdout()))?,
+            }
+        }
+        Command::Restore(path) => match path {
+            Some(path) => indradb::dump::restore(&datastore, BufReader::new(File::open(path)?))?,
+            None => {
+                let stdin = stdin();
+                indradb::dump::restore(&datastore, stdin.lock())?
+            }
+        },
+        Command::Check(_) | Command::Repair | Command::Compact => return Err(errors::Error::UnsupportedCommand),
+    }
+
+    Ok(())
+}
+
+fn check(datastore: &indradb::RocksdbDatastore, repair: bool) -> Result<(), errors::Error> {
+    let issues = datastore.check_integrity(repair)?;
+
+    for issue in &issues {
+        println!("{}", issue);
+    }
 
-    let connection_string = env::var("DATABASE_URL").unwrap_or_else(|_| "memory://".to_string());
+    if repair {
+        eprintln!("repaired {} issue(s)", issues.len());
+    } else {
+        eprintln!("found {} issue(s)", issues.len());
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<(), errors::Error> {
+    let matches = app().get_matches();
+    let command = Command::from_matches(&matches);
+    let config = load_config(&matches)?;
+    let connection_string = config.database_url();
 
     if connection_string.starts_with("rocksdb://") {
         let path = &connection_string[10..connection_string.len()];
 
-        let max_open_files_str = env::var("ROCKSDB_MAX_OPEN_FILES").unwrap_or_else(|_| "512".to_string());
-        let max_open_files = max_open_files_str.parse::<i32>().expect(
-            "Could not parse environment variable `ROCKSDB_MAX_OPEN_FILES`: must be an \
-             i32",
-        );
+        // Repairs have to happen before the database is opened
+        if let Command::Repair = command {
+            indradb::RocksdbDatastore::repair(path, Some(config.rocksdb.max_open_files()))?;
+            return Ok(());
+        }
 
-        let bulk_load_optimized = env::var("ROCKSDB_BULK_LOAD_OPTIMIZED").unwrap_or_else(|_| "".to_string()) == "true";
+        let datastore = indradb::RocksdbDatastore::new_with_options(path, config.rocksdb.to_options())?;
 
-        let datastore = indradb::RocksdbDatastore::new(path, Some(max_open_files), bulk_load_optimized)
-            .expect("Expected to be able to create the RocksDB datastore");
+        match command {
+            Command::Check(repair) => check(&datastore, repair),
+            Command::Compact => {
+                datastore.compact();
+                Ok(())
+            }
+            command => run(datastore, command, &config),
+        }
+    } else {
+        let path = &connection_string[9..connection_string.len()];
 
-        exec.run_until(common::server::run(addr, datastore, exec.spawner()))?;
-        Ok(())
-    } else if connection_string == "memory://" {
-        let datastore = indradb::MemoryDatastore::default();
-        exec.run_until(common::server::run(addr, datastore, exec.spawner()))?;
+        let datastore = if path.is_empty() {
+            indradb::MemoryDatastore::default()
+        } else if Path::new(path).exists() {
+            indradb::MemoryDatastore::read_from(path)?
+        } else {
+            indradb::MemoryDatastore::create_with_path(path)?
+        };
+
+        if !path.is_empty() {
+            if let Command::Serve = command {
+                // Syncs write to the same temporary file, so they mustn't
+                // overlap
+                let sync_lock = Arc::new(Mutex::new(()));
+                let sync_interval = config.memory_sync_interval();
+                let sync_datastore = datastore.clone();
+                let periodic_sync_lock = sync_lock.clone();
+
+                thread::spawn(move || loop {
+                    thread::sleep(Duration::from_secs(sync_interval));
+                    let _guard = periodic_sync_lock.lock().unwrap();
+
+                    if let Err(err) = sync_datastore.sync() {
+                        eprintln!("could not sync the in-memory datastore: {}", err);
+                    }
+                });
+
+                // The server only stops when it's interrupted or terminated,
+                // so sync one last time then, to persist anything written
+                // since the last periodic sync
+                let shutdown_datastore = datastore.clone();
+
+                ctrlc::set_handler(move || {
+                    let _guard = sync_lock.lock().unwrap();
+
+                    match shutdown_datastore.sync() {
+                        Ok(()) => process::exit(0),
+                        Err(err) => {
+                            eprintln!("could not sync the in-memory datastore: {}", err);
+                            process::exit(1);
+                        }
+                    }
+                })?;
+            }
+        }
+
+        run(datastore.clone(), command, &config)?;
+
+        // Persist anything that was restored
+        datastore.sync()?;
         Ok(())
-    } else {
-        Err(errors::Error::CouldNotParseDatabaseURL)
     }
 }
@@ -13,7 +13,7 @@ pub enum Op {
     CreateVertex(Vertex),
     GetVertices(VertexQuery),
     DeleteVertices(VertexQuery),
-    GetVertexCount,
+    GetVertexCount(Option<Type>),
     CreateEdge(EdgeKey),
     GetEdges(EdgeQuery),
     DeleteEdges(EdgeQuery),
@@ -564,9 +564,10 @@ fuzz_target!(|ops: Vec<Op>| {
                 let v2 = t2.delete_vertices(q);
                 cmp!(v1, v2);
             },
-            Op::GetVertexCount => {
-                let v1 = t1.get_vertex_count();
-                let v2 = t2.get_vertex_count();
+            Op::GetVertexCount(t) => {
+                let t: Option<indradb::Type> = t.map(|t| t.into());
+                let v1 = t1.get_vertex_count(t.as_ref());
+                let v2 = t2.get_vertex_count(t.as_ref());
                 cmp!(v1, v2);
             },
             Op::CreateEdge(key) => {
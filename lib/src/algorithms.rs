@@ -0,0 +1,323 @@
+//! Offline analytics over a whole graph.
+//!
+//! The algorithms here work against any datastore. The graph is first read
+//! into memory with range scans over the vertices and their outbound edges,
+//! so memory usage grows with the size of the graph. Results can either be
+//! returned directly, or written back as vertex properties with `run`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::errors::Result;
+use crate::util::for_each_vertex_page;
+use crate::{Datastore, EdgeDirection, SpecificVertexQuery, Transaction, Type, VertexQueryExt};
+
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+/// The number of vertices to read at a time when loading the graph.
+const LOAD_PAGE_SIZE: u32 = 1000;
+
+/// An algorithm whose results can be written back as vertex properties.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Algorithm {
+    /// Ranks vertices by PageRank. Each vertex gets its rank, as a number.
+    /// Ranks across the graph sum to 1.
+    PageRank {
+        /// The probability of following an edge rather than jumping to a
+        /// random vertex, typically 0.85.
+        damping_factor: f64,
+        /// The number of iterations to run.
+        iterations: u32,
+    },
+
+    /// Groups vertices into weakly connected components. Each vertex gets
+    /// the ID of its component, which is the lowest vertex ID in it.
+    WeaklyConnectedComponents,
+
+    /// Counts the triangles that each vertex is a part of, ignoring edge
+    /// directions.
+    TriangleCount,
+}
+
+/// A snapshot of the graph's structure, with vertices numbered by their
+/// position in `ids`.
+struct AdjacencyList {
+    ids: Vec<Uuid>,
+    outbound: Vec<Vec<usize>>,
+}
+
+impl AdjacencyList {
+    /// Reads the graph visible to a transaction.
+    ///
+    /// # Arguments
+    /// * `trans` - The transaction to read from.
+    /// * `t` - Only considers edges of this type.
+    fn load<T: Transaction>(trans: &T, t: Option<&Type>) -> Result<Self> {
+        let mut ids = Vec::new();
+
+        for_each_vertex_page(
+            LOAD_PAGE_SIZE,
+            |q| trans.get_vertices(q),
+            |vertex| vertex.id,
+            |vertex| {
+                ids.push(vertex.id);
+                Ok(())
+            },
+        )?;
+
+        let indices: HashMap<Uuid, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut outbound = vec![Vec::new(); ids.len()];
+
+        for chunk in ids.chunks(LOAD_PAGE_SIZE as usize) {
+            let mut q = SpecificVertexQuery::new(chunk.to_vec()).outbound(u32::MAX);
+
+            if let Some(t) = t {
+                q = q.t(t.clone());
+            }
+
+            for edge in trans.get_edges(q)? {
+                // Edges may reference vertices that don't exist, e.g. after
+                // a bulk insert, so those are skipped
+                if let (Some(from), Some(to)) = (indices.get(&edge.key.outbound_id), indices.get(&edge.key.inbound_id))
+                {
+                    outbound[*from].push(*to);
+                }
+            }
+        }
+
+        Ok(Self { ids, outbound })
+    }
+
+    /// Gets the neighbors of each vertex when edge directions are ignored,
+    /// as sorted lists without duplicates or self-loops.
+    fn undirected(&self) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![Vec::new(); self.ids.len()];
+
+        for (from, tos) in self.outbound.iter().enumerate() {
+            for &to in tos {
+                if from != to {
+                    neighbors[from].push(to);
+                    neighbors[to].push(from);
+                }
+            }
+        }
+
+        for list in &mut neighbors {
+            list.sort_unstable();
+            list.dedup();
+        }
+
+        neighbors
+    }
+
+    fn into_map<V>(self, values: Vec<V>) -> HashMap<Uuid, V> {
+        self.ids.into_iter().zip(values).collect()
+    }
+}
+
+/// Calculates the PageRank of every vertex. Parallel edges between the same
+/// vertices each count, and the rank of vertices without outbound edges is
+/// spread evenly across the graph.
+///
+/// # Arguments
+/// * `trans` - The transaction to read from.
+/// * `t` - Only follows edges of this type.
+/// * `damping_factor` - The probability of following an edge rather than
+///   jumping to a random vertex.
+/// * `iterations` - The number of iterations to run.
+pub fn page_rank<T: Transaction>(
+    trans: &T,
+    t: Option<&Type>,
+    damping_factor: f64,
+    iterations: u32,
+) -> Result<HashMap<Uuid, f64>> {
+    let graph = AdjacencyList::load(trans, t)?;
+    let n = graph.ids.len();
+
+    if n == 0 {
+        return Ok(HashMap::new());
+    }
+
+    let mut ranks = vec![1.0 / n as f64; n];
+
+    for _ in 0..iterations {
+        let mut dangling = 0.0;
+        let mut next_ranks = vec![0.0; n];
+
+        for (from, tos) in graph.outbound.iter().enumerate() {
+            if tos.is_empty() {
+                dangling += ranks[from];
+            } else {
+                let share = ranks[from] / tos.len() as f64;
+
+                for &to in tos {
+                    next_ranks[to] += share;
+                }
+            }
+        }
+
+        let base = (1.0 - damping_factor + damping_factor * dangling) / n as f64;
+
+        for rank in &mut next_ranks {
+            *rank = base + damping_factor * *rank;
+        }
+
+        ranks = next_ranks;
+    }
+
+    Ok(graph.into_map(ranks))
+}
+
+/// Finds the weakly connected component of every vertex, i.e. the vertices
+/// that are reachable from one another when edge directions are ignored.
+/// Components are identified by the lowest vertex ID in them.
+///
+/// # Arguments
+/// * `trans` - The transaction to read from.
+/// * `t` - Only follows edges of this type.
+pub fn weakly_connected_components<T: Transaction>(trans: &T, t: Option<&Type>) -> Result<HashMap<Uuid, Uuid>> {
+    fn find(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+
+        i
+    }
+
+    let graph = AdjacencyList::load(trans, t)?;
+    let mut parents: Vec<usize> = (0..graph.ids.len()).collect();
+
+    for (from, tos) in graph.outbound.iter().enumerate() {
+        for &to in tos {
+            let (a, b) = (find(&mut parents, from), find(&mut parents, to));
+
+            // Vertices are numbered in ID order, so keeping the lower index
+            // as the root keeps the lowest ID as the component's ID
+            if a < b {
+                parents[b] = a;
+            } else if b < a {
+                parents[a] = b;
+            }
+        }
+    }
+
+    let components = (0..graph.ids.len()).map(|i| graph.ids[find(&mut parents, i)]).collect();
+    Ok(graph.into_map(components))
+}
+
+/// Counts the triangles that every vertex is a part of, ignoring edge
+/// directions, parallel edges and self-loops.
+///
+/// # Arguments
+/// * `trans` - The transaction to read from.
+/// * `t` - Only considers edges of this type.
+pub fn triangle_counts<T: Transaction>(trans: &T, t: Option<&Type>) -> Result<HashMap<Uuid, u64>> {
+    let graph = AdjacencyList::load(trans, t)?;
+    let neighbors = graph.undirected();
+    let mut counts = vec![0; graph.ids.len()];
+
+    // Each triangle `a < b < c` is found exactly once, from `a` via `b`
+    for (a, a_neighbors) in neighbors.iter().enumerate() {
+        for &b in a_neighbors.iter().filter(|&&b| b > a) {
+            let b_neighbors = &neighbors[b];
+            let (mut i, mut j) = (0, 0);
+
+            while i < a_neighbors.len() && j < b_neighbors.len() {
+                let (x, y) = (a_neighbors[i], b_neighbors[j]);
+
+                if x < y {
+                    i += 1;
+                } else if y < x {
+                    j += 1;
+                } else {
+                    if x > b {
+                        counts[a] += 1;
+                        counts[b] += 1;
+                        counts[x] += 1;
+                    }
+
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+    }
+
+    Ok(graph.into_map(counts))
+}
+
+/// Gets the degree distribution of the graph, as a map of degrees to the
+/// number of vertices with that degree.
+///
+/// # Arguments
+/// * `trans` - The transaction to read from.
+/// * `t` - Only counts edges of this type.
+/// * `direction` - Whether to count outbound or inbound edges.
+pub fn degree_distribution<T: Transaction>(
+    trans: &T,
+    t: Option<&Type>,
+    direction: EdgeDirection,
+) -> Result<BTreeMap<u64, u64>> {
+    let graph = AdjacencyList::load(trans, t)?;
+
+    let degrees = match direction {
+        EdgeDirection::Outbound => graph.outbound.iter().map(|tos| tos.len() as u64).collect(),
+        EdgeDirection::Inbound => {
+            let mut degrees = vec![0; graph.ids.len()];
+
+            for &to in graph.outbound.iter().flatten() {
+                degrees[to] += 1;
+            }
+
+            degrees
+        }
+    };
+
+    let mut distribution = BTreeMap::new();
+
+    for degree in degrees {
+        *distribution.entry(degree).or_insert(0) += 1;
+    }
+
+    Ok(distribution)
+}
+
+/// Runs an algorithm over the whole graph, writing each vertex's result to
+/// a property via `Transaction::set_vertex_properties`. The results are
+/// committed in a single transaction. Returns the number of vertices that
+/// were written to.
+///
+/// # Arguments
+/// * `datastore` - The datastore to run against.
+/// * `algorithm` - The algorithm to run.
+/// * `t` - Only considers edges of this type.
+/// * `name` - The name of the vertex property to write results to.
+pub fn run<D: Datastore>(datastore: &D, algorithm: &Algorithm, t: Option<&Type>, name: &str) -> Result<u64> {
+    let trans = datastore.transaction()?;
+
+    let values: Vec<(Uuid, JsonValue)> = match *algorithm {
+        Algorithm::PageRank {
+            damping_factor,
+            iterations,
+        } => page_rank(&trans, t, damping_factor, iterations)?
+            .into_iter()
+            .map(|(id, rank)| (id, JsonValue::from(rank)))
+            .collect(),
+        Algorithm::WeaklyConnectedComponents => weakly_connected_components(&trans, t)?
+            .into_iter()
+            .map(|(id, component_id)| (id, JsonValue::from(component_id.to_string())))
+            .collect(),
+        Algorithm::TriangleCount => triangle_counts(&trans, t)?
+            .into_iter()
+            .map(|(id, count)| (id, JsonValue::from(count)))
+            .collect(),
+    };
+
+    for (id, value) in &values {
+        trans.set_vertex_properties(SpecificVertexQuery::single(*id).property(name), value)?;
+    }
+
+    trans.commit()?;
+    Ok(values.len() as u64)
+}
@@ -1,8 +1,12 @@
-use crate::models::{BulkInsertItem, EdgeDirection, EdgeKey, SpecificEdgeQuery, SpecificVertexQuery, Type, Vertex};
+use crate::models::{
+    BulkInsertItem, EdgeDirection, EdgeKey, EdgeQueryExt, SpecificEdgeQuery, SpecificVertexQuery, Type, Vertex,
+    VertexQueryExt,
+};
 use crate::traits::{Datastore, Transaction};
 
 use serde_json::Value as JsonValue;
 use test::Bencher;
+use uuid::Uuid;
 
 pub fn bench_create_vertex<D: Datastore>(b: &mut Bencher, datastore: &mut D) {
     let t = Type::new("bench_create_vertex").unwrap();
@@ -92,6 +96,83 @@ pub fn bench_get_edge_count<D: Datastore>(b: &mut Bencher, datastore: &mut D) {
     });
 }
 
+const PIPE_VERTEX_COUNT: usize = 100;
+const PIPE_EDGES_PER_VERTEX: usize = 10;
+
+/// Creates a set of vertices with edges to one another and a property on
+/// each vertex and edge, so that the pipe benchmarks read from many
+/// neighbouring owners at once.
+fn create_pipe_graph<D: Datastore>(datastore: &mut D, t: &Type) -> Vec<Uuid> {
+    let vertices: Vec<Vertex> = (0..PIPE_VERTEX_COUNT).map(|_| Vertex::new(t.clone())).collect();
+    let mut items = Vec::with_capacity(2 * PIPE_VERTEX_COUNT * (PIPE_EDGES_PER_VERTEX + 1));
+
+    for vertex in &vertices {
+        items.push(BulkInsertItem::Vertex(vertex.clone()));
+        items.push(BulkInsertItem::VertexProperty(
+            vertex.id,
+            "is_benchmark".to_string(),
+            JsonValue::Bool(true),
+        ));
+    }
+
+    for (i, vertex) in vertices.iter().enumerate() {
+        for j in 1..=PIPE_EDGES_PER_VERTEX {
+            let inbound_id = vertices[(i + j) % PIPE_VERTEX_COUNT].id;
+            let key = EdgeKey::new(vertex.id, t.clone(), inbound_id);
+            items.push(BulkInsertItem::Edge(key.clone()));
+            items.push(BulkInsertItem::EdgeProperty(
+                key,
+                "is_benchmark".to_string(),
+                JsonValue::Bool(true),
+            ));
+        }
+    }
+
+    datastore.bulk_insert(items.into_iter()).unwrap();
+    vertices.into_iter().map(|vertex| vertex.id).collect()
+}
+
+pub fn bench_get_piped_edges<D: Datastore>(b: &mut Bencher, datastore: &mut D) {
+    let t = Type::new("bench_get_piped_edges").unwrap();
+    let ids = create_pipe_graph(datastore, &t);
+    let limit = (PIPE_VERTEX_COUNT * PIPE_EDGES_PER_VERTEX) as u32;
+
+    b.iter(|| {
+        let trans = datastore.transaction().unwrap();
+        let q = SpecificVertexQuery::new(ids.clone()).outbound(limit);
+        trans.get_edges(q).unwrap();
+    });
+}
+
+pub fn bench_get_piped_vertex_properties<D: Datastore>(b: &mut Bencher, datastore: &mut D) {
+    let t = Type::new("bench_get_piped_vertex_properties").unwrap();
+    let ids = create_pipe_graph(datastore, &t);
+    let limit = (PIPE_VERTEX_COUNT * PIPE_EDGES_PER_VERTEX) as u32;
+
+    b.iter(|| {
+        let trans = datastore.transaction().unwrap();
+        let q = SpecificVertexQuery::new(ids.clone())
+            .outbound(limit)
+            .inbound(limit)
+            .property("is_benchmark");
+        trans.get_vertex_properties(q).unwrap();
+    });
+}
+
+pub fn bench_get_piped_edge_properties<D: Datastore>(b: &mut Bencher, datastore: &mut D) {
+    let t = Type::new("bench_get_piped_edge_properties").unwrap();
+    let ids = create_pipe_graph(datastore, &t);
+    let limit = (PIPE_VERTEX_COUNT * PIPE_EDGES_PER_VERTEX) as u32;
+
+    b.iter(|| {
+        let trans = datastore.transaction().unwrap();
+        let q = SpecificVertexQuery::new(ids.clone())
+            .outbound(limit)
+            .property("is_benchmark");
+        trans.get_edge_properties(q).unwrap();
+    });
+}
+
 const BULK_INSERT_COUNT: usize = 100;
 
 pub fn bench_bulk_insert<D: Datastore>(b: &mut Bencher, datastore: &mut D) {
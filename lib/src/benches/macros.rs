@@ -21,6 +21,9 @@ macro_rules! full_bench_impl {
         define_bench!(bench_create_edge, $code);
         define_bench!(bench_get_edges, $code);
         define_bench!(bench_get_edge_count, $code);
+        define_bench!(bench_get_piped_edges, $code);
+        define_bench!(bench_get_piped_vertex_properties, $code);
+        define_bench!(bench_get_piped_edge_properties, $code);
         define_bench!(bench_bulk_insert, $code);
     };
 }
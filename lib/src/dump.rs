@@ -0,0 +1,209 @@
+//! Exporting and importing whole graphs, e.g. to move them between
+//! datastores or servers.
+//!
+//! Graphs are dumped as line-delimited JSON, where each line is a single
+//! vertex, edge, vertex property or edge property:
+//!
+//! ```text
+//! {"type":"vertex","id":"a8f9ef4e-6a23-11ea-bc55-0242ac130003","t":"person"}
+//! {"type":"vertex_property","id":"a8f9ef4e-6a23-11ea-bc55-0242ac130003","name":"name","value":"alice"}
+//! {"type":"edge","outbound_id":"a8f9ef4e-...","t":"follows","inbound_id":"b2c4...","created_datetime":"2020-03-20T12:00:00Z"}
+//! {"type":"edge_property","outbound_id":"a8f9ef4e-...","t":"follows","inbound_id":"b2c4...","name":"since","value":2019}
+//! ```
+//!
+//! Every vertex is written before any edge, so dumps can be restored in a
+//! single pass.
+
+use std::io::{BufRead, Write};
+
+use crate::errors::Result;
+use crate::util::for_each_vertex_page;
+use crate::{BulkInsertItem, Datastore, Edge, EdgeKey, SpecificVertexQuery, Transaction, Type, Vertex, VertexQueryExt};
+
+use chrono::offset::Utc;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+/// The number of vertices to read at a time when dumping.
+const DUMP_PAGE_SIZE: u32 = 1000;
+
+/// The number of items to bulk insert at a time when restoring.
+const RESTORE_BATCH_SIZE: usize = 10_000;
+
+/// A single line in a dump.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum DumpItem {
+    Vertex {
+        id: Uuid,
+        t: Type,
+    },
+    Edge {
+        outbound_id: Uuid,
+        t: Type,
+        inbound_id: Uuid,
+        created_datetime: DateTime<Utc>,
+    },
+    VertexProperty {
+        id: Uuid,
+        name: String,
+        value: JsonValue,
+    },
+    EdgeProperty {
+        outbound_id: Uuid,
+        t: Type,
+        inbound_id: Uuid,
+        name: String,
+        value: JsonValue,
+    },
+}
+
+impl From<DumpItem> for BulkInsertItem {
+    fn from(item: DumpItem) -> Self {
+        match item {
+            DumpItem::Vertex { id, t } => BulkInsertItem::Vertex(Vertex::with_id(id, t)),
+            DumpItem::Edge {
+                outbound_id,
+                t,
+                inbound_id,
+                created_datetime,
+            } => {
+                BulkInsertItem::EdgeWithDatetime(Edge::new(EdgeKey::new(outbound_id, t, inbound_id), created_datetime))
+            }
+            DumpItem::VertexProperty { id, name, value } => BulkInsertItem::VertexProperty(id, name, value),
+            DumpItem::EdgeProperty {
+                outbound_id,
+                t,
+                inbound_id,
+                name,
+                value,
+            } => BulkInsertItem::EdgeProperty(EdgeKey::new(outbound_id, t, inbound_id), name, value),
+        }
+    }
+}
+
+fn write_item<W: Write>(writer: &mut W, item: &DumpItem) -> Result<()> {
+    serde_json::to_writer(&mut *writer, item)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Dumps every vertex, edge and property visible to a transaction.
+///
+/// Vertices are read a page at a time, so memory usage stays bounded
+/// regardless of the size of the graph.
+///
+/// # Arguments
+/// * `trans` - The transaction to read from.
+/// * `writer` - Where to write the dump to.
+pub fn dump<T: Transaction, W: Write>(trans: &T, mut writer: W) -> Result<()> {
+    for_each_vertex_page(
+        DUMP_PAGE_SIZE,
+        |q| trans.get_all_vertex_properties(q),
+        |vertex_properties| vertex_properties.vertex.id,
+        |vertex_properties| {
+            let vertex = &vertex_properties.vertex;
+
+            write_item(
+                &mut writer,
+                &DumpItem::Vertex {
+                    id: vertex.id,
+                    t: vertex.t.clone(),
+                },
+            )?;
+
+            for property in vertex_properties.props {
+                write_item(
+                    &mut writer,
+                    &DumpItem::VertexProperty {
+                        id: vertex.id,
+                        name: property.name,
+                        value: property.value,
+                    },
+                )?;
+            }
+
+            Ok(())
+        },
+    )?;
+
+    for_each_vertex_page(
+        DUMP_PAGE_SIZE,
+        |q| trans.get_vertices(q),
+        |vertex| vertex.id,
+        |vertex| {
+            let q = SpecificVertexQuery::single(vertex.id).outbound(u32::MAX);
+
+            for edge_properties in trans.get_all_edge_properties(q)? {
+                let key = &edge_properties.edge.key;
+
+                write_item(
+                    &mut writer,
+                    &DumpItem::Edge {
+                        outbound_id: key.outbound_id,
+                        t: key.t.clone(),
+                        inbound_id: key.inbound_id,
+                        created_datetime: edge_properties.edge.created_datetime,
+                    },
+                )?;
+
+                for property in edge_properties.props {
+                    write_item(
+                        &mut writer,
+                        &DumpItem::EdgeProperty {
+                            outbound_id: key.outbound_id,
+                            t: key.t.clone(),
+                            inbound_id: key.inbound_id,
+                            name: property.name,
+                            value: property.value,
+                        },
+                    )?;
+                }
+            }
+
+            Ok(())
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Restores a dump into a datastore, via `Datastore::bulk_insert`. Items
+/// are inserted in batches, so the dump is never fully loaded into memory.
+///
+/// Edges keep their creation datetimes, so long as the datastore's
+/// `bulk_insert` honors `BulkInsertItem::EdgeWithDatetime`.
+///
+/// # Arguments
+/// * `datastore` - The datastore to restore into.
+/// * `reader` - Where to read the dump from.
+///
+/// # Errors
+/// Returns an error if a line of the dump is malformed. Items in batches
+/// before the malformed line will have already been inserted.
+pub fn restore<D: Datastore, R: BufRead>(datastore: &D, reader: R) -> Result<()> {
+    let mut items: Vec<BulkInsertItem> = Vec::with_capacity(RESTORE_BATCH_SIZE);
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let item: DumpItem = serde_json::from_str(&line)?;
+        items.push(item.into());
+
+        if items.len() == RESTORE_BATCH_SIZE {
+            datastore.bulk_insert(items.drain(..))?;
+        }
+    }
+
+    if !items.is_empty() {
+        datastore.bulk_insert(items.into_iter())?;
+    }
+
+    Ok(())
+}
@@ -1,17 +1,46 @@
+use bincode::Error as BincodeError;
+use csv::Error as CsvError;
 #[cfg(feature = "rocksdb-datastore")]
 use rocksdb::Error as RocksDbError;
 use serde_json::Error as JsonError;
+use std::io::Error as IoError;
 use std::result::Result as StdResult;
+use xml::reader::Error as XmlReaderError;
+use xml::writer::Error as XmlWriterError;
 
 #[derive(Debug, Fail)]
 pub enum Error {
     #[fail(display = "json error: {}", inner)]
     Json { inner: JsonError },
+    #[fail(display = "i/o error: {}", inner)]
+    Io { inner: IoError },
+    #[fail(display = "bincode error: {}", inner)]
+    Bincode { inner: BincodeError },
+    #[fail(display = "csv error: {}", inner)]
+    Csv { inner: CsvError },
+    #[fail(display = "xml error: {}", inner)]
+    XmlRead { inner: XmlReaderError },
+    #[fail(display = "xml error: {}", inner)]
+    XmlWrite { inner: XmlWriterError },
+    #[fail(display = "invalid graph data: {}", message)]
+    InvalidGraphData { message: String },
     #[cfg(feature = "rocksdb-datastore")]
     #[fail(display = "rocksdb error: {}", inner)]
     Rocksdb { inner: RocksDbError },
+    #[fail(display = "property is not indexed")]
+    NotIndexed,
+    #[cfg(feature = "rocksdb-datastore")]
+    #[fail(display = "unsupported datastore version: {}", version)]
+    UnsupportedVersion { version: u32 },
+    #[cfg(feature = "rocksdb-datastore")]
+    #[fail(display = "graph is in use")]
+    GraphInUse,
     #[fail(display = "UUID already taken")]
     UuidTaken,
+    #[fail(display = "invalid graph name")]
+    InvalidGraphName,
+    #[fail(display = "operation is not supported by this datastore")]
+    Unsupported,
 }
 
 impl From<JsonError> for Error {
@@ -20,6 +49,36 @@ impl From<JsonError> for Error {
     }
 }
 
+impl From<IoError> for Error {
+    fn from(err: IoError) -> Self {
+        Error::Io { inner: err }
+    }
+}
+
+impl From<BincodeError> for Error {
+    fn from(err: BincodeError) -> Self {
+        Error::Bincode { inner: err }
+    }
+}
+
+impl From<CsvError> for Error {
+    fn from(err: CsvError) -> Self {
+        Error::Csv { inner: err }
+    }
+}
+
+impl From<XmlReaderError> for Error {
+    fn from(err: XmlReaderError) -> Self {
+        Error::XmlRead { inner: err }
+    }
+}
+
+impl From<XmlWriterError> for Error {
+    fn from(err: XmlWriterError) -> Self {
+        Error::XmlWrite { inner: err }
+    }
+}
+
 #[cfg(feature = "rocksdb-datastore")]
 impl From<RocksDbError> for Error {
     fn from(err: RocksDbError) -> Self {
@@ -0,0 +1,192 @@
+//! Import and export of node and edge CSV pairs.
+//!
+//! The node file has `id` and `label` columns, and the edge file has
+//! `source`, `target` and `label` columns. Every other column is a property,
+//! named after its header. An empty cell means the property isn't set.
+//!
+//! ```text
+//! id,label,name
+//! a8f9ef4e-6a23-11ea-bc55-0242ac130003,person,alice
+//!
+//! source,target,label,since
+//! a8f9ef4e-6a23-11ea-bc55-0242ac130003,b2c4d6e8-6a23-11ea-bc55-0242ac130003,follows,2019
+//! ```
+
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+
+use super::{decode_value, encode_value, for_each_edge, for_each_vertex, invalid, parse_type, IdMap, Inserter, LABEL};
+use crate::errors::Result;
+use crate::{BulkInsertItem, Datastore, EdgeKey, NamedProperty, Transaction, Vertex};
+
+const ID: &str = "id";
+const SOURCE: &str = "source";
+const TARGET: &str = "target";
+
+/// Builds the header for a file, checking that no property shadows one of
+/// the reserved columns.
+fn header(reserved: &[&str], property_names: &BTreeSet<String>) -> Result<Vec<String>> {
+    let mut header: Vec<String> = reserved.iter().map(|name| name.to_string()).collect();
+
+    for name in property_names {
+        if reserved.contains(&name.as_str()) {
+            return Err(invalid(format!("property `{}` conflicts with a reserved column", name)));
+        }
+
+        header.push(name.clone());
+    }
+
+    Ok(header)
+}
+
+/// Builds the property cells of a row, in header order.
+fn property_cells<'a>(property_names: &'a BTreeSet<String>, props: &'a [NamedProperty]) -> Vec<String> {
+    property_names
+        .iter()
+        .map(|name| match props.iter().find(|prop| &prop.name == name) {
+            Some(prop) => encode_value(&prop.value),
+            None => String::new(),
+        })
+        .collect()
+}
+
+/// Returns the index of a required column.
+fn column(headers: &::csv::StringRecord, name: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|header| header == name)
+        .ok_or_else(|| invalid(format!("missing `{}` column", name)))
+}
+
+/// Exports every vertex, edge and property visible to a transaction as a
+/// pair of CSV files.
+///
+/// # Arguments
+/// * `trans` - The transaction to read from.
+/// * `vertices` - Where to write the node file to.
+/// * `edges` - Where to write the edge file to.
+///
+/// # Errors
+/// Returns an error if a property is named after one of the reserved
+/// columns.
+pub fn export_csv<T, V, E>(trans: &T, vertices: V, edges: E) -> Result<()>
+where
+    T: Transaction,
+    V: Write,
+    E: Write,
+{
+    let mut vertex_property_names = BTreeSet::new();
+    let mut edge_property_names = BTreeSet::new();
+
+    for_each_vertex(trans, |vertex_properties| {
+        for prop in &vertex_properties.props {
+            vertex_property_names.insert(prop.name.clone());
+        }
+        Ok(())
+    })?;
+
+    for_each_edge(trans, |edge_properties| {
+        for prop in &edge_properties.props {
+            edge_property_names.insert(prop.name.clone());
+        }
+        Ok(())
+    })?;
+
+    let mut writer = ::csv::Writer::from_writer(vertices);
+    writer.write_record(header(&[ID, LABEL], &vertex_property_names)?)?;
+
+    for_each_vertex(trans, |vertex_properties| {
+        let vertex = &vertex_properties.vertex;
+        let mut record = vec![vertex.id.to_string(), vertex.t.0.clone()];
+        record.extend(property_cells(&vertex_property_names, &vertex_properties.props));
+        writer.write_record(record)?;
+        Ok(())
+    })?;
+
+    writer.flush()?;
+
+    let mut writer = ::csv::Writer::from_writer(edges);
+    writer.write_record(header(&[SOURCE, TARGET, LABEL], &edge_property_names)?)?;
+
+    for_each_edge(trans, |edge_properties| {
+        let key = &edge_properties.edge.key;
+        let mut record = vec![key.outbound_id.to_string(), key.inbound_id.to_string(), key.t.0.clone()];
+        record.extend(property_cells(&edge_property_names, &edge_properties.props));
+        writer.write_record(record)?;
+        Ok(())
+    })?;
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Imports a pair of CSV files into a datastore, via
+/// `Datastore::bulk_insert`. All vertices are inserted before any edges.
+///
+/// # Arguments
+/// * `datastore` - The datastore to import into.
+/// * `vertices` - Where to read the node file from.
+/// * `edges` - Where to read the edge file from.
+///
+/// # Errors
+/// Returns an error if a required column is missing, a type is invalid, or
+/// an edge references a vertex that is neither in the node file nor
+/// identified by a UUID. Items in batches before the error will have already
+/// been inserted.
+pub fn import_csv<D, V, E>(datastore: &D, vertices: V, edges: E) -> Result<()>
+where
+    D: Datastore,
+    V: Read,
+    E: Read,
+{
+    let mut inserter = Inserter::new(datastore);
+    let mut ids = IdMap::default();
+
+    let mut reader = ::csv::Reader::from_reader(vertices);
+    let headers = reader.headers()?.clone();
+    let id_column = column(&headers, ID)?;
+    let label_column = column(&headers, LABEL)?;
+
+    for record in reader.records() {
+        let record = record?;
+        let id = ids.insert(&record[id_column]);
+        inserter.push(BulkInsertItem::Vertex(Vertex::with_id(
+            id,
+            parse_type(&record[label_column])?,
+        )))?;
+
+        for (i, (name, cell)) in headers.iter().zip(record.iter()).enumerate() {
+            if i != id_column && i != label_column && !cell.is_empty() {
+                inserter.push(BulkInsertItem::VertexProperty(id, name.to_string(), decode_value(cell)))?;
+            }
+        }
+    }
+
+    let mut reader = ::csv::Reader::from_reader(edges);
+    let headers = reader.headers()?.clone();
+    let source_column = column(&headers, SOURCE)?;
+    let target_column = column(&headers, TARGET)?;
+    let label_column = column(&headers, LABEL)?;
+
+    for record in reader.records() {
+        let record = record?;
+        let key = EdgeKey::new(
+            ids.get(&record[source_column])?,
+            parse_type(&record[label_column])?,
+            ids.get(&record[target_column])?,
+        );
+        inserter.push(BulkInsertItem::Edge(key.clone()))?;
+
+        for (i, (name, cell)) in headers.iter().zip(record.iter()).enumerate() {
+            if i != source_column && i != target_column && i != label_column && !cell.is_empty() {
+                inserter.push(BulkInsertItem::EdgeProperty(
+                    key.clone(),
+                    name.to_string(),
+                    decode_value(cell),
+                ))?;
+            }
+        }
+    }
+
+    inserter.flush()
+}
@@ -0,0 +1,411 @@
+//! Import and export of GraphML documents.
+//!
+//! Vertices are written as `node` elements and edges as directed `edge`
+//! elements, both identified by UUID. Types are stored in the `label`
+//! attribute, and every property gets its own attribute key. Properties
+//! whose values are all booleans, integers or floats are given the matching
+//! GraphML type; any other property is a `string`, and string values that
+//! are valid JSON are read back as JSON, so nested values round-trip.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+
+use super::{decode_value, encode_value, for_each_edge, for_each_vertex, invalid, parse_type, IdMap, Inserter, LABEL};
+use crate::errors::Result;
+use crate::{BulkInsertItem, Datastore, EdgeKey, NamedProperty, Transaction, Type, Vertex};
+
+use serde_json::{Number as JsonNumber, Value as JsonValue};
+use xml::attribute::OwnedAttribute;
+use xml::reader::{EventReader, XmlEvent as ReaderEvent};
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent as WriterEvent};
+
+const GRAPHML_NS: &str = "http://graphml.graphdrawing.org/xmlns";
+
+/// The GraphML type of an attribute key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AttrType {
+    Boolean,
+    Long,
+    Double,
+    String,
+}
+
+impl AttrType {
+    fn of(value: &JsonValue) -> Self {
+        match value {
+            JsonValue::Bool(_) => AttrType::Boolean,
+            JsonValue::Number(n) if n.is_f64() => AttrType::Double,
+            JsonValue::Number(_) => AttrType::Long,
+            _ => AttrType::String,
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "boolean" => AttrType::Boolean,
+            "int" | "long" => AttrType::Long,
+            "float" | "double" => AttrType::Double,
+            _ => AttrType::String,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AttrType::Boolean => "boolean",
+            AttrType::Long => "long",
+            AttrType::Double => "double",
+            AttrType::String => "string",
+        }
+    }
+
+    /// Encodes a property value as attribute data.
+    fn encode(self, value: &JsonValue) -> String {
+        match self {
+            AttrType::String => encode_value(value),
+            _ => value.to_string(),
+        }
+    }
+
+    /// Decodes attribute data into a property value.
+    fn decode(self, s: &str) -> Result<JsonValue> {
+        let trimmed = s.trim();
+        let value = match self {
+            AttrType::Boolean => trimmed.parse::<bool>().ok().map(JsonValue::Bool),
+            AttrType::Long => trimmed
+                .parse::<JsonNumber>()
+                .ok()
+                .filter(|n| !n.is_f64())
+                .map(JsonValue::Number),
+            AttrType::Double => trimmed
+                .parse::<f64>()
+                .ok()
+                .and_then(JsonNumber::from_f64)
+                .map(JsonValue::Number),
+            AttrType::String if s.is_empty() => Some(JsonValue::String(String::new())),
+            AttrType::String => Some(decode_value(s)),
+        };
+
+        value.ok_or_else(|| invalid(format!("invalid {} value `{}`", self.name(), s)))
+    }
+}
+
+/// Attribute keys for the properties of one kind of element, ordered by
+/// property name.
+struct Keys {
+    prefix: &'static str,
+    types: BTreeMap<String, AttrType>,
+}
+
+impl Keys {
+    fn new(prefix: &'static str) -> Self {
+        Self {
+            prefix,
+            types: BTreeMap::new(),
+        }
+    }
+
+    /// Records a property value, widening the key's type to `string` if its
+    /// values have mixed types.
+    fn observe(&mut self, prop: &NamedProperty) -> Result<()> {
+        if prop.name == LABEL {
+            return Err(invalid(format!(
+                "property `{}` conflicts with the type attribute",
+                LABEL
+            )));
+        }
+
+        let t = AttrType::of(&prop.value);
+        self.types
+            .entry(prop.name.clone())
+            .and_modify(|existing| {
+                if *existing != t {
+                    *existing = AttrType::String;
+                }
+            })
+            .or_insert(t);
+        Ok(())
+    }
+
+    /// Returns the name, ID and type of every key.
+    fn entries(&self) -> impl Iterator<Item = (&str, String, AttrType)> {
+        let prefix = self.prefix;
+        self.types
+            .iter()
+            .enumerate()
+            .map(move |(i, (name, t))| (name.as_str(), format!("{}{}", prefix, i), *t))
+    }
+
+    /// Returns the ID and type of every key, by property name.
+    fn ids(&self) -> HashMap<&str, (String, AttrType)> {
+        self.entries().map(|(name, id, t)| (name, (id, t))).collect()
+    }
+}
+
+fn write_data<W: Write>(writer: &mut EventWriter<W>, key: &str, value: &str) -> Result<()> {
+    writer.write(WriterEvent::start_element("data").attr("key", key))?;
+    writer.write(WriterEvent::characters(value))?;
+    writer.write(WriterEvent::end_element())?;
+    Ok(())
+}
+
+fn write_properties<W: Write>(
+    writer: &mut EventWriter<W>,
+    ids: &HashMap<&str, (String, AttrType)>,
+    t: &Type,
+    props: &[NamedProperty],
+) -> Result<()> {
+    write_data(writer, LABEL, &t.0)?;
+
+    for prop in props {
+        let (id, attr_type) = &ids[prop.name.as_str()];
+        write_data(writer, id, &attr_type.encode(&prop.value))?;
+    }
+
+    Ok(())
+}
+
+/// Exports every vertex, edge and property visible to a transaction as a
+/// GraphML document.
+///
+/// # Arguments
+/// * `trans` - The transaction to read from.
+/// * `writer` - Where to write the document to.
+///
+/// # Errors
+/// Returns an error if a property is named `label`.
+pub fn export_graphml<T: Transaction, W: Write>(trans: &T, writer: W) -> Result<()> {
+    let mut vertex_keys = Keys::new("v");
+    let mut edge_keys = Keys::new("e");
+
+    for_each_vertex(trans, |vertex_properties| {
+        for prop in &vertex_properties.props {
+            vertex_keys.observe(prop)?;
+        }
+        Ok(())
+    })?;
+
+    for_each_edge(trans, |edge_properties| {
+        for prop in &edge_properties.props {
+            edge_keys.observe(prop)?;
+        }
+        Ok(())
+    })?;
+
+    let vertex_ids = vertex_keys.ids();
+    let edge_ids = edge_keys.ids();
+    let mut writer = EmitterConfig::new().perform_indent(true).create_writer(writer);
+
+    writer.write(WriterEvent::start_element("graphml").default_ns(GRAPHML_NS))?;
+
+    writer.write(
+        WriterEvent::start_element("key")
+            .attr("id", LABEL)
+            .attr("for", "all")
+            .attr("attr.name", LABEL)
+            .attr("attr.type", AttrType::String.name()),
+    )?;
+    writer.write(WriterEvent::end_element())?;
+
+    for (domain, keys) in &[("node", &vertex_keys), ("edge", &edge_keys)] {
+        for (name, id, attr_type) in keys.entries() {
+            writer.write(
+                WriterEvent::start_element("key")
+                    .attr("id", &id)
+                    .attr("for", domain)
+                    .attr("attr.name", name)
+                    .attr("attr.type", attr_type.name()),
+            )?;
+            writer.write(WriterEvent::end_element())?;
+        }
+    }
+
+    writer.write(
+        WriterEvent::start_element("graph")
+            .attr("id", "G")
+            .attr("edgedefault", "directed"),
+    )?;
+
+    for_each_vertex(trans, |vertex_properties| {
+        let vertex = &vertex_properties.vertex;
+        let id = vertex.id.to_string();
+        writer.write(WriterEvent::start_element("node").attr("id", &id))?;
+        write_properties(&mut writer, &vertex_ids, &vertex.t, &vertex_properties.props)?;
+        writer.write(WriterEvent::end_element())?;
+        Ok(())
+    })?;
+
+    for_each_edge(trans, |edge_properties| {
+        let key = &edge_properties.edge.key;
+        let source = key.outbound_id.to_string();
+        let target = key.inbound_id.to_string();
+        writer.write(
+            WriterEvent::start_element("edge")
+                .attr("source", &source)
+                .attr("target", &target),
+        )?;
+        write_properties(&mut writer, &edge_ids, &key.t, &edge_properties.props)?;
+        writer.write(WriterEvent::end_element())?;
+        Ok(())
+    })?;
+
+    // Close `graph` and `graphml`
+    writer.write(WriterEvent::end_element())?;
+    writer.write(WriterEvent::end_element())?;
+    writer.into_inner().flush()?;
+    Ok(())
+}
+
+/// An attribute key declared in an imported document.
+struct Key {
+    name: String,
+    attr_type: AttrType,
+}
+
+/// A node or edge element that's being read.
+struct Element {
+    /// The node ID, or the edge source and target.
+    ids: Vec<String>,
+    data: Vec<(String, String)>,
+}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.name.local_name == name)
+        .map(|attribute| attribute.value.as_str())
+}
+
+fn required_attribute<'a>(attributes: &'a [OwnedAttribute], element: &str, name: &str) -> Result<&'a str> {
+    attribute(attributes, name).ok_or_else(|| invalid(format!("`{}` element missing `{}` attribute", element, name)))
+}
+
+/// Resolves an element's data into its type and properties.
+fn resolve(
+    keys: &HashMap<String, Key>,
+    data: Vec<(String, String)>,
+    default_t: &Type,
+) -> Result<(Type, Vec<NamedProperty>)> {
+    let mut t = None;
+    let mut props = Vec::with_capacity(data.len());
+
+    for (key_id, value) in data {
+        let key = keys
+            .get(&key_id)
+            .ok_or_else(|| invalid(format!("unknown attribute key `{}`", key_id)))?;
+
+        if key.name == LABEL {
+            t = Some(parse_type(value.trim())?);
+        } else {
+            props.push(NamedProperty::new(key.name.clone(), key.attr_type.decode(&value)?));
+        }
+    }
+
+    Ok((t.unwrap_or_else(|| default_t.clone()), props))
+}
+
+/// Imports a GraphML document into a datastore, via
+/// `Datastore::bulk_insert`. All vertices are inserted before any edges.
+///
+/// Nested graphs, hyperedges and ports aren't supported, and are ignored.
+///
+/// # Arguments
+/// * `datastore` - The datastore to import into.
+/// * `reader` - Where to read the document from.
+/// * `default_t` - The type to use for nodes and edges without a `label`.
+///
+/// # Errors
+/// Returns an error if the document is malformed, a type or typed value is
+/// invalid, or an edge references a vertex that is neither in the document
+/// nor identified by a UUID. Items in batches before the error will have
+/// already been inserted.
+pub fn import_graphml<D: Datastore, R: Read>(datastore: &D, reader: R, default_t: &Type) -> Result<()> {
+    let mut inserter = Inserter::new(datastore);
+    let mut ids = IdMap::default();
+    let mut keys: HashMap<String, Key> = HashMap::new();
+    let mut edges: Vec<Element> = Vec::new();
+    let mut element: Option<Element> = None;
+    let mut data: Option<(String, String)> = None;
+
+    for event in EventReader::new(reader) {
+        match event? {
+            ReaderEvent::StartElement { name, attributes, .. } => match name.local_name.as_str() {
+                "key" => {
+                    let id = required_attribute(&attributes, "key", "id")?;
+                    let name = attribute(&attributes, "attr.name").unwrap_or(id);
+                    let attr_type = AttrType::parse(attribute(&attributes, "attr.type").unwrap_or("string"));
+                    keys.insert(
+                        id.to_string(),
+                        Key {
+                            name: name.to_string(),
+                            attr_type,
+                        },
+                    );
+                }
+                "node" => {
+                    let id = required_attribute(&attributes, "node", "id")?;
+                    element = Some(Element {
+                        ids: vec![id.to_string()],
+                        data: Vec::new(),
+                    });
+                }
+                "edge" => {
+                    let source = required_attribute(&attributes, "edge", "source")?;
+                    let target = required_attribute(&attributes, "edge", "target")?;
+                    element = Some(Element {
+                        ids: vec![source.to_string(), target.to_string()],
+                        data: Vec::new(),
+                    });
+                }
+                "data" if element.is_some() => {
+                    let key = required_attribute(&attributes, "data", "key")?;
+                    data = Some((key.to_string(), String::new()));
+                }
+                _ => {}
+            },
+            ReaderEvent::Characters(s) | ReaderEvent::CData(s) | ReaderEvent::Whitespace(s) => {
+                if let Some((_, ref mut value)) = data {
+                    value.push_str(&s);
+                }
+            }
+            ReaderEvent::EndElement { name } => match name.local_name.as_str() {
+                "data" => {
+                    if let (Some(element), Some(data)) = (element.as_mut(), data.take()) {
+                        element.data.push(data);
+                    }
+                }
+                "node" => {
+                    if let Some(node) = element.take() {
+                        let id = ids.insert(&node.ids[0]);
+                        let (t, props) = resolve(&keys, node.data, default_t)?;
+                        inserter.push(BulkInsertItem::Vertex(Vertex::with_id(id, t)))?;
+
+                        for prop in props {
+                            inserter.push(BulkInsertItem::VertexProperty(id, prop.name, prop.value))?;
+                        }
+                    }
+                }
+                "edge" => {
+                    // Edges may come before the nodes they reference, so
+                    // they're resolved once the whole document is read
+                    if let Some(edge) = element.take() {
+                        edges.push(edge);
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    for edge in edges {
+        let (t, props) = resolve(&keys, edge.data, default_t)?;
+        let key = EdgeKey::new(ids.get(&edge.ids[0])?, t, ids.get(&edge.ids[1])?);
+        inserter.push(BulkInsertItem::Edge(key.clone()))?;
+
+        for prop in props {
+            inserter.push(BulkInsertItem::EdgeProperty(key.clone(), prop.name, prop.value))?;
+        }
+    }
+
+    inserter.flush()
+}
@@ -0,0 +1,188 @@
+//! Importers and exporters for common graph interchange formats, so that
+//! graphs can be moved between IndraDB and other tools.
+//!
+//! Two formats are supported:
+//! * `graphml` - GraphML documents, as produced and consumed by tools such as
+//!   Gephi, yEd, NetworkX and igraph.
+//! * `csv` - A pair of node and edge CSV files, following the column naming
+//!   conventions of Gephi's spreadsheet importer.
+//!
+//! Vertex and edge types are carried in a `label` attribute or column. Vertex
+//! IDs that are UUIDs are preserved on import; any other IDs are replaced
+//! with newly generated UUIDs. Imports are fed through
+//! `Datastore::bulk_insert`, so they have the same performance
+//! characteristics and caveats.
+
+pub mod csv;
+pub mod graphml;
+
+use std::collections::HashMap;
+
+use crate::errors::{Error, Result};
+use crate::util::{for_each_vertex_page, generate_uuid_v1};
+use crate::{
+    BulkInsertItem, Datastore, EdgeProperties, SpecificVertexQuery, Transaction, Type, VertexProperties, VertexQueryExt,
+};
+
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+/// The number of vertices to read at a time when exporting.
+const EXPORT_PAGE_SIZE: u32 = 1000;
+
+/// The number of items to bulk insert at a time when importing.
+const IMPORT_BATCH_SIZE: usize = 10_000;
+
+/// The name of the attribute or column that holds vertex and edge types.
+const LABEL: &str = "label";
+
+fn invalid<S: Into<String>>(message: S) -> Error {
+    Error::InvalidGraphData {
+        message: message.into(),
+    }
+}
+
+/// Validates a type read from an import.
+fn parse_type(s: &str) -> Result<Type> {
+    Type::new(s).map_err(|err| invalid(format!("invalid type `{}`: {}", s, err)))
+}
+
+/// Calls `f` with every vertex visible to a transaction, along with its
+/// properties. Vertices are read a page at a time.
+fn for_each_vertex<T, F>(trans: &T, mut f: F) -> Result<()>
+where
+    T: Transaction,
+    F: FnMut(&VertexProperties) -> Result<()>,
+{
+    for_each_vertex_page(
+        EXPORT_PAGE_SIZE,
+        |q| trans.get_all_vertex_properties(q),
+        |vertex_properties| vertex_properties.vertex.id,
+        |vertex_properties| f(&vertex_properties),
+    )
+}
+
+/// Calls `f` with every edge visible to a transaction, along with its
+/// properties. Edges are read per outbound vertex.
+fn for_each_edge<T, F>(trans: &T, mut f: F) -> Result<()>
+where
+    T: Transaction,
+    F: FnMut(&EdgeProperties) -> Result<()>,
+{
+    for_each_vertex(trans, |vertex_properties| {
+        let q = SpecificVertexQuery::single(vertex_properties.vertex.id).outbound(u32::MAX);
+
+        for edge_properties in &trans.get_all_edge_properties(q)? {
+            f(edge_properties)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Maps the vertex IDs used in an import to UUIDs.
+#[derive(Default)]
+struct IdMap(HashMap<String, Uuid>);
+
+impl IdMap {
+    /// Returns the UUID for a newly imported vertex: the ID itself if it's a
+    /// valid UUID, otherwise a newly generated one.
+    fn insert(&mut self, id: &str) -> Uuid {
+        *self
+            .0
+            .entry(id.to_string())
+            .or_insert_with(|| Uuid::parse_str(id).unwrap_or_else(|_| generate_uuid_v1()))
+    }
+
+    /// Returns the UUID for a vertex referenced by an edge. Vertices that
+    /// weren't part of the import can still be referenced by UUID.
+    fn get(&self, id: &str) -> Result<Uuid> {
+        match self.0.get(id) {
+            Some(uuid) => Ok(*uuid),
+            None => Uuid::parse_str(id).map_err(|_| invalid(format!("unknown vertex `{}`", id))),
+        }
+    }
+}
+
+/// Buffers imported items, bulk inserting them in batches.
+struct Inserter<'a, D: Datastore> {
+    datastore: &'a D,
+    items: Vec<BulkInsertItem>,
+}
+
+impl<'a, D: Datastore> Inserter<'a, D> {
+    fn new(datastore: &'a D) -> Self {
+        Self {
+            datastore,
+            items: Vec::with_capacity(IMPORT_BATCH_SIZE),
+        }
+    }
+
+    fn push(&mut self, item: BulkInsertItem) -> Result<()> {
+        self.items.push(item);
+
+        if self.items.len() == IMPORT_BATCH_SIZE {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.items.is_empty() {
+            self.datastore.bulk_insert(self.items.drain(..))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Encodes a property value as text. Strings are written as-is, unless
+/// they're empty or would otherwise be read back as some other JSON value,
+/// in which case they're quoted. Everything else is written as JSON.
+fn encode_value(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) if !s.is_empty() && serde_json::from_str::<JsonValue>(s).is_err() => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+/// Decodes a property value written by `encode_value`. Text that isn't
+/// valid JSON is read as a string.
+fn decode_value(s: &str) -> JsonValue {
+    serde_json::from_str(s).unwrap_or_else(|_| JsonValue::String(s.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_value, encode_value};
+    use serde_json::{json, Value as JsonValue};
+
+    #[test]
+    fn should_round_trip_values() {
+        let values = vec![
+            json!("alice"),
+            json!(""),
+            json!("123"),
+            json!("true"),
+            json!("\"quoted\""),
+            json!(123),
+            json!(1.5),
+            json!(false),
+            JsonValue::Null,
+            json!([1, "two"]),
+            json!({"a": {"b": 1}}),
+        ];
+
+        for value in values {
+            assert_eq!(decode_value(&encode_value(&value)), value);
+        }
+    }
+
+    #[test]
+    fn should_encode_plain_strings_unquoted() {
+        assert_eq!(encode_value(&json!("alice")), "alice");
+        assert_eq!(encode_value(&json!("123")), "\"123\"");
+        assert_eq!(encode_value(&json!("")), "\"\"");
+    }
+}
@@ -11,16 +11,22 @@
 #[cfg(feature = "bench-suite")]
 extern crate test;
 
+extern crate bincode;
 extern crate chrono;
 extern crate core;
+extern crate csv;
 #[macro_use]
 extern crate failure;
 #[macro_use]
 extern crate lazy_static;
+#[macro_use]
+extern crate log;
 extern crate rand;
 extern crate regex;
+extern crate serde;
 extern crate serde_json;
 extern crate uuid;
+extern crate xml;
 
 #[cfg(feature = "rocksdb-datastore")]
 extern crate byteorder;
@@ -35,10 +41,14 @@ pub mod tests;
 #[macro_use]
 pub mod benches;
 
+pub mod algorithms;
+pub mod dump;
 mod errors;
+pub mod interchange;
 mod memory;
 mod models;
 mod traits;
+mod traversal;
 pub mod util;
 
 pub use crate::errors::*;
@@ -49,4 +59,7 @@ pub use crate::traits::*;
 #[cfg(feature = "rocksdb-datastore")]
 mod rdb;
 #[cfg(feature = "rocksdb-datastore")]
-pub use crate::rdb::{RocksdbDatastore, RocksdbTransaction};
+pub use crate::rdb::{
+    ColumnFamily, CompactionStyle, Compression, Counter, IntegrityIssue, RocksdbDatastore, RocksdbOptions,
+    RocksdbTransaction,
+};
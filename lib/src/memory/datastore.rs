@@ -1,14 +1,27 @@
-use std::collections::{BTreeMap, HashSet};
-use std::sync::{Arc, RwLock};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{rename, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::iter::Peekable;
+use std::ops::Bound;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::thread;
+use std::vec;
 
 use crate::errors::Result;
+use crate::traversal::{breadth_first, shortest_path, Graph};
+use crate::util::{next_uuid, validate_graph_name};
 use crate::{
-    Datastore, Edge, EdgeDirection, EdgeKey, EdgeProperties, EdgeProperty, EdgePropertyQuery, EdgeQuery, NamedProperty,
-    Transaction, Type, Vertex, VertexProperties, VertexProperty, VertexPropertyQuery, VertexQuery,
+    Datastore, Edge, EdgeCursor, EdgeDirection, EdgeKey, EdgeProperties, EdgeProperty, EdgePropertyQuery, EdgeQuery,
+    NamedGraphs, NamedProperty, PipeEdgeQuery, PipeVertexQuery, PropertyFilter, RangeVertexQuery, ShortestPathQuery,
+    SpecificEdgeQuery, SpecificVertexQuery, Transaction, TraversalQuery, Type, Vertex, VertexProperties,
+    VertexProperty, VertexPropertyQuery, VertexQuery,
 };
 
 use chrono::offset::Utc;
 use chrono::DateTime;
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use uuid::Uuid;
 
@@ -16,30 +29,288 @@ use uuid::Uuid;
 // internally to the datastore itself. This way, we can wrap an rwlock around
 // the entire datastore, rather than on a per-data structure basis, as the
 // latter approach would risk deadlocking without extreme care.
-#[derive(Debug)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct InternalMemoryDatastore {
+    #[serde(with = "json_map")]
     edge_properties: BTreeMap<(EdgeKey, String), JsonValue>,
     edges: BTreeMap<EdgeKey, DateTime<Utc>>,
+    // An index of edges keyed by (inbound_id, type, outbound_id), mirroring
+    // the rocksdb datastore's reversed edge ranges. It's derived from
+    // `edges`, so it isn't persisted.
+    #[serde(skip)]
+    reversed_edges: BTreeSet<(Uuid, Type, Uuid)>,
+    #[serde(with = "json_map")]
     vertex_properties: BTreeMap<(Uuid, String), JsonValue>,
     vertices: BTreeMap<Uuid, Type>,
 }
 
+// Bincode can't deserialize JSON values directly, since it's not a
+// self-describing format. So property values are persisted as JSON strings
+// instead.
+mod json_map {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value as JsonValue;
+    use std::collections::BTreeMap;
+
+    pub fn serialize<K, S>(map: &BTreeMap<K, JsonValue>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter().map(|(key, value)| (key, value.to_string())))
+    }
+
+    pub fn deserialize<'de, K, D>(deserializer: D) -> Result<BTreeMap<K, JsonValue>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        D: Deserializer<'de>,
+    {
+        let items: Vec<(K, String)> = Vec::deserialize(deserializer)?;
+        let mut map = BTreeMap::new();
+
+        for (key, value) in items {
+            map.insert(key, serde_json::from_str(&value).map_err(D::Error::custom)?);
+        }
+
+        Ok(map)
+    }
+}
+
 type QueryIter<'a, T> = Box<dyn Iterator<Item = T> + 'a>;
 
+fn set_or_remove<K: Ord, V>(map: &mut BTreeMap<K, V>, key: K, value: Option<V>) {
+    match value {
+        Some(value) => {
+            map.insert(key, value);
+        }
+        None => {
+            map.remove(&key);
+        }
+    }
+}
+
+/// Gets a value from a map, taking into account changes made to it.
+fn lookup<'a, K: Ord, V>(base: &'a BTreeMap<K, V>, changes: &'a BTreeMap<K, Option<V>>, key: &K) -> Option<&'a V> {
+    match changes.get(key) {
+        Some(value) => value.as_ref(),
+        None => base.get(key),
+    }
+}
+
+/// Merges changes into an iterator over a map, in key order.
+struct Merge<'a, K: 'a, V: 'a, B, C>
+where
+    B: Iterator<Item = (&'a K, &'a V)>,
+    C: Iterator<Item = (&'a K, &'a Option<V>)>,
+{
+    base: Peekable<B>,
+    changes: Peekable<C>,
+}
+
+fn merge<'a, K: 'a, V: 'a, B, C>(base: B, changes: C) -> Merge<'a, K, V, B, C>
+where
+    B: Iterator<Item = (&'a K, &'a V)>,
+    C: Iterator<Item = (&'a K, &'a Option<V>)>,
+{
+    Merge {
+        base: base.peekable(),
+        changes: changes.peekable(),
+    }
+}
+
+impl<'a, K: Ord + 'a, V: 'a, B, C> Iterator for Merge<'a, K, V, B, C>
+where
+    B: Iterator<Item = (&'a K, &'a V)>,
+    C: Iterator<Item = (&'a K, &'a Option<V>)>,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ordering = match (self.base.peek(), self.changes.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((key, _)), Some((changed_key, _))) => key.cmp(changed_key),
+            };
+
+            match ordering {
+                Ordering::Less => return self.base.next(),
+                // The change replaces the original value
+                Ordering::Equal => {
+                    self.base.next();
+                }
+                Ordering::Greater => (),
+            }
+
+            // Deleted keys are skipped
+            if let Some((key, Some(value))) = self.changes.next() {
+                return Some((key, value));
+            }
+        }
+    }
+}
+
 impl InternalMemoryDatastore {
-    fn get_vertex_values_by_query<'a>(&'a self, q: VertexQuery) -> Result<QueryIter<'a, (Uuid, Type)>> {
+    // Rebuilds the indices that aren't persisted, after deserializing.
+    fn rebuild_indices(&mut self) {
+        self.reversed_edges = self
+            .edges
+            .keys()
+            .map(|key| (key.inbound_id, key.t.clone(), key.outbound_id))
+            .collect();
+    }
+
+    /// Writes changes made by a transaction.
+    fn apply(&mut self, changes: Changes) {
+        for (id, t) in changes.vertices {
+            set_or_remove(&mut self.vertices, id, t);
+        }
+
+        for (key, update_datetime) in changes.edges {
+            self.reversed_edges
+                .remove(&(key.inbound_id, key.t.clone(), key.outbound_id));
+
+            if update_datetime.is_some() {
+                self.reversed_edges
+                    .insert((key.inbound_id, key.t.clone(), key.outbound_id));
+            }
+
+            set_or_remove(&mut self.edges, key, update_datetime);
+        }
+
+        for (key, value) in changes.vertex_properties {
+            set_or_remove(&mut self.vertex_properties, key, value);
+        }
+
+        for (key, value) in changes.edge_properties {
+            set_or_remove(&mut self.edge_properties, key, value);
+        }
+    }
+}
+
+/// Changes made by a transaction that haven't been committed yet. Each entry
+/// is the new value of a key, where `None` means the key is deleted.
+#[derive(Clone, Debug, Default)]
+struct Changes {
+    vertices: BTreeMap<Uuid, Option<Type>>,
+    edges: BTreeMap<EdgeKey, Option<DateTime<Utc>>>,
+    // Mirrors the changes to `edges`, like `reversed_edges` in the datastore
+    reversed_edges: BTreeMap<(Uuid, Type, Uuid), Option<()>>,
+    vertex_properties: BTreeMap<(Uuid, String), Option<JsonValue>>,
+    edge_properties: BTreeMap<(EdgeKey, String), Option<JsonValue>>,
+}
+
+/// The datastore as seen by a transaction: its current contents, with the
+/// transaction's uncommitted changes on top.
+#[derive(Clone, Copy)]
+struct View<'a> {
+    base: &'a InternalMemoryDatastore,
+    changes: &'a Changes,
+}
+
+impl<'a> View<'a> {
+    fn get_vertex(self, id: Uuid) -> Option<&'a Type> {
+        lookup(&self.base.vertices, &self.changes.vertices, &id)
+    }
+
+    fn get_edge(self, key: &EdgeKey) -> Option<(&'a EdgeKey, &'a DateTime<Utc>)> {
+        match self.changes.edges.get_key_value(key) {
+            Some((key, update_datetime)) => update_datetime.as_ref().map(|update_datetime| (key, update_datetime)),
+            None => self.base.edges.get_key_value(key),
+        }
+    }
+
+    fn get_vertex_property(self, id: Uuid, name: &str) -> Option<&'a JsonValue> {
+        let key = (id, name.to_string());
+        lookup(&self.base.vertex_properties, &self.changes.vertex_properties, &key)
+    }
+
+    fn get_edge_property(self, key: &EdgeKey, name: &str) -> Option<&'a JsonValue> {
+        let key = (key.clone(), name.to_string());
+        lookup(&self.base.edge_properties, &self.changes.edge_properties, &key)
+    }
+
+    /// Iterates over the properties of a vertex, ordered by name.
+    fn iterate_vertex_properties(self, id: Uuid) -> QueryIter<'a, (&'a (Uuid, String), &'a JsonValue)> {
+        let range = (id, "".to_string())..;
+
+        Box::new(
+            merge(
+                self.base.vertex_properties.range(range.clone()),
+                self.changes.vertex_properties.range(range),
+            )
+            .take_while(move |((property_vertex_id, _), _)| *property_vertex_id == id),
+        )
+    }
+
+    /// Iterates over the properties of an edge, ordered by name.
+    fn iterate_edge_properties(self, key: &EdgeKey) -> QueryIter<'a, (&'a (EdgeKey, String), &'a JsonValue)> {
+        let range = (key.clone(), "".to_string())..;
+        let key = key.clone();
+
+        Box::new(
+            merge(
+                self.base.edge_properties.range(range.clone()),
+                self.changes.edge_properties.range(range),
+            )
+            .take_while(move |((property_edge_key, _), _)| *property_edge_key == key),
+        )
+    }
+
+    fn get_vertex_count(self, t: Option<&Type>) -> u64 {
+        let matches = |value: Option<&Type>| match (value, t) {
+            (Some(value), Some(t)) => value == t,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        let mut count = match t {
+            Some(t) => self.base.vertices.values().filter(|v| v == &t).count() as i64,
+            None => self.base.vertices.len() as i64,
+        };
+
+        for (id, value) in &self.changes.vertices {
+            count += matches(value.as_ref()) as i64 - matches(self.base.vertices.get(id)) as i64;
+        }
+
+        count as u64
+    }
+
+    fn vertex_matches(self, id: Uuid, filters: &[PropertyFilter]) -> bool {
+        filters
+            .iter()
+            .all(|filter| filter.matches(self.get_vertex_property(id, &filter.name)))
+    }
+
+    fn edge_matches(self, key: &EdgeKey, filters: &[PropertyFilter]) -> bool {
+        filters
+            .iter()
+            .all(|filter| filter.matches(self.get_edge_property(key, &filter.name)))
+    }
+
+    fn get_vertex_values_by_query(self, q: VertexQuery) -> Result<QueryIter<'a, (Uuid, Type)>> {
         match q {
             VertexQuery::Range(range) => {
-                let mut iter: QueryIter<(&Uuid, &Type)> = if let Some(start_id) = range.start_id {
-                    Box::new(self.vertices.range(start_id..))
-                } else {
-                    Box::new(self.vertices.iter())
-                };
+                let bounds = (
+                    range.start_id.map_or(Bound::Unbounded, Bound::Included),
+                    Bound::Unbounded,
+                );
+                let mut iter: QueryIter<(&Uuid, &Type)> = Box::new(merge(
+                    self.base.vertices.range(bounds),
+                    self.changes.vertices.range(bounds),
+                ));
 
                 if let Some(t) = range.t {
                     iter = Box::new(iter.filter(move |(_, v)| v == &&t));
                 }
 
+                if !range.filters.is_empty() {
+                    let filters = range.filters;
+                    iter = Box::new(iter.filter(move |(k, _)| self.vertex_matches(**k, &filters)));
+                }
+
                 let iter: QueryIter<(Uuid, Type)> =
                     Box::new(iter.take(range.limit as usize).map(|(k, v)| (*k, v.clone())));
 
@@ -50,13 +321,18 @@ impl InternalMemoryDatastore {
                     specific
                         .ids
                         .into_iter()
-                        .filter_map(move |id| self.vertices.get(&id).map(|value| (id, value.clone()))),
+                        .filter_map(move |id| self.get_vertex(id).map(|value| (id, value.clone()))),
                 );
 
                 Ok(iter)
             }
             VertexQuery::Pipe(pipe) => {
-                let edge_values = self.get_edge_values_by_query(*pipe.inner)?;
+                let inner = match (*pipe.inner, pipe.cursor) {
+                    (EdgeQuery::Pipe(inner), Some(cursor)) => inner.cursor(cursor).into(),
+                    (inner, _) => inner,
+                };
+
+                let edge_values = self.get_edge_values_by_query(inner)?;
 
                 let iter: QueryIter<Uuid> = match pipe.direction {
                     EdgeDirection::Outbound => Box::new(edge_values.map(|(key, _)| key.outbound_id)),
@@ -64,7 +340,7 @@ impl InternalMemoryDatastore {
                 };
 
                 let mut iter: QueryIter<(Uuid, &Type)> = Box::new(
-                    iter.map(move |id| (id, self.vertices.get(&id)))
+                    iter.map(move |id| (id, self.get_vertex(id)))
                         .filter_map(|(k, v)| Some((k, v?))),
                 );
 
@@ -72,6 +348,11 @@ impl InternalMemoryDatastore {
                     iter = Box::new(iter.filter(move |(_, v)| v == &&t));
                 }
 
+                if !pipe.filters.is_empty() {
+                    let filters = pipe.filters;
+                    iter = Box::new(iter.filter(move |(k, _)| self.vertex_matches(*k, &filters)));
+                }
+
                 let iter: QueryIter<(Uuid, Type)> =
                     Box::new(iter.take(pipe.limit as usize).map(|(k, v)| (k, v.clone())));
 
@@ -80,59 +361,65 @@ impl InternalMemoryDatastore {
         }
     }
 
-    fn get_edge_values_by_query<'a>(&'a self, q: EdgeQuery) -> Result<QueryIter<'a, (EdgeKey, DateTime<Utc>)>> {
+    fn get_edge_values_by_query(self, q: EdgeQuery) -> Result<QueryIter<'a, (EdgeKey, DateTime<Utc>)>> {
         match q {
             EdgeQuery::Specific(specific) => {
-                let iter: QueryIter<(EdgeKey, DateTime<Utc>)> = Box::new(
-                    specific
-                        .keys
-                        .into_iter()
-                        .filter_map(move |key| self.edges.get(&key).map(|update_datetime| (key, *update_datetime))),
-                );
+                let iter: QueryIter<(EdgeKey, DateTime<Utc>)> =
+                    Box::new(specific.keys.into_iter().filter_map(move |key| {
+                        self.get_edge(&key).map(|(_, update_datetime)| (key, *update_datetime))
+                    }));
 
                 Ok(iter)
             }
             EdgeQuery::Pipe(pipe) => {
+                let ordered_by_id = matches!(*pipe.inner, VertexQuery::Range(_));
                 let iter = self.get_vertex_values_by_query(*pipe.inner)?;
 
-                let mut iter: QueryIter<(&EdgeKey, &DateTime<Utc>)> = match pipe.direction {
-                    EdgeDirection::Outbound => {
-                        let t = pipe.t.clone();
-
-                        let iter = iter.flat_map(move |(id, _)| {
-                            let lower_bound = match &t {
-                                Some(t) => EdgeKey::new(id, t.clone(), Uuid::default()),
-                                None => {
-                                    let empty_type = Type::default();
-                                    EdgeKey::new(id, empty_type, Uuid::default())
-                                }
-                            };
-
-                            self.edges
-                                .range(lower_bound..)
-                                .take_while(move |(key, _)| key.outbound_id == id)
-                        });
-
-                        Box::new(iter)
-                    }
-                    EdgeDirection::Inbound => {
-                        let mut candidate_ids = HashSet::new();
-                        for (id, _) in iter {
-                            candidate_ids.insert(id);
-                        }
-
-                        let iter = self
-                            .edges
-                            .iter()
-                            .filter(move |(key, _)| candidate_ids.contains(&key.inbound_id));
-
-                        Box::new(iter)
+                let direction = pipe.direction;
+                let t = pipe.t;
+
+                // Edges are returned in order of the ID of the vertex they're
+                // piped from, so that a cursor can be resumed from by
+                // comparison. Range queries are already ordered by ID, while
+                // anything else is gathered and sorted first.
+                let iter: QueryIter<(Uuid, Type)> = if ordered_by_id {
+                    iter
+                } else {
+                    let mut vertices: Vec<(Uuid, Type)> = iter.collect();
+                    vertices.sort_by_key(|(id, _)| *id);
+                    Box::new(vertices.into_iter())
+                };
+
+                let mut cursor = pipe.cursor.map(|cursor| match direction {
+                    EdgeDirection::Outbound => (
+                        cursor.key.outbound_id,
+                        (cursor.key.t, cursor.update_datetime, cursor.key.inbound_id),
+                    ),
+                    EdgeDirection::Inbound => (
+                        cursor.key.inbound_id,
+                        (cursor.key.t, cursor.update_datetime, cursor.key.outbound_id),
+                    ),
+                });
+
+                // If the cursor's vertex has since been deleted, or no longer
+                // matches the inner query, this resumes from the vertex that
+                // took its place
+                let iter: QueryIter<(Uuid, Type)> = match &cursor {
+                    Some((first_id, _)) => {
+                        let first_id = *first_id;
+                        Box::new(iter.skip_while(move |(id, _)| *id < first_id))
                     }
+                    None => iter,
                 };
 
-                if let Some(t) = pipe.t {
-                    iter = Box::new(iter.filter(move |(key, _)| key.t == t));
-                }
+                let mut iter: QueryIter<(&EdgeKey, &DateTime<Utc>)> = Box::new(iter.flat_map(move |(id, _)| {
+                    let after = match cursor.take() {
+                        Some((first_id, after)) if first_id == id => Some(after),
+                        _ => None,
+                    };
+
+                    self.ordered_edge_range(id, direction, t.clone(), after)
+                }));
 
                 if let Some(high) = pipe.high {
                     iter = Box::new(iter.filter(move |(_, update_datetime)| update_datetime <= &&high));
@@ -142,6 +429,11 @@ impl InternalMemoryDatastore {
                     iter = Box::new(iter.filter(move |(_, update_datetime)| update_datetime >= &&low));
                 }
 
+                if !pipe.filters.is_empty() {
+                    let filters = pipe.filters;
+                    iter = Box::new(iter.filter(move |(key, _)| self.edge_matches(key, &filters)));
+                }
+
                 let iter = iter
                     .take(pipe.limit as usize)
                     .map(move |(key, value)| (key.clone(), *value));
@@ -151,314 +443,948 @@ impl InternalMemoryDatastore {
         }
     }
 
-    fn delete_vertices(&mut self, vertices: Vec<Uuid>) {
-        for vertex_id in vertices {
-            self.vertices.remove(&vertex_id);
+    /// Iterates over the edges adjacent to a vertex, ordered by type and
+    /// then by the ID of the vertex at the other end. Inbound edges are read
+    /// through `reversed_edges`, so this is proportional to the vertex's
+    /// degree rather than the size of the graph.
+    ///
+    /// # Arguments
+    /// * `id` - The vertex.
+    /// * `direction` - Whether to iterate over outbound or inbound edges.
+    /// * `t` - Only iterates over edges of this type.
+    fn edge_range(
+        self,
+        id: Uuid,
+        direction: EdgeDirection,
+        t: Option<Type>,
+    ) -> QueryIter<'a, (&'a EdgeKey, &'a DateTime<Utc>)> {
+        let first_t = t.clone().unwrap_or_default();
+        let type_matches = move |key_t: &Type| t.as_ref().map_or(true, |t| key_t == t);
+
+        match direction {
+            EdgeDirection::Outbound => {
+                let bounds = (
+                    Bound::Included(EdgeKey::new(id, first_t, Uuid::default())),
+                    Bound::Unbounded,
+                );
 
-            let mut deletable_vertex_properties: Vec<(Uuid, String)> = Vec::new();
+                Box::new(
+                    merge(self.base.edges.range(bounds.clone()), self.changes.edges.range(bounds))
+                        .take_while(move |(key, _)| key.outbound_id == id && type_matches(&key.t)),
+                )
+            }
+            EdgeDirection::Inbound => {
+                let bounds = (Bound::Included((id, first_t, Uuid::default())), Bound::Unbounded);
+
+                Box::new(
+                    merge(
+                        self.base.reversed_edges.range(bounds.clone()).map(|key| (key, &())),
+                        self.changes.reversed_edges.range(bounds),
+                    )
+                    .take_while(move |((inbound_id, key_t, _), _)| *inbound_id == id && type_matches(key_t))
+                    .filter_map(move |((inbound_id, key_t, outbound_id), _)| {
+                        self.get_edge(&EdgeKey::new(*outbound_id, key_t.clone(), *inbound_id))
+                    }),
+                )
+            }
+        }
+    }
 
-            for (property_key, _) in self.vertex_properties.range((vertex_id, "".to_string())..) {
-                let &(ref property_vertex_id, _) = property_key;
+    /// Like `edge_range`, but ordered like the rocksdb datastore's edge
+    /// ranges, so that pipe queries page through edges in the same order
+    /// in both datastores: by type, with shorter type names first, then by
+    /// update datetime, newest first, then by the ID of the vertex at the
+    /// other end.
+    ///
+    /// # Arguments
+    /// * `id` - The vertex.
+    /// * `direction` - Whether to iterate over outbound or inbound edges.
+    /// * `t` - Only iterates over edges of this type.
+    /// * `after` - Only iterates over the edges that come after this type,
+    ///   update datetime and other vertex ID.
+    fn ordered_edge_range(
+        self,
+        id: Uuid,
+        direction: EdgeDirection,
+        t: Option<Type>,
+        after: Option<(Type, DateTime<Utc>, Uuid)>,
+    ) -> QueryIter<'a, (&'a EdgeKey, &'a DateTime<Utc>)> {
+        let position = move |key: &EdgeKey, update_datetime: DateTime<Utc>| {
+            let other_id = match direction {
+                EdgeDirection::Outbound => key.inbound_id,
+                EdgeDirection::Inbound => key.outbound_id,
+            };
 
-                if &vertex_id != property_vertex_id {
-                    break;
-                }
+            (key.t.0.len(), key.t.clone(), Reverse(update_datetime), other_id)
+        };
 
-                deletable_vertex_properties.push(property_key.clone());
-            }
+        let mut edges: Vec<(&EdgeKey, &DateTime<Utc>)> = self.edge_range(id, direction, t).collect();
+        edges.sort_by_key(|(key, update_datetime)| position(key, **update_datetime));
 
-            for property_key in deletable_vertex_properties {
-                self.vertex_properties.remove(&property_key);
+        match after {
+            Some((after_t, after_update_datetime, after_other_id)) => {
+                let after = (after_t.0.len(), after_t, Reverse(after_update_datetime), after_other_id);
+                Box::new(
+                    edges
+                        .into_iter()
+                        .skip_while(move |(key, update_datetime)| position(key, **update_datetime) <= after),
+                )
             }
+            None => Box::new(edges.into_iter()),
+        }
+    }
+}
 
-            let mut deletable_edges: Vec<EdgeKey> = Vec::new();
+impl<'a> Graph for View<'a> {
+    fn vertex_type(&self, id: Uuid) -> Result<Option<Type>> {
+        Ok(self.get_vertex(id).cloned())
+    }
 
-            for edge_key in self.edges.keys() {
-                if edge_key.outbound_id == vertex_id || edge_key.inbound_id == vertex_id {
-                    deletable_edges.push(edge_key.clone());
-                }
-            }
+    fn adjacent_edges(&self, ids: &[Uuid], direction: EdgeDirection, t: Option<&Type>) -> Result<Vec<EdgeKey>> {
+        Ok(ids
+            .iter()
+            .flat_map(|id| self.edge_range(*id, direction, t.cloned()))
+            .map(|(key, _)| key.clone())
+            .collect())
+    }
 
-            self.delete_edges(deletable_edges);
-        }
+    fn edge_property(&self, key: &EdgeKey, name: &str) -> Result<Option<JsonValue>> {
+        Ok(self.get_edge_property(key, name).cloned())
     }
+}
 
-    fn delete_edges(&mut self, edges: Vec<EdgeKey>) {
-        for edge_key in edges {
-            self.edges.remove(&edge_key);
+/// A write made by a transaction, which is replayed against the datastore
+/// when the transaction is committed.
+#[derive(Debug)]
+enum WriteOp {
+    CreateVertex(Vertex),
+    DeleteVertices(Vec<Uuid>),
+    CreateEdge(EdgeKey, DateTime<Utc>),
+    DeleteEdges(Vec<EdgeKey>),
+    SetVertexProperties(Vec<Uuid>, String, JsonValue),
+    DeleteVertexProperties(Vec<Uuid>, String),
+    SetEdgeProperties(Vec<EdgeKey>, String, JsonValue),
+    DeleteEdgeProperties(Vec<EdgeKey>, String),
+}
 
-            let mut deletable_edge_properties: Vec<(EdgeKey, String)> = Vec::new();
+/// Records writes as changes on top of a datastore.
+struct Writer<'a> {
+    base: &'a InternalMemoryDatastore,
+    changes: &'a mut Changes,
+}
 
-            for (property_key, _) in self.edge_properties.range((edge_key.clone(), "".to_string())..) {
-                let &(ref property_edge_key, _) = property_key;
+impl<'a> Writer<'a> {
+    fn view(&self) -> View<'_> {
+        View {
+            base: self.base,
+            changes: self.changes,
+        }
+    }
 
-                if &edge_key != property_edge_key {
-                    break;
+    /// Applies a write. Returns false if nothing was written, i.e. when
+    /// creating a vertex that already exists, or an edge whose vertices
+    /// don't exist. Properties are only set on vertices and edges that
+    /// exist, since they may have been deleted since the write was made.
+    fn apply(&mut self, op: &WriteOp) -> bool {
+        match op {
+            WriteOp::CreateVertex(vertex) => {
+                if self.view().get_vertex(vertex.id).is_some() {
+                    return false;
                 }
 
-                deletable_edge_properties.push(property_key.clone());
+                self.changes.vertices.insert(vertex.id, Some(vertex.t.clone()));
             }
+            WriteOp::DeleteVertices(ids) => {
+                for id in ids {
+                    self.delete_vertex(*id);
+                }
+            }
+            WriteOp::CreateEdge(key, update_datetime) => {
+                let view = self.view();
+
+                if view.get_vertex(key.outbound_id).is_none() || view.get_vertex(key.inbound_id).is_none() {
+                    return false;
+                }
 
-            for property_key in deletable_edge_properties {
-                self.edge_properties.remove(&property_key);
+                self.set_edge(key, Some(*update_datetime));
+            }
+            WriteOp::DeleteEdges(keys) => {
+                for key in keys {
+                    self.delete_edge(key);
+                }
+            }
+            WriteOp::SetVertexProperties(ids, name, value) => {
+                for id in ids {
+                    if self.view().get_vertex(*id).is_some() {
+                        self.changes
+                            .vertex_properties
+                            .insert((*id, name.clone()), Some(value.clone()));
+                    }
+                }
+            }
+            WriteOp::DeleteVertexProperties(ids, name) => {
+                for id in ids {
+                    self.changes.vertex_properties.insert((*id, name.clone()), None);
+                }
+            }
+            WriteOp::SetEdgeProperties(keys, name, value) => {
+                for key in keys {
+                    if self.view().get_edge(key).is_some() {
+                        self.changes
+                            .edge_properties
+                            .insert((key.clone(), name.clone()), Some(value.clone()));
+                    }
+                }
+            }
+            WriteOp::DeleteEdgeProperties(keys, name) => {
+                for key in keys {
+                    self.changes.edge_properties.insert((key.clone(), name.clone()), None);
+                }
             }
         }
+
+        true
+    }
+
+    fn set_edge(&mut self, key: &EdgeKey, update_datetime: Option<DateTime<Utc>>) {
+        self.changes.reversed_edges.insert(
+            (key.inbound_id, key.t.clone(), key.outbound_id),
+            update_datetime.map(|_| ()),
+        );
+        self.changes.edges.insert(key.clone(), update_datetime);
+    }
+
+    fn delete_vertex(&mut self, id: Uuid) {
+        let view = self.view();
+
+        let properties: Vec<(Uuid, String)> = view
+            .iterate_vertex_properties(id)
+            .map(|(property_key, _)| property_key.clone())
+            .collect();
+
+        let edges: Vec<EdgeKey> = view
+            .edge_range(id, EdgeDirection::Outbound, None)
+            .chain(view.edge_range(id, EdgeDirection::Inbound, None))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        self.changes.vertices.insert(id, None);
+
+        for property_key in properties {
+            self.changes.vertex_properties.insert(property_key, None);
+        }
+
+        for key in edges {
+            self.delete_edge(&key);
+        }
+    }
+
+    fn delete_edge(&mut self, key: &EdgeKey) {
+        let properties: Vec<(EdgeKey, String)> = self
+            .view()
+            .iterate_edge_properties(key)
+            .map(|(property_key, _)| property_key.clone())
+            .collect();
+
+        self.set_edge(key, None);
+
+        for property_key in properties {
+            self.changes.edge_properties.insert(property_key, None);
+        }
+    }
+}
+
+// The default graph and the named graphs, which are shared by every
+// `MemoryDatastore` that refers to any of them.
+#[derive(Debug, Default)]
+struct MemoryGraphs {
+    default: Arc<RwLock<InternalMemoryDatastore>>,
+    named: RwLock<BTreeMap<String, Arc<RwLock<InternalMemoryDatastore>>>>,
+}
+
+impl MemoryGraphs {
+    fn new(default: InternalMemoryDatastore, named: BTreeMap<String, InternalMemoryDatastore>) -> Self {
+        Self {
+            default: Arc::new(RwLock::new(default)),
+            named: RwLock::new(
+                named
+                    .into_iter()
+                    .map(|(name, datastore)| (name, Arc::new(RwLock::new(datastore))))
+                    .collect(),
+            ),
+        }
     }
 }
 
-/// An in-memory-only datastore.
+/// An in-memory datastore.
+///
+/// By default, data is not persisted. Datastores created via
+/// `create_with_path` or `read_from` are associated with a file, which they
+/// can be saved to with `sync`. Named graphs are saved to the same file.
 #[derive(Debug, Clone)]
-pub struct MemoryDatastore(Arc<RwLock<InternalMemoryDatastore>>);
+pub struct MemoryDatastore {
+    datastore: Arc<RwLock<InternalMemoryDatastore>>,
+    graphs: Arc<MemoryGraphs>,
+    path: Option<PathBuf>,
+}
 
 impl MemoryDatastore {
     /// Creates a new in-memory datastore.
     pub fn default() -> MemoryDatastore {
+        Self::with_graphs(MemoryGraphs::default(), None)
+    }
+
+    fn with_graphs(graphs: MemoryGraphs, path: Option<PathBuf>) -> MemoryDatastore {
         Self {
-            0: Arc::new(RwLock::new(InternalMemoryDatastore {
-                edge_properties: BTreeMap::new(),
-                edges: BTreeMap::new(),
-                vertex_properties: BTreeMap::new(),
-                vertices: BTreeMap::new(),
-            })),
+            datastore: graphs.default.clone(),
+            graphs: Arc::new(graphs),
+            path,
         }
     }
+
+    /// Creates a new, empty in-memory datastore that is persisted to a file.
+    /// The file is written immediately, replacing any existing one, and is
+    /// updated on every call to `sync`.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the file.
+    ///
+    /// # Errors
+    /// Returns an error if the file could not be written.
+    pub fn create_with_path<P: Into<PathBuf>>(path: P) -> Result<MemoryDatastore> {
+        let datastore = Self::with_graphs(MemoryGraphs::default(), Some(path.into()));
+        datastore.sync()?;
+        Ok(datastore)
+    }
+
+    /// Reads an in-memory datastore from a file that was previously written
+    /// by `sync`. Calls to `sync` will write back to the same file.
+    ///
+    /// # Arguments
+    /// * `path` - The path to the file.
+    ///
+    /// # Errors
+    /// Returns an error if the file could not be read, or is not a valid
+    /// datastore file.
+    pub fn read_from<P: Into<PathBuf>>(path: P) -> Result<MemoryDatastore> {
+        let path = path.into();
+        let mut reader = BufReader::new(File::open(&path)?);
+        let mut default: InternalMemoryDatastore = bincode::deserialize_from(&mut reader)?;
+
+        // The default graph is followed by the named graphs, except in files
+        // written before named graphs were supported
+        let mut named: BTreeMap<String, InternalMemoryDatastore> = if reader.fill_buf()?.is_empty() {
+            BTreeMap::new()
+        } else {
+            bincode::deserialize_from(&mut reader)?
+        };
+
+        default.rebuild_indices();
+
+        for datastore in named.values_mut() {
+            datastore.rebuild_indices();
+        }
+
+        Ok(Self::with_graphs(MemoryGraphs::new(default, named), Some(path)))
+    }
+
+    /// Saves the datastore to its file. This is a no-op for datastores that
+    /// are not persisted.
+    ///
+    /// The datastore is first written to a temporary file next to the
+    /// destination, which then replaces it, so a crash mid-write won't
+    /// corrupt the previously saved data. Writes are blocked while syncing.
+    ///
+    /// # Errors
+    /// Returns an error if the file could not be written.
+    pub fn sync(&self) -> Result<()> {
+        if let Some(ref path) = self.path {
+            let named = self.graphs.named.read().unwrap();
+            let default = self.graphs.default.read().unwrap();
+            let named_guards: Vec<(&String, RwLockReadGuard<InternalMemoryDatastore>)> = named
+                .iter()
+                .map(|(name, datastore)| (name, datastore.read().unwrap()))
+                .collect();
+            let named_datastores: BTreeMap<&String, &InternalMemoryDatastore> = named_guards
+                .iter()
+                .map(|(name, datastore)| (*name, &**datastore))
+                .collect();
+
+            let mut temp_path = path.clone().into_os_string();
+            temp_path.push(".tmp");
+
+            let file = File::create(&temp_path)?;
+            let mut writer = BufWriter::new(&file);
+            bincode::serialize_into(&mut writer, &*default)?;
+            bincode::serialize_into(&mut writer, &named_datastores)?;
+            writer.flush()?;
+            drop(writer);
+            file.sync_all()?;
+            rename(&temp_path, path)?;
+        }
+
+        Ok(())
+    }
 }
 
 impl Datastore for MemoryDatastore {
     type Trans = MemoryTransaction;
 
     fn transaction(&self) -> Result<Self::Trans> {
-        Ok(MemoryTransaction {
-            datastore: Arc::clone(&self.0),
-        })
+        Ok(MemoryTransaction::new(Arc::clone(&self.datastore)))
+    }
+}
+
+impl NamedGraphs for MemoryDatastore {
+    fn create_graph(&self, name: &str) -> Result<bool> {
+        validate_graph_name(name)?;
+        let mut named = self.graphs.named.write().unwrap();
+
+        if named.contains_key(name) {
+            return Ok(false);
+        }
+
+        named.insert(
+            name.to_string(),
+            Arc::new(RwLock::new(InternalMemoryDatastore::default())),
+        );
+        Ok(true)
+    }
+
+    // Datastores that still refer to a dropped graph can keep using it, but
+    // it's no longer reachable by name, and isn't saved by `sync`.
+    fn drop_graph(&self, name: &str) -> Result<bool> {
+        validate_graph_name(name)?;
+        Ok(self.graphs.named.write().unwrap().remove(name).is_some())
+    }
+
+    fn list_graphs(&self) -> Result<Vec<String>> {
+        Ok(self.graphs.named.read().unwrap().keys().cloned().collect())
+    }
+
+    fn graph(&self, name: &str) -> Result<Option<Self>> {
+        validate_graph_name(name)?;
+
+        Ok(self.graphs.named.read().unwrap().get(name).map(|datastore| Self {
+            datastore: datastore.clone(),
+            graphs: self.graphs.clone(),
+            path: self.path.clone(),
+        }))
     }
 }
 
 /// A transaction for manipulating in-memory-only datastores.
+///
+/// Writes are staged in the transaction until it's committed, so until then
+/// they're only visible to the transaction itself. Reads see the datastore
+/// as it currently is, with the transaction's writes on top.
+///
+/// Committing replays the writes against the datastore all at once, so that
+/// they take into account anything committed by other transactions in the
+/// meantime; e.g. an edge isn't created if one of its vertices has since
+/// been deleted. Rolling back discards the writes.
 #[derive(Debug)]
 pub struct MemoryTransaction {
     datastore: Arc<RwLock<InternalMemoryDatastore>>,
+    pending: Mutex<Pending>,
 }
 
-impl Transaction for MemoryTransaction {
-    fn create_vertex(&self, vertex: &Vertex) -> Result<bool> {
-        let mut datastore = self.datastore.write().unwrap();
-        let mut inserted = false;
+/// The writes made by a transaction since it was last committed or rolled
+/// back.
+#[derive(Debug, Default)]
+struct Pending {
+    /// The combined changes of the writes. Iterators that are still reading
+    /// them hold a reference too.
+    changes: Arc<Changes>,
+    ops: Vec<WriteOp>,
+}
 
-        datastore.vertices.entry(vertex.id).or_insert_with(|| {
-            inserted = true;
-            vertex.t.clone()
-        });
+/// How many results paged iterators read at a time.
+const ITER_PAGE_SIZE: u32 = 1000;
 
-        Ok(inserted)
-    }
+/// Reads the results of a query a page at a time, resuming each page from
+/// where the previous one left off.
+trait Pager {
+    type Item;
 
-    fn get_vertices<Q: Into<VertexQuery>>(&self, q: Q) -> Result<Vec<Vertex>> {
-        let datastore = self.datastore.read().unwrap();
-        let iter = datastore.get_vertex_values_by_query(q.into())?;
-        let iter = iter.map(|(uuid, t)| Vertex::with_id(uuid, t));
-        Ok(iter.collect())
-    }
+    /// Reads the next page of results. Returns `None` once there are none
+    /// left; pages before then may be empty.
+    fn next_page(&mut self, view: View) -> Result<Option<Vec<Self::Item>>>;
+}
 
-    fn delete_vertices<Q: Into<VertexQuery>>(&self, q: Q) -> Result<()> {
-        let mut datastore = self.datastore.write().unwrap();
-        let deletable_vertices = datastore
-            .get_vertex_values_by_query(q.into())?
-            .map(|(k, _)| k)
-            .collect();
-        datastore.delete_vertices(deletable_vertices);
-        Ok(())
-    }
+/// Pages through the results of a vertex query. Range queries resume from
+/// the ID after the last one read, specific queries from the next ID in the
+/// list, and pipe queries from the last edge read by their inner query.
+enum VertexPager {
+    Range(RangeVertexQuery),
+    Specific(vec::IntoIter<Uuid>),
+    Pipe {
+        inner: EdgePager,
+        direction: EdgeDirection,
+        limit: u32,
+        t: Option<Type>,
+        filters: Vec<PropertyFilter>,
+    },
+    Done,
+}
 
-    fn get_vertex_count(&self) -> Result<u64> {
-        let datastore = self.datastore.read().unwrap();
-        Ok(datastore.vertices.len() as u64)
+impl VertexPager {
+    fn new(q: VertexQuery) -> Self {
+        match q {
+            VertexQuery::Range(range) => VertexPager::Range(range),
+            VertexQuery::Specific(specific) => VertexPager::Specific(specific.ids.into_iter()),
+            VertexQuery::Pipe(pipe) => {
+                let inner = match (*pipe.inner, pipe.cursor) {
+                    (EdgeQuery::Pipe(inner), Some(cursor)) => inner.cursor(cursor).into(),
+                    (inner, _) => inner,
+                };
+
+                VertexPager::Pipe {
+                    inner: EdgePager::new(inner),
+                    direction: pipe.direction,
+                    limit: pipe.limit,
+                    t: pipe.t,
+                    filters: pipe.filters,
+                }
+            }
+        }
     }
+}
 
-    fn create_edge(&self, key: &EdgeKey) -> Result<bool> {
-        let mut datastore = self.datastore.write().unwrap();
+impl Pager for VertexPager {
+    type Item = (Uuid, Type);
 
-        if !datastore.vertices.contains_key(&key.outbound_id) || !datastore.vertices.contains_key(&key.inbound_id) {
-            return Ok(false);
-        }
+    fn next_page(&mut self, view: View) -> Result<Option<Vec<Self::Item>>> {
+        let page: Vec<(Uuid, Type)> = match self {
+            VertexPager::Range(range) if range.limit > 0 => {
+                let page_limit = range.limit.min(ITER_PAGE_SIZE);
+                let q = RangeVertexQuery {
+                    limit: page_limit,
+                    ..range.clone()
+                };
+                let page: Vec<(Uuid, Type)> = view.get_vertex_values_by_query(q.into())?.collect();
+                range.limit -= page.len() as u32;
+
+                match page.last() {
+                    Some((id, _)) if page.len() as u32 == page_limit => match next_uuid(*id) {
+                        Ok(next_id) => range.start_id = Some(next_id),
+                        Err(_) => range.limit = 0,
+                    },
+                    _ => range.limit = 0,
+                }
 
-        datastore.edges.insert(key.clone(), Utc::now());
-        Ok(true)
-    }
+                page
+            }
+            VertexPager::Specific(ids) if ids.len() > 0 => {
+                let ids = ids.by_ref().take(ITER_PAGE_SIZE as usize).collect();
+                view.get_vertex_values_by_query(SpecificVertexQuery::new(ids).into())?
+                    .collect()
+            }
+            VertexPager::Pipe {
+                inner,
+                direction,
+                limit,
+                t,
+                filters,
+            } if *limit > 0 => {
+                let keys = match inner.next_page(view)? {
+                    Some(edges) => edges.into_iter().map(|(key, _)| key).collect(),
+                    None => return Ok(None),
+                };
 
-    fn get_edges<Q: Into<EdgeQuery>>(&self, q: Q) -> Result<Vec<Edge>> {
-        let edge_values: Vec<(EdgeKey, DateTime<Utc>)> = {
-            let datastore = self.datastore.read().unwrap();
-            let iter = datastore.get_edge_values_by_query(q.into())?;
-            iter.collect()
+                let q = PipeVertexQuery {
+                    inner: Box::new(SpecificEdgeQuery::new(keys).into()),
+                    direction: *direction,
+                    limit: *limit,
+                    t: t.clone(),
+                    filters: filters.clone(),
+                    cursor: None,
+                };
+                let page: Vec<(Uuid, Type)> = view.get_vertex_values_by_query(q.into())?.collect();
+                *limit -= page.len() as u32;
+                page
+            }
+            _ => {
+                *self = VertexPager::Done;
+                return Ok(None);
+            }
         };
 
-        let iter = edge_values
-            .into_iter()
-            .map(|(key, update_datetime)| Edge::new(key, update_datetime));
-        Ok(iter.collect())
+        Ok(Some(page))
     }
+}
 
-    fn delete_edges<Q: Into<EdgeQuery>>(&self, q: Q) -> Result<()> {
-        let mut datastore = self.datastore.write().unwrap();
-        let deletable_edges: Vec<EdgeKey> = datastore.get_edge_values_by_query(q.into())?.map(|(k, _)| k).collect();
-        datastore.delete_edges(deletable_edges);
-        Ok(())
+/// Pages through the results of an edge query. Specific queries resume
+/// from the next key in the list, and pipe queries from the last edge read.
+enum EdgePager {
+    Specific(vec::IntoIter<EdgeKey>),
+    Pipe(PipeEdgeQuery),
+    Done,
+}
+
+impl EdgePager {
+    fn new(q: EdgeQuery) -> Self {
+        match q {
+            EdgeQuery::Specific(specific) => EdgePager::Specific(specific.keys.into_iter()),
+            EdgeQuery::Pipe(pipe) => EdgePager::Pipe(pipe),
+        }
     }
+}
 
-    fn get_edge_count(&self, id: Uuid, t: Option<&Type>, direction: EdgeDirection) -> Result<u64> {
-        let datastore = self.datastore.read().unwrap();
+impl Pager for EdgePager {
+    type Item = (EdgeKey, DateTime<Utc>);
 
-        if direction == EdgeDirection::Outbound {
-            let lower_bound = match t {
-                Some(t) => EdgeKey::new(id, t.clone(), Uuid::default()),
-                None => {
-                    let empty_type = Type::default();
-                    EdgeKey::new(id, empty_type, Uuid::default())
+    fn next_page(&mut self, view: View) -> Result<Option<Vec<Self::Item>>> {
+        let page: Vec<(EdgeKey, DateTime<Utc>)> = match self {
+            EdgePager::Specific(keys) if keys.len() > 0 => {
+                let keys = keys.by_ref().take(ITER_PAGE_SIZE as usize).collect();
+                view.get_edge_values_by_query(SpecificEdgeQuery::new(keys).into())?
+                    .collect()
+            }
+            EdgePager::Pipe(pipe) if pipe.limit > 0 => {
+                let page_limit = pipe.limit.min(ITER_PAGE_SIZE);
+                let q = PipeEdgeQuery {
+                    limit: page_limit,
+                    ..pipe.clone()
+                };
+                let page: Vec<(EdgeKey, DateTime<Utc>)> = view.get_edge_values_by_query(q.into())?.collect();
+                pipe.limit -= page.len() as u32;
+
+                match page.last() {
+                    Some((key, update_datetime)) if page.len() as u32 == page_limit => {
+                        pipe.cursor = Some(EdgeCursor::new(key.clone(), *update_datetime));
+                    }
+                    _ => pipe.limit = 0,
                 }
+
+                page
+            }
+            _ => {
+                *self = EdgePager::Done;
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(page))
+    }
+}
+
+/// An iterator over query results that reads them a page at a time. The
+/// datastore's read lock is only held while a page is read, so writers
+/// aren't blocked for as long as the iterator is alive. Each page sees
+/// whatever had been committed when it was read, along with the
+/// transaction's changes as they were when the iterator was created.
+struct PagedIter<'a, P: Pager> {
+    datastore: &'a RwLock<InternalMemoryDatastore>,
+    changes: Arc<Changes>,
+    pager: P,
+    page: vec::IntoIter<P::Item>,
+    done: bool,
+}
+
+impl<'a, P: Pager> Iterator for PagedIter<'a, P> {
+    type Item = Result<P::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if let Some(item) = self.page.next() {
+                return Some(Ok(item));
+            }
+
+            let datastore = self.datastore.read().unwrap();
+            let view = View {
+                base: &datastore,
+                changes: &self.changes,
             };
-            let range = datastore.edges.range(lower_bound..);
 
-            let range = range.take_while(|&(k, _)| {
-                if let Some(t) = t {
-                    k.outbound_id == id && &k.t == t
-                } else {
-                    k.outbound_id == id
+            match self.pager.next_page(view) {
+                Ok(Some(page)) => self.page = page.into_iter(),
+                Ok(None) => self.done = true,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
                 }
-            });
+            }
+        }
 
-            Ok(range.count() as u64)
-        } else {
-            let range = datastore.edges.iter().filter(|&(k, _)| {
-                if let Some(t) = t {
-                    k.inbound_id == id && &k.t == t
-                } else {
-                    k.inbound_id == id
-                }
-            });
+        None
+    }
+}
 
-            Ok(range.count() as u64)
+impl MemoryTransaction {
+    fn new(datastore: Arc<RwLock<InternalMemoryDatastore>>) -> Self {
+        Self {
+            datastore,
+            pending: Mutex::new(Pending::default()),
         }
     }
 
-    fn get_vertex_properties(&self, q: VertexPropertyQuery) -> Result<Vec<VertexProperty>> {
-        let mut result = Vec::new();
+    /// Stages a write. Returns false if nothing was written; see
+    /// `Writer::apply`.
+    fn write(&self, op: WriteOp) -> bool {
+        let mut pending = self.pending.lock().unwrap();
         let datastore = self.datastore.read().unwrap();
-        let vertex_values = datastore.get_vertex_values_by_query(q.inner)?;
 
-        for (id, _) in vertex_values {
-            let property_value = datastore.vertex_properties.get(&(id, q.name.clone()));
+        let written = Writer {
+            base: &datastore,
+            changes: Arc::make_mut(&mut pending.changes),
+        }
+        .apply(&op);
 
-            if let Some(property_value) = property_value {
-                result.push(VertexProperty::new(id, property_value.clone()));
-            }
+        if written {
+            pending.ops.push(op);
         }
 
-        Ok(result)
+        written
     }
 
-    fn get_all_vertex_properties<Q: Into<VertexQuery>>(&self, q: Q) -> Result<Vec<VertexProperties>> {
+    /// Reads from the datastore, with the transaction's changes on top.
+    fn read<T, F: FnOnce(View<'_>) -> T>(&self, f: F) -> T {
+        let changes = self.pending.lock().unwrap().changes.clone();
         let datastore = self.datastore.read().unwrap();
-        let vertex_values = datastore.get_vertex_values_by_query(q.into())?;
 
-        let mut result = Vec::new();
-        for (id, t) in vertex_values {
-            let from = &(id, "".to_string());
-            let to = &(crate::util::next_uuid(id).unwrap(), "".to_string());
+        f(View {
+            base: &datastore,
+            changes: &changes,
+        })
+    }
 
-            let properties = datastore.vertex_properties.range(from..to);
-            result.push(VertexProperties::new(
-                Vertex::with_id(id, t),
-                properties
-                    .map(|(n, p)| NamedProperty::new(n.1.clone(), p.clone()))
-                    .collect(),
-            ));
+    /// Builds an iterator that reads from the datastore a page at a time,
+    /// with the transaction's current changes on top.
+    fn iterate_paged<P: Pager>(&self, pager: P) -> PagedIter<'_, P> {
+        PagedIter {
+            datastore: &self.datastore,
+            changes: self.pending.lock().unwrap().changes.clone(),
+            pager,
+            page: Vec::new().into_iter(),
+            done: false,
         }
+    }
+}
 
-        Ok(result)
+impl Transaction for MemoryTransaction {
+    fn create_vertex(&self, vertex: &Vertex) -> Result<bool> {
+        Ok(self.write(WriteOp::CreateVertex(vertex.clone())))
     }
 
-    fn set_vertex_properties(&self, q: VertexPropertyQuery, value: &JsonValue) -> Result<()> {
-        let mut datastore = self.datastore.write().unwrap();
-        let vertex_values: Vec<(Uuid, Type)> = datastore.get_vertex_values_by_query(q.inner)?.collect();
+    fn get_vertices<Q: Into<VertexQuery>>(&self, q: Q) -> Result<Vec<Vertex>> {
+        self.read(|view| {
+            let iter = view.get_vertex_values_by_query(q.into())?;
+            Ok(iter.map(|(uuid, t)| Vertex::with_id(uuid, t)).collect())
+        })
+    }
 
-        for (id, _) in vertex_values.into_iter() {
-            datastore.vertex_properties.insert((id, q.name.clone()), value.clone());
-        }
+    fn iter_vertices<'a, Q: Into<VertexQuery>>(
+        &'a self,
+        q: Q,
+    ) -> Result<Box<dyn Iterator<Item = Result<Vertex>> + 'a>> {
+        let iter = self.iterate_paged(VertexPager::new(q.into()));
+        Ok(Box::new(
+            iter.map(|item| item.map(|(uuid, t)| Vertex::with_id(uuid, t))),
+        ))
+    }
 
+    fn delete_vertices<Q: Into<VertexQuery>>(&self, q: Q) -> Result<()> {
+        let ids = self.read(|view| -> Result<Vec<Uuid>> {
+            Ok(view.get_vertex_values_by_query(q.into())?.map(|(k, _)| k).collect())
+        })?;
+
+        self.write(WriteOp::DeleteVertices(ids));
         Ok(())
     }
 
-    fn delete_vertex_properties(&self, q: VertexPropertyQuery) -> Result<()> {
-        let mut datastore = self.datastore.write().unwrap();
+    fn get_vertex_count(&self, t: Option<&Type>) -> Result<u64> {
+        Ok(self.read(|view| view.get_vertex_count(t)))
+    }
 
-        let vertex_values: Vec<(Uuid, Type)> = datastore.get_vertex_values_by_query(q.inner)?.collect();
+    fn create_edge(&self, key: &EdgeKey) -> Result<bool> {
+        self.create_edge_with_datetime(key, Utc::now())
+    }
 
-        for (id, _) in vertex_values.into_iter() {
-            datastore.vertex_properties.remove(&(id, q.name.clone()));
-        }
+    fn create_edge_with_datetime(&self, key: &EdgeKey, datetime: DateTime<Utc>) -> Result<bool> {
+        Ok(self.write(WriteOp::CreateEdge(key.clone(), datetime)))
+    }
 
+    fn get_edges<Q: Into<EdgeQuery>>(&self, q: Q) -> Result<Vec<Edge>> {
+        self.read(|view| {
+            let iter = view.get_edge_values_by_query(q.into())?;
+            Ok(iter
+                .map(|(key, update_datetime)| Edge::new(key, update_datetime))
+                .collect())
+        })
+    }
+
+    fn iter_edges<'a, Q: Into<EdgeQuery>>(&'a self, q: Q) -> Result<Box<dyn Iterator<Item = Result<Edge>> + 'a>> {
+        let iter = self.iterate_paged(EdgePager::new(q.into()));
+        Ok(Box::new(iter.map(|item| {
+            item.map(|(key, update_datetime)| Edge::new(key, update_datetime))
+        })))
+    }
+
+    fn delete_edges<Q: Into<EdgeQuery>>(&self, q: Q) -> Result<()> {
+        let keys = self.read(|view| -> Result<Vec<EdgeKey>> {
+            Ok(view.get_edge_values_by_query(q.into())?.map(|(k, _)| k).collect())
+        })?;
+
+        self.write(WriteOp::DeleteEdges(keys));
         Ok(())
     }
 
-    fn get_edge_properties(&self, q: EdgePropertyQuery) -> Result<Vec<EdgeProperty>> {
-        let mut result = Vec::new();
-        let datastore = self.datastore.read().unwrap();
-        let edge_values = datastore.get_edge_values_by_query(q.inner)?;
+    fn get_edge_count(&self, id: Uuid, t: Option<&Type>, direction: EdgeDirection) -> Result<u64> {
+        Ok(self.read(|view| view.edge_range(id, direction, t.cloned()).count() as u64))
+    }
 
-        for (key, _) in edge_values {
-            let property_value = datastore.edge_properties.get(&(key.clone(), q.name.clone()));
+    fn traverse(&self, q: TraversalQuery) -> Result<Vec<Vertex>> {
+        self.read(|view| {
+            let start = view.get_vertex_values_by_query((*q.start).clone())?.collect();
+            breadth_first(&view, start, &q)
+        })
+    }
+
+    fn shortest_path(&self, q: ShortestPathQuery) -> Result<Option<Vec<EdgeKey>>> {
+        self.read(|view| shortest_path(&view, &q))
+    }
 
-            if let Some(property_value) = property_value {
-                result.push(EdgeProperty::new(key, property_value.clone()));
+    fn get_vertex_properties(&self, q: VertexPropertyQuery) -> Result<Vec<VertexProperty>> {
+        self.read(|view| {
+            let mut result = Vec::new();
+
+            for (id, _) in view.get_vertex_values_by_query(q.inner)? {
+                if let Some(property_value) = view.get_vertex_property(id, &q.name) {
+                    result.push(VertexProperty::new(id, property_value.clone()));
+                }
             }
-        }
 
-        Ok(result)
+            Ok(result)
+        })
+    }
+
+    fn get_all_vertex_properties<Q: Into<VertexQuery>>(&self, q: Q) -> Result<Vec<VertexProperties>> {
+        self.read(|view| {
+            let mut result = Vec::new();
+
+            for (id, t) in view.get_vertex_values_by_query(q.into())? {
+                let properties = view.iterate_vertex_properties(id);
+                result.push(VertexProperties::new(
+                    Vertex::with_id(id, t),
+                    properties
+                        .map(|(n, p)| NamedProperty::new(n.1.clone(), p.clone()))
+                        .collect(),
+                ));
+            }
+
+            Ok(result)
+        })
+    }
+
+    fn set_vertex_properties(&self, q: VertexPropertyQuery, value: &JsonValue) -> Result<()> {
+        let inner = q.inner;
+        let ids = self.read(|view| -> Result<Vec<Uuid>> {
+            Ok(view.get_vertex_values_by_query(inner)?.map(|(k, _)| k).collect())
+        })?;
+
+        self.write(WriteOp::SetVertexProperties(ids, q.name, value.clone()));
+        Ok(())
+    }
+
+    fn delete_vertex_properties(&self, q: VertexPropertyQuery) -> Result<()> {
+        let inner = q.inner;
+        let ids = self.read(|view| -> Result<Vec<Uuid>> {
+            Ok(view.get_vertex_values_by_query(inner)?.map(|(k, _)| k).collect())
+        })?;
+
+        self.write(WriteOp::DeleteVertexProperties(ids, q.name));
+        Ok(())
+    }
+
+    fn get_edge_properties(&self, q: EdgePropertyQuery) -> Result<Vec<EdgeProperty>> {
+        self.read(|view| {
+            let mut result = Vec::new();
+
+            for (key, _) in view.get_edge_values_by_query(q.inner)? {
+                if let Some(property_value) = view.get_edge_property(&key, &q.name) {
+                    result.push(EdgeProperty::new(key, property_value.clone()));
+                }
+            }
+
+            Ok(result)
+        })
     }
 
     fn get_all_edge_properties<Q: Into<EdgeQuery>>(&self, q: Q) -> Result<Vec<EdgeProperties>> {
-        let datastore = self.datastore.read().unwrap();
-        let edge_values = datastore.get_edge_values_by_query(q.into())?;
-
-        let mut result = Vec::new();
-        for (id, t) in edge_values {
-            let from = &(id.clone(), "".to_string());
-
-            let properties = datastore
-                .edge_properties
-                .range(from..)
-                .take_while(|((key, _name), _value)| *key == id);
-            result.push(EdgeProperties::new(
-                Edge::new(id.clone(), t),
-                properties
+        self.read(|view| {
+            let mut result = Vec::new();
+
+            for (key, update_datetime) in view.get_edge_values_by_query(q.into())? {
+                let properties = view
+                    .iterate_edge_properties(&key)
                     .map(|(n, p)| NamedProperty::new(n.1.clone(), p.clone()))
-                    .collect(),
-            ));
-        }
+                    .collect();
+                result.push(EdgeProperties::new(Edge::new(key, update_datetime), properties));
+            }
 
-        Ok(result)
+            Ok(result)
+        })
     }
 
     fn set_edge_properties(&self, q: EdgePropertyQuery, value: &JsonValue) -> Result<()> {
-        let mut datastore = self.datastore.write().unwrap();
-        let edge_values: Vec<(EdgeKey, DateTime<Utc>)> = datastore.get_edge_values_by_query(q.inner)?.collect();
-
-        for (key, _) in edge_values.into_iter() {
-            datastore.edge_properties.insert((key, q.name.clone()), value.clone());
-        }
+        let inner = q.inner;
+        let keys = self.read(|view| -> Result<Vec<EdgeKey>> {
+            Ok(view.get_edge_values_by_query(inner)?.map(|(k, _)| k).collect())
+        })?;
 
+        self.write(WriteOp::SetEdgeProperties(keys, q.name, value.clone()));
         Ok(())
     }
 
     fn delete_edge_properties(&self, q: EdgePropertyQuery) -> Result<()> {
-        let mut datastore = self.datastore.write().unwrap();
-        let edge_values: Vec<(EdgeKey, DateTime<Utc>)> = datastore.get_edge_values_by_query(q.inner)?.collect();
+        let inner = q.inner;
+        let keys = self.read(|view| -> Result<Vec<EdgeKey>> {
+            Ok(view.get_edge_values_by_query(inner)?.map(|(k, _)| k).collect())
+        })?;
+
+        self.write(WriteOp::DeleteEdgeProperties(keys, q.name));
+        Ok(())
+    }
+
+    fn commit(&self) -> Result<()> {
+        let mut pending = self.pending.lock().unwrap();
+
+        if !pending.ops.is_empty() {
+            let mut datastore = self.datastore.write().unwrap();
+            let mut changes = Changes::default();
+            let mut writer = Writer {
+                base: &datastore,
+                changes: &mut changes,
+            };
 
-        for (key, _) in edge_values {
-            datastore.edge_properties.remove(&(key, q.name.clone()));
+            for op in &pending.ops {
+                writer.apply(op);
+            }
+
+            datastore.apply(changes);
         }
 
+        *pending = Pending::default();
+        Ok(())
+    }
+
+    fn rollback(&self) -> Result<()> {
+        *self.pending.lock().unwrap() = Pending::default();
         Ok(())
     }
 }
+
+impl Drop for MemoryTransaction {
+    fn drop(&mut self) {
+        // Committing can't currently fail, but in case that changes, errors
+        // are handled the same way as for other datastores
+        if let Err(err) = self.commit() {
+            error!("could not commit memory transaction on drop: {}", err);
+
+            if cfg!(debug_assertions) && !thread::panicking() {
+                panic!("could not commit memory transaction on drop: {}", err);
+            }
+        }
+    }
+}
@@ -1,14 +1,19 @@
 //! The in-memory-only datastore implementation. This is the simplest and
 //! generally fastest implementation, but it has these drawbacks:
 //!
-//! * Data is not persisted.
-//! * Transaction changes cannot be rolled back on error.
+//! * Data is not persisted, unless the datastore is associated with a file,
+//!   and even then only when `sync` is called.
+//! * Transactions only isolate their own uncommitted changes; reads see
+//!   whatever other transactions have committed so far.
 //! * Locking is coarse-grained; only one thread can write to the datastore at
 //!   a time. Consequently, this may actually perform worse on highly
 //!   concurrent write-heavy workloads.
 
 mod datastore;
 
+#[cfg(feature = "test-suite")]
+mod tests;
+
 pub use self::datastore::{MemoryDatastore, MemoryTransaction};
 
 #[cfg(feature = "bench-suite")]
@@ -16,3 +21,6 @@ full_bench_impl!(MemoryDatastore::default());
 
 #[cfg(feature = "test-suite")]
 full_test_impl!(MemoryDatastore::default());
+
+#[cfg(feature = "test-suite")]
+named_graphs_test_impl!(MemoryDatastore::default());
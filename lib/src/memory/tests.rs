@@ -0,0 +1,207 @@
+#[test]
+fn should_sync_and_read_from_path() {
+    use super::MemoryDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{
+        Datastore, EdgeKey, EdgeQueryExt, SpecificEdgeQuery, SpecificVertexQuery, Transaction, Type, Vertex,
+        VertexQueryExt,
+    };
+    use serde_json::json;
+
+    let path = generate_temporary_path();
+    let datastore = MemoryDatastore::create_with_path(&path).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let outbound_v = Vertex::new(t.clone());
+    let inbound_v = Vertex::new(t.clone());
+    trans.create_vertex(&outbound_v).unwrap();
+    trans.create_vertex(&inbound_v).unwrap();
+    let key = EdgeKey::new(outbound_v.id, t, inbound_v.id);
+    trans.create_edge(&key).unwrap();
+    trans
+        .set_vertex_properties(
+            SpecificVertexQuery::single(outbound_v.id).property("name"),
+            &json!("alice"),
+        )
+        .unwrap();
+    trans
+        .set_edge_properties(
+            SpecificEdgeQuery::single(key.clone()).property("weight"),
+            &json!({"value": 1.5}),
+        )
+        .unwrap();
+    let edges = trans.get_edges(SpecificEdgeQuery::single(key.clone())).unwrap();
+    trans.commit().unwrap();
+
+    // Nothing is persisted until syncing
+    let empty = MemoryDatastore::read_from(&path).unwrap();
+    assert_eq!(empty.transaction().unwrap().get_vertex_count(None).unwrap(), 0);
+
+    datastore.sync().unwrap();
+    let datastore = MemoryDatastore::read_from(&path).unwrap();
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 2);
+    assert_eq!(
+        trans
+            .get_vertices(SpecificVertexQuery::new(vec![outbound_v.id, inbound_v.id]))
+            .unwrap(),
+        vec![outbound_v.clone(), inbound_v]
+    );
+    assert_eq!(trans.get_edges(SpecificEdgeQuery::single(key.clone())).unwrap(), edges);
+
+    let properties = trans
+        .get_vertex_properties(SpecificVertexQuery::single(outbound_v.id).property("name"))
+        .unwrap();
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, json!("alice"));
+
+    let properties = trans
+        .get_edge_properties(SpecificEdgeQuery::single(key).property("weight"))
+        .unwrap();
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, json!({"value": 1.5}));
+}
+
+#[test]
+fn should_not_read_from_invalid_path() {
+    use super::MemoryDatastore;
+    use crate::util::generate_temporary_path;
+
+    assert!(MemoryDatastore::read_from(generate_temporary_path()).is_err());
+}
+
+#[test]
+fn should_keep_inbound_edges_in_sync() {
+    use super::MemoryDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, EdgeDirection, EdgeKey, SpecificVertexQuery, Transaction, Type, Vertex, VertexQueryExt};
+
+    let path = generate_temporary_path();
+    let datastore = MemoryDatastore::create_with_path(&path).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let outbound_v = Vertex::new(t.clone());
+    let inbound_v = Vertex::new(t.clone());
+    trans.create_vertex(&outbound_v).unwrap();
+    trans.create_vertex(&inbound_v).unwrap();
+    trans.commit().unwrap();
+
+    let key = EdgeKey::new(outbound_v.id, t.clone(), inbound_v.id);
+    let q = SpecificVertexQuery::single(inbound_v.id).inbound(10);
+    trans.create_edge(&key).unwrap();
+    assert_eq!(trans.get_edges(q.clone()).unwrap().len(), 1);
+    assert_eq!(
+        trans
+            .get_edge_count(inbound_v.id, Some(&t), EdgeDirection::Inbound)
+            .unwrap(),
+        1
+    );
+
+    // Rolled back edges are removed from the inbound index too
+    trans.rollback().unwrap();
+    assert!(trans.get_edges(q.clone()).unwrap().is_empty());
+
+    // The inbound index isn't persisted, so it's rebuilt when reading
+    trans.create_edge(&key).unwrap();
+    trans.commit().unwrap();
+    datastore.sync().unwrap();
+    let datastore = MemoryDatastore::read_from(&path).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let edges = trans.get_edges(q.clone()).unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key, key);
+
+    // Deleting the outbound vertex cleans up the edge from both sides
+    trans
+        .delete_vertices(SpecificVertexQuery::single(outbound_v.id))
+        .unwrap();
+    assert!(trans.get_edges(q).unwrap().is_empty());
+}
+
+#[test]
+fn should_sync_and_read_named_graphs() {
+    use super::MemoryDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, NamedGraphs, Transaction, Type};
+
+    let path = generate_temporary_path();
+    let datastore = MemoryDatastore::create_with_path(&path).unwrap();
+    let t = Type::new("foo").unwrap();
+    datastore
+        .transaction()
+        .unwrap()
+        .create_vertex_from_type(t.clone())
+        .unwrap();
+    datastore.create_graph("tenant-a").unwrap();
+    datastore.create_graph("tenant-b").unwrap();
+
+    {
+        let trans = datastore.graph("tenant-a").unwrap().unwrap().transaction().unwrap();
+        trans.create_vertex_from_type(t.clone()).unwrap();
+        trans.create_vertex_from_type(t).unwrap();
+    }
+
+    // Syncing from a named graph saves everything
+    datastore.graph("tenant-b").unwrap().unwrap().sync().unwrap();
+
+    let datastore = MemoryDatastore::read_from(&path).unwrap();
+    assert_eq!(
+        datastore.list_graphs().unwrap(),
+        vec!["tenant-a".to_string(), "tenant-b".to_string()]
+    );
+    assert_eq!(datastore.transaction().unwrap().get_vertex_count(None).unwrap(), 1);
+
+    let graph = datastore.graph("tenant-a").unwrap().unwrap();
+    assert_eq!(graph.transaction().unwrap().get_vertex_count(None).unwrap(), 2);
+}
+
+#[test]
+fn should_not_block_writers_while_iterating() {
+    use super::MemoryDatastore;
+    use crate::{
+        Datastore, EdgeKey, EdgeQueryExt, RangeVertexQuery, SpecificVertexQuery, Transaction, Type, Vertex,
+        VertexQueryExt,
+    };
+    use uuid::Uuid;
+
+    let datastore = MemoryDatastore::default();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let hub = Vertex::new(t.clone());
+    trans.create_vertex(&hub).unwrap();
+    let mut ids = Vec::new();
+
+    // More results than are read per page
+    for _ in 0..2500 {
+        let v = Vertex::new(t.clone());
+        trans.create_vertex(&v).unwrap();
+        trans.create_edge(&EdgeKey::new(v.id, t.clone(), hub.id)).unwrap();
+        ids.push(v.id);
+    }
+
+    trans.commit().unwrap();
+
+    // Committing while iterating would deadlock if the iterator held the
+    // datastore's read lock throughout. The new vertex sorts last, so the
+    // iterator gets to it on a later page.
+    let mut iter = trans.iter_vertices(RangeVertexQuery::new(u32::MAX)).unwrap();
+    assert!(iter.next().unwrap().is_ok());
+    let last_v = Vertex::with_id(Uuid::from_bytes([255; 16]), t);
+    let writer = datastore.transaction().unwrap();
+    writer.create_vertex(&last_v).unwrap();
+    writer.commit().unwrap();
+    let rest: Vec<Vertex> = iter.map(|v| v.unwrap()).collect();
+    assert_eq!(rest.len(), 2501);
+    assert_eq!(rest.last().unwrap().id, last_v.id);
+
+    let q = SpecificVertexQuery::new(ids.clone());
+    assert_eq!(trans.iter_vertices(q.clone()).unwrap().count(), 2500);
+    assert_eq!(trans.iter_edges(q.clone().outbound(u32::MAX)).unwrap().count(), 2500);
+    assert_eq!(trans.iter_edges(q.outbound(2000)).unwrap().count(), 2000);
+
+    let q = SpecificVertexQuery::single(hub.id).inbound(u32::MAX).outbound(u32::MAX);
+    let mut piped_ids: Vec<Uuid> = trans.iter_vertices(q).unwrap().map(|v| v.unwrap().id).collect();
+    piped_ids.sort();
+    ids.sort();
+    assert_eq!(piped_ids, ids);
+}
@@ -1,12 +1,17 @@
-use super::edges::EdgeKey;
+use super::edges::{Edge, EdgeKey};
 use super::vertices::Vertex;
 use serde_json::Value as JsonValue;
 use uuid::Uuid;
 
+/// An item to insert via `Datastore::bulk_insert`.
 #[derive(Clone, Debug, PartialEq)]
 pub enum BulkInsertItem {
     Vertex(Vertex),
+    /// An edge, which is timestamped with the time it's inserted.
     Edge(EdgeKey),
+    /// An edge with an explicit creation datetime, e.g. when migrating or
+    /// replaying existing data.
+    EdgeWithDatetime(Edge),
     VertexProperty(Uuid, String, JsonValue),
     EdgeProperty(EdgeKey, String, JsonValue),
 }
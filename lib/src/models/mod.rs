@@ -9,5 +9,5 @@ pub use self::bulk_insert::BulkInsertItem;
 pub use self::edges::{Edge, EdgeKey};
 pub use self::properties::{EdgeProperties, EdgeProperty, NamedProperty, VertexProperties, VertexProperty};
 pub use self::queries::*;
-pub use self::types::Type;
+pub use self::types::{Type, MAX_TYPE_LENGTH};
 pub use self::vertices::Vertex;
@@ -1,8 +1,10 @@
-use super::edges::EdgeKey;
+use super::edges::{Edge, EdgeKey};
 use super::types::Type;
 use crate::errors;
 use chrono::offset::Utc;
 use chrono::DateTime;
+use serde_json::Value as JsonValue;
+use std::cmp::Ordering;
 use std::str::FromStr;
 use uuid::Uuid;
 
@@ -41,6 +43,127 @@ impl From<EdgeDirection> for String {
     }
 }
 
+/// A condition on the value of a property.
+///
+/// Comparisons are only made between two numbers or two strings; a property
+/// value of any other type never matches a comparison predicate.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum PropertyPredicate {
+    /// Matches if the property is set, regardless of its value.
+    Exists,
+    /// Matches if the property value is equal to the given value.
+    Equals(JsonValue),
+    /// Matches if the property value is greater than the given value.
+    GreaterThan(JsonValue),
+    /// Matches if the property value is greater than or equal to the given
+    /// value.
+    GreaterThanOrEqual(JsonValue),
+    /// Matches if the property value is less than the given value.
+    LessThan(JsonValue),
+    /// Matches if the property value is less than or equal to the given
+    /// value.
+    LessThanOrEqual(JsonValue),
+}
+
+impl PropertyPredicate {
+    /// Checks whether a property value satisfies the predicate.
+    ///
+    /// # Arguments
+    /// * `value` - The property value, or `None` if the property is not set.
+    pub fn matches(&self, value: Option<&JsonValue>) -> bool {
+        let value = match value {
+            Some(value) => value,
+            None => return false,
+        };
+
+        match self {
+            PropertyPredicate::Exists => true,
+            PropertyPredicate::Equals(expected) => value == expected,
+            PropertyPredicate::GreaterThan(bound) => compare(value, bound) == Some(Ordering::Greater),
+            PropertyPredicate::GreaterThanOrEqual(bound) => {
+                matches!(compare(value, bound), Some(Ordering::Greater | Ordering::Equal))
+            }
+            PropertyPredicate::LessThan(bound) => compare(value, bound) == Some(Ordering::Less),
+            PropertyPredicate::LessThanOrEqual(bound) => {
+                matches!(compare(value, bound), Some(Ordering::Less | Ordering::Equal))
+            }
+        }
+    }
+}
+
+fn compare(value: &JsonValue, bound: &JsonValue) -> Option<Ordering> {
+    match (value, bound) {
+        (JsonValue::Number(value), JsonValue::Number(bound)) => value.as_f64()?.partial_cmp(&bound.as_f64()?),
+        (JsonValue::String(value), JsonValue::String(bound)) => Some(value.cmp(bound)),
+        _ => None,
+    }
+}
+
+/// Filters the items returned by a query on the value of one of their
+/// properties.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct PropertyFilter {
+    /// The name of the property to filter on.
+    pub name: String,
+
+    /// The condition the property value must satisfy.
+    pub predicate: PropertyPredicate,
+}
+
+impl PropertyFilter {
+    /// Creates a new property filter.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the property to filter on.
+    /// * `predicate` - The condition the property value must satisfy.
+    pub fn new<S: Into<String>>(name: S, predicate: PropertyPredicate) -> Self {
+        Self {
+            name: name.into(),
+            predicate,
+        }
+    }
+
+    /// Checks whether a property value satisfies the filter.
+    ///
+    /// # Arguments
+    /// * `value` - The value of the filtered property, or `None` if it is
+    ///   not set.
+    pub fn matches(&self, value: Option<&JsonValue>) -> bool {
+        self.predicate.matches(value)
+    }
+}
+
+/// A continuation cursor for paging through the results of pipe queries.
+///
+/// A cursor identifies the last edge that was seen; a query with the cursor
+/// set will return the items that follow it. Cursors are typically created
+/// from the last edge returned by the previous page.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct EdgeCursor {
+    /// The key of the last seen edge.
+    pub key: EdgeKey,
+
+    /// The update datetime of the last seen edge.
+    pub update_datetime: DateTime<Utc>,
+}
+
+impl EdgeCursor {
+    /// Creates a new edge cursor.
+    ///
+    /// # Arguments
+    /// * `key` - The key of the last seen edge.
+    /// * `update_datetime` - The update datetime of the last seen edge.
+    pub fn new(key: EdgeKey, update_datetime: DateTime<Utc>) -> Self {
+        Self { key, update_datetime }
+    }
+}
+
+impl<'a> From<&'a Edge> for EdgeCursor {
+    fn from(edge: &'a Edge) -> Self {
+        Self::new(edge.key.clone(), edge.created_datetime)
+    }
+}
+
 /// A query for vertices.
 ///
 /// Generally you shouldn't need to instantiate a `VertexQuery` directly, but
@@ -96,6 +219,16 @@ pub trait VertexQueryExt: Into<VertexQuery> {
     fn property<S: Into<String>>(self, name: S) -> VertexPropertyQuery {
         VertexPropertyQuery::new(self.into(), name)
     }
+
+    /// Gets the vertices reachable from the vertices, breadth-first.
+    ///
+    /// # Arguments
+    /// * `direction` - Whether to follow outbound or inbound edges.
+    /// * `max_depth` - The maximum number of hops to make.
+    /// * `limit` - Limits the number of returned results.
+    fn traverse(self, direction: EdgeDirection, max_depth: u32, limit: u32) -> TraversalQuery {
+        TraversalQuery::new(Box::new(self.into()), direction, max_depth, limit)
+    }
 }
 
 /// Gets a range of vertices.
@@ -107,8 +240,14 @@ pub struct RangeVertexQuery {
     /// Filters the type of vertices returned.
     pub t: Option<Type>,
 
-    /// Sets the lowest vertex ID to return.
+    /// Sets the lowest vertex ID to return. This is inclusive, so to page
+    /// through vertices, start the next page at `util::next_uuid` of the
+    /// last ID seen, rather than the ID itself. The rocksdb datastore used
+    /// to treat this as exclusive, unlike the memory datastore.
     pub start_id: Option<Uuid>,
+
+    /// Filters the vertices returned by their property values.
+    pub filters: Vec<PropertyFilter>,
 }
 
 impl VertexQueryExt for RangeVertexQuery {}
@@ -123,6 +262,7 @@ impl RangeVertexQuery {
             limit,
             t: None,
             start_id: None,
+            filters: Vec::new(),
         }
     }
 
@@ -135,10 +275,12 @@ impl RangeVertexQuery {
             limit: self.limit,
             t: Some(t),
             start_id: self.start_id,
+            filters: self.filters,
         }
     }
 
-    /// Sets the lowest vertex ID to return.
+    /// Sets the lowest vertex ID to return. The vertex with this ID is
+    /// included, if it exists.
     ///
     /// # Arguments
     /// * `start_id` - The lowest vertex ID to return.
@@ -147,6 +289,24 @@ impl RangeVertexQuery {
             limit: self.limit,
             t: self.t,
             start_id: Some(start_id),
+            filters: self.filters,
+        }
+    }
+
+    /// Filter the vertices returned by a property value. Can be called
+    /// multiple times, in which case vertices must match every filter.
+    ///
+    /// # Arguments
+    /// * `filter` - The property filter to add.
+    pub fn filter(self, filter: PropertyFilter) -> Self {
+        let mut filters = self.filters;
+        filters.push(filter);
+
+        Self {
+            limit: self.limit,
+            t: self.t,
+            start_id: self.start_id,
+            filters,
         }
     }
 }
@@ -196,6 +356,14 @@ pub struct PipeVertexQuery {
 
     /// Filters the type of vertices returned.
     pub t: Option<Type>,
+
+    /// Filters the vertices returned by their property values.
+    pub filters: Vec<PropertyFilter>,
+
+    /// Resumes piping after the given edge. This only applies when the
+    /// inner query is a pipe edge query, in which case it overrides that
+    /// query's cursor.
+    pub cursor: Option<EdgeCursor>,
 }
 
 impl VertexQueryExt for PipeVertexQuery {}
@@ -214,6 +382,8 @@ impl PipeVertexQuery {
             direction,
             limit,
             t: None,
+            filters: Vec::new(),
+            cursor: None,
         }
     }
 
@@ -227,6 +397,223 @@ impl PipeVertexQuery {
             direction: self.direction,
             limit: self.limit,
             t: Some(t),
+            filters: self.filters,
+            cursor: self.cursor,
+        }
+    }
+
+    /// Filter the vertices returned by a property value. Can be called
+    /// multiple times, in which case vertices must match every filter.
+    ///
+    /// # Arguments
+    /// * `filter` - The property filter to add.
+    pub fn filter(self, filter: PropertyFilter) -> Self {
+        let mut filters = self.filters;
+        filters.push(filter);
+
+        Self {
+            inner: self.inner,
+            direction: self.direction,
+            limit: self.limit,
+            t: self.t,
+            filters,
+            cursor: self.cursor,
+        }
+    }
+
+    /// Resumes piping after the given edge.
+    ///
+    /// # Arguments
+    /// * `cursor` - The cursor of the last edge that vertices were piped
+    ///   from.
+    pub fn cursor(self, cursor: EdgeCursor) -> Self {
+        Self {
+            inner: self.inner,
+            direction: self.direction,
+            limit: self.limit,
+            t: self.t,
+            filters: self.filters,
+            cursor: Some(cursor),
+        }
+    }
+}
+
+/// Gets the vertices reachable from a set of start vertices, via a
+/// breadth-first traversal of edges.
+///
+/// Each vertex is returned at most once, at the depth at which it's first
+/// reached, and vertices are returned in order of depth. This avoids the
+/// duplicates that come from nesting pipe queries by hand.
+///
+/// Generally, you shouldn't need to construct this directly, but rather call
+/// `.traverse()` on a vertex query.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct TraversalQuery {
+    /// The vertices to start from, which are at depth 0.
+    pub start: Box<VertexQuery>,
+
+    /// Whether to follow outbound or inbound edges.
+    pub direction: EdgeDirection,
+
+    /// Limits the number of vertices to get.
+    pub limit: u32,
+
+    /// Only follows edges of this type.
+    pub t: Option<Type>,
+
+    /// The minimum depth of returned vertices. Vertices closer than this are
+    /// still traversed, but not returned.
+    pub min_depth: u32,
+
+    /// The maximum depth of returned vertices, i.e. the maximum number of
+    /// hops to make.
+    pub max_depth: u32,
+}
+
+impl TraversalQuery {
+    /// Creates a new traversal query. By default, the start vertices are not
+    /// returned, i.e. the minimum depth is 1.
+    ///
+    /// Arguments
+    /// * `start` - The vertices to start from.
+    /// * `direction` - Whether to follow outbound or inbound edges.
+    /// * `max_depth` - The maximum number of hops to make.
+    /// * `limit` - Limits the number of vertices to get.
+    pub fn new(start: Box<VertexQuery>, direction: EdgeDirection, max_depth: u32, limit: u32) -> Self {
+        Self {
+            start,
+            direction,
+            limit,
+            t: None,
+            min_depth: 1,
+            max_depth,
+        }
+    }
+
+    /// Only follow edges of a given type.
+    ///
+    /// # Arguments
+    /// * `t` - Sets the edge type filter.
+    pub fn t(self, t: Type) -> Self {
+        Self {
+            start: self.start,
+            direction: self.direction,
+            limit: self.limit,
+            t: Some(t),
+            min_depth: self.min_depth,
+            max_depth: self.max_depth,
+        }
+    }
+
+    /// Sets the minimum depth of returned vertices.
+    ///
+    /// # Arguments
+    /// * `min_depth` - The minimum depth. Use 0 to return the start vertices.
+    pub fn min_depth(self, min_depth: u32) -> Self {
+        Self {
+            start: self.start,
+            direction: self.direction,
+            limit: self.limit,
+            t: self.t,
+            min_depth,
+            max_depth: self.max_depth,
+        }
+    }
+}
+
+/// How the shortest path between two vertices is found.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum ShortestPathAlgorithm {
+    /// Finds the path with the fewest edges, via a breadth-first search from
+    /// the start vertex.
+    BreadthFirst,
+    /// Finds the path with the fewest edges, via breadth-first searches from
+    /// both the start and end vertices that meet in the middle. This
+    /// usually visits far fewer vertices on large graphs.
+    BidirectionalBreadthFirst,
+    /// Finds the path with the lowest total weight, where the weight of an
+    /// edge is the value of the named property. Edges without a
+    /// non-negative numeric value for the property are not followed.
+    Weighted(String),
+}
+
+/// Finds the shortest directed path between two vertices, following
+/// outbound edges from the start vertex to the end vertex.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ShortestPathQuery {
+    /// The vertex to start from.
+    pub start_id: Uuid,
+
+    /// The vertex to end at.
+    pub end_id: Uuid,
+
+    /// Only follows edges of this type.
+    pub t: Option<Type>,
+
+    /// The maximum number of edges in the path. This only applies to the
+    /// breadth-first algorithms.
+    pub max_depth: u32,
+
+    /// How the path is found.
+    pub algorithm: ShortestPathAlgorithm,
+}
+
+impl ShortestPathQuery {
+    /// Creates a new shortest path query, which uses a unidirectional
+    /// breadth-first search with no maximum depth by default.
+    ///
+    /// Arguments
+    /// * `start_id` - The vertex to start from.
+    /// * `end_id` - The vertex to end at.
+    pub fn new(start_id: Uuid, end_id: Uuid) -> Self {
+        Self {
+            start_id,
+            end_id,
+            t: None,
+            max_depth: u32::MAX,
+            algorithm: ShortestPathAlgorithm::BreadthFirst,
+        }
+    }
+
+    /// Only follow edges of a given type.
+    ///
+    /// # Arguments
+    /// * `t` - Sets the edge type filter.
+    pub fn t(self, t: Type) -> Self {
+        Self {
+            start_id: self.start_id,
+            end_id: self.end_id,
+            t: Some(t),
+            max_depth: self.max_depth,
+            algorithm: self.algorithm,
+        }
+    }
+
+    /// Sets the maximum number of edges in the path.
+    ///
+    /// # Arguments
+    /// * `max_depth` - The maximum depth.
+    pub fn max_depth(self, max_depth: u32) -> Self {
+        Self {
+            start_id: self.start_id,
+            end_id: self.end_id,
+            t: self.t,
+            max_depth,
+            algorithm: self.algorithm,
+        }
+    }
+
+    /// Sets how the path is found.
+    ///
+    /// # Arguments
+    /// * `algorithm` - The algorithm to use.
+    pub fn algorithm(self, algorithm: ShortestPathAlgorithm) -> Self {
+        Self {
+            start_id: self.start_id,
+            end_id: self.end_id,
+            t: self.t,
+            max_depth: self.max_depth,
+            algorithm,
         }
     }
 }
@@ -335,6 +722,12 @@ impl SpecificEdgeQuery {
 
 /// Gets the edges associated with vertices.
 ///
+/// Edges are returned in order of the ID of the vertex they're piped from,
+/// whatever order the inner query returns vertices in. Each vertex's edges
+/// are ordered by type, with shorter type names first, then by update
+/// datetime, newest first, then by the ID of the vertex at the other end.
+/// The rocksdb and memory datastores both use this order.
+///
 /// Generally, you shouldn't need to construct this directly, but rather call
 /// `.outbound()` or `.inbound()` on a vertex query.
 #[derive(Eq, PartialEq, Clone, Debug)]
@@ -356,6 +749,12 @@ pub struct PipeEdgeQuery {
 
     /// Specifies the oldest update datetime for returned edges.
     pub low: Option<DateTime<Utc>>,
+
+    /// Filters the edges returned by their property values.
+    pub filters: Vec<PropertyFilter>,
+
+    /// Only returns edges that come after the given edge.
+    pub cursor: Option<EdgeCursor>,
 }
 
 impl EdgeQueryExt for PipeEdgeQuery {}
@@ -376,6 +775,8 @@ impl PipeEdgeQuery {
             t: None,
             high: None,
             low: None,
+            filters: Vec::new(),
+            cursor: None,
         }
     }
 
@@ -391,6 +792,8 @@ impl PipeEdgeQuery {
             t: Some(t),
             high: self.high,
             low: self.low,
+            filters: self.filters,
+            cursor: self.cursor,
         }
     }
 
@@ -406,6 +809,8 @@ impl PipeEdgeQuery {
             t: self.t,
             high: Some(high),
             low: self.low,
+            filters: self.filters,
+            cursor: self.cursor,
         }
     }
 
@@ -421,6 +826,49 @@ impl PipeEdgeQuery {
             t: self.t,
             high: self.high,
             low: Some(low),
+            filters: self.filters,
+            cursor: self.cursor,
+        }
+    }
+
+    /// Filter the edges returned by a property value. Can be called multiple
+    /// times, in which case edges must match every filter.
+    ///
+    /// # Arguments
+    /// * `filter` - The property filter to add.
+    pub fn filter(self, filter: PropertyFilter) -> Self {
+        let mut filters = self.filters;
+        filters.push(filter);
+
+        Self {
+            inner: self.inner,
+            direction: self.direction,
+            limit: self.limit,
+            t: self.t,
+            high: self.high,
+            low: self.low,
+            filters,
+            cursor: self.cursor,
+        }
+    }
+
+    /// Only return edges that come after the given edge. Pipe edge queries
+    /// return edges in a stable order, so this can be used to page through
+    /// them. Paging carries on from the next edge in that order even if
+    /// the cursor's edge or vertex has since been deleted.
+    ///
+    /// # Arguments
+    /// * `cursor` - The cursor of the last seen edge.
+    pub fn cursor(self, cursor: EdgeCursor) -> Self {
+        Self {
+            inner: self.inner,
+            direction: self.direction,
+            limit: self.limit,
+            t: self.t,
+            high: self.high,
+            low: self.low,
+            filters: self.filters,
+            cursor: Some(cursor),
         }
     }
 }
@@ -451,7 +899,8 @@ impl EdgePropertyQuery {
 
 #[cfg(test)]
 mod tests {
-    use super::EdgeDirection;
+    use super::{EdgeDirection, PropertyPredicate};
+    use serde_json::json;
     use std::str::FromStr;
 
     #[test]
@@ -468,4 +917,19 @@ mod tests {
         let s: String = EdgeDirection::Inbound.into();
         assert_eq!(s, "inbound".to_string());
     }
+
+    #[test]
+    fn should_match_property_predicates() {
+        assert!(PropertyPredicate::Exists.matches(Some(&json!(null))));
+        assert!(!PropertyPredicate::Exists.matches(None));
+        assert!(PropertyPredicate::Equals(json!({"a": 1})).matches(Some(&json!({"a": 1}))));
+        assert!(!PropertyPredicate::Equals(json!(1)).matches(Some(&json!("1"))));
+        assert!(PropertyPredicate::GreaterThan(json!(1)).matches(Some(&json!(1.5))));
+        assert!(!PropertyPredicate::GreaterThan(json!(1)).matches(Some(&json!(1))));
+        assert!(PropertyPredicate::GreaterThanOrEqual(json!(1)).matches(Some(&json!(1))));
+        assert!(PropertyPredicate::LessThan(json!("b")).matches(Some(&json!("a"))));
+        assert!(PropertyPredicate::LessThanOrEqual(json!("b")).matches(Some(&json!("b"))));
+        assert!(!PropertyPredicate::LessThan(json!(1)).matches(Some(&json!("0"))));
+        assert!(!PropertyPredicate::LessThan(json!(1)).matches(None));
+    }
 }
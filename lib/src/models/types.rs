@@ -1,16 +1,23 @@
 use crate::errors::{ValidationError, ValidationResult};
+use core::convert::TryFrom;
 use core::str::FromStr;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 lazy_static! {
-    static ref TYPE_VALIDATOR: Regex = Regex::new("^[a-zA-Z0-9-_]+$").unwrap();
+    static ref TYPE_VALIDATOR: Regex = Regex::new(r"^\P{Cc}+$").unwrap();
 }
 
+/// The maximum length of a type, in bytes.
+pub const MAX_TYPE_LENGTH: usize = 65_535;
+
 /// An edge or vertex type.
 ///
-/// Types must be less than 256 characters long, and can only contain letters,
-/// numbers, dashes and underscores.
-#[derive(Eq, PartialEq, Clone, Debug, Hash, Ord, PartialOrd)]
+/// Types can contain any Unicode characters other than control characters,
+/// e.g. `ns:Employee` or `Mitarbeiter`, and must be non-empty and at most
+/// `MAX_TYPE_LENGTH` bytes long. This is checked when deserializing too.
+#[derive(Eq, PartialEq, Clone, Debug, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(try_from = "String")]
 pub struct Type(pub String);
 
 impl Type {
@@ -18,15 +25,16 @@ impl Type {
     ///
     /// # Arguments
     ///
-    /// * `t` - The type, which must be less than 256 characters long.
+    /// * `t` - The type, which must be at most `MAX_TYPE_LENGTH` bytes
+    ///   long.
     ///
     /// # Errors
-    /// Returns a `ValidationError` if the type is longer than 255 characters,
-    /// or has invalid characters.
+    /// Returns a `ValidationError` if the type is longer than
+    /// `MAX_TYPE_LENGTH` bytes, is empty, or has invalid characters.
     pub fn new<S: Into<String>>(s: S) -> ValidationResult<Self> {
         let s = s.into();
 
-        if s.len() > 255 {
+        if s.len() > MAX_TYPE_LENGTH {
             Err(ValidationError::ValueTooLong)
         } else if !TYPE_VALIDATOR.is_match(&s[..]) {
             Err(ValidationError::InvalidValue)
@@ -39,7 +47,8 @@ impl Type {
     ///
     /// # Arguments
     ///
-    /// * `t` - The type, which must be less than 256 characters long.
+    /// * `t` - The type, which must be at most `MAX_TYPE_LENGTH` bytes
+    ///   long.
     ///
     /// # Safety
     /// This function is marked unsafe because there's no verification that
@@ -63,20 +72,48 @@ impl FromStr for Type {
     }
 }
 
+impl TryFrom<String> for Type {
+    type Error = ValidationError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Type;
+    use super::{Type, MAX_TYPE_LENGTH};
     use crate::util::generate_random_secret;
     use std::str::FromStr;
 
     #[test]
     fn should_fail_for_invalid_types() {
-        assert!(Type::new(generate_random_secret(256)).is_err());
-        assert!(Type::new("$").is_err());
+        assert!(Type::new(generate_random_secret(MAX_TYPE_LENGTH + 1)).is_err());
+        assert!(Type::new("").is_err());
+        assert!(Type::new("foo\nbar").is_err());
+        assert!(Type::new("\u{0}").is_err());
+    }
+
+    #[test]
+    fn should_allow_long_and_unicode_types() {
+        assert!(Type::new(generate_random_secret(MAX_TYPE_LENGTH)).is_ok());
+        assert!(Type::new("ns:Employee").is_ok());
+        assert!(Type::new("Straße").is_ok());
+        assert!(Type::new("社員").is_ok());
+        assert!(Type::new("$").is_ok());
     }
 
     #[test]
     fn should_convert_str_to_type() {
         assert_eq!(Type::from_str("foo").unwrap(), Type::new("foo").unwrap());
     }
+
+    #[test]
+    fn should_validate_deserialized_types() {
+        let t: Type = serde_json::from_str("\"foo\"").unwrap();
+        assert_eq!(t, Type::new("foo").unwrap());
+        assert_eq!(serde_json::to_string(&t).unwrap(), "\"foo\"");
+        assert!(serde_json::from_str::<Type>("\"\"").is_err());
+        assert!(serde_json::from_str::<Type>("\"foo\\nbar\"").is_err());
+    }
 }
@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use std::mem;
+use std::ops::Bound;
+use std::sync::{Mutex, MutexGuard};
+
+use super::managers::Source;
+use crate::errors::Result;
+
+use byteorder::{BigEndian, ByteOrder};
+use rocksdb::{WriteBatch, WriteOptions, DB};
+
+/// Reads a counter value.
+pub fn read_counter(value: &[u8]) -> u64 {
+    BigEndian::read_u64(value)
+}
+
+/// Adds `delta` to the counter stored under `key` in `batch`. Counters that
+/// drop to zero are deleted.
+fn apply_counter_delta(db: &DB, batch: &mut WriteBatch, cf_name: &str, key: &[u8], delta: i64) -> Result<()> {
+    let cf = db.cf_handle(cf_name).unwrap();
+    let current = db.get_cf(cf, key)?.map_or(0, |value| read_counter(&value));
+    let updated = (current as i64 + delta).max(0) as u64;
+
+    if updated == 0 {
+        batch.delete_cf(cf, key)?;
+    } else {
+        let mut buf = [0; 8];
+        BigEndian::write_u64(&mut buf, updated);
+        batch.put_cf(cf, key, buf)?;
+    }
+
+    Ok(())
+}
+
+/// Changes that haven't been written to the database yet. Entries are
+/// ordered by column family and key, so that they can be merged into
+/// iterators over the database.
+#[derive(Clone, Debug, Default)]
+pub struct Changes {
+    /// Values to write, where `None` means the key is deleted.
+    values: BTreeMap<(&'static str, Vec<u8>), Option<Vec<u8>>>,
+    /// Amounts to add to counters, which are resolved against their current
+    /// values when the changes are written.
+    counter_deltas: BTreeMap<(&'static str, Vec<u8>), i64>,
+}
+
+impl Changes {
+    /// Gets the changed value of a key. Returns `None` if the key hasn't
+    /// been changed, and `Some(None)` if it's been deleted.
+    pub fn get(&self, cf_name: &'static str, key: &[u8]) -> Option<Option<&[u8]>> {
+        self.values
+            .get(&(cf_name, key.to_vec()))
+            .map(|value| value.as_ref().map(|value| &value[..]))
+    }
+
+    /// Iterates over the changed keys of a column family in order, starting
+    /// at `from`, and stopping before `until` if it's set.
+    pub fn range<'a>(
+        &'a self,
+        cf_name: &'static str,
+        from: &[u8],
+        until: Option<&[u8]>,
+    ) -> impl Iterator<Item = (&'a [u8], Option<&'a [u8]>)> + 'a {
+        let upper = match until {
+            Some(until) => Bound::Excluded((cf_name, until.to_vec())),
+            None => Bound::Unbounded,
+        };
+
+        self.values
+            .range((Bound::Included((cf_name, from.to_vec())), upper))
+            .take_while(move |((key_cf_name, _), _)| *key_cf_name == cf_name)
+            .map(|((_, key), value)| (&key[..], value.as_ref().map(|value| &value[..])))
+    }
+
+    /// Gets the amount to add to a counter.
+    pub fn counter_delta(&self, cf_name: &'static str, key: &[u8]) -> i64 {
+        self.counter_deltas.get(&(cf_name, key.to_vec())).cloned().unwrap_or(0)
+    }
+
+    /// Iterates over the amounts to add to the counters whose keys start
+    /// with `prefix`.
+    pub fn counter_deltas<'a>(
+        &'a self,
+        cf_name: &'static str,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (&'a [u8], i64)> + 'a {
+        self.counter_deltas
+            .range((cf_name, prefix.to_vec())..)
+            .take_while(move |((key_cf_name, key), _)| *key_cf_name == cf_name && key.starts_with(prefix))
+            .map(|((_, key), delta)| (&key[..], *delta))
+    }
+
+    pub fn put(&mut self, cf_name: &'static str, key: &[u8], value: &[u8]) {
+        self.values.insert((cf_name, key.to_vec()), Some(value.to_vec()));
+    }
+
+    pub fn delete(&mut self, cf_name: &'static str, key: &[u8]) {
+        self.values.insert((cf_name, key.to_vec()), None);
+    }
+
+    pub fn add_to_counter(&mut self, cf_name: &'static str, key: Vec<u8>, delta: i64) {
+        *self.counter_deltas.entry((cf_name, key)).or_insert(0) += delta;
+    }
+
+    /// Adds changes that were made after these ones.
+    pub fn extend(&mut self, other: Changes) {
+        self.values.extend(other.values);
+
+        for (key, delta) in other.counter_deltas {
+            *self.counter_deltas.entry(key).or_insert(0) += delta;
+        }
+    }
+
+    /// Atomically writes the changes to the database. Counters are updated
+    /// relative to their current values, so the datastore's write lock must
+    /// be held, so that nothing can change them between reading and writing
+    /// them.
+    ///
+    /// # Arguments
+    /// * `db` - The database to write to.
+    /// * `opts` - The options to write with.
+    pub fn write(&self, db: &DB, opts: &WriteOptions) -> Result<()> {
+        let mut batch = WriteBatch::default();
+
+        for ((cf_name, key), value) in &self.values {
+            let cf = db.cf_handle(cf_name).unwrap();
+
+            match value {
+                Some(value) => batch.put_cf(cf, key, value)?,
+                None => batch.delete_cf(cf, key)?,
+            }
+        }
+
+        for ((cf_name, key), delta) in &self.counter_deltas {
+            if *delta != 0 {
+                apply_counter_delta(db, &mut batch, cf_name, key, *delta)?;
+            }
+        }
+
+        db.write_opt(batch, opts)?;
+        Ok(())
+    }
+}
+
+/// Collects changes on top of a source, e.g. everything done by a single
+/// transaction method call. Reads through the batch see the changes made to
+/// it so far.
+pub struct Batch<'a> {
+    db: &'a DB,
+    source: Source<'a>,
+    changes: Changes,
+    _guard: Option<MutexGuard<'a, ()>>,
+}
+
+impl<'a> Batch<'a> {
+    /// Creates a batch that is written straight to the database.
+    ///
+    /// The batch holds the datastore's write lock for as long as it's alive,
+    /// so that existence checks and counter updates made while building it
+    /// can't race with other writers.
+    pub fn new(db: &'a DB, write_lock: &'a Mutex<()>) -> Self {
+        Batch {
+            db,
+            source: Source::db(db),
+            changes: Changes::default(),
+            _guard: Some(write_lock.lock().unwrap()),
+        }
+    }
+
+    /// Creates a batch on top of a transaction's view of the database, whose
+    /// changes are added to the transaction's rather than written.
+    pub fn staged(db: &'a DB, source: Source<'a>) -> Self {
+        Batch {
+            db,
+            source,
+            changes: Changes::default(),
+            _guard: None,
+        }
+    }
+
+    /// Gets a source that reads through the batch.
+    pub fn source(&self) -> Source<'_> {
+        self.source.with_changes(&self.changes)
+    }
+
+    /// Reads a value, taking into account what's been written to the batch
+    /// so far.
+    pub fn get_cf(&self, cf_name: &'static str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.source().get_cf(cf_name, key)
+    }
+
+    pub fn put_cf(&mut self, cf_name: &'static str, key: &[u8], value: &[u8]) -> Result<()> {
+        self.changes.put(cf_name, key, value);
+        Ok(())
+    }
+
+    pub fn delete_cf(&mut self, cf_name: &'static str, key: &[u8]) -> Result<()> {
+        self.changes.delete(cf_name, key);
+        Ok(())
+    }
+
+    /// Adds an amount to a counter when the batch is written.
+    pub fn add_to_counter(&mut self, cf_name: &'static str, key: Vec<u8>, delta: i64) {
+        self.changes.add_to_counter(cf_name, key, delta);
+    }
+
+    /// Writes the batch to the database.
+    pub fn write(self) -> Result<()> {
+        self.write_opt(&WriteOptions::default())
+    }
+
+    /// Like `write`, but with custom write options.
+    pub fn write_opt(self, opts: &WriteOptions) -> Result<()> {
+        debug_assert!(self._guard.is_some(), "staged batches can't be written directly");
+
+        // The write lock is still held here, so nothing can change the
+        // counters between reading and writing them
+        self.changes.write(self.db, opts)
+    }
+
+    /// Writes what's been added to the batch so far and empties it, while
+    /// keeping hold of the write lock. Useful for bounding the memory used
+    /// by long-running writes.
+    pub fn flush(&mut self) -> Result<()> {
+        debug_assert!(self._guard.is_some(), "staged batches can't be written directly");
+        let changes = mem::take(&mut self.changes);
+        changes.write(self.db, &WriteOptions::default())
+    }
+
+    /// Gets the changes made to a staged batch.
+    pub fn into_changes(self) -> Changes {
+        self.changes
+    }
+}
@@ -20,9 +20,47 @@ lazy_static! {
             .unwrap();
 }
 
+/// Gets the number of bytes needed to write `value` as a varint.
+fn varint_len(value: usize) -> usize {
+    if value < 0x80 {
+        1
+    } else if value < 0x4000 {
+        2
+    } else {
+        3
+    }
+}
+
+/// Writes a varint that sorts in the same order as the value it encodes. The
+/// high bits of the first byte say how many bytes follow: values below 128
+/// are written as a single byte, values below 2^14 as two bytes, and values
+/// below 2^21 as three bytes.
+pub fn write_varint<W: Write>(writer: &mut W, value: usize) -> Result<(), IoError> {
+    debug_assert!(value < 0x20_0000);
+
+    match varint_len(value) {
+        1 => writer.write_all(&[value as u8]),
+        2 => writer.write_all(&[0x80 | (value >> 8) as u8, value as u8]),
+        _ => writer.write_all(&[0xC0 | (value >> 16) as u8, (value >> 8) as u8, value as u8]),
+    }
+}
+
+pub fn read_varint<R: Read>(reader: &mut R) -> Result<usize, IoError> {
+    let first = reader.read_u8()? as usize;
+
+    if first < 0x80 {
+        Ok(first)
+    } else if first < 0xC0 {
+        Ok(((first & 0x3F) << 8) | reader.read_u8()? as usize)
+    } else {
+        Ok(((first & 0x1F) << 16) | (reader.read_u16::<BigEndian>()? as usize))
+    }
+}
+
 pub enum Component<'a> {
     Uuid(Uuid),
     UnsizedString(&'a str),
+    SizedString(&'a str),
     Type(&'a models::Type),
     DateTime(DateTime<Utc>),
 }
@@ -32,7 +70,8 @@ impl<'a> Component<'a> {
         match *self {
             Component::Uuid(_) => 16,
             Component::UnsizedString(s) => s.len(),
-            Component::Type(t) => t.0.len() + 1,
+            Component::SizedString(s) => s.len() + 4,
+            Component::Type(t) => varint_len(t.0.len()) + t.0.len(),
             Component::DateTime(_) => 8,
         }
     }
@@ -45,8 +84,12 @@ impl<'a> Component<'a> {
             Component::UnsizedString(s) => {
                 cursor.write_all(s.as_bytes())?;
             }
+            Component::SizedString(s) => {
+                cursor.write_u32::<BigEndian>(s.len() as u32)?;
+                cursor.write_all(s.as_bytes())?;
+            }
             Component::Type(t) => {
-                cursor.write_all(&[t.0.len() as u8])?;
+                write_varint(cursor, t.0.len())?;
                 cursor.write_all(t.0.as_bytes())?;
             }
             Component::DateTime(datetime) => {
@@ -79,12 +122,7 @@ pub fn read_uuid<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> Uuid {
 }
 
 pub fn read_type<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> models::Type {
-    let t_len = {
-        let mut buf: [u8; 1] = [0; 1];
-        cursor.read_exact(&mut buf).unwrap();
-        buf[0] as usize
-    };
-
+    let t_len = read_varint(cursor).unwrap();
     let mut buf = vec![0u8; t_len];
     cursor.read_exact(&mut buf).unwrap();
 
@@ -100,6 +138,13 @@ pub fn read_unsized_string<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> String {
     buf
 }
 
+pub fn read_sized_string<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> String {
+    let len = cursor.read_u32::<BigEndian>().unwrap() as usize;
+    let mut buf = vec![0u8; len];
+    cursor.read_exact(&mut buf).unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
 pub fn read_datetime<T: AsRef<[u8]>>(cursor: &mut Cursor<T>) -> DateTime<Utc> {
     let time_to_end = cursor.read_u64::<BigEndian>().unwrap();
     assert!(time_to_end <= i64::MAX as u64);
@@ -1,102 +1,162 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
 use std::i32;
-use std::sync::Arc;
+use std::iter;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
 use std::u64;
 use std::usize;
 
+use super::batch::{Batch, Changes};
+use super::integrity::{self, check, IntegrityIssue};
 use super::managers::*;
-use crate::errors::Result;
-use crate::util::next_uuid;
+use super::migrations::migrate;
+use super::options::{ColumnFamily, RocksdbOptions};
+use crate::errors::{Error, Result};
+use crate::traversal::{breadth_first, shortest_path, Graph};
+use crate::util::validate_graph_name;
 use crate::{
     BulkInsertItem, Datastore, Edge, EdgeDirection, EdgeKey, EdgeProperties, EdgeProperty, EdgePropertyQuery,
-    EdgeQuery, NamedProperty, Transaction, Type, Vertex, VertexProperties, VertexProperty, VertexPropertyQuery,
-    VertexQuery,
+    EdgeQuery, NamedGraphs, NamedProperty, PropertyFilter, PropertyPredicate, ShortestPathQuery, Transaction,
+    TraversalQuery, Type, Vertex, VertexProperties, VertexProperty, VertexPropertyQuery, VertexQuery,
 };
 
 use chrono::offset::Utc;
-use rocksdb::{DBCompactionStyle, MemtableFactory, Options, WriteBatch, WriteOptions, DB};
+use chrono::DateTime;
+use rocksdb::{Snapshot, WriteOptions, DB};
 use serde_json::Value as JsonValue;
 use uuid::Uuid;
 
-const CF_NAMES: [&str; 6] = [
-    "vertices:v1",
-    "edges:v1",
-    "edge_ranges:v1",
-    "reversed_edge_ranges:v1",
-    "vertex_properties:v1",
-    "edge_properties:v1",
-];
-
-fn get_options(max_open_files: Option<i32>, bulk_load_optimized: bool) -> Options {
-    // Current tuning based off of the total ordered example, flash
-    // storage example on
-    // https://github.com/facebook/rocksdb/wiki/RocksDB-Tuning-Guide
-    let mut opts = Options::default();
-    opts.create_if_missing(true);
-    opts.set_compaction_style(DBCompactionStyle::Level);
-    opts.set_write_buffer_size(67_108_864); // 64mb
-    opts.set_max_write_buffer_number(3);
-    opts.set_target_file_size_base(67_108_864); // 64mb
-    opts.set_max_background_compactions(4);
-    opts.set_level_zero_file_num_compaction_trigger(8);
-    opts.set_level_zero_slowdown_writes_trigger(17);
-    opts.set_level_zero_stop_writes_trigger(24);
-    opts.set_num_levels(4);
-    opts.set_max_bytes_for_level_base(536_870_912); // 512mb
-    opts.set_max_bytes_for_level_multiplier(8.0);
-
-    if let Some(max_open_files) = max_open_files {
-        opts.set_max_open_files(max_open_files);
-    }
-
-    if bulk_load_optimized {
-        // Via https://github.com/facebook/rocksdb/wiki/RocksDB-FAQ
-        opts.set_allow_concurrent_memtable_write(false);
-        opts.set_memtable_factory(MemtableFactory::Vector);
-        opts.set_max_background_flushes(8);
-        opts.set_disable_auto_compactions(true);
-        opts.set_level_zero_file_num_compaction_trigger(1024);
-        opts.set_level_zero_slowdown_writes_trigger(1024 * 5);
-        opts.set_level_zero_stop_writes_trigger(1024 * 6);
-    }
-
-    opts
+/// How many index entries to add before writing them when indexing a
+/// property, so that indexing a big database doesn't hold all of them in
+/// memory at once.
+const INDEX_BATCH_SIZE: usize = 1000;
+
+fn vertex_matches(manager: &VertexPropertyManager, id: Uuid, filters: &[PropertyFilter]) -> Result<bool> {
+    for filter in filters {
+        if !filter.matches(manager.get(id, &filter.name)?.as_ref()) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn edge_matches(manager: &EdgePropertyManager, item: &EdgeRangeItem, filters: &[PropertyFilter]) -> Result<bool> {
+    let (out_id, t, _, in_id) = item;
+
+    for filter in filters {
+        if !filter.matches(manager.get(*out_id, t, *in_id, &filter.name)?.as_ref()) {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn filter_vertex_items<'a>(
+    db: &'a DB,
+    source: Source<'a>,
+    iter: Box<dyn Iterator<Item = Result<VertexItem>> + 'a>,
+    filters: Vec<PropertyFilter>,
+) -> Box<dyn Iterator<Item = Result<VertexItem>> + 'a> {
+    if filters.is_empty() {
+        return iter;
+    }
+
+    let manager = VertexPropertyManager::new(db).with_source(source);
+
+    Box::new(iter.filter_map(move |item| match item {
+        Ok((id, t)) => match vertex_matches(&manager, id, &filters) {
+            Ok(true) => Some(Ok((id, t))),
+            Ok(false) => None,
+            Err(err) => Some(Err(err)),
+        },
+        Err(err) => Some(Err(err)),
+    }))
+}
+
+fn filter_edge_items<'a>(
+    db: &'a DB,
+    source: Source<'a>,
+    iter: Box<dyn Iterator<Item = Result<EdgeRangeItem>> + 'a>,
+    filters: Vec<PropertyFilter>,
+) -> Box<dyn Iterator<Item = Result<EdgeRangeItem>> + 'a> {
+    if filters.is_empty() {
+        return iter;
+    }
+
+    let manager = EdgePropertyManager::new(db).with_source(source);
+
+    Box::new(iter.filter_map(move |item| match item {
+        Ok(edge) => match edge_matches(&manager, &edge, &filters) {
+            Ok(true) => Some(Ok(edge)),
+            Ok(false) => None,
+            Err(err) => Some(Err(err)),
+        },
+        Err(err) => Some(Err(err)),
+    }))
 }
 
-fn execute_vertex_query(db: &DB, q: VertexQuery) -> Result<Vec<VertexItem>> {
+/// Lazily executes a vertex query. Nothing is read from the database until
+/// the returned iterator is advanced.
+fn execute_vertex_query<'a>(
+    db: &'a DB,
+    source: Source<'a>,
+    q: VertexQuery,
+) -> Result<Box<dyn Iterator<Item = Result<VertexItem>> + 'a>> {
     match q {
         VertexQuery::Range(q) => {
-            let vertex_manager = VertexManager::new(db);
-
-            let next_uuid = match q.start_id {
-                Some(start_id) => {
-                    match next_uuid(start_id) {
-                        Ok(next_uuid) => next_uuid,
-                        // If we get an error back, it's because
-                        // `start_id` is the maximum possible value. We
-                        // know that no vertices exist whose ID is greater
-                        // than the maximum possible value, so just return
-                        // an empty list.
-                        Err(_) => return Ok(vec![]),
+            let vertex_manager = VertexManager::new(db).with_source(source);
+            let indexed_property_manager = IndexedPropertyManager::new(db).with_source(source);
+            let vertex_property_index_manager = VertexPropertyIndexManager::new(db).with_source(source);
+
+            // Use an index if one of the filters is an equality check on an
+            // indexed property
+            let mut index_filter = None;
+
+            for filter in &q.filters {
+                if let PropertyPredicate::Equals(ref value) = filter.predicate {
+                    if indexed_property_manager.exists(&filter.name)? {
+                        index_filter = Some((&filter.name, value));
+                        break;
                     }
                 }
-                None => Uuid::default(),
-            };
+            }
+
+            let start_id = q.start_id.unwrap_or_default();
+
+            let mut iter: Box<dyn Iterator<Item = Result<VertexItem>> + 'a> = match index_filter {
+                Some((name, value)) => {
+                    let iter = vertex_property_index_manager.iterate_for_value(name, value, start_id)?;
 
-            let mut iter: Box<dyn Iterator<Item = Result<VertexItem>>> =
-                Box::new(vertex_manager.iterate_for_range(next_uuid)?);
+                    Box::new(iter.filter_map(move |item| match item {
+                        Ok(id) => match vertex_manager.get(id) {
+                            Ok(Some(t)) => Some(Ok((id, t))),
+                            Ok(None) => None,
+                            Err(err) => Some(Err(err)),
+                        },
+                        Err(err) => Some(Err(err)),
+                    }))
+                }
+                None => Box::new(vertex_manager.iterate_for_range(start_id)?),
+            };
 
-            if let Some(ref t) = q.t {
+            if let Some(t) = q.t {
                 iter = Box::new(iter.filter(move |item| match item {
-                    Ok((_, v)) => v == t,
+                    Ok((_, v)) => v == &t,
                     Err(_) => true,
                 }));
             }
 
-            let vertices: Result<Vec<VertexItem>> = iter.take(q.limit as usize).collect();
-            vertices
+            let iter = filter_vertex_items(db, source, iter, q.filters);
+            Ok(Box::new(iter.take(q.limit as usize)))
         }
         VertexQuery::Specific(q) => {
-            let vertex_manager = VertexManager::new(db);
+            let vertex_manager = VertexManager::new(db).with_source(source);
 
             let iter = q.ids.into_iter().map(move |id| match vertex_manager.get(id)? {
                 Some(value) => Ok(Some((id, value))),
@@ -109,15 +169,22 @@ fn execute_vertex_query(db: &DB, q: VertexQuery) -> Result<Vec<VertexItem>> {
                 _ => None,
             });
 
-            let vertices: Result<Vec<VertexItem>> = iter.collect();
-            vertices
+            Ok(Box::new(iter))
         }
         VertexQuery::Pipe(q) => {
-            let vertex_manager = VertexManager::new(db);
-            let iter = execute_edge_query(db, *q.inner)?.into_iter();
+            let vertex_manager = VertexManager::new(db).with_source(source);
+
+            let inner = match (*q.inner, q.cursor) {
+                (EdgeQuery::Pipe(inner), Some(cursor)) => inner.cursor(cursor).into(),
+                (inner, _) => inner,
+            };
+
+            let iter = execute_edge_query(db, source, inner)?;
             let direction = q.direction;
 
-            let iter = iter.map(move |(out_id, _, _, in_id)| {
+            let iter = iter.map(move |item| {
+                let (out_id, _, _, in_id) = item?;
+
                 let id = match direction {
                     EdgeDirection::Outbound => out_id,
                     EdgeDirection::Inbound => in_id,
@@ -135,25 +202,31 @@ fn execute_vertex_query(db: &DB, q: VertexQuery) -> Result<Vec<VertexItem>> {
                 _ => None,
             });
 
-            let mut iter: Box<dyn Iterator<Item = Result<VertexItem>>> = Box::new(iter);
+            let mut iter: Box<dyn Iterator<Item = Result<VertexItem>> + 'a> = Box::new(iter);
 
-            if let Some(ref t) = q.t {
+            if let Some(t) = q.t {
                 iter = Box::new(iter.filter(move |item| match item {
-                    Ok((_, v)) => v == t,
+                    Ok((_, v)) => v == &t,
                     Err(_) => true,
                 }));
             }
 
-            let vertices: Result<Vec<VertexItem>> = iter.take(q.limit as usize).collect();
-            vertices
+            let iter = filter_vertex_items(db, source, iter, q.filters);
+            Ok(Box::new(iter.take(q.limit as usize)))
         }
     }
 }
 
-fn execute_edge_query(db: &DB, q: EdgeQuery) -> Result<Vec<EdgeRangeItem>> {
+/// Lazily executes an edge query. Nothing is read from the database until
+/// the returned iterator is advanced.
+fn execute_edge_query<'a>(
+    db: &'a DB,
+    source: Source<'a>,
+    q: EdgeQuery,
+) -> Result<Box<dyn Iterator<Item = Result<EdgeRangeItem>> + 'a>> {
     match q {
         EdgeQuery::Specific(q) => {
-            let edge_manager = EdgeManager::new(&db);
+            let edge_manager = EdgeManager::new(db).with_source(source);
 
             let iter = q.keys.into_iter().map(move |key| -> Result<Option<EdgeRangeItem>> {
                 match edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
@@ -170,70 +243,170 @@ fn execute_edge_query(db: &DB, q: EdgeQuery) -> Result<Vec<EdgeRangeItem>> {
                 _ => None,
             });
 
-            let edges: Result<Vec<EdgeRangeItem>> = iter.collect();
-            edges
+            Ok(Box::new(iter))
         }
         EdgeQuery::Pipe(q) => {
-            let vertices = execute_vertex_query(db, *q.inner)?;
+            let ordered_by_id = matches!(*q.inner, VertexQuery::Range(_));
+            let vertices = execute_vertex_query(db, source, *q.inner)?;
 
             let edge_range_manager = match q.direction {
-                EdgeDirection::Outbound => EdgeRangeManager::new(&db),
-                EdgeDirection::Inbound => EdgeRangeManager::new_reversed(&db),
+                EdgeDirection::Outbound => EdgeRangeManager::new(db).with_source(source),
+                EdgeDirection::Inbound => EdgeRangeManager::new_reversed(db).with_source(source),
             };
 
-            // Ideally we'd use iterators all the way down, but things
-            // start breaking apart due to conditional expressions not
-            // returning the same type signature, issues with `Result`s
-            // and some of the iterators, etc. So at this point, we'll
-            // just resort to building a vector.
-            let mut edges: Vec<EdgeRangeItem> = Vec::new();
+            // Each vertex's edges are returned in the order of their edge
+            // range keys, so resuming from a cursor means starting right
+            // after the cursor's key within the cursor's vertex
+            let direction = q.direction;
+            let mut cursor = q.cursor.map(|cursor| match direction {
+                EdgeDirection::Outbound => (
+                    cursor.key.outbound_id,
+                    cursor.key.t,
+                    cursor.update_datetime,
+                    cursor.key.inbound_id,
+                ),
+                EdgeDirection::Inbound => (
+                    cursor.key.inbound_id,
+                    cursor.key.t,
+                    cursor.update_datetime,
+                    cursor.key.outbound_id,
+                ),
+            });
 
-            for (id, _) in vertices.into_iter() {
-                let edge_iterator = edge_range_manager.iterate_for_range(id, q.t.as_ref(), q.high)?;
+            // Edges are returned in order of the ID of the vertex they're
+            // piped from, so that a cursor can be resumed from by comparison.
+            // Range queries are already ordered by ID, while anything else
+            // is gathered and sorted first.
+            let vertices: Box<dyn Iterator<Item = Result<VertexItem>> + 'a> = if ordered_by_id {
+                vertices
+            } else {
+                let mut vertices = vertices.collect::<Result<Vec<VertexItem>>>()?;
+                vertices.sort_by_key(|(id, _)| *id);
+                Box::new(vertices.into_iter().map(Ok))
+            };
 
-                for item in edge_iterator {
-                    let (edge_range_first_id, edge_range_t, edge_range_update_datetime, edge_range_second_id) = item?;
+            // If the cursor's vertex has since been deleted, or no longer
+            // matches the inner query, this resumes from the vertex that took
+            // its place
+            let vertices: Box<dyn Iterator<Item = Result<VertexItem>> + 'a> = match cursor {
+                Some((first_id, _, _, _)) => Box::new(vertices.skip_while(move |item| match item {
+                    Ok((id, _)) => *id < first_id,
+                    Err(_) => false,
+                })),
+                None => vertices,
+            };
 
-                    if let Some(low) = q.low {
-                        if edge_range_update_datetime < low {
-                            break;
-                        }
-                    }
+            let t = q.t;
+            let high = q.high;
+            let low = q.low;
 
-                    edges.push(match q.direction {
-                        EdgeDirection::Outbound => (
-                            edge_range_first_id,
-                            edge_range_t,
-                            edge_range_update_datetime,
-                            edge_range_second_id,
-                        ),
-                        EdgeDirection::Inbound => (
-                            edge_range_second_id,
-                            edge_range_t,
-                            edge_range_update_datetime,
-                            edge_range_first_id,
-                        ),
-                    });
-
-                    if edges.len() == q.limit as usize {
-                        break;
-                    }
+            let iter = vertices.flat_map(move |item| -> Box<dyn Iterator<Item = Result<EdgeRangeItem>> + 'a> {
+                let id = match item {
+                    Ok((id, _)) => id,
+                    Err(err) => return Box::new(iter::once(Err(err))),
+                };
+
+                let edge_iterator = match cursor.take() {
+                    Some((first_id, ref after_t, update_datetime, second_id)) if first_id == id => edge_range_manager
+                        .iterate_for_range_after(id, t.as_ref(), high, Some((after_t, update_datetime, second_id))),
+                    _ => edge_range_manager.iterate_for_range(id, t.as_ref(), high),
+                };
+
+                let edge_iterator = match edge_iterator {
+                    Ok(edge_iterator) => edge_iterator,
+                    Err(err) => return Box::new(iter::once(Err(err))),
+                };
+
+                // Edge range items are sorted by descending update datetime
+                // for each vertex, so everything after the first item that's
+                // older than `low` can be skipped
+                Box::new(edge_iterator.take_while(move |item| match (item, low) {
+                    (Ok((_, _, update_datetime, _)), Some(low)) => *update_datetime >= low,
+                    _ => true,
+                }))
+            });
+
+            let iter = iter.map(move |item| {
+                let (edge_range_first_id, edge_range_t, edge_range_update_datetime, edge_range_second_id) = item?;
+
+                match direction {
+                    EdgeDirection::Outbound => Ok((
+                        edge_range_first_id,
+                        edge_range_t,
+                        edge_range_update_datetime,
+                        edge_range_second_id,
+                    )),
+                    EdgeDirection::Inbound => Ok((
+                        edge_range_second_id,
+                        edge_range_t,
+                        edge_range_update_datetime,
+                        edge_range_first_id,
+                    )),
                 }
-            }
+            });
 
-            Ok(edges)
+            let iter = filter_edge_items(db, source, Box::new(iter), q.filters);
+            Ok(Box::new(iter.take(q.limit as usize)))
         }
     }
 }
 
+/// Opens a database, creating it if it doesn't exist, and migrating it to
+/// the current on-disk format if needed.
+fn open_db(path: &Path, options: &RocksdbOptions) -> Result<(Arc<DB>, Arc<Mutex<()>>)> {
+    let opts = options.to_rocksdb_options();
+
+    // Listing column families fails if the database doesn't exist yet
+    let is_new = DB::list_cf(&opts, path).is_err();
+
+    let db = Arc::new(DB::open_cf_descriptors(
+        &opts,
+        path,
+        options.to_column_family_descriptors(),
+    )?);
+    let write_lock = Arc::new(Mutex::new(()));
+
+    migrate(&db, || Batch::new(&db, &write_lock), is_new)?;
+
+    Ok((db, write_lock))
+}
+
+/// The named graphs of a datastore. Each named graph is a separate database,
+/// with its own set of column families, under the `graphs` directory of the
+/// default graph's database. Their names are recorded in the default graph's
+/// metadata.
+///
+/// Column families can't be created while a database is shared, which is
+/// why named graphs aren't column families in the default graph's database.
+#[derive(Debug)]
+struct Graphs {
+    path: PathBuf,
+    options: Arc<RocksdbOptions>,
+    db: Arc<DB>,
+    write_lock: Arc<Mutex<()>>,
+    /// Named graphs that have been opened so far, which are kept open until
+    /// they're dropped.
+    open: Mutex<BTreeMap<String, (Arc<DB>, Arc<Mutex<()>>)>>,
+}
+
+impl Graphs {
+    fn path(&self, name: &str) -> PathBuf {
+        self.path.join("graphs").join(name)
+    }
+}
+
 /// A datastore that is backed by rocksdb.
 #[derive(Debug)]
 pub struct RocksdbDatastore {
     db: Arc<DB>,
+    write_lock: Arc<Mutex<()>>,
+    graphs: Arc<Graphs>,
 }
 
 impl RocksdbDatastore {
-    /// Creates a new rocksdb datastore.
+    /// Creates a new rocksdb datastore, or opens an existing one. Existing
+    /// databases written by an older version of the on-disk format are
+    /// migrated to the current one.
     ///
     /// # Arguments
     /// * `path` - The file path to the rocksdb database.
@@ -242,23 +415,135 @@ impl RocksdbDatastore {
     /// * `bulk_load_optimized` - Whether to configure the database to
     ///   optimize for bulk loading, based off of suggestions from the RocksDB
     ///   FAQ.
+    ///
+    /// # Errors
+    /// Returns `Error::UnsupportedVersion` if the database was written by a
+    /// newer version of the on-disk format.
     pub fn new(path: &str, max_open_files: Option<i32>, bulk_load_optimized: bool) -> Result<RocksdbDatastore> {
-        let opts = get_options(max_open_files, bulk_load_optimized);
+        let options = RocksdbOptions::default()
+            .max_open_files(max_open_files)
+            .bulk_load_optimized(bulk_load_optimized);
+        Self::new_with_options(path, options)
+    }
 
-        let db = match DB::open_cf(&opts, path, &CF_NAMES) {
-            Ok(db) => db,
-            Err(_) => {
-                let mut db = DB::open(&opts, path)?;
+    /// Creates a new rocksdb datastore, or opens an existing one, with the
+    /// given tuning options. Existing databases written by an older version
+    /// of the on-disk format are migrated to the current one.
+    ///
+    /// # Arguments
+    /// * `path` - The file path to the rocksdb database.
+    /// * `options` - The tuning options, which also apply to named graphs.
+    ///
+    /// # Errors
+    /// Returns `Error::UnsupportedVersion` if the database was written by a
+    /// newer version of the on-disk format.
+    pub fn new_with_options(path: &str, options: RocksdbOptions) -> Result<RocksdbDatastore> {
+        let (db, write_lock) = open_db(Path::new(path), &options)?;
+
+        let graphs = Graphs {
+            path: PathBuf::from(path),
+            options: Arc::new(options),
+            db: db.clone(),
+            write_lock: write_lock.clone(),
+            open: Mutex::new(BTreeMap::new()),
+        };
+
+        Ok(RocksdbDatastore {
+            db,
+            write_lock,
+            graphs: Arc::new(graphs),
+        })
+    }
 
-                for cf_name in &CF_NAMES {
-                    db.create_cf(cf_name, &opts)?;
+    /// Indexes a property, so that vertices and edges can be looked up by its
+    /// value. Existing values are indexed immediately, and the index is kept
+    /// up-to-date from then on. Indexing an already indexed property is a
+    /// no-op.
+    ///
+    /// Vertex range queries with an equality filter on an indexed property
+    /// will use the index rather than scanning every vertex.
+    ///
+    /// Existing values are indexed in chunks, and the property is only
+    /// marked as indexed once they all are. An interrupted call leaves the
+    /// property unindexed, with index entries that `check_integrity`
+    /// reports as dangling; calling this again finishes the job.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the property to index.
+    pub fn index_property(&self, name: &str) -> Result<()> {
+        let indexed_property_manager = IndexedPropertyManager::new(&self.db);
+        let mut batch = Batch::new(&self.db, &self.write_lock);
+
+        if indexed_property_manager.exists(name)? {
+            return Ok(());
+        }
+
+        let vertex_property_manager = VertexPropertyManager::new(&self.db);
+        let vertex_property_index_manager = VertexPropertyIndexManager::new(&self.db);
+        let edge_property_manager = EdgePropertyManager::new(&self.db);
+        let edge_property_index_manager = EdgePropertyIndexManager::new(&self.db);
+        let mut indexed = 0;
+
+        for item in vertex_property_manager.iterate()? {
+            let ((id, property_name), value) = item?;
+
+            if property_name == name {
+                vertex_property_index_manager.set(&mut batch, name, &value, id)?;
+                indexed += 1;
+
+                if indexed % INDEX_BATCH_SIZE == 0 {
+                    batch.flush()?;
                 }
+            }
+        }
+
+        for item in edge_property_manager.iterate()? {
+            let ((out_id, t, in_id, property_name), value) = item?;
+
+            if property_name == name {
+                edge_property_index_manager.set(&mut batch, name, &value, out_id, &t, in_id)?;
+                indexed += 1;
 
-                db
+                if indexed % INDEX_BATCH_SIZE == 0 {
+                    batch.flush()?;
+                }
             }
-        };
+        }
+
+        // The write lock has been held throughout, so no writes have been
+        // missed by the index
+        indexed_property_manager.create(&mut batch, name)?;
+        batch.write()?;
+        Ok(())
+    }
+
+    /// Checks the database for dangling references, such as edges whose
+    /// vertices don't exist, or properties whose owners don't, as well as
+    /// property index entries and counters that are out of sync with what
+    /// they index or count. These should not occur in normal operation, but
+    /// can be left behind by bugs or by writes that bypass the datastore.
+    /// The check reads from a snapshot, so writes can carry on meanwhile.
+    ///
+    /// Returns the issues that were found in the snapshot.
+    ///
+    /// # Arguments
+    /// * `repair` - Whether to also fix the issues, by deleting dangling
+    ///   edges, edge ranges, properties and index entries, recreating
+    ///   missing edge ranges and index entries, and correcting counters.
+    ///   Writes are blocked while repairing, and each issue is checked
+    ///   again first, in case it's changed since the snapshot was taken.
+    ///   Repairs are written in chunks as they're made.
+    pub fn check_integrity(&self, repair: bool) -> Result<Vec<IntegrityIssue>> {
+        let snapshot = self.db.snapshot();
+        let issues = check(&self.db, Source::snapshot(&self.db, &snapshot))?;
+
+        if repair {
+            let mut batch = Batch::new(&self.db, &self.write_lock);
+            integrity::repair(&self.db, &mut batch, &issues)?;
+            batch.write()?;
+        }
 
-        Ok(RocksdbDatastore { db: Arc::new(db) })
+        Ok(issues)
     }
 
     /// Runs a repair operation on the rocksdb database.
@@ -268,10 +553,111 @@ impl RocksdbDatastore {
     /// * `max_open_files` - The maximum number of open files to have. If
     ///   `None`, the default will be used.
     pub fn repair(path: &str, max_open_files: Option<i32>) -> Result<()> {
-        let opts = get_options(max_open_files, false);
+        let opts = RocksdbOptions::default()
+            .max_open_files(max_open_files)
+            .to_rocksdb_options();
         DB::repair(&opts, path)?;
         Ok(())
     }
+
+    /// Compacts every column family, discarding deleted and overwritten
+    /// entries. This can take a long time on large databases, but is useful
+    /// after a bulk load with `bulk_load_optimized`, which disables automatic
+    /// compactions.
+    pub fn compact(&self) {
+        for cf in ColumnFamily::ALL.iter() {
+            let cf = self.db.cf_handle(cf.name()).unwrap();
+            self.db.compact_range_cf::<&[u8], &[u8]>(cf, None, None);
+        }
+    }
+}
+
+/// Named graphs are opened the first time they're used, and kept open until
+/// they're dropped.
+///
+/// `drop_graph` returns `Error::GraphInUse` if the graph is still being
+/// used elsewhere, e.g. by a transaction or by a datastore returned from
+/// `graph`.
+impl NamedGraphs for RocksdbDatastore {
+    fn create_graph(&self, name: &str) -> Result<bool> {
+        validate_graph_name(name)?;
+        let mut open = self.graphs.open.lock().unwrap();
+        let metadata_manager = MetadataManager::new(&self.graphs.db);
+
+        if metadata_manager.graph_exists(name)? {
+            return Ok(false);
+        }
+
+        // Clear out anything left behind by an interrupted create or drop
+        let path = self.graphs.path(name);
+        DB::destroy(&self.graphs.options.to_rocksdb_options(), &path)?;
+        fs::create_dir_all(path.parent().unwrap())?;
+        let graph = open_db(&path, &self.graphs.options)?;
+
+        let mut batch = Batch::new(&self.graphs.db, &self.graphs.write_lock);
+        metadata_manager.add_graph(&mut batch, name)?;
+        batch.write()?;
+
+        open.insert(name.to_string(), graph);
+        Ok(true)
+    }
+
+    fn drop_graph(&self, name: &str) -> Result<bool> {
+        validate_graph_name(name)?;
+        let mut open = self.graphs.open.lock().unwrap();
+        let metadata_manager = MetadataManager::new(&self.graphs.db);
+
+        if !metadata_manager.graph_exists(name)? {
+            return Ok(false);
+        }
+
+        // The database has to be closed before it can be destroyed, which
+        // can only happen if nothing else refers to it
+        if let Some((db, write_lock)) = open.remove(name) {
+            match Arc::try_unwrap(db) {
+                Ok(db) => drop(db),
+                Err(db) => {
+                    open.insert(name.to_string(), (db, write_lock));
+                    return Err(Error::GraphInUse);
+                }
+            }
+        }
+
+        let mut batch = Batch::new(&self.graphs.db, &self.graphs.write_lock);
+        metadata_manager.remove_graph(&mut batch, name)?;
+        batch.write()?;
+
+        DB::destroy(&self.graphs.options.to_rocksdb_options(), self.graphs.path(name))?;
+        Ok(true)
+    }
+
+    fn list_graphs(&self) -> Result<Vec<String>> {
+        MetadataManager::new(&self.graphs.db).iterate_graphs()?.collect()
+    }
+
+    fn graph(&self, name: &str) -> Result<Option<Self>> {
+        validate_graph_name(name)?;
+        let mut open = self.graphs.open.lock().unwrap();
+
+        let (db, write_lock) = match open.get(name) {
+            Some((db, write_lock)) => (db.clone(), write_lock.clone()),
+            None => {
+                if !MetadataManager::new(&self.graphs.db).graph_exists(name)? {
+                    return Ok(None);
+                }
+
+                let graph = open_db(&self.graphs.path(name), &self.graphs.options)?;
+                open.insert(name.to_string(), graph.clone());
+                graph
+            }
+        };
+
+        Ok(Some(RocksdbDatastore {
+            db,
+            write_lock,
+            graphs: self.graphs.clone(),
+        }))
+    }
 }
 
 impl Datastore for RocksdbDatastore {
@@ -288,7 +674,7 @@ impl Datastore for RocksdbDatastore {
         let edge_manager = EdgeManager::new(&db);
         let vertex_property_manager = VertexPropertyManager::new(&db);
         let edge_property_manager = EdgePropertyManager::new(&db);
-        let mut batch = WriteBatch::default();
+        let mut batch = Batch::new(&db, &self.write_lock);
         let mut compact_vertices = false;
         let mut compact_edges = false;
         let mut compact_vertex_properties = false;
@@ -304,6 +690,17 @@ impl Datastore for RocksdbDatastore {
                     edge_manager.set(&mut batch, key.outbound_id, &key.t, key.inbound_id, Utc::now())?;
                     compact_edges = true;
                 }
+                BulkInsertItem::EdgeWithDatetime(ref edge) => {
+                    let key = &edge.key;
+                    edge_manager.set(
+                        &mut batch,
+                        key.outbound_id,
+                        &key.t,
+                        key.inbound_id,
+                        edge.created_datetime,
+                    )?;
+                    compact_edges = true;
+                }
                 BulkInsertItem::VertexProperty(id, ref name, ref value) => {
                     vertex_property_manager.set(&mut batch, id, name, value)?;
                     compact_vertex_properties = true;
@@ -320,7 +717,7 @@ impl Datastore for RocksdbDatastore {
         let mut opts = WriteOptions::default();
         opts.set_sync(false);
         opts.disable_wal(true);
-        self.db.write_opt(batch, &opts)?;
+        batch.write_opt(&opts)?;
 
         // manually compact
         if compact_vertices {
@@ -340,40 +737,340 @@ impl Datastore for RocksdbDatastore {
     }
 
     fn transaction(&self) -> Result<Self::Trans> {
-        RocksdbTransaction::new(self.db.clone())
+        RocksdbTransaction::new(self.db.clone(), self.write_lock.clone(), self.graphs.options.clone())
     }
 }
 
-/// A transaction that is backed by rocksdb.
+/// Exposes a source to traversals.
+struct SourceGraph<'a> {
+    vertex_manager: VertexManager<'a>,
+    edge_range_manager: EdgeRangeManager<'a>,
+    reversed_edge_range_manager: EdgeRangeManager<'a>,
+    edge_property_manager: EdgePropertyManager<'a>,
+}
+
+impl<'a> SourceGraph<'a> {
+    fn new(db: &'a DB, source: Source<'a>) -> Self {
+        Self {
+            vertex_manager: VertexManager::new(db).with_source(source),
+            edge_range_manager: EdgeRangeManager::new(db).with_source(source),
+            reversed_edge_range_manager: EdgeRangeManager::new_reversed(db).with_source(source),
+            edge_property_manager: EdgePropertyManager::new(db).with_source(source),
+        }
+    }
+}
+
+impl<'a> Graph for SourceGraph<'a> {
+    fn vertex_type(&self, id: Uuid) -> Result<Option<Type>> {
+        self.vertex_manager.get(id)
+    }
+
+    fn adjacent_edges(&self, ids: &[Uuid], direction: EdgeDirection, t: Option<&Type>) -> Result<Vec<EdgeKey>> {
+        let mut keys = Vec::new();
+
+        for id in ids {
+            match direction {
+                EdgeDirection::Outbound => {
+                    for item in self.edge_range_manager.iterate_for_range(*id, t, None)? {
+                        let (outbound_id, t, _, inbound_id) = item?;
+                        keys.push(EdgeKey::new(outbound_id, t, inbound_id));
+                    }
+                }
+                EdgeDirection::Inbound => {
+                    for item in self.reversed_edge_range_manager.iterate_for_range(*id, t, None)? {
+                        let (inbound_id, t, _, outbound_id) = item?;
+                        keys.push(EdgeKey::new(outbound_id, t, inbound_id));
+                    }
+                }
+            }
+        }
+
+        Ok(keys)
+    }
+
+    fn edge_property(&self, key: &EdgeKey, name: &str) -> Result<Option<JsonValue>> {
+        self.edge_property_manager
+            .get(key.outbound_id, &key.t, key.inbound_id, name)
+    }
+}
+
+/// A write made by a transaction, which is replayed against the database
+/// when the transaction is committed.
 #[derive(Debug)]
+enum WriteOp {
+    CreateVertex(Vertex),
+    DeleteVertices(Vec<Uuid>),
+    CreateEdge(EdgeKey, DateTime<Utc>),
+    DeleteEdges(Vec<EdgeRangeItem>),
+    SetVertexProperties(Vec<Uuid>, String, JsonValue),
+    DeleteVertexProperties(Vec<Uuid>, String),
+    SetEdgeProperties(Vec<EdgeKey>, String, JsonValue),
+    DeleteEdgeProperties(Vec<EdgeKey>, String),
+}
+
+/// Applies a write to a batch. Returns false if nothing was written, i.e.
+/// when creating a vertex that already exists, or an edge whose vertices
+/// don't exist. Properties are only set on vertices and edges that exist,
+/// since they may have been deleted since the write was made.
+///
+/// # Arguments
+/// * `db` - The database.
+/// * `batch` - The batch to write to. Existence checks read through it.
+/// * `op` - The write to apply.
+fn apply(db: &DB, batch: &mut Batch, op: &WriteOp) -> Result<bool> {
+    match op {
+        WriteOp::CreateVertex(vertex) => {
+            if VertexManager::new(db).with_source(batch.source()).exists(vertex.id)? {
+                return Ok(false);
+            }
+
+            VertexManager::new(db).create(batch, vertex)?;
+        }
+        WriteOp::DeleteVertices(ids) => {
+            for id in ids {
+                VertexManager::new(db).delete(batch, *id)?;
+            }
+        }
+        WriteOp::CreateEdge(key, update_datetime) => {
+            let vertex_manager = VertexManager::new(db).with_source(batch.source());
+
+            if !vertex_manager.exists(key.outbound_id)? || !vertex_manager.exists(key.inbound_id)? {
+                return Ok(false);
+            }
+
+            EdgeManager::new(db).set(batch, key.outbound_id, &key.t, key.inbound_id, *update_datetime)?;
+        }
+        WriteOp::DeleteEdges(items) => {
+            for (out_id, t, update_datetime, in_id) in items {
+                EdgeManager::new(db).delete(batch, *out_id, t, *in_id, *update_datetime)?;
+            }
+        }
+        WriteOp::SetVertexProperties(ids, name, value) => {
+            for id in ids {
+                if VertexManager::new(db).with_source(batch.source()).exists(*id)? {
+                    VertexPropertyManager::new(db).set(batch, *id, name, value)?;
+                }
+            }
+        }
+        WriteOp::DeleteVertexProperties(ids, name) => {
+            for id in ids {
+                VertexPropertyManager::new(db).delete(batch, *id, name)?;
+            }
+        }
+        WriteOp::SetEdgeProperties(keys, name, value) => {
+            for key in keys {
+                let edge_manager = EdgeManager::new(db).with_source(batch.source());
+
+                if edge_manager.get(key.outbound_id, &key.t, key.inbound_id)?.is_some() {
+                    EdgePropertyManager::new(db).set(batch, key.outbound_id, &key.t, key.inbound_id, name, value)?;
+                }
+            }
+        }
+        WriteOp::DeleteEdgeProperties(keys, name) => {
+            for key in keys {
+                EdgePropertyManager::new(db).delete(batch, key.outbound_id, &key.t, key.inbound_id, name)?;
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// A transaction that is backed by rocksdb.
+///
+/// Writes are staged in the transaction until it's committed, so until then
+/// they're only visible to the transaction itself. Reads go through a
+/// snapshot that is pinned when the transaction is created, and re-pinned
+/// when it's committed or rolled back, with the transaction's writes on top.
+///
+/// Committing replays the writes against the database and writes the result
+/// atomically, so that they take into account anything committed by other
+/// transactions in the meantime; e.g. an edge isn't created if one of its
+/// vertices has since been deleted. Rolling back discards the writes.
+///
+/// Transactions are committed when dropped, but errors can't be returned
+/// from there, so they're only logged; call `commit` to handle them. A
+/// failed commit keeps the transaction's writes, so it can be retried.
 pub struct RocksdbTransaction {
+    snapshot: RwLock<Arc<OwnedSnapshot>>,
     db: Arc<DB>,
+    write_lock: Arc<Mutex<()>>,
+    options: Arc<RocksdbOptions>,
+    pending: Mutex<Pending>,
+}
+
+/// The writes made by a transaction since it was last committed or rolled
+/// back.
+#[derive(Debug, Default)]
+struct Pending {
+    /// The combined changes of the writes. Iterators that are still reading
+    /// them hold a reference too.
+    changes: Arc<Changes>,
+    ops: Vec<WriteOp>,
+}
+
+impl fmt::Debug for RocksdbTransaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RocksdbTransaction").field("db", &self.db).finish()
+    }
+}
+
+/// An iterator over the results of a query against a snapshot, which owns a
+/// reference to that snapshot, and to the transaction's changes at the time
+/// it was created.
+struct SnapshotIterator<'a, T> {
+    // NOTE: this must be declared before `_snapshot` and `_changes`, since
+    // fields are dropped in declaration order, and it borrows them; see
+    // `RocksdbTransaction::iterate_snapshot`.
+    iter: Box<dyn Iterator<Item = Result<T>> + 'a>,
+    _snapshot: Arc<OwnedSnapshot>,
+    _changes: Arc<Changes>,
+}
+
+impl<'a, T> Iterator for SnapshotIterator<'a, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+/// A snapshot that holds a reference to the database it was taken from, so
+/// that it can outlive the borrow it was created with.
+struct OwnedSnapshot {
+    // NOTE: this must be declared before `_db`, since fields are dropped in
+    // declaration order, and releasing the snapshot uses the database.
+    snapshot: Snapshot<'static>,
+    _db: Arc<DB>,
+}
+
+// SAFETY: In rocksdb 0.13, `Snapshot` is neither `Send` nor `Sync` only
+// because it holds a raw pointer to the underlying snapshot handle, next to
+// its `&DB`. RocksDB snapshots are immutable once taken, and can be read
+// from and released on any thread, and `DB` itself is `Send` and `Sync`.
+unsafe impl Send for OwnedSnapshot {}
+unsafe impl Sync for OwnedSnapshot {}
+
+impl OwnedSnapshot {
+    fn new(db: Arc<DB>) -> Self {
+        // SAFETY: In rocksdb 0.13, `Snapshot<'a>` holds a `&'a DB`, which it
+        // reads from and uses to release the snapshot when it's dropped.
+        // Here that reference points into the `Arc`'s allocation, which
+        // doesn't move when the `Arc` does, and which `_db` keeps alive
+        // until after `snapshot` has been dropped; see the field order
+        // above. The `'static` lifetime never escapes: `get` shortens it to
+        // the lifetime of `&self`.
+        let snapshot = unsafe { mem::transmute::<Snapshot<'_>, Snapshot<'static>>(db.snapshot()) };
+        OwnedSnapshot { snapshot, _db: db }
+    }
+
+    fn get(&self) -> &Snapshot<'_> {
+        &self.snapshot
+    }
 }
 
 impl RocksdbTransaction {
-    fn new(db: Arc<DB>) -> Result<Self> {
-        Ok(RocksdbTransaction { db })
+    fn new(db: Arc<DB>, write_lock: Arc<Mutex<()>>, options: Arc<RocksdbOptions>) -> Result<Self> {
+        Ok(RocksdbTransaction {
+            snapshot: RwLock::new(Arc::new(OwnedSnapshot::new(db.clone()))),
+            db,
+            write_lock,
+            options,
+            pending: Mutex::new(Pending::default()),
+        })
+    }
+
+    /// Stages a write. Returns false if nothing was written; see `apply`.
+    /// If the write fails part way through, none of it is staged.
+    fn write(&self, op: WriteOp) -> Result<bool> {
+        let mut pending = self.pending.lock().unwrap();
+        let snapshot = self.snapshot();
+        let source = Source::snapshot_with_changes(&self.db, snapshot.get(), &pending.changes);
+        let mut batch = Batch::staged(&self.db, source);
+
+        if !apply(&self.db, &mut batch, &op)? {
+            return Ok(false);
+        }
+
+        let changes = batch.into_changes();
+        Arc::make_mut(&mut pending.changes).extend(changes);
+        pending.ops.push(op);
+        Ok(true)
+    }
+
+    fn repin_snapshot(&self) {
+        *self.snapshot.write().unwrap() = Arc::new(OwnedSnapshot::new(self.db.clone()));
+    }
+
+    fn snapshot(&self) -> Arc<OwnedSnapshot> {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    fn changes(&self) -> Arc<Changes> {
+        self.pending.lock().unwrap().changes.clone()
+    }
+
+    /// Reads from the snapshot, with the transaction's changes on top.
+    fn read<T, F: FnOnce(&DB, Source<'_>) -> Result<T>>(&self, f: F) -> Result<T> {
+        let changes = self.changes();
+        let snapshot = self.snapshot();
+        f(
+            &self.db,
+            Source::snapshot_with_changes(&self.db, snapshot.get(), &changes),
+        )
+    }
+
+    fn execute_vertex_query(&self, q: VertexQuery) -> Result<Vec<VertexItem>> {
+        self.read(|db, source| execute_vertex_query(db, source, q)?.collect())
+    }
+
+    fn execute_edge_query(&self, q: EdgeQuery) -> Result<Vec<EdgeRangeItem>> {
+        self.read(|db, source| execute_edge_query(db, source, q)?.collect())
+    }
+
+    /// Builds an iterator that reads from the current snapshot and changes,
+    /// and keeps them alive for as long as the iterator is. Because the
+    /// iterator holds its own references to them, the transaction can still
+    /// be written to while iterating.
+    fn iterate_snapshot<'a, T, F>(&'a self, f: F) -> Result<Box<dyn Iterator<Item = Result<T>> + 'a>>
+    where
+        T: 'a,
+        F: FnOnce(&'a DB, Source<'a>) -> Result<Box<dyn Iterator<Item = Result<T>> + 'a>>,
+    {
+        let changes = self.changes();
+        let snapshot = self.snapshot();
+
+        // SAFETY: `f` needs the snapshot and changes for `'a`, but they're
+        // only kept alive by the `Arc`s here, since the transaction can
+        // replace its own on commit or write. The references point into the
+        // `Arc`s' allocations, which don't move when the `Arc`s are moved
+        // into the `SnapshotIterator`, and which it only releases after
+        // dropping `iter`; see the field order there. This matters all the
+        // more because rocksdb 0.13's snapshot iterators only borrow the
+        // `DB`, not the snapshot, so the compiler wouldn't catch one
+        // outliving it. Neither allocation is mutated while shared:
+        // `OwnedSnapshot` is never mutated, and `write` goes through
+        // `Arc::make_mut`, which copies the changes if anything else holds
+        // them.
+        let snapshot_ref = unsafe { &*(&snapshot.snapshot as *const Snapshot<'static>) };
+        let changes_ref = unsafe { &*(&*changes as *const Changes) };
+        let source = Source::snapshot_with_changes(&self.db, snapshot_ref, changes_ref);
+
+        Ok(Box::new(SnapshotIterator {
+            iter: f(&self.db, source)?,
+            _snapshot: snapshot,
+            _changes: changes,
+        }))
     }
 }
 
 impl Transaction for RocksdbTransaction {
     fn create_vertex(&self, vertex: &Vertex) -> Result<bool> {
-        let db = self.db.clone();
-        let vertex_manager = VertexManager::new(&db);
-
-        if vertex_manager.exists(vertex.id)? {
-            Ok(false)
-        } else {
-            let mut batch = WriteBatch::default();
-            vertex_manager.create(&mut batch, vertex)?;
-            self.db.write(batch)?;
-            Ok(true)
-        }
+        self.write(WriteOp::CreateVertex(vertex.clone()))
     }
 
     fn get_vertices<Q: Into<VertexQuery>>(&self, q: Q) -> Result<Vec<Vertex>> {
-        let db = self.db.clone();
-        let iter = execute_vertex_query(&db, q.into())?.into_iter();
+        let iter = self.execute_vertex_query(q.into())?.into_iter();
 
         let iter = iter.map(move |(id, t)| {
             let vertex = Vertex::with_id(id, t);
@@ -383,46 +1080,41 @@ impl Transaction for RocksdbTransaction {
         iter.collect()
     }
 
-    fn delete_vertices<Q: Into<VertexQuery>>(&self, q: Q) -> Result<()> {
-        let db = self.db.clone();
-        let iter = execute_vertex_query(&db, q.into())?.into_iter();
-        let db = self.db.clone();
-        let vertex_manager = VertexManager::new(&db);
-        let mut batch = WriteBatch::default();
-
-        for (id, _) in iter {
-            vertex_manager.delete(&mut batch, id)?;
-        }
+    fn iter_vertices<'a, Q: Into<VertexQuery>>(
+        &'a self,
+        q: Q,
+    ) -> Result<Box<dyn Iterator<Item = Result<Vertex>> + 'a>> {
+        let q = q.into();
+
+        self.iterate_snapshot(move |db, source| {
+            let iter = execute_vertex_query(db, source, q)?;
+            Ok(Box::new(iter.map(|item| {
+                let (id, t) = item?;
+                Ok(Vertex::with_id(id, t))
+            })))
+        })
+    }
 
-        self.db.write(batch)?;
+    fn delete_vertices<Q: Into<VertexQuery>>(&self, q: Q) -> Result<()> {
+        let iter = self.execute_vertex_query(q.into())?.into_iter();
+        self.write(WriteOp::DeleteVertices(iter.map(|(id, _)| id).collect()))?;
         Ok(())
     }
 
-    fn get_vertex_count(&self) -> Result<u64> {
-        let db = self.db.clone();
-        let vertex_manager = VertexManager::new(&db);
-        let iterator = vertex_manager.iterate_for_range(Uuid::default())?;
-        Ok(iterator.count() as u64)
+    fn get_vertex_count(&self, t: Option<&Type>) -> Result<u64> {
+        self.read(|db, source| CounterManager::new(db).with_source(source).get_vertex_count(t))
     }
 
     fn create_edge(&self, key: &EdgeKey) -> Result<bool> {
-        let db = self.db.clone();
-        let vertex_manager = VertexManager::new(&db);
+        self.create_edge_with_datetime(key, Utc::now())
+    }
 
-        if !vertex_manager.exists(key.outbound_id)? || !vertex_manager.exists(key.inbound_id)? {
-            Ok(false)
-        } else {
-            let edge_manager = EdgeManager::new(&db);
-            let mut batch = WriteBatch::default();
-            edge_manager.set(&mut batch, key.outbound_id, &key.t, key.inbound_id, Utc::now())?;
-            self.db.write(batch)?;
-            Ok(true)
-        }
+    fn create_edge_with_datetime(&self, key: &EdgeKey, datetime: DateTime<Utc>) -> Result<bool> {
+        self.write(WriteOp::CreateEdge(key.clone(), datetime))
     }
 
     fn get_edges<Q: Into<EdgeQuery>>(&self, q: Q) -> Result<Vec<Edge>> {
-        let db = self.db.clone();
-        let iter = execute_edge_query(&db, q.into())?.into_iter();
+        let iter = self.execute_edge_query(q.into())?.into_iter();
 
         let iter = iter.map(move |(out_id, t, update_datetime, in_id)| {
             let key = EdgeKey::new(out_id, t, in_id);
@@ -433,159 +1125,220 @@ impl Transaction for RocksdbTransaction {
         iter.collect()
     }
 
-    fn delete_edges<Q: Into<EdgeQuery>>(&self, q: Q) -> Result<()> {
-        let db = self.db.clone();
-        let edge_manager = EdgeManager::new(&db);
-        let vertex_manager = VertexManager::new(&db);
-        let iter = execute_edge_query(&db, q.into())?;
-        let mut batch = WriteBatch::default();
+    fn iter_edges<'a, Q: Into<EdgeQuery>>(&'a self, q: Q) -> Result<Box<dyn Iterator<Item = Result<Edge>> + 'a>> {
+        let q = q.into();
 
-        for (out_id, t, update_datetime, in_id) in iter {
-            if vertex_manager.get(out_id)?.is_some() {
-                edge_manager.delete(&mut batch, out_id, &t, in_id, update_datetime)?;
-            };
-        }
+        self.iterate_snapshot(move |db, source| {
+            let iter = execute_edge_query(db, source, q)?;
+            Ok(Box::new(iter.map(|item| {
+                let (out_id, t, update_datetime, in_id) = item?;
+                Ok(Edge::new(EdgeKey::new(out_id, t, in_id), update_datetime))
+            })))
+        })
+    }
 
-        self.db.write(batch)?;
+    fn delete_edges<Q: Into<EdgeQuery>>(&self, q: Q) -> Result<()> {
+        let items = self.execute_edge_query(q.into())?;
+        self.write(WriteOp::DeleteEdges(items))?;
         Ok(())
     }
 
     fn get_edge_count(&self, id: Uuid, t: Option<&Type>, direction: EdgeDirection) -> Result<u64> {
-        let db = self.db.clone();
-
-        let edge_range_manager = match direction {
-            EdgeDirection::Outbound => EdgeRangeManager::new(&db),
-            EdgeDirection::Inbound => EdgeRangeManager::new_reversed(&db),
-        };
+        self.read(|db, source| {
+            CounterManager::new(db)
+                .with_source(source)
+                .get_edge_count(id, t, direction)
+        })
+    }
 
-        let count = edge_range_manager.iterate_for_range(id, t, None)?.count();
+    fn traverse(&self, q: TraversalQuery) -> Result<Vec<Vertex>> {
+        self.read(|db, source| {
+            let start = execute_vertex_query(db, source, (*q.start).clone())?.collect::<Result<Vec<_>>>()?;
+            breadth_first(&SourceGraph::new(db, source), start, &q)
+        })
+    }
 
-        Ok(count as u64)
+    fn shortest_path(&self, q: ShortestPathQuery) -> Result<Option<Vec<EdgeKey>>> {
+        self.read(|db, source| shortest_path(&SourceGraph::new(db, source), &q))
     }
 
     fn get_vertex_properties(&self, q: VertexPropertyQuery) -> Result<Vec<VertexProperty>> {
-        let db = self.db.clone();
-        let manager = VertexPropertyManager::new(&db);
-        let mut properties = Vec::new();
+        self.read(|db, source| {
+            let manager = VertexPropertyManager::new(db).with_source(source);
+            let mut properties = Vec::new();
 
-        for (id, _) in execute_vertex_query(&db, q.inner)?.into_iter() {
-            let value = manager.get(id, &q.name)?;
+            for item in execute_vertex_query(db, source, q.inner)? {
+                let (id, _) = item?;
+                let value = manager.get(id, &q.name)?;
 
-            if let Some(value) = value {
-                properties.push(VertexProperty::new(id, value));
+                if let Some(value) = value {
+                    properties.push(VertexProperty::new(id, value));
+                }
             }
-        }
 
-        Ok(properties)
+            Ok(properties)
+        })
     }
 
     fn get_all_vertex_properties<Q: Into<VertexQuery>>(&self, q: Q) -> Result<Vec<VertexProperties>> {
-        let db = self.db.clone();
-        let iter = execute_vertex_query(&db, q.into())?.into_iter();
-        let manager = VertexPropertyManager::new(&db);
+        self.read(|db, source| {
+            let manager = VertexPropertyManager::new(db).with_source(source);
 
-        let iter = iter.map(move |(id, t)| {
-            let vertex = Vertex::with_id(id, t);
+            let iter = execute_vertex_query(db, source, q.into())?.map(move |item| {
+                let (id, t) = item?;
+                let vertex = Vertex::with_id(id, t);
 
-            let it = manager.iterate_for_owner(id)?;
-            let props: Result<Vec<_>> = it.map(|r| r).collect();
-            let props_iter = props?.into_iter();
-            let props = props_iter
-                .map(|((_, name), value)| NamedProperty::new(name, value))
-                .collect();
+                let it = manager.iterate_for_owner(id)?;
+                let props: Result<Vec<_>> = it.map(|r| r).collect();
+                let props_iter = props?.into_iter();
+                let props = props_iter
+                    .map(|((_, name), value)| NamedProperty::new(name, value))
+                    .collect();
 
-            Ok(VertexProperties::new(vertex, props))
-        });
+                Ok(VertexProperties::new(vertex, props))
+            });
 
-        iter.collect()
+            iter.collect()
+        })
     }
 
     fn set_vertex_properties(&self, q: VertexPropertyQuery, value: &JsonValue) -> Result<()> {
-        let db = self.db.clone();
-        let manager = VertexPropertyManager::new(&db);
-        let mut batch = WriteBatch::default();
-
-        for (id, _) in execute_vertex_query(&db, q.inner)?.into_iter() {
-            manager.set(&mut batch, id, &q.name, value)?;
-        }
-
-        self.db.write(batch)?;
+        let ids = self
+            .execute_vertex_query(q.inner)?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        self.write(WriteOp::SetVertexProperties(ids, q.name, value.clone()))?;
         Ok(())
     }
 
     fn delete_vertex_properties(&self, q: VertexPropertyQuery) -> Result<()> {
-        let db = self.db.clone();
-        let manager = VertexPropertyManager::new(&db);
-        let mut batch = WriteBatch::default();
-
-        for (id, _) in execute_vertex_query(&db, q.inner)?.into_iter() {
-            manager.delete(&mut batch, id, &q.name)?;
-        }
-
-        self.db.write(batch)?;
+        let ids = self
+            .execute_vertex_query(q.inner)?
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        self.write(WriteOp::DeleteVertexProperties(ids, q.name))?;
         Ok(())
     }
 
     fn get_edge_properties(&self, q: EdgePropertyQuery) -> Result<Vec<EdgeProperty>> {
-        let db = self.db.clone();
-        let manager = EdgePropertyManager::new(&db);
-        let mut properties = Vec::new();
+        self.read(|db, source| {
+            let manager = EdgePropertyManager::new(db).with_source(source);
+            let mut properties = Vec::new();
 
-        for (out_id, t, _, in_id) in execute_edge_query(&db, q.inner)?.into_iter() {
-            let value = manager.get(out_id, &t, in_id, &q.name)?;
+            for item in execute_edge_query(db, source, q.inner)? {
+                let (out_id, t, _, in_id) = item?;
+                let value = manager.get(out_id, &t, in_id, &q.name)?;
 
-            if let Some(value) = value {
-                let key = EdgeKey::new(out_id, t, in_id);
-                properties.push(EdgeProperty::new(key, value));
+                if let Some(value) = value {
+                    let key = EdgeKey::new(out_id, t, in_id);
+                    properties.push(EdgeProperty::new(key, value));
+                }
             }
-        }
 
-        Ok(properties)
+            Ok(properties)
+        })
     }
 
     fn get_all_edge_properties<Q: Into<EdgeQuery>>(&self, q: Q) -> Result<Vec<EdgeProperties>> {
-        let db = self.db.clone();
-        let iter = execute_edge_query(&db, q.into())?.into_iter();
-        let manager = EdgePropertyManager::new(&db);
-
-        let iter = iter.map(move |(out_id, t, time, in_id)| {
-            let edge = Edge::new(EdgeKey::new(out_id, t.clone(), in_id), time);
-            let it = manager.iterate_for_owner(out_id, &t, in_id)?;
-            let props: Result<Vec<_>> = it.map(|r| r).collect();
-            let props_iter = props?.into_iter();
-            let props = props_iter
-                .map(|((_, _, _, name), value)| NamedProperty::new(name, value))
-                .collect();
-
-            Ok(EdgeProperties::new(edge, props))
-        });
+        self.read(|db, source| {
+            let manager = EdgePropertyManager::new(db).with_source(source);
+
+            let iter = execute_edge_query(db, source, q.into())?.map(move |item| {
+                let (out_id, t, time, in_id) = item?;
+                let edge = Edge::new(EdgeKey::new(out_id, t.clone(), in_id), time);
+                let it = manager.iterate_for_owner(out_id, &t, in_id)?;
+                let props: Result<Vec<_>> = it.map(|r| r).collect();
+                let props_iter = props?.into_iter();
+                let props = props_iter
+                    .map(|((_, _, _, name), value)| NamedProperty::new(name, value))
+                    .collect();
+
+                Ok(EdgeProperties::new(edge, props))
+            });
 
-        iter.collect()
+            iter.collect()
+        })
     }
 
     fn set_edge_properties(&self, q: EdgePropertyQuery, value: &JsonValue) -> Result<()> {
-        let db = self.db.clone();
-        let manager = EdgePropertyManager::new(&db);
-        let mut batch = WriteBatch::default();
-
-        for (out_id, t, _, in_id) in execute_edge_query(&db, q.inner)?.into_iter() {
-            manager.set(&mut batch, out_id, &t, in_id, &q.name, value)?;
-        }
-
-        self.db.write(batch)?;
+        let keys = self
+            .execute_edge_query(q.inner)?
+            .into_iter()
+            .map(|(out_id, t, _, in_id)| EdgeKey::new(out_id, t, in_id))
+            .collect();
+        self.write(WriteOp::SetEdgeProperties(keys, q.name, value.clone()))?;
         Ok(())
     }
 
     fn delete_edge_properties(&self, q: EdgePropertyQuery) -> Result<()> {
-        let db = self.db.clone();
-        let manager = EdgePropertyManager::new(&db);
-        let mut batch = WriteBatch::default();
+        let keys = self
+            .execute_edge_query(q.inner)?
+            .into_iter()
+            .map(|(out_id, t, _, in_id)| EdgeKey::new(out_id, t, in_id))
+            .collect();
+        self.write(WriteOp::DeleteEdgeProperties(keys, q.name))?;
+        Ok(())
+    }
+
+    fn get_edges_by_indexed_property(&self, name: &str, value: &JsonValue) -> Result<Vec<Edge>> {
+        self.read(|db, source| {
+            if !IndexedPropertyManager::new(db).with_source(source).exists(name)? {
+                return Err(Error::NotIndexed);
+            }
+
+            let edge_manager = EdgeManager::new(db).with_source(source);
+            let edge_property_index_manager = EdgePropertyIndexManager::new(db).with_source(source);
+            let mut edges = Vec::new();
+
+            for item in edge_property_index_manager.iterate_for_value(name, value)? {
+                let (out_id, t, in_id) = item?;
 
-        for (out_id, t, _, in_id) in execute_edge_query(&db, q.inner)?.into_iter() {
-            manager.delete(&mut batch, out_id, &t, in_id, &q.name)?;
+                if let Some(update_datetime) = edge_manager.get(out_id, &t, in_id)? {
+                    edges.push(Edge::new(EdgeKey::new(out_id, t, in_id), update_datetime));
+                }
+            }
+
+            Ok(edges)
+        })
+    }
+
+    fn commit(&self) -> Result<()> {
+        let mut pending = self.pending.lock().unwrap();
+
+        // On error, the writes are deliberately left pending, so that the
+        // commit can be retried or rolled back
+        if !pending.ops.is_empty() {
+            let mut batch = Batch::new(&self.db, &self.write_lock);
+
+            for op in &pending.ops {
+                apply(&self.db, &mut batch, op)?;
+            }
+
+            batch.write_opt(&self.options.to_write_options())?;
         }
 
-        self.db.write(batch)?;
+        *pending = Pending::default();
+        self.repin_snapshot();
         Ok(())
     }
+
+    fn rollback(&self) -> Result<()> {
+        *self.pending.lock().unwrap() = Pending::default();
+        self.repin_snapshot();
+        Ok(())
+    }
+}
+
+impl Drop for RocksdbTransaction {
+    fn drop(&mut self) {
+        if let Err(err) = self.commit() {
+            error!("could not commit rocksdb transaction on drop: {}", err);
+
+            if cfg!(debug_assertions) && !thread::panicking() {
+                panic!("could not commit rocksdb transaction on drop: {}", err);
+            }
+        }
+    }
 }
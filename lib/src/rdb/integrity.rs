@@ -0,0 +1,503 @@
+use std::fmt;
+
+use super::batch::Batch;
+use super::managers::*;
+use crate::errors::Result;
+use crate::models::{EdgeDirection, EdgeKey};
+
+use chrono::offset::Utc;
+use chrono::DateTime;
+use rocksdb::DB;
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+/// How many repairs to collect before writing them, so that repairing a
+/// badly damaged database doesn't hold every repair in memory at once.
+const REPAIR_BATCH_SIZE: usize = 1000;
+
+/// A dangling or missing reference found by
+/// `RocksdbDatastore::check_integrity`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntegrityIssue {
+    /// An edge whose outbound or inbound vertex does not exist.
+    DanglingEdge { key: EdgeKey },
+    /// An edge that is missing one of its edge ranges. `reversed` is whether
+    /// the missing range is the one indexed by the inbound vertex.
+    MissingEdgeRange {
+        key: EdgeKey,
+        update_datetime: DateTime<Utc>,
+        reversed: bool,
+    },
+    /// An edge range whose edge does not exist, or exists with a different
+    /// update datetime.
+    DanglingEdgeRange {
+        key: EdgeKey,
+        update_datetime: DateTime<Utc>,
+        reversed: bool,
+    },
+    /// A vertex property whose vertex does not exist.
+    DanglingVertexProperty { id: Uuid, name: String },
+    /// An edge property whose edge does not exist.
+    DanglingEdgeProperty { key: EdgeKey, name: String },
+    /// A vertex property index entry whose property is not indexed, does
+    /// not exist, or has a different value.
+    DanglingVertexPropertyIndex { id: Uuid, name: String, value: JsonValue },
+    /// An indexed vertex property that is missing from the index.
+    MissingVertexPropertyIndex { id: Uuid, name: String, value: JsonValue },
+    /// An edge property index entry whose property is not indexed, does not
+    /// exist, or has a different value.
+    DanglingEdgePropertyIndex {
+        key: EdgeKey,
+        name: String,
+        value: JsonValue,
+    },
+    /// An indexed edge property that is missing from the index.
+    MissingEdgePropertyIndex {
+        key: EdgeKey,
+        name: String,
+        value: JsonValue,
+    },
+    /// A counter that doesn't match the number of vertices or edges it
+    /// counts.
+    WrongCounter {
+        counter: Counter,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+fn fmt_edge_key(f: &mut fmt::Formatter, key: &EdgeKey) -> fmt::Result {
+    write!(f, "{} -[{}]-> {}", key.outbound_id, key.t.0, key.inbound_id)
+}
+
+fn fmt_counter(f: &mut fmt::Formatter, counter: &Counter) -> fmt::Result {
+    match counter {
+        Counter::Vertices => write!(f, "vertex count"),
+        Counter::VerticesOfType(t) => write!(f, "count of vertices of type `{}`", t.0),
+        Counter::Edges { id, t, direction } => write!(
+            f,
+            "count of {} edges of type `{}` for vertex {}",
+            direction_name(*direction),
+            t.0,
+            id
+        ),
+    }
+}
+
+fn direction_name(direction: EdgeDirection) -> &'static str {
+    match direction {
+        EdgeDirection::Outbound => "outbound",
+        EdgeDirection::Inbound => "inbound",
+    }
+}
+
+fn range_name(reversed: bool) -> &'static str {
+    if reversed {
+        "reversed edge range"
+    } else {
+        "edge range"
+    }
+}
+
+impl fmt::Display for IntegrityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntegrityIssue::DanglingEdge { key } => {
+                write!(f, "edge ")?;
+                fmt_edge_key(f, key)?;
+                write!(f, " references a missing vertex")
+            }
+            IntegrityIssue::MissingEdgeRange {
+                key,
+                update_datetime,
+                reversed,
+            } => {
+                write!(f, "edge ")?;
+                fmt_edge_key(f, key)?;
+                write!(f, " is missing its {} at {}", range_name(*reversed), update_datetime)
+            }
+            IntegrityIssue::DanglingEdgeRange {
+                key,
+                update_datetime,
+                reversed,
+            } => {
+                write!(
+                    f,
+                    "{} at {} references a missing edge ",
+                    range_name(*reversed),
+                    update_datetime
+                )?;
+                fmt_edge_key(f, key)
+            }
+            IntegrityIssue::DanglingVertexProperty { id, name } => {
+                write!(f, "vertex property `{}` references a missing vertex {}", name, id)
+            }
+            IntegrityIssue::DanglingEdgeProperty { key, name } => {
+                write!(f, "edge property `{}` references a missing edge ", name)?;
+                fmt_edge_key(f, key)
+            }
+            IntegrityIssue::DanglingVertexPropertyIndex { id, name, value } => write!(
+                f,
+                "index entry for vertex property `{}` = {} references a missing or unindexed property of vertex {}",
+                name, value, id
+            ),
+            IntegrityIssue::MissingVertexPropertyIndex { id, name, value } => write!(
+                f,
+                "vertex property `{}` = {} of vertex {} is missing from its index",
+                name, value, id
+            ),
+            IntegrityIssue::DanglingEdgePropertyIndex { key, name, value } => {
+                write!(
+                    f,
+                    "index entry for edge property `{}` = {} references a missing or unindexed property of edge ",
+                    name, value
+                )?;
+                fmt_edge_key(f, key)
+            }
+            IntegrityIssue::MissingEdgePropertyIndex { key, name, value } => {
+                write!(f, "edge property `{}` = {} of edge ", name, value)?;
+                fmt_edge_key(f, key)?;
+                write!(f, " is missing from its index")
+            }
+            IntegrityIssue::WrongCounter {
+                counter,
+                expected,
+                actual,
+            } => {
+                fmt_counter(f, counter)?;
+                write!(f, " is {}, but should be {}", actual, expected)
+            }
+        }
+    }
+}
+
+/// Counts a repair, writing the repairs collected so far once there are
+/// enough of them.
+fn count_repair(batch: &mut Batch, repairs: &mut usize) -> Result<()> {
+    *repairs += 1;
+
+    if *repairs % REPAIR_BATCH_SIZE == 0 {
+        batch.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Walks the edges, edge ranges, reversed edge ranges, vertex properties,
+/// edge properties and property index column families, checking their
+/// references against each other and against the vertices column family.
+/// Then checks the counters against the vertices and edges.
+///
+/// # Arguments
+/// * `db` - The database to check.
+/// * `source` - Where to read from, usually a snapshot, so that writes can
+///   carry on while checking.
+pub fn check(db: &DB, source: Source) -> Result<Vec<IntegrityIssue>> {
+    let vertex_manager = VertexManager::new(db).with_source(source);
+    let edge_manager = EdgeManager::new(db).with_source(source);
+    let edge_range_manager = EdgeRangeManager::new(db).with_source(source);
+    let reversed_edge_range_manager = EdgeRangeManager::new_reversed(db).with_source(source);
+    let vertex_property_manager = VertexPropertyManager::new(db).with_source(source);
+    let edge_property_manager = EdgePropertyManager::new(db).with_source(source);
+    let indexed_property_manager = IndexedPropertyManager::new(db).with_source(source);
+    let vertex_property_index_manager = VertexPropertyIndexManager::new(db).with_source(source);
+    let edge_property_index_manager = EdgePropertyIndexManager::new(db).with_source(source);
+    let counter_manager = CounterManager::new(db).with_source(source);
+    let mut issues = Vec::new();
+
+    for item in edge_manager.iterate()? {
+        let (out_id, t, update_datetime, in_id) = item?;
+
+        if !vertex_manager.exists(out_id)? || !vertex_manager.exists(in_id)? {
+            issues.push(IntegrityIssue::DanglingEdge {
+                key: EdgeKey::new(out_id, t, in_id),
+            });
+            continue;
+        }
+
+        if !edge_range_manager.exists(out_id, &t, update_datetime, in_id)? {
+            issues.push(IntegrityIssue::MissingEdgeRange {
+                key: EdgeKey::new(out_id, t.clone(), in_id),
+                update_datetime,
+                reversed: false,
+            });
+        }
+
+        if !reversed_edge_range_manager.exists(in_id, &t, update_datetime, out_id)? {
+            issues.push(IntegrityIssue::MissingEdgeRange {
+                key: EdgeKey::new(out_id, t, in_id),
+                update_datetime,
+                reversed: true,
+            });
+        }
+    }
+
+    for (manager, reversed) in &[(&edge_range_manager, false), (&reversed_edge_range_manager, true)] {
+        for item in manager.iterate_all()? {
+            let (first_id, t, update_datetime, second_id) = item?;
+            let (out_id, in_id) = if *reversed {
+                (second_id, first_id)
+            } else {
+                (first_id, second_id)
+            };
+
+            if edge_manager.get(out_id, &t, in_id)? != Some(update_datetime) {
+                issues.push(IntegrityIssue::DanglingEdgeRange {
+                    key: EdgeKey::new(out_id, t, in_id),
+                    update_datetime,
+                    reversed: *reversed,
+                });
+            }
+        }
+    }
+
+    for item in vertex_property_manager.iterate()? {
+        let ((id, name), value) = item?;
+
+        if !vertex_manager.exists(id)? {
+            issues.push(IntegrityIssue::DanglingVertexProperty { id, name });
+            continue;
+        }
+
+        if indexed_property_manager.exists(&name)? && !vertex_property_index_manager.exists(&name, &value, id)? {
+            issues.push(IntegrityIssue::MissingVertexPropertyIndex { id, name, value });
+        }
+    }
+
+    for item in edge_property_manager.iterate()? {
+        let ((out_id, t, in_id, name), value) = item?;
+
+        if edge_manager.get(out_id, &t, in_id)?.is_none() {
+            issues.push(IntegrityIssue::DanglingEdgeProperty {
+                key: EdgeKey::new(out_id, t, in_id),
+                name,
+            });
+            continue;
+        }
+
+        if indexed_property_manager.exists(&name)?
+            && !edge_property_index_manager.exists(&name, &value, out_id, &t, in_id)?
+        {
+            issues.push(IntegrityIssue::MissingEdgePropertyIndex {
+                key: EdgeKey::new(out_id, t, in_id),
+                name,
+                value,
+            });
+        }
+    }
+
+    for item in vertex_property_index_manager.iterate()? {
+        let (name, value, id) = item?;
+
+        if !indexed_property_manager.exists(&name)? || vertex_property_manager.get(id, &name)? != Some(value.clone()) {
+            issues.push(IntegrityIssue::DanglingVertexPropertyIndex { id, name, value });
+        }
+    }
+
+    for item in edge_property_index_manager.iterate()? {
+        let (name, value, out_id, t, in_id) = item?;
+
+        if !indexed_property_manager.exists(&name)?
+            || edge_property_manager.get(out_id, &t, in_id, &name)? != Some(value.clone())
+        {
+            issues.push(IntegrityIssue::DanglingEdgePropertyIndex {
+                key: EdgeKey::new(out_id, t, in_id),
+                name,
+                value,
+            });
+        }
+    }
+
+    for (counter, expected, actual) in counter_manager.verify()? {
+        issues.push(IntegrityIssue::WrongCounter {
+            counter,
+            expected,
+            actual,
+        });
+    }
+
+    Ok(issues)
+}
+
+/// Repairs issues found by `check`, by deleting dangling edges, edge ranges,
+/// properties and index entries, and recreating missing edge ranges and
+/// index entries. The issues may have been found in an older snapshot, so
+/// each one is checked again through `batch` first, and skipped if it's
+/// been fixed or changed since.
+///
+/// Counters are then recounted and corrected, rather than trusting the
+/// issues, since the other repairs can change them.
+///
+/// # Arguments
+/// * `db` - The database to repair.
+/// * `batch` - The batch to write repairs to. It should hold the write lock,
+///   and is flushed as repairs accumulate.
+/// * `issues` - The issues to repair.
+pub fn repair(db: &DB, batch: &mut Batch, issues: &[IntegrityIssue]) -> Result<()> {
+    let mut repairs = 0;
+
+    for issue in issues {
+        if repair_issue(db, batch, issue)? {
+            count_repair(batch, &mut repairs)?;
+        }
+    }
+
+    // Deleting dangling edges also updates the counters, so they're
+    // checked against what's left after the repairs above
+    batch.flush()?;
+    let counter_manager = CounterManager::new(db);
+
+    for (counter, expected, actual) in counter_manager.verify()? {
+        counter_manager.update(batch, &counter, expected as i64 - actual as i64);
+        count_repair(batch, &mut repairs)?;
+    }
+
+    Ok(())
+}
+
+/// Repairs a single issue if it's still present. Returns whether it was.
+fn repair_issue(db: &DB, batch: &mut Batch, issue: &IntegrityIssue) -> Result<bool> {
+    let source = batch.source();
+    let vertex_manager = VertexManager::new(db).with_source(source);
+    let edge_manager = EdgeManager::new(db).with_source(source);
+    let vertex_property_manager = VertexPropertyManager::new(db).with_source(source);
+    let edge_property_manager = EdgePropertyManager::new(db).with_source(source);
+    let indexed_property_manager = IndexedPropertyManager::new(db).with_source(source);
+    let vertex_property_index_manager = VertexPropertyIndexManager::new(db).with_source(source);
+    let edge_property_index_manager = EdgePropertyIndexManager::new(db).with_source(source);
+
+    let edge_range_manager = |reversed: bool| {
+        if reversed {
+            EdgeRangeManager::new_reversed(db)
+        } else {
+            EdgeRangeManager::new(db)
+        }
+    };
+
+    match issue {
+        IntegrityIssue::DanglingEdge { key } => {
+            let update_datetime = match edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? {
+                Some(update_datetime) => update_datetime,
+                None => return Ok(false),
+            };
+
+            if vertex_manager.exists(key.outbound_id)? && vertex_manager.exists(key.inbound_id)? {
+                return Ok(false);
+            }
+
+            // Deleting the edge cascades to its edge ranges and properties
+            EdgeManager::new(db).delete(batch, key.outbound_id, &key.t, key.inbound_id, update_datetime)?;
+        }
+        IntegrityIssue::MissingEdgeRange {
+            key,
+            update_datetime,
+            reversed,
+        } => {
+            let (first_id, second_id) = range_ids(key, *reversed);
+            let manager = edge_range_manager(*reversed).with_source(source);
+
+            if edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? != Some(*update_datetime)
+                || manager.exists(first_id, &key.t, *update_datetime, second_id)?
+            {
+                return Ok(false);
+            }
+
+            edge_range_manager(*reversed).set(batch, first_id, &key.t, *update_datetime, second_id)?;
+        }
+        IntegrityIssue::DanglingEdgeRange {
+            key,
+            update_datetime,
+            reversed,
+        } => {
+            let (first_id, second_id) = range_ids(key, *reversed);
+            let manager = edge_range_manager(*reversed).with_source(source);
+
+            if edge_manager.get(key.outbound_id, &key.t, key.inbound_id)? == Some(*update_datetime)
+                || !manager.exists(first_id, &key.t, *update_datetime, second_id)?
+            {
+                return Ok(false);
+            }
+
+            edge_range_manager(*reversed).delete(batch, first_id, &key.t, *update_datetime, second_id)?;
+        }
+        IntegrityIssue::DanglingVertexProperty { id, name } => {
+            if vertex_manager.exists(*id)? || vertex_property_manager.get(*id, name)?.is_none() {
+                return Ok(false);
+            }
+
+            // Deleting the property also deletes its index entry
+            VertexPropertyManager::new(db).delete(batch, *id, name)?;
+        }
+        IntegrityIssue::DanglingEdgeProperty { key, name } => {
+            if edge_manager.get(key.outbound_id, &key.t, key.inbound_id)?.is_some()
+                || edge_property_manager
+                    .get(key.outbound_id, &key.t, key.inbound_id, name)?
+                    .is_none()
+            {
+                return Ok(false);
+            }
+
+            // Deleting the property also deletes its index entry
+            EdgePropertyManager::new(db).delete(batch, key.outbound_id, &key.t, key.inbound_id, name)?;
+        }
+        IntegrityIssue::MissingVertexPropertyIndex { id, name, value } => {
+            if !indexed_property_manager.exists(name)?
+                || vertex_property_manager.get(*id, name)?.as_ref() != Some(value)
+                || vertex_property_index_manager.exists(name, value, *id)?
+            {
+                return Ok(false);
+            }
+
+            VertexPropertyIndexManager::new(db).set(batch, name, value, *id)?;
+        }
+        IntegrityIssue::MissingEdgePropertyIndex { key, name, value } => {
+            if !indexed_property_manager.exists(name)?
+                || edge_property_manager
+                    .get(key.outbound_id, &key.t, key.inbound_id, name)?
+                    .as_ref()
+                    != Some(value)
+                || edge_property_index_manager.exists(name, value, key.outbound_id, &key.t, key.inbound_id)?
+            {
+                return Ok(false);
+            }
+
+            EdgePropertyIndexManager::new(db).set(batch, name, value, key.outbound_id, &key.t, key.inbound_id)?;
+        }
+        IntegrityIssue::DanglingVertexPropertyIndex { id, name, value } => {
+            if !vertex_property_index_manager.exists(name, value, *id)?
+                || (indexed_property_manager.exists(name)?
+                    && vertex_property_manager.get(*id, name)?.as_ref() == Some(value))
+            {
+                return Ok(false);
+            }
+
+            VertexPropertyIndexManager::new(db).delete(batch, name, value, *id)?;
+        }
+        IntegrityIssue::DanglingEdgePropertyIndex { key, name, value } => {
+            if !edge_property_index_manager.exists(name, value, key.outbound_id, &key.t, key.inbound_id)?
+                || (indexed_property_manager.exists(name)?
+                    && edge_property_manager
+                        .get(key.outbound_id, &key.t, key.inbound_id, name)?
+                        .as_ref()
+                        == Some(value))
+            {
+                return Ok(false);
+            }
+
+            EdgePropertyIndexManager::new(db).delete(batch, name, value, key.outbound_id, &key.t, key.inbound_id)?;
+        }
+        // Counters are recounted once everything else has been repaired
+        IntegrityIssue::WrongCounter { .. } => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+/// Gets the IDs an edge range is keyed by, in order.
+fn range_ids(key: &EdgeKey, reversed: bool) -> (Uuid, Uuid) {
+    if reversed {
+        (key.inbound_id, key.outbound_id)
+    } else {
+        (key.outbound_id, key.inbound_id)
+    }
+}
@@ -1,14 +1,19 @@
-use std::io::Cursor;
-use std::ops::Deref;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+use std::iter;
+use std::iter::Peekable;
 use std::u8;
 
+use super::batch::{read_counter, Batch, Changes};
 use super::bytes::*;
 use crate::errors::Result;
 use crate::models;
 
+use byteorder::{BigEndian, ByteOrder};
 use chrono::offset::Utc;
 use chrono::DateTime;
-use rocksdb::{ColumnFamily, DBIterator, Direction, IteratorMode, WriteBatch, DB};
+use rocksdb::{ColumnFamily, Direction, IteratorMode, ReadOptions, Snapshot, DB};
 use serde_json;
 use serde_json::Value as JsonValue;
 use uuid::Uuid;
@@ -17,39 +22,254 @@ pub type OwnedPropertyItem = ((Uuid, String), JsonValue);
 pub type VertexItem = (Uuid, models::Type);
 pub type EdgeRangeItem = (Uuid, models::Type, DateTime<Utc>, Uuid);
 pub type EdgePropertyItem = ((Uuid, models::Type, Uuid, String), JsonValue);
+pub type VertexPropertyIndexItem = (String, JsonValue, Uuid);
+pub type EdgePropertyIndexItem = (String, JsonValue, Uuid, models::Type, Uuid);
+
+/// Gets the smallest key that is greater than every key starting with
+/// `prefix`, or `None` if there is no such key.
+fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut bound = prefix.to_vec();
+
+    while let Some(last) = bound.pop() {
+        if last < u8::MAX {
+            bound.push(last + 1);
+            return Some(bound);
+        }
+    }
+
+    None
+}
+
+/// A boxed iterator over the keys and values of a column family.
+pub type KeyValueIterator<'a> = Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>;
+
+/// Merges changes into an iterator over a column family, in key order.
+struct Overlay<'a, I: Iterator<Item = (&'a [u8], Option<&'a [u8]>)>> {
+    iter: Peekable<KeyValueIterator<'a>>,
+    changes: Peekable<I>,
+}
+
+impl<'a, I: Iterator<Item = (&'a [u8], Option<&'a [u8]>)>> Iterator for Overlay<'a, I> {
+    type Item = (Box<[u8]>, Box<[u8]>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ordering = match (self.iter.peek(), self.changes.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((key, _)), Some((changed_key, _))) => key[..].cmp(changed_key),
+            };
+
+            match ordering {
+                Ordering::Less => return self.iter.next(),
+                // The change replaces the original value
+                Ordering::Equal => {
+                    self.iter.next();
+                }
+                Ordering::Greater => (),
+            }
+
+            // Deleted keys are skipped
+            if let Some((key, Some(value))) = self.changes.next() {
+                return Some((key.into(), value.into()));
+            }
+        }
+    }
+}
+
+/// Where the managers read from: either the live database, or a consistent
+/// snapshot of it, overlaid with any number of layers of changes that haven't
+/// been written yet, e.g. a transaction's, then those of a batch on top.
+#[derive(Clone, Copy)]
+pub struct Source<'a> {
+    db: &'a DB,
+    snapshot: Option<&'a Snapshot<'a>>,
+    /// Changes on top of what's read from `below`.
+    changes: Option<&'a Changes>,
+    /// The source that the changes are on top of, if it has changes of its
+    /// own. Otherwise, what's below is the database or snapshot.
+    below: Option<&'a Source<'a>>,
+}
+
+impl<'a> Source<'a> {
+    pub fn db(db: &'a DB) -> Self {
+        Source {
+            db,
+            snapshot: None,
+            changes: None,
+            below: None,
+        }
+    }
+
+    pub fn snapshot(db: &'a DB, snapshot: &'a Snapshot<'a>) -> Self {
+        Source {
+            db,
+            snapshot: Some(snapshot),
+            changes: None,
+            below: None,
+        }
+    }
+
+    /// Reads from a snapshot, with changes on top.
+    pub fn snapshot_with_changes(db: &'a DB, snapshot: &'a Snapshot<'a>, changes: &'a Changes) -> Self {
+        Source {
+            changes: Some(changes),
+            ..Self::snapshot(db, snapshot)
+        }
+    }
+
+    /// Overlays changes on top of what's read from this source.
+    pub fn with_changes<'b>(&'b self, changes: &'b Changes) -> Source<'b> {
+        Source {
+            db: self.db,
+            snapshot: self.snapshot,
+            changes: Some(changes),
+            below: if self.changes.is_some() { Some(self) } else { None },
+        }
+    }
+
+    /// Iterates over the layers of changes, from the top down.
+    fn layers(&self) -> impl Iterator<Item = &'a Changes> {
+        iter::successors(Some(*self), |source| source.below.copied()).filter_map(|source| source.changes)
+    }
+
+    pub fn get_cf(&self, cf_name: &'static str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        // Higher layers take precedence
+        for changes in self.layers() {
+            if let Some(value) = changes.get(cf_name, key) {
+                return Ok(value.map(|value| value.to_vec()));
+            }
+        }
+
+        let cf = self.db.cf_handle(cf_name).unwrap();
+
+        let value = match self.snapshot {
+            None => self.db.get_cf(cf, key)?,
+            Some(snapshot) => snapshot.get_cf(cf, key)?,
+        };
+
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    /// Gets the amount that's yet to be added to a counter.
+    fn counter_delta(&self, cf_name: &'static str, key: &[u8]) -> i64 {
+        self.layers().map(|changes| changes.counter_delta(cf_name, key)).sum()
+    }
+
+    /// Gets the amounts that are yet to be added to the counters whose keys
+    /// start with `prefix`.
+    fn counter_deltas(&self, cf_name: &'static str, prefix: &[u8]) -> BTreeMap<Vec<u8>, i64> {
+        let mut deltas = BTreeMap::new();
+
+        for changes in self.layers() {
+            for (key, delta) in changes.counter_deltas(cf_name, prefix) {
+                *deltas.entry(key.to_vec()).or_insert(0) += delta;
+            }
+        }
+
+        deltas
+    }
+
+    fn iterator_cf_opt(
+        &self,
+        cf_name: &'static str,
+        opts: ReadOptions,
+        from: &[u8],
+        until: Option<&[u8]>,
+    ) -> Result<KeyValueIterator<'a>> {
+        let cf = self.db.cf_handle(cf_name).unwrap();
+        let mode = IteratorMode::From(from, Direction::Forward);
+
+        let iterator: KeyValueIterator<'a> = match self.below {
+            Some(below) => below.iterator_cf_opt(cf_name, opts, from, until)?,
+            None => match self.snapshot {
+                None => Box::new(self.db.iterator_cf_opt(cf, opts, mode)?),
+                Some(snapshot) => Box::new(snapshot.iterator_cf_opt(cf, opts, mode)?),
+            },
+        };
+
+        Ok(match self.changes {
+            Some(changes) => Box::new(Overlay {
+                iter: iterator.peekable(),
+                changes: changes.range(cf_name, from, until).peekable(),
+            }),
+            None => iterator,
+        })
+    }
+
+    /// Iterates over the keys of a column family, starting at `from`.
+    fn iterator_cf(&self, cf_name: &'static str, from: &[u8]) -> Result<KeyValueIterator<'a>> {
+        self.iterator_cf_opt(cf_name, ReadOptions::default(), from, None)
+    }
+
+    /// Iterates over every key in a column family. Seeks in column families
+    /// with a prefix extractor only consider keys with the same prefix by
+    /// default, so this asks for a total order seek.
+    fn iterator_cf_all(&self, cf_name: &'static str) -> Result<KeyValueIterator<'a>> {
+        let mut opts = ReadOptions::default();
+        opts.set_total_order_seek(true);
+        self.iterator_cf_opt(cf_name, opts, &[], None)
+    }
+
+    /// Iterates over the keys starting with `prefix`, beginning at `from`.
+    /// Iteration stops at the end of the prefix, rather than reading past it
+    /// only to have the caller discard what's read; and in column families
+    /// with a prefix extractor, prefix bloom filters let rocksdb skip files
+    /// without any of the keys.
+    fn iterator_cf_prefix(&self, cf_name: &'static str, prefix: &[u8], from: &[u8]) -> Result<KeyValueIterator<'a>> {
+        let mut opts = ReadOptions::default();
+        opts.set_prefix_same_as_start(true);
+        let upper_bound = prefix_upper_bound(prefix);
+
+        if let Some(ref upper_bound) = upper_bound {
+            opts.set_iterate_upper_bound(upper_bound.clone());
+        }
+
+        self.iterator_cf_opt(cf_name, opts, from, upper_bound.as_ref().map(|bound| &bound[..]))
+    }
+}
 
 pub struct VertexManager<'a> {
     pub db: &'a DB,
     pub cf: &'a ColumnFamily,
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
 }
 
 impl<'a> VertexManager<'a> {
     pub fn new(db: &'a DB) -> Self {
         VertexManager {
             cf: db.cf_handle("vertices:v1").unwrap(),
+            cf_name: "vertices:v1",
+            source: Source::db(db),
             db,
         }
     }
 
+    pub fn with_source(self, source: Source<'a>) -> Self {
+        Self { source, ..self }
+    }
+
     fn key(&self, id: Uuid) -> Vec<u8> {
         build(&[Component::Uuid(id)])
     }
 
     pub fn exists(&self, id: Uuid) -> Result<bool> {
-        Ok(self.db.get_cf(self.cf, &self.key(id))?.is_some())
+        Ok(self.source.get_cf(self.cf_name, &self.key(id))?.is_some())
     }
 
     pub fn get(&self, id: Uuid) -> Result<Option<models::Type>> {
-        match self.db.get_cf(self.cf, &self.key(id))? {
+        match self.source.get_cf(self.cf_name, &self.key(id))? {
             Some(value_bytes) => {
-                let mut cursor = Cursor::new(value_bytes.deref());
+                let mut cursor = Cursor::new(&value_bytes[..]);
                 Ok(Some(read_type(&mut cursor)))
             }
             None => Ok(None),
         }
     }
 
-    fn iterate(&'a self, iterator: DBIterator<'a>) -> Result<impl Iterator<Item = Result<VertexItem>> + 'a> {
+    fn iterate(&self, iterator: KeyValueIterator<'a>) -> Result<impl Iterator<Item = Result<VertexItem>> + 'a> {
         Ok(iterator.map(|item| -> Result<VertexItem> {
             let (k, v) = item;
 
@@ -65,64 +285,95 @@ impl<'a> VertexManager<'a> {
         }))
     }
 
-    pub fn iterate_for_range(&'a self, id: Uuid) -> Result<impl Iterator<Item = Result<VertexItem>> + 'a> {
+    pub fn iterate_for_range(&self, id: Uuid) -> Result<impl Iterator<Item = Result<VertexItem>> + 'a> {
         let low_key = build(&[Component::Uuid(id)]);
-        let iter = self
-            .db
-            .iterator_cf(self.cf, IteratorMode::From(&low_key, Direction::Forward))?;
+        let iter = self.source.iterator_cf(self.cf_name, &low_key)?;
         self.iterate(iter)
     }
 
-    pub fn create(&self, batch: &mut WriteBatch, vertex: &models::Vertex) -> Result<()> {
+    pub fn create(&self, batch: &mut Batch, vertex: &models::Vertex) -> Result<()> {
         let key = self.key(vertex.id);
-        batch.put_cf(self.cf, &key, &build(&[Component::Type(&vertex.t)]))?;
+        let counter_manager = CounterManager::new(self.db);
+
+        match batch.get_cf(self.cf_name, &key)? {
+            Some(value_bytes) => {
+                let mut cursor = Cursor::new(value_bytes);
+                let old_t = read_type(&mut cursor);
+
+                if old_t != vertex.t {
+                    counter_manager.update_vertex_count(batch, &old_t, -1);
+                    counter_manager.update_vertex_count(batch, &vertex.t, 1);
+                }
+            }
+            None => counter_manager.update_vertex_count(batch, &vertex.t, 1),
+        }
+
+        batch.put_cf(self.cf_name, &key, &build(&[Component::Type(&vertex.t)]))?;
         Ok(())
     }
 
-    pub fn delete(&self, mut batch: &mut WriteBatch, id: Uuid) -> Result<()> {
-        batch.delete_cf(self.cf, &self.key(id))?;
+    pub fn delete(&self, mut batch: &mut Batch, id: Uuid) -> Result<()> {
+        let key = self.key(id);
+
+        if let Some(value_bytes) = batch.get_cf(self.cf_name, &key)? {
+            let mut cursor = Cursor::new(value_bytes);
+            let t = read_type(&mut cursor);
+            CounterManager::new(self.db).update_vertex_count(batch, &t, -1);
+        }
 
+        batch.delete_cf(self.cf_name, &key)?;
+
+        // Read through the batch, so that items created earlier in it are
+        // deleted too. Items are collected first, since the batch can't be
+        // written to while it's being read from.
         let vertex_property_manager = VertexPropertyManager::new(self.db);
-        for item in vertex_property_manager.iterate_for_owner(id)? {
-            let ((vertex_property_owner_id, vertex_property_name), _) = item?;
+        let vertex_properties: Vec<OwnedPropertyItem> = VertexPropertyManager::new(self.db)
+            .with_source(batch.source())
+            .iterate_for_owner(id)?
+            .collect::<Result<_>>()?;
+
+        for ((vertex_property_owner_id, vertex_property_name), _) in vertex_properties {
             vertex_property_manager.delete(&mut batch, vertex_property_owner_id, &vertex_property_name[..])?;
         }
 
         let edge_manager = EdgeManager::new(self.db);
 
-        {
-            let edge_range_manager = EdgeRangeManager::new(self.db);
-            for item in edge_range_manager.iterate_for_owner(id)? {
-                let (edge_range_out_id, edge_range_t, edge_range_update_datetime, edge_range_in_id) = item?;
-                debug_assert_eq!(edge_range_out_id, id);
-                edge_manager.delete(
-                    &mut batch,
-                    edge_range_out_id,
-                    &edge_range_t,
-                    edge_range_in_id,
-                    edge_range_update_datetime,
-                )?;
-            }
+        let edge_ranges: Vec<EdgeRangeItem> = EdgeRangeManager::new(self.db)
+            .with_source(batch.source())
+            .iterate_for_owner(id)?
+            .collect::<Result<_>>()?;
+
+        for (edge_range_out_id, edge_range_t, edge_range_update_datetime, edge_range_in_id) in edge_ranges {
+            debug_assert_eq!(edge_range_out_id, id);
+            edge_manager.delete(
+                &mut batch,
+                edge_range_out_id,
+                &edge_range_t,
+                edge_range_in_id,
+                edge_range_update_datetime,
+            )?;
         }
 
+        let reversed_edge_ranges: Vec<EdgeRangeItem> = EdgeRangeManager::new_reversed(self.db)
+            .with_source(batch.source())
+            .iterate_for_owner(id)?
+            .collect::<Result<_>>()?;
+
+        for (
+            reversed_edge_range_in_id,
+            reversed_edge_range_t,
+            reversed_edge_range_update_datetime,
+            reversed_edge_range_out_id,
+        ) in reversed_edge_ranges
         {
-            let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.db);
-            for item in reversed_edge_range_manager.iterate_for_owner(id)? {
-                let (
-                    reversed_edge_range_in_id,
-                    reversed_edge_range_t,
-                    reversed_edge_range_update_datetime,
-                    reversed_edge_range_out_id,
-                ) = item?;
-                debug_assert_eq!(reversed_edge_range_in_id, id);
-                edge_manager.delete(
-                    &mut batch,
-                    reversed_edge_range_out_id,
-                    &reversed_edge_range_t,
-                    reversed_edge_range_in_id,
-                    reversed_edge_range_update_datetime,
-                )?;
-            }
+            debug_assert_eq!(reversed_edge_range_in_id, id);
+            edge_manager.delete(
+                &mut batch,
+                reversed_edge_range_out_id,
+                &reversed_edge_range_t,
+                reversed_edge_range_in_id,
+                reversed_edge_range_update_datetime,
+            )?;
         }
 
         Ok(())
@@ -136,33 +387,58 @@ impl<'a> VertexManager<'a> {
 pub struct EdgeManager<'a> {
     pub db: &'a DB,
     pub cf: &'a ColumnFamily,
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
 }
 
 impl<'a> EdgeManager<'a> {
     pub fn new(db: &'a DB) -> Self {
         EdgeManager {
             cf: db.cf_handle("edges:v1").unwrap(),
+            cf_name: "edges:v1",
+            source: Source::db(db),
             db,
         }
     }
 
+    pub fn with_source(self, source: Source<'a>) -> Self {
+        Self { source, ..self }
+    }
+
     fn key(&self, out_id: Uuid, t: &models::Type, in_id: Uuid) -> Vec<u8> {
         build(&[Component::Uuid(out_id), Component::Type(t), Component::Uuid(in_id)])
     }
 
     pub fn get(&self, out_id: Uuid, t: &models::Type, in_id: Uuid) -> Result<Option<DateTime<Utc>>> {
-        match self.db.get_cf(self.cf, &self.key(out_id, t, in_id))? {
+        match self.source.get_cf(self.cf_name, &self.key(out_id, t, in_id))? {
             Some(value_bytes) => {
-                let mut cursor = Cursor::new(value_bytes.deref());
+                let mut cursor = Cursor::new(&value_bytes[..]);
                 Ok(Some(read_datetime(&mut cursor)))
             }
             None => Ok(None),
         }
     }
 
+    /// Iterates over every edge, yielding items in the same shape as
+    /// outbound edge ranges.
+    pub fn iterate(&self) -> Result<impl Iterator<Item = Result<EdgeRangeItem>> + 'a> {
+        let iterator = self.source.iterator_cf_all(self.cf_name)?;
+
+        Ok(iterator.map(|item| -> Result<EdgeRangeItem> {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            let out_id = read_uuid(&mut cursor);
+            let t = read_type(&mut cursor);
+            let in_id = read_uuid(&mut cursor);
+            let mut cursor = Cursor::new(v);
+            let update_datetime = read_datetime(&mut cursor);
+            Ok((out_id, t, update_datetime, in_id))
+        }))
+    }
+
     pub fn set(
         &self,
-        mut batch: &mut WriteBatch,
+        mut batch: &mut Batch,
         out_id: Uuid,
         t: &models::Type,
         in_id: Uuid,
@@ -170,14 +446,21 @@ impl<'a> EdgeManager<'a> {
     ) -> Result<()> {
         let edge_range_manager = EdgeRangeManager::new(self.db);
         let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.db);
+        let key = self.key(out_id, t, in_id);
 
-        if let Some(update_datetime) = self.get(out_id, t, in_id)? {
-            edge_range_manager.delete(&mut batch, out_id, t, update_datetime, in_id)?;
-            reversed_edge_range_manager.delete(&mut batch, in_id, t, update_datetime, out_id)?;
+        // Read through the batch, since the edge may have already been set
+        // earlier in it, e.g. by a bulk insert
+        match batch.get_cf(self.cf_name, &key)? {
+            Some(value_bytes) => {
+                let mut cursor = Cursor::new(value_bytes);
+                let update_datetime = read_datetime(&mut cursor);
+                edge_range_manager.delete(&mut batch, out_id, t, update_datetime, in_id)?;
+                reversed_edge_range_manager.delete(&mut batch, in_id, t, update_datetime, out_id)?;
+            }
+            None => CounterManager::new(self.db).update_edge_count(batch, out_id, t, in_id, 1),
         }
 
-        let key = self.key(out_id, t, in_id);
-        batch.put_cf(self.cf, &key, &build(&[Component::DateTime(new_update_datetime)]))?;
+        batch.put_cf(self.cf_name, &key, &build(&[Component::DateTime(new_update_datetime)]))?;
         edge_range_manager.set(&mut batch, out_id, t, new_update_datetime, in_id)?;
         reversed_edge_range_manager.set(&mut batch, in_id, t, new_update_datetime, out_id)?;
         Ok(())
@@ -185,23 +468,43 @@ impl<'a> EdgeManager<'a> {
 
     pub fn delete(
         &self,
-        mut batch: &mut WriteBatch,
+        mut batch: &mut Batch,
         out_id: Uuid,
         t: &models::Type,
         in_id: Uuid,
         update_datetime: DateTime<Utc>,
     ) -> Result<()> {
-        batch.delete_cf(self.cf, &self.key(out_id, t, in_id))?;
-
+        let key = self.key(out_id, t, in_id);
         let edge_range_manager = EdgeRangeManager::new(self.db);
-        edge_range_manager.delete(&mut batch, out_id, t, update_datetime, in_id)?;
-
         let reversed_edge_range_manager = EdgeRangeManager::new_reversed(self.db);
+
+        // The same edge can be deleted more than once in a batch, e.g. when
+        // deleting both of its vertices, so only count it the first time
+        if let Some(value_bytes) = batch.get_cf(self.cf_name, &key)? {
+            CounterManager::new(self.db).update_edge_count(batch, out_id, t, in_id, -1);
+
+            // If the caller's update datetime is stale, the edge ranges are
+            // stored under a different one, so clean those up too
+            let mut cursor = Cursor::new(value_bytes);
+            let stored_update_datetime = read_datetime(&mut cursor);
+
+            if stored_update_datetime != update_datetime {
+                edge_range_manager.delete(&mut batch, out_id, t, stored_update_datetime, in_id)?;
+                reversed_edge_range_manager.delete(&mut batch, in_id, t, stored_update_datetime, out_id)?;
+            }
+        }
+
+        batch.delete_cf(self.cf_name, &key)?;
+        edge_range_manager.delete(&mut batch, out_id, t, update_datetime, in_id)?;
         reversed_edge_range_manager.delete(&mut batch, in_id, t, update_datetime, out_id)?;
 
         let edge_property_manager = EdgePropertyManager::new(self.db);
-        for item in edge_property_manager.iterate_for_owner(out_id, t, in_id)? {
-            let ((edge_property_out_id, edge_property_t, edge_property_in_id, edge_property_name), _) = item?;
+        let edge_properties: Vec<EdgePropertyItem> = EdgePropertyManager::new(self.db)
+            .with_source(batch.source())
+            .iterate_for_owner(out_id, t, in_id)?
+            .collect::<Result<_>>()?;
+
+        for ((edge_property_out_id, edge_property_t, edge_property_in_id, edge_property_name), _) in edge_properties {
             edge_property_manager.delete(
                 &mut batch,
                 edge_property_out_id,
@@ -224,12 +527,16 @@ impl<'a> EdgeManager<'a> {
 pub struct EdgeRangeManager<'a> {
     pub db: &'a DB,
     pub cf: &'a ColumnFamily,
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
 }
 
 impl<'a> EdgeRangeManager<'a> {
     pub fn new(db: &'a DB) -> Self {
         EdgeRangeManager {
             cf: db.cf_handle("edge_ranges:v1").unwrap(),
+            cf_name: "edge_ranges:v1",
+            source: Source::db(db),
             db,
         }
     }
@@ -237,10 +544,16 @@ impl<'a> EdgeRangeManager<'a> {
     pub fn new_reversed(db: &'a DB) -> Self {
         EdgeRangeManager {
             cf: db.cf_handle("reversed_edge_ranges:v1").unwrap(),
+            cf_name: "reversed_edge_ranges:v1",
+            source: Source::db(db),
             db,
         }
     }
 
+    pub fn with_source(self, source: Source<'a>) -> Self {
+        Self { source, ..self }
+    }
+
     fn key(&self, first_id: Uuid, t: &models::Type, update_datetime: DateTime<Utc>, second_id: Uuid) -> Vec<u8> {
         build(&[
             Component::Uuid(first_id),
@@ -251,8 +564,8 @@ impl<'a> EdgeRangeManager<'a> {
     }
 
     fn iterate(
-        &'a self,
-        iterator: DBIterator<'a>,
+        &self,
+        iterator: KeyValueIterator<'a>,
         prefix: Vec<u8>,
     ) -> Result<impl Iterator<Item = Result<EdgeRangeItem>> + 'a> {
         let filtered = iterator.take_while(move |item| -> bool {
@@ -272,26 +585,47 @@ impl<'a> EdgeRangeManager<'a> {
     }
 
     pub fn iterate_for_range(
-        &'a self,
+        &self,
         id: Uuid,
         t: Option<&models::Type>,
         high: Option<DateTime<Utc>>,
     ) -> Result<Box<dyn Iterator<Item = Result<EdgeRangeItem>> + 'a>> {
-        match t {
+        self.iterate_for_range_after(id, t, high, None)
+    }
+
+    /// Like `iterate_for_range`, but only yields the items that come after
+    /// `after`, a `(type, update datetime, second ID)` triple identifying an
+    /// edge range item owned by `id`.
+    pub fn iterate_for_range_after(
+        &self,
+        id: Uuid,
+        t: Option<&models::Type>,
+        high: Option<DateTime<Utc>>,
+        after: Option<(&models::Type, DateTime<Utc>, Uuid)>,
+    ) -> Result<Box<dyn Iterator<Item = Result<EdgeRangeItem>> + 'a>> {
+        let after_key = after.map(|(after_t, after_update_datetime, after_second_id)| {
+            self.key(id, after_t, after_update_datetime, after_second_id)
+        });
+
+        let mapped: Box<dyn Iterator<Item = Result<EdgeRangeItem>> + 'a> = match t {
             Some(t) => {
                 let high = high.unwrap_or_else(|| *MAX_DATETIME);
                 let prefix = build(&[Component::Uuid(id), Component::Type(t)]);
-                let low_key = build(&[Component::Uuid(id), Component::Type(t), Component::DateTime(high)]);
-                let iterator = self
-                    .db
-                    .iterator_cf(self.cf, IteratorMode::From(&low_key, Direction::Forward))?;
-                Ok(Box::new(self.iterate(iterator, prefix)?))
+                let mut low_key = build(&[Component::Uuid(id), Component::Type(t), Component::DateTime(high)]);
+
+                if let Some(ref after_key) = after_key {
+                    if after_key > &low_key {
+                        low_key = after_key.clone();
+                    }
+                }
+
+                let iterator = self.source.iterator_cf_prefix(self.cf_name, &prefix, &low_key)?;
+                Box::new(self.iterate(iterator, prefix)?)
             }
             None => {
                 let prefix = build(&[Component::Uuid(id)]);
-                let iterator = self
-                    .db
-                    .iterator_cf(self.cf, IteratorMode::From(&prefix, Direction::Forward))?;
+                let low_key = after_key.clone().unwrap_or_else(|| prefix.clone());
+                let iterator = self.source.iterator_cf_prefix(self.cf_name, &prefix, &low_key)?;
                 let mapped = self.iterate(iterator, prefix)?;
 
                 if let Some(high) = high {
@@ -306,44 +640,75 @@ impl<'a> EdgeRangeManager<'a> {
                         }
                     });
 
-                    Ok(Box::new(filtered))
+                    Box::new(filtered)
                 } else {
-                    Ok(Box::new(mapped))
+                    Box::new(mapped)
                 }
             }
+        };
+
+        match after {
+            Some((after_t, after_update_datetime, after_second_id)) => {
+                let after_t = after_t.clone();
+
+                // Iteration starts at the `after` item itself if it still
+                // exists, so skip over it
+                Ok(Box::new(mapped.skip_while(move |item| match item {
+                    Ok((_, t, update_datetime, second_id)) => {
+                        t == &after_t && *update_datetime == after_update_datetime && *second_id == after_second_id
+                    }
+                    Err(_) => false,
+                })))
+            }
+            None => Ok(mapped),
         }
     }
 
-    pub fn iterate_for_owner(&'a self, id: Uuid) -> Result<impl Iterator<Item = Result<EdgeRangeItem>> + 'a> {
+    pub fn exists(
+        &self,
+        first_id: Uuid,
+        t: &models::Type,
+        update_datetime: DateTime<Utc>,
+        second_id: Uuid,
+    ) -> Result<bool> {
+        let key = self.key(first_id, t, update_datetime, second_id);
+        Ok(self.source.get_cf(self.cf_name, &key)?.is_some())
+    }
+
+    /// Iterates over every edge range item.
+    pub fn iterate_all(&self) -> Result<impl Iterator<Item = Result<EdgeRangeItem>> + 'a> {
+        let iterator = self.source.iterator_cf_all(self.cf_name)?;
+        self.iterate(iterator, Vec::new())
+    }
+
+    pub fn iterate_for_owner(&self, id: Uuid) -> Result<impl Iterator<Item = Result<EdgeRangeItem>> + 'a> {
         let prefix = build(&[Component::Uuid(id)]);
-        let iterator = self
-            .db
-            .iterator_cf(self.cf, IteratorMode::From(&prefix, Direction::Forward))?;
+        let iterator = self.source.iterator_cf_prefix(self.cf_name, &prefix, &prefix)?;
         self.iterate(iterator, prefix)
     }
 
     pub fn set(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         first_id: Uuid,
         t: &models::Type,
         update_datetime: DateTime<Utc>,
         second_id: Uuid,
     ) -> Result<()> {
         let key = self.key(first_id, t, update_datetime, second_id);
-        batch.put_cf(self.cf, &key, &[])?;
+        batch.put_cf(self.cf_name, &key, &[])?;
         Ok(())
     }
 
     pub fn delete(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
         first_id: Uuid,
         t: &models::Type,
         update_datetime: DateTime<Utc>,
         second_id: Uuid,
     ) -> Result<()> {
-        batch.delete_cf(self.cf, &self.key(first_id, t, update_datetime, second_id))?;
+        batch.delete_cf(self.cf_name, &self.key(first_id, t, update_datetime, second_id))?;
         Ok(())
     }
 
@@ -355,29 +720,31 @@ impl<'a> EdgeRangeManager<'a> {
 pub struct VertexPropertyManager<'a> {
     pub db: &'a DB,
     pub cf: &'a ColumnFamily,
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
 }
 
 impl<'a> VertexPropertyManager<'a> {
     pub fn new(db: &'a DB) -> Self {
         VertexPropertyManager {
             cf: db.cf_handle("vertex_properties:v1").unwrap(),
+            cf_name: "vertex_properties:v1",
+            source: Source::db(db),
             db,
         }
     }
 
+    pub fn with_source(self, source: Source<'a>) -> Self {
+        Self { source, ..self }
+    }
+
     fn key(&self, vertex_id: Uuid, name: &str) -> Vec<u8> {
         build(&[Component::Uuid(vertex_id), Component::UnsizedString(name)])
     }
 
-    pub fn iterate_for_owner(
-        &'a self,
-        vertex_id: Uuid,
-    ) -> Result<impl Iterator<Item = Result<OwnedPropertyItem>> + 'a> {
+    pub fn iterate_for_owner(&self, vertex_id: Uuid) -> Result<impl Iterator<Item = Result<OwnedPropertyItem>> + 'a> {
         let prefix = build(&[Component::Uuid(vertex_id)]);
-
-        let iterator = self
-            .db
-            .iterator_cf(self.cf, IteratorMode::From(&prefix, Direction::Forward))?;
+        let iterator = self.source.iterator_cf_prefix(self.cf_name, &prefix, &prefix)?;
 
         let filtered = iterator.take_while(move |item| -> bool {
             let (ref k, _) = *item;
@@ -398,42 +765,88 @@ impl<'a> VertexPropertyManager<'a> {
     pub fn get(&self, vertex_id: Uuid, name: &str) -> Result<Option<JsonValue>> {
         let key = self.key(vertex_id, name);
 
-        match self.db.get_cf(self.cf, &key)? {
+        match self.source.get_cf(self.cf_name, &key)? {
             Some(value_bytes) => Ok(Some(serde_json::from_slice(&value_bytes)?)),
             None => Ok(None),
         }
     }
 
-    pub fn set(&self, batch: &mut WriteBatch, vertex_id: Uuid, name: &str, value: &JsonValue) -> Result<()> {
+    /// Gets a property value, taking into account what's been written to
+    /// `batch` so far.
+    fn get_in_batch(&self, batch: &Batch, vertex_id: Uuid, name: &str) -> Result<Option<JsonValue>> {
+        VertexPropertyManager::new(self.db)
+            .with_source(batch.source())
+            .get(vertex_id, name)
+    }
+
+    pub fn iterate(&self) -> Result<impl Iterator<Item = Result<OwnedPropertyItem>> + 'a> {
+        let iterator = self.source.iterator_cf_all(self.cf_name)?;
+
+        Ok(iterator.map(move |item| -> Result<OwnedPropertyItem> {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            let owner_id = read_uuid(&mut cursor);
+            let name = read_unsized_string(&mut cursor);
+            let value = serde_json::from_slice(&v)?;
+            Ok(((owner_id, name), value))
+        }))
+    }
+
+    pub fn set(&self, mut batch: &mut Batch, vertex_id: Uuid, name: &str, value: &JsonValue) -> Result<()> {
+        if IndexedPropertyManager::new(self.db).exists(name)? {
+            let index_manager = VertexPropertyIndexManager::new(self.db);
+
+            if let Some(old_value) = self.get_in_batch(batch, vertex_id, name)? {
+                index_manager.delete(&mut batch, name, &old_value, vertex_id)?;
+            }
+
+            index_manager.set(&mut batch, name, value, vertex_id)?;
+        }
+
         let key = self.key(vertex_id, name);
         let value_json = serde_json::to_vec(value)?;
-        batch.put_cf(self.cf, &key, &value_json)?;
+        batch.put_cf(self.cf_name, &key, &value_json)?;
         Ok(())
     }
 
-    pub fn delete(&self, batch: &mut WriteBatch, vertex_id: Uuid, name: &str) -> Result<()> {
-        batch.delete_cf(self.cf, &self.key(vertex_id, name))?;
+    pub fn delete(&self, mut batch: &mut Batch, vertex_id: Uuid, name: &str) -> Result<()> {
+        if IndexedPropertyManager::new(self.db).exists(name)? {
+            if let Some(old_value) = self.get_in_batch(batch, vertex_id, name)? {
+                VertexPropertyIndexManager::new(self.db).delete(&mut batch, name, &old_value, vertex_id)?;
+            }
+        }
+
+        batch.delete_cf(self.cf_name, &self.key(vertex_id, name))?;
         Ok(())
     }
 
     pub fn compact(&self) {
         self.db.compact_range_cf::<&[u8], &[u8]>(self.cf, None, None);
+        VertexPropertyIndexManager::new(self.db).compact();
     }
 }
 
 pub struct EdgePropertyManager<'a> {
     pub db: &'a DB,
     pub cf: &'a ColumnFamily,
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
 }
 
 impl<'a> EdgePropertyManager<'a> {
     pub fn new(db: &'a DB) -> Self {
         EdgePropertyManager {
             cf: db.cf_handle("edge_properties:v1").unwrap(),
+            cf_name: "edge_properties:v1",
+            source: Source::db(db),
             db,
         }
     }
 
+    pub fn with_source(self, source: Source<'a>) -> Self {
+        Self { source, ..self }
+    }
+
     fn key(&self, out_id: Uuid, t: &models::Type, in_id: Uuid, name: &str) -> Vec<u8> {
         build(&[
             Component::Uuid(out_id),
@@ -444,16 +857,13 @@ impl<'a> EdgePropertyManager<'a> {
     }
 
     pub fn iterate_for_owner(
-        &'a self,
+        &self,
         out_id: Uuid,
         t: &'a models::Type,
         in_id: Uuid,
     ) -> Result<Box<dyn Iterator<Item = Result<EdgePropertyItem>> + 'a>> {
         let prefix = build(&[Component::Uuid(out_id), Component::Type(t), Component::Uuid(in_id)]);
-
-        let iterator = self
-            .db
-            .iterator_cf(self.cf, IteratorMode::From(&prefix, Direction::Forward))?;
+        let iterator = self.source.iterator_cf_prefix(self.cf_name, &prefix, &prefix)?;
 
         let filtered = iterator.take_while(move |item| -> bool {
             let (ref k, _) = *item;
@@ -493,36 +903,314 @@ impl<'a> EdgePropertyManager<'a> {
     pub fn get(&self, out_id: Uuid, t: &models::Type, in_id: Uuid, name: &str) -> Result<Option<JsonValue>> {
         let key = self.key(out_id, t, in_id, name);
 
-        match self.db.get_cf(self.cf, &key)? {
+        match self.source.get_cf(self.cf_name, &key)? {
             Some(value_bytes) => Ok(Some(serde_json::from_slice(&value_bytes)?)),
             None => Ok(None),
         }
     }
 
+    /// Gets a property value, taking into account what's been written to
+    /// `batch` so far.
+    fn get_in_batch(
+        &self,
+        batch: &Batch,
+        out_id: Uuid,
+        t: &models::Type,
+        in_id: Uuid,
+        name: &str,
+    ) -> Result<Option<JsonValue>> {
+        EdgePropertyManager::new(self.db)
+            .with_source(batch.source())
+            .get(out_id, t, in_id, name)
+    }
+
+    pub fn iterate(&self) -> Result<impl Iterator<Item = Result<EdgePropertyItem>> + 'a> {
+        let iterator = self.source.iterator_cf_all(self.cf_name)?;
+
+        Ok(iterator.map(move |item| -> Result<EdgePropertyItem> {
+            let (k, v) = item;
+            let mut cursor = Cursor::new(k);
+            let out_id = read_uuid(&mut cursor);
+            let t = read_type(&mut cursor);
+            let in_id = read_uuid(&mut cursor);
+            let name = read_unsized_string(&mut cursor);
+            let value = serde_json::from_slice(&v)?;
+            Ok(((out_id, t, in_id, name), value))
+        }))
+    }
+
     pub fn set(
         &self,
-        batch: &mut WriteBatch,
+        mut batch: &mut Batch,
         out_id: Uuid,
         t: &models::Type,
         in_id: Uuid,
         name: &str,
         value: &JsonValue,
     ) -> Result<()> {
+        if IndexedPropertyManager::new(self.db).exists(name)? {
+            let index_manager = EdgePropertyIndexManager::new(self.db);
+
+            if let Some(old_value) = self.get_in_batch(batch, out_id, t, in_id, name)? {
+                index_manager.delete(&mut batch, name, &old_value, out_id, t, in_id)?;
+            }
+
+            index_manager.set(&mut batch, name, value, out_id, t, in_id)?;
+        }
+
         let key = self.key(out_id, t, in_id, name);
         let value_json = serde_json::to_vec(value)?;
-        batch.put_cf(self.cf, &key, &value_json)?;
+        batch.put_cf(self.cf_name, &key, &value_json)?;
         Ok(())
     }
 
-    pub fn delete(
+    pub fn delete(&self, mut batch: &mut Batch, out_id: Uuid, t: &models::Type, in_id: Uuid, name: &str) -> Result<()> {
+        if IndexedPropertyManager::new(self.db).exists(name)? {
+            if let Some(old_value) = self.get_in_batch(batch, out_id, t, in_id, name)? {
+                EdgePropertyIndexManager::new(self.db).delete(&mut batch, name, &old_value, out_id, t, in_id)?;
+            }
+        }
+
+        batch.delete_cf(self.cf_name, &self.key(out_id, t, in_id, name))?;
+        Ok(())
+    }
+
+    pub fn compact(&self) {
+        self.db.compact_range_cf::<&[u8], &[u8]>(self.cf, None, None);
+        EdgePropertyIndexManager::new(self.db).compact();
+    }
+}
+
+pub struct IndexedPropertyManager<'a> {
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
+}
+
+impl<'a> IndexedPropertyManager<'a> {
+    pub fn new(db: &'a DB) -> Self {
+        IndexedPropertyManager {
+            cf_name: "indexed_properties:v1",
+            source: Source::db(db),
+        }
+    }
+
+    pub fn with_source(self, source: Source<'a>) -> Self {
+        Self { source, ..self }
+    }
+
+    fn key(&self, name: &str) -> Vec<u8> {
+        build(&[Component::UnsizedString(name)])
+    }
+
+    pub fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.source.get_cf(self.cf_name, &self.key(name))?.is_some())
+    }
+
+    pub fn create(&self, batch: &mut Batch, name: &str) -> Result<()> {
+        batch.put_cf(self.cf_name, &self.key(name), &[])?;
+        Ok(())
+    }
+}
+
+pub struct VertexPropertyIndexManager<'a> {
+    pub db: &'a DB,
+    pub cf: &'a ColumnFamily,
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
+}
+
+impl<'a> VertexPropertyIndexManager<'a> {
+    pub fn new(db: &'a DB) -> Self {
+        VertexPropertyIndexManager {
+            cf: db.cf_handle("vertex_property_values:v1").unwrap(),
+            cf_name: "vertex_property_values:v1",
+            source: Source::db(db),
+            db,
+        }
+    }
+
+    pub fn with_source(self, source: Source<'a>) -> Self {
+        Self { source, ..self }
+    }
+
+    fn key(&self, name: &str, value: &JsonValue, vertex_id: Uuid) -> Vec<u8> {
+        build(&[
+            Component::SizedString(name),
+            Component::SizedString(&value.to_string()),
+            Component::Uuid(vertex_id),
+        ])
+    }
+
+    /// Iterates over the IDs of vertices whose property `name` is set to
+    /// `value`, starting from `start_id`.
+    pub fn iterate_for_value(
+        &self,
+        name: &str,
+        value: &JsonValue,
+        start_id: Uuid,
+    ) -> Result<impl Iterator<Item = Result<Uuid>> + 'a> {
+        let value_json = value.to_string();
+        let prefix = build(&[Component::SizedString(name), Component::SizedString(&value_json)]);
+        let low_key = self.key(name, value, start_id);
+
+        let iterator = self.source.iterator_cf(self.cf_name, &low_key)?;
+
+        let filtered = iterator.take_while(move |item| -> bool {
+            let (ref k, _) = *item;
+            k.starts_with(&prefix)
+        });
+
+        Ok(filtered.map(move |item| -> Result<Uuid> {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            read_sized_string(&mut cursor);
+            read_sized_string(&mut cursor);
+            Ok(read_uuid(&mut cursor))
+        }))
+    }
+
+    pub fn exists(&self, name: &str, value: &JsonValue, vertex_id: Uuid) -> Result<bool> {
+        Ok(self
+            .source
+            .get_cf(self.cf_name, &self.key(name, value, vertex_id))?
+            .is_some())
+    }
+
+    /// Iterates over every entry in the index.
+    pub fn iterate(&self) -> Result<impl Iterator<Item = Result<VertexPropertyIndexItem>> + 'a> {
+        let iterator = self.source.iterator_cf_all(self.cf_name)?;
+
+        Ok(iterator.map(move |item| -> Result<VertexPropertyIndexItem> {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            let name = read_sized_string(&mut cursor);
+            let value = serde_json::from_str(&read_sized_string(&mut cursor))?;
+            let vertex_id = read_uuid(&mut cursor);
+            Ok((name, value, vertex_id))
+        }))
+    }
+
+    pub fn set(&self, batch: &mut Batch, name: &str, value: &JsonValue, vertex_id: Uuid) -> Result<()> {
+        batch.put_cf(self.cf_name, &self.key(name, value, vertex_id), &[])?;
+        Ok(())
+    }
+
+    pub fn delete(&self, batch: &mut Batch, name: &str, value: &JsonValue, vertex_id: Uuid) -> Result<()> {
+        batch.delete_cf(self.cf_name, &self.key(name, value, vertex_id))?;
+        Ok(())
+    }
+
+    pub fn compact(&self) {
+        self.db.compact_range_cf::<&[u8], &[u8]>(self.cf, None, None);
+    }
+}
+
+pub struct EdgePropertyIndexManager<'a> {
+    pub db: &'a DB,
+    pub cf: &'a ColumnFamily,
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
+}
+
+impl<'a> EdgePropertyIndexManager<'a> {
+    pub fn new(db: &'a DB) -> Self {
+        EdgePropertyIndexManager {
+            cf: db.cf_handle("edge_property_values:v1").unwrap(),
+            cf_name: "edge_property_values:v1",
+            source: Source::db(db),
+            db,
+        }
+    }
+
+    pub fn with_source(self, source: Source<'a>) -> Self {
+        Self { source, ..self }
+    }
+
+    fn key(&self, name: &str, value: &JsonValue, out_id: Uuid, t: &models::Type, in_id: Uuid) -> Vec<u8> {
+        build(&[
+            Component::SizedString(name),
+            Component::SizedString(&value.to_string()),
+            Component::Uuid(out_id),
+            Component::Type(t),
+            Component::Uuid(in_id),
+        ])
+    }
+
+    /// Iterates over the keys of edges whose property `name` is set to
+    /// `value`.
+    pub fn iterate_for_value(
+        &self,
+        name: &str,
+        value: &JsonValue,
+    ) -> Result<impl Iterator<Item = Result<(Uuid, models::Type, Uuid)>> + 'a> {
+        let value_json = value.to_string();
+        let prefix = build(&[Component::SizedString(name), Component::SizedString(&value_json)]);
+
+        let iterator = self.source.iterator_cf(self.cf_name, &prefix)?;
+
+        let filtered = iterator.take_while(move |item| -> bool {
+            let (ref k, _) = *item;
+            k.starts_with(&prefix)
+        });
+
+        Ok(filtered.map(move |item| -> Result<(Uuid, models::Type, Uuid)> {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            read_sized_string(&mut cursor);
+            read_sized_string(&mut cursor);
+            let out_id = read_uuid(&mut cursor);
+            let t = read_type(&mut cursor);
+            let in_id = read_uuid(&mut cursor);
+            Ok((out_id, t, in_id))
+        }))
+    }
+
+    pub fn exists(&self, name: &str, value: &JsonValue, out_id: Uuid, t: &models::Type, in_id: Uuid) -> Result<bool> {
+        Ok(self
+            .source
+            .get_cf(self.cf_name, &self.key(name, value, out_id, t, in_id))?
+            .is_some())
+    }
+
+    /// Iterates over every entry in the index.
+    pub fn iterate(&self) -> Result<impl Iterator<Item = Result<EdgePropertyIndexItem>> + 'a> {
+        let iterator = self.source.iterator_cf_all(self.cf_name)?;
+
+        Ok(iterator.map(move |item| -> Result<EdgePropertyIndexItem> {
+            let (k, _) = item;
+            let mut cursor = Cursor::new(k);
+            let name = read_sized_string(&mut cursor);
+            let value = serde_json::from_str(&read_sized_string(&mut cursor))?;
+            let out_id = read_uuid(&mut cursor);
+            let t = read_type(&mut cursor);
+            let in_id = read_uuid(&mut cursor);
+            Ok((name, value, out_id, t, in_id))
+        }))
+    }
+
+    pub fn set(
         &self,
-        batch: &mut WriteBatch,
+        batch: &mut Batch,
+        name: &str,
+        value: &JsonValue,
         out_id: Uuid,
         t: &models::Type,
         in_id: Uuid,
+    ) -> Result<()> {
+        batch.put_cf(self.cf_name, &self.key(name, value, out_id, t, in_id), &[])?;
+        Ok(())
+    }
+
+    pub fn delete(
+        &self,
+        batch: &mut Batch,
         name: &str,
+        value: &JsonValue,
+        out_id: Uuid,
+        t: &models::Type,
+        in_id: Uuid,
     ) -> Result<()> {
-        batch.delete_cf(self.cf, &self.key(out_id, t, in_id, name))?;
+        batch.delete_cf(self.cf_name, &self.key(name, value, out_id, t, in_id))?;
         Ok(())
     }
 
@@ -530,3 +1218,331 @@ impl<'a> EdgePropertyManager<'a> {
         self.db.compact_range_cf::<&[u8], &[u8]>(self.cf, None, None);
     }
 }
+
+/// A count maintained by `CounterManager`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Counter {
+    /// The number of vertices.
+    Vertices,
+    /// The number of vertices of a type.
+    VerticesOfType(models::Type),
+    /// The number of edges of a type going out of, or coming into, a vertex.
+    Edges {
+        id: Uuid,
+        t: models::Type,
+        direction: models::EdgeDirection,
+    },
+}
+
+/// Maintains counts of vertices and edges, so that they can be read without
+/// scanning. Counters are updated in the same batch as the change they
+/// count, so they are always consistent with the rest of the database.
+pub struct CounterManager<'a> {
+    pub db: &'a DB,
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
+}
+
+impl<'a> CounterManager<'a> {
+    pub fn new(db: &'a DB) -> Self {
+        CounterManager {
+            cf_name: "counters:v1",
+            source: Source::db(db),
+            db,
+        }
+    }
+
+    pub fn with_source(self, source: Source<'a>) -> Self {
+        Self { source, ..self }
+    }
+
+    fn vertex_count_key(&self) -> Vec<u8> {
+        build(&[Component::UnsizedString("v")])
+    }
+
+    fn vertex_type_count_key(&self, t: &models::Type) -> Vec<u8> {
+        build(&[Component::UnsizedString("t"), Component::Type(t)])
+    }
+
+    fn edge_count_prefix(&self, id: Uuid, direction: models::EdgeDirection) -> Vec<u8> {
+        let tag = match direction {
+            models::EdgeDirection::Outbound => "o",
+            models::EdgeDirection::Inbound => "i",
+        };
+
+        build(&[Component::UnsizedString(tag), Component::Uuid(id)])
+    }
+
+    fn edge_count_key(&self, id: Uuid, t: &models::Type, direction: models::EdgeDirection) -> Vec<u8> {
+        let mut key = self.edge_count_prefix(id, direction);
+        key.extend(build(&[Component::Type(t)]));
+        key
+    }
+
+    fn counter_key(&self, counter: &Counter) -> Vec<u8> {
+        match counter {
+            Counter::Vertices => self.vertex_count_key(),
+            Counter::VerticesOfType(t) => self.vertex_type_count_key(t),
+            Counter::Edges { id, t, direction } => self.edge_count_key(*id, t, *direction),
+        }
+    }
+
+    fn read_counter_key(&self, key: &[u8]) -> Counter {
+        let mut cursor = Cursor::new(key);
+        let mut tag = [0; 1];
+        cursor.read_exact(&mut tag).unwrap();
+
+        match &tag {
+            b"v" => Counter::Vertices,
+            b"t" => Counter::VerticesOfType(read_type(&mut cursor)),
+            _ => {
+                let direction = if &tag == b"o" {
+                    models::EdgeDirection::Outbound
+                } else {
+                    models::EdgeDirection::Inbound
+                };
+
+                let id = read_uuid(&mut cursor);
+                let t = read_type(&mut cursor);
+                Counter::Edges { id, t, direction }
+            }
+        }
+    }
+
+    /// Gets a counter, including amounts that are yet to be added to it.
+    fn get(&self, key: &[u8]) -> Result<u64> {
+        let stored = match self.source.get_cf(self.cf_name, key)? {
+            Some(value_bytes) => read_counter(&value_bytes),
+            None => 0,
+        };
+
+        Ok((stored as i64 + self.source.counter_delta(self.cf_name, key)).max(0) as u64)
+    }
+
+    pub fn get_vertex_count(&self, t: Option<&models::Type>) -> Result<u64> {
+        match t {
+            Some(t) => self.get(&self.vertex_type_count_key(t)),
+            None => self.get(&self.vertex_count_key()),
+        }
+    }
+
+    pub fn get_edge_count(&self, id: Uuid, t: Option<&models::Type>, direction: models::EdgeDirection) -> Result<u64> {
+        if let Some(t) = t {
+            return self.get(&self.edge_count_key(id, t, direction));
+        }
+
+        let prefix = self.edge_count_prefix(id, direction);
+        let mut counts = self.source.counter_deltas(self.cf_name, &prefix);
+
+        for (k, v) in self.source.iterator_cf_prefix(self.cf_name, &prefix, &prefix)? {
+            *counts.entry(k.to_vec()).or_insert(0) += read_counter(&v) as i64;
+        }
+
+        Ok(counts.values().map(|count| (*count).max(0) as u64).sum())
+    }
+
+    /// Adds `delta` to the total vertex count, and to the count of vertices
+    /// of type `t`.
+    pub fn update_vertex_count(&self, batch: &mut Batch, t: &models::Type, delta: i64) {
+        batch.add_to_counter(self.cf_name, self.vertex_count_key(), delta);
+        batch.add_to_counter(self.cf_name, self.vertex_type_count_key(t), delta);
+    }
+
+    /// Adds `delta` to the outbound edge count of `out_id` and the inbound
+    /// edge count of `in_id`, for edges of type `t`.
+    pub fn update_edge_count(&self, batch: &mut Batch, out_id: Uuid, t: &models::Type, in_id: Uuid, delta: i64) {
+        let outbound_key = self.edge_count_key(out_id, t, models::EdgeDirection::Outbound);
+        let inbound_key = self.edge_count_key(in_id, t, models::EdgeDirection::Inbound);
+        batch.add_to_counter(self.cf_name, outbound_key, delta);
+        batch.add_to_counter(self.cf_name, inbound_key, delta);
+    }
+
+    /// Adds `delta` to a counter.
+    pub fn update(&self, batch: &mut Batch, counter: &Counter, delta: i64) {
+        batch.add_to_counter(self.cf_name, self.counter_key(counter), delta);
+    }
+
+    /// Counts the vertices and edges in the database, and compares the
+    /// counts with the stored counters. Returns each counter that is wrong,
+    /// along with its expected and actual values.
+    pub fn verify(&self) -> Result<Vec<(Counter, u64, u64)>> {
+        let mut expected: BTreeMap<Vec<u8>, (Counter, u64)> = BTreeMap::new();
+
+        let mut count = |counter: Counter| {
+            let key = self.counter_key(&counter);
+            expected.entry(key).or_insert((counter, 0)).1 += 1;
+        };
+
+        for item in VertexManager::new(self.db)
+            .with_source(self.source)
+            .iterate_for_range(Uuid::default())?
+        {
+            let (_, t) = item?;
+            count(Counter::Vertices);
+            count(Counter::VerticesOfType(t));
+        }
+
+        for item in EdgeManager::new(self.db).with_source(self.source).iterate()? {
+            let (out_id, t, _, in_id) = item?;
+
+            count(Counter::Edges {
+                id: out_id,
+                t: t.clone(),
+                direction: models::EdgeDirection::Outbound,
+            });
+
+            count(Counter::Edges {
+                id: in_id,
+                t,
+                direction: models::EdgeDirection::Inbound,
+            });
+        }
+
+        let mut wrong = Vec::new();
+
+        for (k, v) in self.source.iterator_cf_all(self.cf_name)? {
+            let actual = read_counter(&v);
+
+            let (counter, expected) = match expected.remove(&k[..]) {
+                Some((counter, expected)) => (counter, expected),
+                None => (self.read_counter_key(&k), 0),
+            };
+
+            if expected != actual {
+                wrong.push((counter, expected, actual));
+            }
+        }
+
+        // Whatever's left was counted, but has no stored counter
+        for (_, (counter, expected)) in expected {
+            wrong.push((counter, expected, 0));
+        }
+
+        Ok(wrong)
+    }
+}
+
+/// Stores information about the database itself, such as the version of its
+/// on-disk format, and the names of the named graphs stored alongside it.
+pub struct MetadataManager<'a> {
+    pub cf_name: &'static str,
+    pub source: Source<'a>,
+}
+
+impl<'a> MetadataManager<'a> {
+    pub fn new(db: &'a DB) -> Self {
+        MetadataManager {
+            cf_name: "metadata:v1",
+            source: Source::db(db),
+        }
+    }
+
+    fn key(&self, name: &str) -> Vec<u8> {
+        build(&[Component::UnsizedString(name)])
+    }
+
+    pub fn get_version(&self) -> Result<Option<u32>> {
+        match self.source.get_cf(self.cf_name, &self.key("version"))? {
+            Some(value_bytes) => Ok(Some(BigEndian::read_u32(&value_bytes))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_version(&self, batch: &mut Batch, version: u32) -> Result<()> {
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, version);
+        batch.put_cf(self.cf_name, &self.key("version"), &buf)?;
+        Ok(())
+    }
+
+    /// Gets how far an interrupted migration got: the step it was on, and
+    /// the last key it rewrote in that step, if any.
+    pub fn get_migration_progress(&self) -> Result<Option<(u32, Option<Vec<u8>>)>> {
+        match self.source.get_cf(self.cf_name, &self.key("migration_progress"))? {
+            Some(value_bytes) => {
+                let step = BigEndian::read_u32(&value_bytes);
+                let last_key = if value_bytes.len() > 4 {
+                    Some(value_bytes[4..].to_vec())
+                } else {
+                    None
+                };
+
+                Ok(Some((step, last_key)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_migration_progress(&self, batch: &mut Batch, step: u32, last_key: Option<&[u8]>) -> Result<()> {
+        let mut buf = vec![0; 4];
+        BigEndian::write_u32(&mut buf, step);
+
+        if let Some(last_key) = last_key {
+            buf.extend(last_key);
+        }
+
+        batch.put_cf(self.cf_name, &self.key("migration_progress"), &buf)?;
+        Ok(())
+    }
+
+    pub fn delete_migration_progress(&self, batch: &mut Batch) -> Result<()> {
+        batch.delete_cf(self.cf_name, &self.key("migration_progress"))?;
+        Ok(())
+    }
+
+    fn migrated_key(&self, key: &[u8]) -> Vec<u8> {
+        let mut migrated_key = self.key("migrated/");
+        migrated_key.extend(key);
+        migrated_key
+    }
+
+    /// Holds onto an entry that a migration has moved to a new key, until
+    /// it's safe to write it to its column family.
+    pub fn stage_migrated(&self, batch: &mut Batch, key: &[u8], value: &[u8]) -> Result<()> {
+        batch.put_cf(self.cf_name, &self.migrated_key(key), value)?;
+        Ok(())
+    }
+
+    /// Iterates over the keys and values of staged migrated entries.
+    pub fn iterate_migrated(&self) -> Result<impl Iterator<Item = (Vec<u8>, Box<[u8]>)> + 'a> {
+        let prefix = self.key("migrated/");
+        let iterator = self.source.iterator_cf_prefix(self.cf_name, &prefix, &prefix)?;
+        Ok(iterator.map(|(k, v)| (k["migrated/".len()..].to_vec(), v)))
+    }
+
+    pub fn unstage_migrated(&self, batch: &mut Batch, key: &[u8]) -> Result<()> {
+        batch.delete_cf(self.cf_name, &self.migrated_key(key))?;
+        Ok(())
+    }
+
+    fn graph_key(&self, name: &str) -> Vec<u8> {
+        build(&[Component::UnsizedString("graphs/"), Component::UnsizedString(name)])
+    }
+
+    pub fn graph_exists(&self, name: &str) -> Result<bool> {
+        Ok(self.source.get_cf(self.cf_name, &self.graph_key(name))?.is_some())
+    }
+
+    /// Iterates over the names of the named graphs, in sorted order.
+    pub fn iterate_graphs(&self) -> Result<impl Iterator<Item = Result<String>> + 'a> {
+        let prefix = self.key("graphs/");
+        let iterator = self.source.iterator_cf(self.cf_name, &prefix)?;
+
+        Ok(iterator
+            .take_while(move |(k, _)| k.starts_with(&prefix))
+            .map(|(k, _)| -> Result<String> {
+                let mut cursor = Cursor::new(&k["graphs/".len()..]);
+                Ok(read_unsized_string(&mut cursor))
+            }))
+    }
+
+    pub fn add_graph(&self, batch: &mut Batch, name: &str) -> Result<()> {
+        batch.put_cf(self.cf_name, &self.graph_key(name), &[])?;
+        Ok(())
+    }
+
+    pub fn remove_graph(&self, batch: &mut Batch, name: &str) -> Result<()> {
+        batch.delete_cf(self.cf_name, &self.graph_key(name))?;
+        Ok(())
+    }
+}
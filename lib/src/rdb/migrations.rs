@@ -0,0 +1,373 @@
+//! Versioning of the on-disk format, and migrations between versions.
+//!
+//! Whenever the format changes, e.g. how keys are encoded in `bytes.rs`, bump
+//! `CURRENT_VERSION` and add a migration to `MIGRATIONS` that rewrites
+//! existing data from the previous version.
+
+use std::io::{Cursor, Read};
+
+use super::batch::Batch;
+use super::bytes::{read_type, read_uuid, write_varint};
+use super::managers::{CounterManager, MetadataManager};
+use crate::errors::{Error, Result};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use rocksdb::{Direction, IteratorMode, ReadOptions, DB};
+
+/// The version of the on-disk format written by this build.
+pub const CURRENT_VERSION: u32 = 3;
+
+/// How many entries to rewrite before writing them, so that migrating a big
+/// database doesn't hold all of it in memory at once.
+const MIGRATION_BATCH_SIZE: usize = 1000;
+
+/// Maps a key and value written by one version of the format to the next.
+type Rewrite = fn(&[u8], &[u8]) -> (Vec<u8>, Vec<u8>);
+
+/// Adds an entry's key and value to the counters it counts towards.
+type Count = fn(&CounterManager, &mut Batch, &[u8], &[u8]);
+
+/// A step of a migration. Steps are run in order, and the migration's
+/// progress is recorded as the index of the step it's on.
+enum Step {
+    /// Rewrites every key and value in a column family; see `rewrite`.
+    Rewrite(&'static str, Rewrite),
+    /// Writes the entries that the previous `Rewrite` step moved; see
+    /// `write_migrated`.
+    WriteMigrated(&'static str),
+    /// Deletes every entry in a column family.
+    Clear(&'static str),
+    /// Counts every entry in a column family towards the counters.
+    Count(&'static str, Count),
+}
+
+/// Rewrites the data in a database to the next version.
+type Migration = &'static [Step];
+
+/// Migrations, where the migration at index `i` upgrades a database from
+/// version `i + 1` to version `i + 2`.
+const MIGRATIONS: [Migration; (CURRENT_VERSION - 1) as usize] = [
+    // Version 2 allows types longer than 255 bytes, by prefixing types with
+    // a varint length rather than a single byte. Lengths below 128 are
+    // encoded the same either way, so only entries with longer types
+    // actually change.
+    &[
+        Step::Rewrite("vertices:v1", reencode_vertex),
+        Step::WriteMigrated("vertices:v1"),
+        Step::Rewrite("edges:v1", reencode_edge_keyed),
+        Step::WriteMigrated("edges:v1"),
+        Step::Rewrite("edge_ranges:v1", reencode_edge_keyed),
+        Step::WriteMigrated("edge_ranges:v1"),
+        Step::Rewrite("reversed_edge_ranges:v1", reencode_edge_keyed),
+        Step::WriteMigrated("reversed_edge_ranges:v1"),
+        Step::Rewrite("edge_properties:v1", reencode_edge_keyed),
+        Step::WriteMigrated("edge_properties:v1"),
+        Step::Rewrite("edge_property_values:v1", reencode_edge_property_value),
+        Step::WriteMigrated("edge_property_values:v1"),
+        Step::Rewrite("counters:v1", reencode_counter),
+        Step::WriteMigrated("counters:v1"),
+    ],
+    // Version 3 recounts the vertex and edge counters from scratch.
+    // Databases created before counters were maintained don't have any,
+    // and earlier builds calculated them all at once when such a database
+    // was opened, so an interruption could leave them partly written.
+    &[
+        Step::Clear("counters:v1"),
+        Step::Count("vertices:v1", count_vertex),
+        Step::Count("edges:v1", count_edge),
+    ],
+];
+
+/// Calls `f` with every key and value in a column family, in chunks. After
+/// each chunk, the last key is recorded as the migration's progress, and
+/// the batch is flushed, so a resumed step carries on right after it.
+///
+/// # Arguments
+/// * `db` - The database to read from.
+/// * `batch` - The batch to write to.
+/// * `step` - The step of the migration, to record progress under.
+/// * `cf_name` - The column family to iterate over.
+/// * `after` - The last key that was handled, if resuming.
+/// * `f` - Handles each key and value.
+fn for_each_chunked<F>(
+    db: &DB,
+    batch: &mut Batch,
+    step: u32,
+    cf_name: &'static str,
+    after: Option<Vec<u8>>,
+    mut f: F,
+) -> Result<()>
+where
+    F: FnMut(&mut Batch, &[u8], &[u8]) -> Result<()>,
+{
+    let cf = db.cf_handle(cf_name).unwrap();
+    let metadata_manager = MetadataManager::new(db);
+
+    // Column families may have prefix extractors, so ask for every key
+    // rather than just those with the first key's prefix
+    let mut opts = ReadOptions::default();
+    opts.set_total_order_seek(true);
+
+    let mode = match after {
+        Some(ref after) => IteratorMode::From(after, Direction::Forward),
+        None => IteratorMode::Start,
+    };
+
+    let mut count = 0;
+
+    for (key, value) in db.iterator_cf_opt(cf, opts, mode)? {
+        if after.as_deref() == Some(&key[..]) {
+            continue;
+        }
+
+        f(batch, &key, &value)?;
+        count += 1;
+
+        if count % MIGRATION_BATCH_SIZE == 0 {
+            metadata_manager.set_migration_progress(batch, step, Some(&key))?;
+            batch.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Rewrites every key and value in a column family, in chunks. Entries that
+/// move to a new key are staged in the metadata, rather than written
+/// straight away, so that a new key that's the same as some other old key
+/// isn't clobbered, and so that a resumed migration doesn't come across
+/// entries it has already rewritten.
+///
+/// # Arguments
+/// * `db` - The database to read from.
+/// * `batch` - The batch to write to.
+/// * `step` - The step of the migration, to record progress under.
+/// * `cf_name` - The column family to rewrite.
+/// * `f` - Maps old keys and values to new ones.
+/// * `after` - The last key that was rewritten, if resuming.
+fn rewrite(
+    db: &DB,
+    batch: &mut Batch,
+    step: u32,
+    cf_name: &'static str,
+    f: Rewrite,
+    after: Option<Vec<u8>>,
+) -> Result<()> {
+    let metadata_manager = MetadataManager::new(db);
+
+    for_each_chunked(db, batch, step, cf_name, after, |batch, key, value| {
+        let (new_key, new_value) = f(key, value);
+
+        if new_key[..] != key[..] {
+            batch.delete_cf(cf_name, key)?;
+            metadata_manager.stage_migrated(batch, &new_key, &new_value)?;
+        } else if new_value[..] != value[..] {
+            batch.put_cf(cf_name, key, &new_value)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Writes the entries staged by `rewrite` to their column family, in chunks.
+///
+/// # Arguments
+/// * `db` - The database to read from.
+/// * `batch` - The batch to write to.
+/// * `cf_name` - The column family the entries belong to.
+fn write_migrated(db: &DB, batch: &mut Batch, cf_name: &'static str) -> Result<()> {
+    let metadata_manager = MetadataManager::new(db);
+    let mut count = 0;
+
+    // Entries are unstaged in the same chunk they're written in, so a
+    // resumed migration just picks up whatever is still staged
+    for (key, value) in metadata_manager.iterate_migrated()? {
+        batch.put_cf(cf_name, &key, &value)?;
+        metadata_manager.unstage_migrated(batch, &key)?;
+        count += 1;
+
+        if count % MIGRATION_BATCH_SIZE == 0 {
+            batch.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a migration, writing its progress to the metadata as it goes, so
+/// that an interrupted migration resumes where it left off.
+///
+/// # Arguments
+/// * `db` - The database to migrate.
+/// * `batch` - The batch to write to. It's flushed after each chunk.
+/// * `migration` - The migration to run.
+fn run(db: &DB, batch: &mut Batch, migration: Migration) -> Result<()> {
+    let metadata_manager = MetadataManager::new(db);
+    let counter_manager = CounterManager::new(db);
+
+    let (first_step, mut after) = match metadata_manager.get_migration_progress()? {
+        Some((step, after)) => (step, after),
+        None => (0, None),
+    };
+
+    for step in first_step..migration.len() as u32 {
+        match migration[step as usize] {
+            Step::Rewrite(cf_name, f) => rewrite(db, batch, step, cf_name, f, after.take())?,
+            Step::WriteMigrated(cf_name) => write_migrated(db, batch, cf_name)?,
+            // Deleting is idempotent, so this just starts over if resumed
+            Step::Clear(cf_name) => for_each_chunked(db, batch, step, cf_name, after.take(), |batch, key, _| {
+                batch.delete_cf(cf_name, key)
+            })?,
+            // Counts are written in the same chunk as the progress, so a
+            // resumed step doesn't count anything twice
+            Step::Count(cf_name, f) => {
+                for_each_chunked(db, batch, step, cf_name, after.take(), |batch, key, value| {
+                    f(&counter_manager, batch, key, value);
+                    Ok(())
+                })?
+            }
+        }
+
+        metadata_manager.set_migration_progress(batch, step + 1, None)?;
+        batch.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Re-encodes a key or value written by version 1 of the format, where
+/// types were prefixed by a single length byte, to use varint type lengths.
+struct TypeReencoder<'a> {
+    old: Cursor<&'a [u8]>,
+    new: Vec<u8>,
+}
+
+impl<'a> TypeReencoder<'a> {
+    fn new(old: &'a [u8]) -> Self {
+        TypeReencoder {
+            old: Cursor::new(old),
+            new: Vec::with_capacity(old.len() + 1),
+        }
+    }
+
+    fn bytes(mut self, len: usize) -> Self {
+        let mut buf = vec![0u8; len];
+        self.old.read_exact(&mut buf).unwrap();
+        self.new.extend(buf);
+        self
+    }
+
+    fn uuid(self) -> Self {
+        self.bytes(16)
+    }
+
+    fn sized_string(mut self) -> Self {
+        let len = self.old.read_u32::<BigEndian>().unwrap();
+        self.new.extend(&len.to_be_bytes());
+        self.bytes(len as usize)
+    }
+
+    fn t(mut self) -> Self {
+        let len = self.old.read_u8().unwrap() as usize;
+        write_varint(&mut self.new, len).unwrap();
+        self.bytes(len)
+    }
+
+    /// Copies whatever is left over as-is.
+    fn finish(mut self) -> Vec<u8> {
+        self.old.read_to_end(&mut self.new).unwrap();
+        self.new
+    }
+}
+
+/// Re-encodes the types of vertices.
+fn reencode_vertex(k: &[u8], v: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    (k.to_vec(), TypeReencoder::new(v).t().finish())
+}
+
+/// Re-encodes the keys of column families that start with an edge's
+/// outbound or inbound vertex ID, then its type.
+fn reencode_edge_keyed(k: &[u8], v: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    (TypeReencoder::new(k).uuid().t().finish(), v.to_vec())
+}
+
+/// Re-encodes the types of edges in the edge property index.
+fn reencode_edge_property_value(k: &[u8], v: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let k = TypeReencoder::new(k).sized_string().sized_string().uuid().t().finish();
+    (k, v.to_vec())
+}
+
+/// Re-encodes the types in the keys of vertex type and edge counters.
+fn reencode_counter(k: &[u8], v: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let k = match k[0] {
+        b't' => TypeReencoder::new(k).bytes(1).t().finish(),
+        b'o' | b'i' => TypeReencoder::new(k).bytes(1).uuid().t().finish(),
+        _ => k.to_vec(),
+    };
+
+    (k, v.to_vec())
+}
+
+/// Counts a vertex towards the vertex counters.
+fn count_vertex(counter_manager: &CounterManager, batch: &mut Batch, _: &[u8], v: &[u8]) {
+    let t = read_type(&mut Cursor::new(v));
+    counter_manager.update_vertex_count(batch, &t, 1);
+}
+
+/// Counts an edge towards the edge counters of its vertices.
+fn count_edge(counter_manager: &CounterManager, batch: &mut Batch, k: &[u8], _: &[u8]) {
+    let mut cursor = Cursor::new(k);
+    let out_id = read_uuid(&mut cursor);
+    let t = read_type(&mut cursor);
+    let in_id = read_uuid(&mut cursor);
+    counter_manager.update_edge_count(batch, out_id, &t, in_id, 1);
+}
+
+/// Brings a database up to `CURRENT_VERSION`, running any migrations that
+/// are needed. Migrations are written in chunks, and record their progress
+/// in the metadata, so an interrupted upgrade resumes where it left off.
+///
+/// # Arguments
+/// * `db` - The database to migrate.
+/// * `new_batch` - Creates batches to write migrations to.
+/// * `is_new` - Whether the database was just created.
+///
+/// # Errors
+/// Returns `Error::UnsupportedVersion` if the database was written by a newer
+/// version of the format.
+pub fn migrate<'a, F>(db: &'a DB, new_batch: F, is_new: bool) -> Result<()>
+where
+    F: Fn() -> Batch<'a>,
+{
+    let metadata_manager = MetadataManager::new(db);
+
+    let (mut version, mut recorded) = match metadata_manager.get_version()? {
+        Some(version) => (version, true),
+        // New databases start out at the current version, and existing ones
+        // without a version predate versioning
+        None if is_new => (CURRENT_VERSION, false),
+        None => (1, false),
+    };
+
+    if version > CURRENT_VERSION {
+        return Err(Error::UnsupportedVersion { version });
+    }
+
+    while version < CURRENT_VERSION {
+        let mut batch = new_batch();
+        run(db, &mut batch, MIGRATIONS[version as usize - 1])?;
+        version += 1;
+        metadata_manager.set_version(&mut batch, version)?;
+        metadata_manager.delete_migration_progress(&mut batch)?;
+        batch.write()?;
+        recorded = true;
+    }
+
+    if !recorded {
+        let mut batch = new_batch();
+        metadata_manager.set_version(&mut batch, version)?;
+        batch.write()?;
+    }
+
+    Ok(())
+}
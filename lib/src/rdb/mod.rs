@@ -1,13 +1,20 @@
 //! The rocksdb datastore implementation.
 
+mod batch;
 mod bytes;
 mod datastore;
+mod integrity;
 mod managers;
+mod migrations;
+mod options;
 
 #[cfg(feature = "test-suite")]
 mod tests;
 
 pub use self::datastore::{RocksdbDatastore, RocksdbTransaction};
+pub use self::integrity::IntegrityIssue;
+pub use self::managers::Counter;
+pub use self::options::{ColumnFamily, CompactionStyle, Compression, RocksdbOptions};
 
 mod normal_config {
     #[cfg(feature = "bench-suite")]
@@ -23,6 +30,13 @@ mod normal_config {
         use crate::util::generate_temporary_path;
         RocksdbDatastore::new(&generate_temporary_path(), Some(1), false).unwrap()
     });
+
+    #[cfg(feature = "test-suite")]
+    named_graphs_test_impl!({
+        use super::RocksdbDatastore;
+        use crate::util::generate_temporary_path;
+        RocksdbDatastore::new(&generate_temporary_path(), Some(1), false).unwrap()
+    });
 }
 
 mod bulk_load_optimized_config {
@@ -40,3 +54,25 @@ mod bulk_load_optimized_config {
         RocksdbDatastore::new(&generate_temporary_path(), Some(1), true).unwrap()
     });
 }
+
+mod no_prefix_extractors_config {
+    #[cfg(feature = "bench-suite")]
+    full_bench_impl!({
+        use super::{RocksdbDatastore, RocksdbOptions};
+        use crate::util::generate_temporary_path;
+        let options = RocksdbOptions::default()
+            .max_open_files(Some(1))
+            .prefix_extractors(false);
+        RocksdbDatastore::new_with_options(&generate_temporary_path(), options).unwrap()
+    });
+
+    #[cfg(feature = "test-suite")]
+    full_test_impl!({
+        use super::{RocksdbDatastore, RocksdbOptions};
+        use crate::util::generate_temporary_path;
+        let options = RocksdbOptions::default()
+            .max_open_files(Some(1))
+            .prefix_extractors(false);
+        RocksdbDatastore::new_with_options(&generate_temporary_path(), options).unwrap()
+    });
+}
@@ -0,0 +1,549 @@
+use std::collections::BTreeMap;
+
+use rocksdb::{
+    BlockBasedOptions, ColumnFamilyDescriptor, DBCompactionStyle, DBCompressionType, MemtableFactory, Options,
+    SliceTransform, WriteOptions,
+};
+use serde::{Deserialize, Serialize};
+
+/// The length of the prefix of keys that identifies the vertex that owns
+/// them, in column families keyed by vertex.
+const OWNER_PREFIX_LEN: usize = 16;
+
+/// The size of the prefix bloom filters of column families keyed by vertex,
+/// unless overridden with `RocksdbOptions::bloom_filter`.
+const DEFAULT_PREFIX_BLOOM_FILTER_BITS_PER_KEY: i32 = 10;
+
+/// The share of the memtable to dedicate to prefix bloom filters.
+const MEMTABLE_PREFIX_BLOOM_RATIO: f64 = 0.1;
+
+/// A column family of a rocksdb datastore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnFamily {
+    Vertices,
+    Edges,
+    /// Edges, keyed by their outbound vertex.
+    EdgeRanges,
+    /// Edges, keyed by their inbound vertex.
+    ReversedEdgeRanges,
+    VertexProperties,
+    EdgeProperties,
+    IndexedProperties,
+    VertexPropertyValues,
+    EdgePropertyValues,
+    Counters,
+    Metadata,
+}
+
+impl ColumnFamily {
+    pub(crate) const ALL: [ColumnFamily; 11] = [
+        ColumnFamily::Vertices,
+        ColumnFamily::Edges,
+        ColumnFamily::EdgeRanges,
+        ColumnFamily::ReversedEdgeRanges,
+        ColumnFamily::VertexProperties,
+        ColumnFamily::EdgeProperties,
+        ColumnFamily::IndexedProperties,
+        ColumnFamily::VertexPropertyValues,
+        ColumnFamily::EdgePropertyValues,
+        ColumnFamily::Counters,
+        ColumnFamily::Metadata,
+    ];
+
+    /// Whether keys start with the ID of the vertex that owns them, and are
+    /// usually read by owner.
+    fn is_keyed_by_owner(self) -> bool {
+        matches!(
+            self,
+            ColumnFamily::EdgeRanges
+                | ColumnFamily::ReversedEdgeRanges
+                | ColumnFamily::VertexProperties
+                | ColumnFamily::EdgeProperties
+        )
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ColumnFamily::Vertices => "vertices:v1",
+            ColumnFamily::Edges => "edges:v1",
+            ColumnFamily::EdgeRanges => "edge_ranges:v1",
+            ColumnFamily::ReversedEdgeRanges => "reversed_edge_ranges:v1",
+            ColumnFamily::VertexProperties => "vertex_properties:v1",
+            ColumnFamily::EdgeProperties => "edge_properties:v1",
+            ColumnFamily::IndexedProperties => "indexed_properties:v1",
+            ColumnFamily::VertexPropertyValues => "vertex_property_values:v1",
+            ColumnFamily::EdgePropertyValues => "edge_property_values:v1",
+            ColumnFamily::Counters => "counters:v1",
+            ColumnFamily::Metadata => "metadata:v1",
+        }
+    }
+}
+
+/// How rocksdb compacts the files of a database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactionStyle {
+    /// Files are organized into levels of increasing size, with each level
+    /// compacted into the next. Favors reads and space usage.
+    Level,
+    /// Files are merged into sorted runs. Favors writes.
+    Universal,
+    /// Old files are deleted once the database grows past a limit. Only
+    /// suitable for caches.
+    Fifo,
+}
+
+/// A compression algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    Snappy,
+    Zlib,
+    Bz2,
+    Lz4,
+    Lz4hc,
+    Zstd,
+}
+
+impl Compression {
+    fn to_rocksdb(self) -> DBCompressionType {
+        match self {
+            Compression::None => DBCompressionType::None,
+            Compression::Snappy => DBCompressionType::Snappy,
+            Compression::Zlib => DBCompressionType::Zlib,
+            Compression::Bz2 => DBCompressionType::Bz2,
+            Compression::Lz4 => DBCompressionType::Lz4,
+            Compression::Lz4hc => DBCompressionType::Lz4hc,
+            Compression::Zstd => DBCompressionType::Zstd,
+        }
+    }
+}
+
+/// Options for tuning a rocksdb datastore.
+///
+/// The defaults are based off of the total ordered, flash storage example in
+/// https://github.com/facebook/rocksdb/wiki/RocksDB-Tuning-Guide
+#[derive(Clone, Debug)]
+pub struct RocksdbOptions {
+    max_open_files: Option<i32>,
+    bulk_load_optimized: bool,
+    write_buffer_size: usize,
+    max_write_buffer_number: i32,
+    target_file_size_base: u64,
+    compaction_style: CompactionStyle,
+    max_background_compactions: i32,
+    level_zero_file_num_compaction_trigger: i32,
+    level_zero_slowdown_writes_trigger: i32,
+    level_zero_stop_writes_trigger: i32,
+    num_levels: i32,
+    max_bytes_for_level_base: u64,
+    max_bytes_for_level_multiplier: f64,
+    block_cache_size: Option<usize>,
+    bloom_filter_bits_per_key: BTreeMap<ColumnFamily, i32>,
+    compression: Option<Compression>,
+    compression_per_level: Option<Vec<Compression>>,
+    prefix_extractors: bool,
+    wal_enabled: bool,
+    sync_writes: bool,
+    use_fsync: bool,
+    bytes_per_sync: Option<u64>,
+}
+
+impl Default for RocksdbOptions {
+    fn default() -> Self {
+        Self {
+            max_open_files: None,
+            bulk_load_optimized: false,
+            write_buffer_size: 67_108_864, // 64mb
+            max_write_buffer_number: 3,
+            target_file_size_base: 67_108_864, // 64mb
+            compaction_style: CompactionStyle::Level,
+            max_background_compactions: 4,
+            level_zero_file_num_compaction_trigger: 8,
+            level_zero_slowdown_writes_trigger: 17,
+            level_zero_stop_writes_trigger: 24,
+            num_levels: 4,
+            max_bytes_for_level_base: 536_870_912, // 512mb
+            max_bytes_for_level_multiplier: 8.0,
+            block_cache_size: None,
+            bloom_filter_bits_per_key: BTreeMap::new(),
+            compression: None,
+            compression_per_level: None,
+            prefix_extractors: true,
+            wal_enabled: true,
+            sync_writes: false,
+            use_fsync: false,
+            bytes_per_sync: None,
+        }
+    }
+}
+
+impl RocksdbOptions {
+    /// Sets the maximum number of open files to have.
+    ///
+    /// # Arguments
+    /// * `value` - The maximum number of open files. If `None`, rocksdb's
+    ///   default will be used.
+    pub fn max_open_files(self, value: Option<i32>) -> Self {
+        Self {
+            max_open_files: value,
+            ..self
+        }
+    }
+
+    /// Sets whether to optimize for bulk loading, based off of suggestions
+    /// from the RocksDB FAQ. This disables automatic compactions, and
+    /// overrides the level zero triggers.
+    ///
+    /// # Arguments
+    /// * `value` - Whether to optimize for bulk loading.
+    pub fn bulk_load_optimized(self, value: bool) -> Self {
+        Self {
+            bulk_load_optimized: value,
+            ..self
+        }
+    }
+
+    /// Sets the size of a memtable, in bytes.
+    ///
+    /// # Arguments
+    /// * `value` - The size.
+    pub fn write_buffer_size(self, value: usize) -> Self {
+        Self {
+            write_buffer_size: value,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of memtables, both active and waiting to be
+    /// flushed.
+    ///
+    /// # Arguments
+    /// * `value` - The maximum number of memtables.
+    pub fn max_write_buffer_number(self, value: i32) -> Self {
+        Self {
+            max_write_buffer_number: value,
+            ..self
+        }
+    }
+
+    /// Sets the target size of level one files, in bytes.
+    ///
+    /// # Arguments
+    /// * `value` - The target size.
+    pub fn target_file_size_base(self, value: u64) -> Self {
+        Self {
+            target_file_size_base: value,
+            ..self
+        }
+    }
+
+    /// Sets the compaction style.
+    ///
+    /// # Arguments
+    /// * `value` - The compaction style.
+    pub fn compaction_style(self, value: CompactionStyle) -> Self {
+        Self {
+            compaction_style: value,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of concurrent background compactions.
+    ///
+    /// # Arguments
+    /// * `value` - The maximum number of compactions.
+    pub fn max_background_compactions(self, value: i32) -> Self {
+        Self {
+            max_background_compactions: value,
+            ..self
+        }
+    }
+
+    /// Sets the number of level zero files that triggers a compaction.
+    ///
+    /// # Arguments
+    /// * `value` - The number of files.
+    pub fn level_zero_file_num_compaction_trigger(self, value: i32) -> Self {
+        Self {
+            level_zero_file_num_compaction_trigger: value,
+            ..self
+        }
+    }
+
+    /// Sets the number of level zero files at which writes are slowed down.
+    ///
+    /// # Arguments
+    /// * `value` - The number of files.
+    pub fn level_zero_slowdown_writes_trigger(self, value: i32) -> Self {
+        Self {
+            level_zero_slowdown_writes_trigger: value,
+            ..self
+        }
+    }
+
+    /// Sets the number of level zero files at which writes are stopped.
+    ///
+    /// # Arguments
+    /// * `value` - The number of files.
+    pub fn level_zero_stop_writes_trigger(self, value: i32) -> Self {
+        Self {
+            level_zero_stop_writes_trigger: value,
+            ..self
+        }
+    }
+
+    /// Sets the number of levels.
+    ///
+    /// # Arguments
+    /// * `value` - The number of levels.
+    pub fn num_levels(self, value: i32) -> Self {
+        Self {
+            num_levels: value,
+            ..self
+        }
+    }
+
+    /// Sets the maximum total size of level one, in bytes.
+    ///
+    /// # Arguments
+    /// * `value` - The maximum size.
+    pub fn max_bytes_for_level_base(self, value: u64) -> Self {
+        Self {
+            max_bytes_for_level_base: value,
+            ..self
+        }
+    }
+
+    /// Sets how much bigger each level is allowed to be than the one before
+    /// it.
+    ///
+    /// # Arguments
+    /// * `value` - The multiplier.
+    pub fn max_bytes_for_level_multiplier(self, value: f64) -> Self {
+        Self {
+            max_bytes_for_level_multiplier: value,
+            ..self
+        }
+    }
+
+    /// Sets the size of the LRU cache for uncompressed blocks, in bytes.
+    /// Caches can't be shared between column families, so each column
+    /// family gets its own cache of this size.
+    ///
+    /// # Arguments
+    /// * `value` - The size of the cache. If `None`, rocksdb's default will
+    ///   be used.
+    pub fn block_cache_size(self, value: Option<usize>) -> Self {
+        Self {
+            block_cache_size: value,
+            ..self
+        }
+    }
+
+    /// Adds a bloom filter to a column family, which lets point lookups of
+    /// keys that don't exist skip reading from disk.
+    ///
+    /// # Arguments
+    /// * `cf` - The column family.
+    /// * `bits_per_key` - The size of the filter. 10 bits per key gives a
+    ///   false positive rate of about 1%.
+    pub fn bloom_filter(mut self, cf: ColumnFamily, bits_per_key: i32) -> Self {
+        self.bloom_filter_bits_per_key.insert(cf, bits_per_key);
+        self
+    }
+
+    /// Sets the compression algorithm to use for every level.
+    ///
+    /// # Arguments
+    /// * `value` - The compression algorithm.
+    pub fn compression(self, value: Compression) -> Self {
+        Self {
+            compression: Some(value),
+            ..self
+        }
+    }
+
+    /// Sets the compression algorithm to use for each level, overriding
+    /// `compression`. Lower levels are rewritten often, so are typically
+    /// left uncompressed or compressed with a fast algorithm.
+    ///
+    /// # Arguments
+    /// * `value` - The compression algorithm for each level, starting with
+    ///   level zero. There should be one per level.
+    pub fn compression_per_level(self, value: Vec<Compression>) -> Self {
+        Self {
+            compression_per_level: Some(value),
+            ..self
+        }
+    }
+
+    /// Sets whether to configure prefix extractors and prefix bloom filters
+    /// on the column families keyed by vertex: the edge ranges, and vertex
+    /// and edge properties. Reading the edges or properties of a vertex,
+    /// e.g. in pipe queries, can then skip any files and memtables that
+    /// have none for it. Enabled by default.
+    ///
+    /// # Arguments
+    /// * `value` - Whether to configure prefix extractors.
+    pub fn prefix_extractors(self, value: bool) -> Self {
+        Self {
+            prefix_extractors: value,
+            ..self
+        }
+    }
+
+    /// Sets whether transactions write to the write-ahead log. Writes made
+    /// without it are faster, but are lost if the process crashes before
+    /// they're flushed.
+    ///
+    /// # Arguments
+    /// * `value` - Whether to write to the write-ahead log.
+    pub fn wal_enabled(self, value: bool) -> Self {
+        Self {
+            wal_enabled: value,
+            ..self
+        }
+    }
+
+    /// Sets whether the write-ahead log is synced to disk before a
+    /// transaction's write returns. Without this, writes survive a crash of
+    /// the process, but not of the machine.
+    ///
+    /// # Arguments
+    /// * `value` - Whether to sync writes.
+    pub fn sync_writes(self, value: bool) -> Self {
+        Self {
+            sync_writes: value,
+            ..self
+        }
+    }
+
+    /// Sets whether files are synced with `fsync` rather than `fdatasync`.
+    ///
+    /// # Arguments
+    /// * `value` - Whether to use `fsync`.
+    pub fn use_fsync(self, value: bool) -> Self {
+        Self {
+            use_fsync: value,
+            ..self
+        }
+    }
+
+    /// Sets how many bytes are written to a file before it's incrementally
+    /// synced in the background, which smooths out the cost of syncing.
+    ///
+    /// # Arguments
+    /// * `value` - The number of bytes. If `None`, files are only synced
+    ///   when they're complete.
+    pub fn bytes_per_sync(self, value: Option<u64>) -> Self {
+        Self {
+            bytes_per_sync: value,
+            ..self
+        }
+    }
+
+    /// Gets the options to open each column family with.
+    pub(crate) fn to_column_family_descriptors(&self) -> Vec<ColumnFamilyDescriptor> {
+        ColumnFamily::ALL
+            .iter()
+            .map(|cf| {
+                let mut opts = self.to_rocksdb_options();
+                let prefixed = self.prefix_extractors && cf.is_keyed_by_owner();
+
+                // With a prefix extractor, bloom filters also cover prefixes
+                let bloom_filter_bits_per_key = match self.bloom_filter_bits_per_key.get(cf) {
+                    Some(bits_per_key) => Some(*bits_per_key),
+                    None if prefixed => Some(DEFAULT_PREFIX_BLOOM_FILTER_BITS_PER_KEY),
+                    None => None,
+                };
+
+                if self.block_cache_size.is_some() || bloom_filter_bits_per_key.is_some() {
+                    let mut block_opts = BlockBasedOptions::default();
+
+                    if let Some(block_cache_size) = self.block_cache_size {
+                        block_opts.set_lru_cache(block_cache_size);
+                    }
+
+                    if let Some(bits_per_key) = bloom_filter_bits_per_key {
+                        block_opts.set_bloom_filter(bits_per_key, false);
+                    }
+
+                    opts.set_block_based_table_factory(&block_opts);
+                }
+
+                if prefixed {
+                    opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(OWNER_PREFIX_LEN));
+                    opts.set_memtable_prefix_bloom_ratio(MEMTABLE_PREFIX_BLOOM_RATIO);
+                }
+
+                ColumnFamilyDescriptor::new(cf.name(), opts)
+            })
+            .collect()
+    }
+
+    /// Gets the options to write transactions with.
+    pub(crate) fn to_write_options(&self) -> WriteOptions {
+        let mut opts = WriteOptions::default();
+        opts.set_sync(self.sync_writes);
+        opts.disable_wal(!self.wal_enabled);
+        opts
+    }
+
+    /// Gets the options for the database as a whole.
+    pub(crate) fn to_rocksdb_options(&self) -> Options {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        opts.set_compaction_style(match self.compaction_style {
+            CompactionStyle::Level => DBCompactionStyle::Level,
+            CompactionStyle::Universal => DBCompactionStyle::Universal,
+            CompactionStyle::Fifo => DBCompactionStyle::Fifo,
+        });
+        opts.set_write_buffer_size(self.write_buffer_size);
+        opts.set_max_write_buffer_number(self.max_write_buffer_number);
+        opts.set_target_file_size_base(self.target_file_size_base);
+        opts.set_max_background_compactions(self.max_background_compactions);
+        opts.set_level_zero_file_num_compaction_trigger(self.level_zero_file_num_compaction_trigger);
+        opts.set_level_zero_slowdown_writes_trigger(self.level_zero_slowdown_writes_trigger);
+        opts.set_level_zero_stop_writes_trigger(self.level_zero_stop_writes_trigger);
+        opts.set_num_levels(self.num_levels);
+        opts.set_max_bytes_for_level_base(self.max_bytes_for_level_base);
+        opts.set_max_bytes_for_level_multiplier(self.max_bytes_for_level_multiplier);
+
+        if let Some(max_open_files) = self.max_open_files {
+            opts.set_max_open_files(max_open_files);
+        }
+
+        if let Some(compression) = self.compression {
+            opts.set_compression_type(compression.to_rocksdb());
+        }
+
+        if let Some(ref compression_per_level) = self.compression_per_level {
+            let compression_per_level: Vec<DBCompressionType> =
+                compression_per_level.iter().map(|c| c.to_rocksdb()).collect();
+            opts.set_compression_per_level(&compression_per_level);
+        }
+
+        opts.set_use_fsync(self.use_fsync);
+
+        if let Some(bytes_per_sync) = self.bytes_per_sync {
+            opts.set_bytes_per_sync(bytes_per_sync);
+        }
+
+        if self.bulk_load_optimized {
+            // Via https://github.com/facebook/rocksdb/wiki/RocksDB-FAQ
+            opts.set_allow_concurrent_memtable_write(false);
+            opts.set_memtable_factory(MemtableFactory::Vector);
+            opts.set_max_background_flushes(8);
+            opts.set_disable_auto_compactions(true);
+            opts.set_level_zero_file_num_compaction_trigger(1024);
+            opts.set_level_zero_slowdown_writes_trigger(1024 * 5);
+            opts.set_level_zero_stop_writes_trigger(1024 * 6);
+        }
+
+        opts
+    }
+}
@@ -11,3 +11,1269 @@ fn should_repair() {
     // Now try to repair
     RocksdbDatastore::repair(&path, Some(1)).unwrap();
 }
+
+#[test]
+fn should_read_from_snapshot() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, RangeVertexQuery, SpecificVertexQuery, Transaction, Type, Vertex};
+
+    let path = generate_temporary_path();
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    let reader = datastore.transaction().unwrap();
+    let writer = datastore.transaction().unwrap();
+    let vertex = Vertex::new(Type::new("foo").unwrap());
+    writer.create_vertex(&vertex).unwrap();
+
+    // The reader shouldn't see the vertex, since its snapshot was pinned
+    // before the vertex was created
+    let vertices = reader.get_vertices(SpecificVertexQuery::single(vertex.id)).unwrap();
+    assert_eq!(vertices.len(), 0);
+    let vertices = reader.get_vertices(RangeVertexQuery::new(u32::max_value())).unwrap();
+    assert_eq!(vertices.len(), 0);
+    assert_eq!(reader.get_vertex_count(None).unwrap(), 0);
+
+    // ... but the writer should, and so should new transactions once it's
+    // been committed
+    let vertices = writer.get_vertices(SpecificVertexQuery::single(vertex.id)).unwrap();
+    assert_eq!(vertices.len(), 1);
+    writer.commit().unwrap();
+    assert_eq!(reader.get_vertex_count(None).unwrap(), 0);
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 1);
+}
+
+#[test]
+fn should_keep_snapshot_across_writes() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, SpecificVertexQuery, Transaction, Type, Vertex};
+
+    let path = generate_temporary_path();
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    let t = Type::new("foo").unwrap();
+    let reader = datastore.transaction().unwrap();
+    let writer = datastore.transaction().unwrap();
+    let committed_v = Vertex::new(t.clone());
+    writer.create_vertex(&committed_v).unwrap();
+    writer.commit().unwrap();
+
+    // Writing through the reader shouldn't move its snapshot forward, so it
+    // still doesn't see the other transaction's vertex
+    let own_v = Vertex::new(t);
+    reader.create_vertex(&own_v).unwrap();
+    let vertices = reader
+        .get_vertices(SpecificVertexQuery::new(vec![committed_v.id, own_v.id]))
+        .unwrap();
+    assert_eq!(vertices.len(), 1);
+    assert_eq!(vertices[0].id, own_v.id);
+    assert_eq!(reader.get_vertex_count(None).unwrap(), 1);
+
+    // Deleting the other transaction's vertex is checked against the
+    // snapshot too, so it's a no-op
+    reader
+        .delete_vertices(SpecificVertexQuery::single(committed_v.id))
+        .unwrap();
+    reader.commit().unwrap();
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 2);
+}
+
+#[test]
+fn should_use_property_indexes() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{
+        Datastore, EdgeKey, EdgeQueryExt, PropertyFilter, PropertyPredicate, RangeVertexQuery, SpecificEdgeQuery,
+        SpecificVertexQuery, Transaction, Type, Vertex, VertexQueryExt,
+    };
+    use serde_json::json;
+
+    let path = generate_temporary_path();
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("user").unwrap();
+    let nl_v = Vertex::new(t.clone());
+    let us_v = Vertex::new(t.clone());
+    trans.create_vertex(&nl_v).unwrap();
+    trans.create_vertex(&us_v).unwrap();
+    let key = EdgeKey::new(nl_v.id, Type::new("follows").unwrap(), us_v.id);
+    trans.create_edge(&key).unwrap();
+    trans
+        .set_vertex_properties(SpecificVertexQuery::single(nl_v.id).property("country"), &json!("NL"))
+        .unwrap();
+    trans
+        .set_vertex_properties(SpecificVertexQuery::single(us_v.id).property("country"), &json!("US"))
+        .unwrap();
+    trans
+        .set_edge_properties(SpecificEdgeQuery::single(key.clone()).property("since"), &json!(2019))
+        .unwrap();
+
+    // Looking up an unindexed property should fail
+    assert!(trans.get_edges_by_indexed_property("since", &json!(2019)).is_err());
+
+    // Existing values should be backfilled
+    trans.commit().unwrap();
+    datastore.index_property("country").unwrap();
+    datastore.index_property("since").unwrap();
+    let trans = datastore.transaction().unwrap();
+    let q = RangeVertexQuery::new(10).filter(PropertyFilter::new("country", PropertyPredicate::Equals(json!("NL"))));
+    let vertices = trans.get_vertices(q.clone()).unwrap();
+    assert_eq!(vertices, vec![nl_v.clone()]);
+    let edges = trans.get_edges_by_indexed_property("since", &json!(2019)).unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key, key);
+
+    // Changed and deleted values should be reflected in the index
+    trans
+        .set_vertex_properties(SpecificVertexQuery::single(us_v.id).property("country"), &json!("NL"))
+        .unwrap();
+    trans
+        .set_vertex_properties(SpecificVertexQuery::single(nl_v.id).property("country"), &json!("BE"))
+        .unwrap();
+    trans
+        .delete_edge_properties(SpecificEdgeQuery::single(key.clone()).property("since"))
+        .unwrap();
+    let vertices = trans.get_vertices(q.clone()).unwrap();
+    assert_eq!(vertices, vec![us_v.clone()]);
+    assert_eq!(
+        trans
+            .get_edges_by_indexed_property("since", &json!(2019))
+            .unwrap()
+            .len(),
+        0
+    );
+
+    trans.delete_vertices(SpecificVertexQuery::single(us_v.id)).unwrap();
+    assert_eq!(trans.get_vertices(q).unwrap().len(), 0);
+}
+
+#[test]
+fn should_not_leave_stale_index_entries_for_uncommitted_values() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, EdgeKey, EdgeQueryExt, SpecificEdgeQuery, Transaction, Type, Vertex};
+    use serde_json::json;
+
+    let path = generate_temporary_path();
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    datastore.index_property("since").unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("user").unwrap();
+    let a = Vertex::new(t.clone());
+    let b = Vertex::new(t);
+    trans.create_vertex(&a).unwrap();
+    trans.create_vertex(&b).unwrap();
+    let ab = EdgeKey::new(a.id, Type::new("follows").unwrap(), b.id);
+    let ba = EdgeKey::new(b.id, Type::new("follows").unwrap(), a.id);
+    trans.create_edge(&ab).unwrap();
+    trans.create_edge(&ba).unwrap();
+
+    // Overwrite and delete values that were never committed, so the old
+    // values only exist in the transaction's pending writes
+    let ab_q = SpecificEdgeQuery::single(ab.clone()).property("since");
+    let ba_q = SpecificEdgeQuery::single(ba).property("since");
+    trans.set_edge_properties(ab_q.clone(), &json!(2019)).unwrap();
+    trans.set_edge_properties(ab_q, &json!(2020)).unwrap();
+    trans.set_edge_properties(ba_q.clone(), &json!(2019)).unwrap();
+    trans.delete_edge_properties(ba_q).unwrap();
+    assert_eq!(
+        trans
+            .get_edges_by_indexed_property("since", &json!(2019))
+            .unwrap()
+            .len(),
+        0
+    );
+    trans.commit().unwrap();
+
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(
+        trans
+            .get_edges_by_indexed_property("since", &json!(2019))
+            .unwrap()
+            .len(),
+        0
+    );
+    let edges = trans.get_edges_by_indexed_property("since", &json!(2020)).unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key, ab);
+}
+
+#[test]
+fn should_maintain_counters() {
+    use super::bytes::{build, Component};
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{
+        BulkInsertItem, Datastore, EdgeDirection, EdgeKey, SpecificEdgeQuery, SpecificVertexQuery, Transaction, Type,
+        Vertex,
+    };
+    use byteorder::{BigEndian, ByteOrder};
+
+    let path = generate_temporary_path();
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    let t = Type::new("foo").unwrap();
+    let edge_t = Type::new("bar").unwrap();
+    let a = Vertex::new(t.clone());
+    let b = Vertex::new(t.clone());
+    let c = Vertex::new(t.clone());
+    let ab = EdgeKey::new(a.id, edge_t.clone(), b.id);
+    let ba = EdgeKey::new(b.id, edge_t.clone(), a.id);
+    let ac = EdgeKey::new(a.id, edge_t.clone(), c.id);
+
+    // Duplicates within a bulk insert should only be counted once
+    datastore
+        .bulk_insert(
+            vec![
+                BulkInsertItem::Vertex(a.clone()),
+                BulkInsertItem::Vertex(b.clone()),
+                BulkInsertItem::Vertex(b.clone()),
+                BulkInsertItem::Vertex(c.clone()),
+                BulkInsertItem::Edge(ab.clone()),
+                BulkInsertItem::Edge(ab.clone()),
+                BulkInsertItem::Edge(ba.clone()),
+                BulkInsertItem::Edge(ac.clone()),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 3);
+    assert_eq!(trans.get_edge_count(a.id, None, EdgeDirection::Outbound).unwrap(), 2);
+    assert_eq!(
+        trans
+            .get_edge_count(a.id, Some(&edge_t), EdgeDirection::Inbound)
+            .unwrap(),
+        1
+    );
+    assert_eq!(trans.get_edge_count(b.id, Some(&t), EdgeDirection::Inbound).unwrap(), 0);
+
+    // Edges shared by deleted vertices should only be uncounted once, and
+    // rolling back should restore the counts
+    trans.delete_edges(SpecificEdgeQuery::single(ac.clone())).unwrap();
+    trans
+        .delete_vertices(SpecificVertexQuery::new(vec![a.id, b.id]))
+        .unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 1);
+    assert_eq!(trans.get_edge_count(a.id, None, EdgeDirection::Outbound).unwrap(), 0);
+    assert_eq!(trans.get_edge_count(c.id, None, EdgeDirection::Inbound).unwrap(), 0);
+    trans.rollback().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 3);
+    assert_eq!(trans.get_edge_count(a.id, None, EdgeDirection::Outbound).unwrap(), 2);
+    assert_eq!(trans.get_edge_count(c.id, None, EdgeDirection::Inbound).unwrap(), 1);
+    drop(trans);
+    drop(datastore);
+
+    // Counters should be recounted when migrating databases that don't
+    // have them yet
+    {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+        let mut db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+        db.drop_cf("counters:v1").unwrap();
+        let metadata = db.cf_handle("metadata:v1").unwrap();
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, 2);
+        db.put_cf(metadata, build(&[Component::UnsizedString("version")]), buf)
+            .unwrap();
+    }
+
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 3);
+    assert_eq!(trans.get_edge_count(a.id, None, EdgeDirection::Outbound).unwrap(), 2);
+    assert_eq!(
+        trans
+            .get_edge_count(b.id, Some(&edge_t), EdgeDirection::Inbound)
+            .unwrap(),
+        1
+    );
+}
+
+#[test]
+fn should_check_integrity() {
+    use super::bytes::{build, Component};
+    use super::{Counter, IntegrityIssue, RocksdbDatastore};
+    use crate::util::generate_temporary_path;
+    use crate::{
+        Datastore, EdgeDirection, EdgeKey, EdgeQueryExt, SpecificEdgeQuery, SpecificVertexQuery, Transaction, Type,
+        VertexQueryExt,
+    };
+    use serde_json::json;
+
+    let path = generate_temporary_path();
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let a = trans.create_vertex_from_type(t.clone()).unwrap();
+    let b = trans.create_vertex_from_type(t.clone()).unwrap();
+    let c = trans.create_vertex_from_type(t.clone()).unwrap();
+    let ab = EdgeKey::new(a, t.clone(), b);
+    let bc = EdgeKey::new(b, t.clone(), c);
+    trans.create_edge(&ab).unwrap();
+    trans.create_edge(&bc).unwrap();
+    trans
+        .set_vertex_properties(SpecificVertexQuery::single(c).property("name"), &json!("c"))
+        .unwrap();
+    trans
+        .set_edge_properties(SpecificEdgeQuery::single(bc.clone()).property("weight"), &json!(1))
+        .unwrap();
+    let ab_datetime = trans.get_edges(SpecificEdgeQuery::single(ab.clone())).unwrap()[0].created_datetime;
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+    drop(trans);
+    drop(datastore);
+
+    // Corrupt the database by deleting a vertex without cascading, deleting
+    // an edge range, and adding an edge range for an edge that doesn't exist
+    {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+        let db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+        let vertices = db.cf_handle("vertices:v1").unwrap();
+        db.delete_cf(vertices, build(&[Component::Uuid(c)])).unwrap();
+        let reversed_edge_ranges = db.cf_handle("reversed_edge_ranges:v1").unwrap();
+        let key = build(&[
+            Component::Uuid(b),
+            Component::Type(&t),
+            Component::DateTime(ab_datetime),
+            Component::Uuid(a),
+        ]);
+        db.delete_cf(reversed_edge_ranges, key).unwrap();
+        let edge_ranges = db.cf_handle("edge_ranges:v1").unwrap();
+        let key = build(&[
+            Component::Uuid(a),
+            Component::Type(&t),
+            Component::DateTime(ab_datetime),
+            Component::Uuid(c),
+        ]);
+        db.put_cf(edge_ranges, key, b"").unwrap();
+    }
+
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    let issues = datastore.check_integrity(false).unwrap();
+    assert_eq!(issues.len(), 6);
+    assert!(issues.contains(&IntegrityIssue::DanglingEdge { key: bc.clone() }));
+    assert!(issues.contains(&IntegrityIssue::MissingEdgeRange {
+        key: ab.clone(),
+        update_datetime: ab_datetime,
+        reversed: true,
+    }));
+    assert!(issues.contains(&IntegrityIssue::DanglingEdgeRange {
+        key: EdgeKey::new(a, t.clone(), c),
+        update_datetime: ab_datetime,
+        reversed: false,
+    }));
+    assert!(issues.contains(&IntegrityIssue::DanglingVertexProperty {
+        id: c,
+        name: "name".to_string(),
+    }));
+    assert!(issues.contains(&IntegrityIssue::WrongCounter {
+        counter: Counter::Vertices,
+        expected: 2,
+        actual: 3,
+    }));
+    assert!(issues.contains(&IntegrityIssue::WrongCounter {
+        counter: Counter::VerticesOfType(t.clone()),
+        expected: 2,
+        actual: 3,
+    }));
+
+    // Checking shouldn't have changed anything, but repairing should
+    assert_eq!(datastore.check_integrity(true).unwrap(), issues);
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 2);
+    assert_eq!(trans.get_edge_count(b, None, EdgeDirection::Outbound).unwrap(), 0);
+    let edges = trans.get_edges(SpecificVertexQuery::single(b).inbound(10)).unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key, ab);
+    let properties = trans
+        .get_edge_properties(SpecificEdgeQuery::single(bc).property("weight"))
+        .unwrap();
+    assert_eq!(properties.len(), 0);
+}
+
+#[test]
+fn should_check_property_indexes_and_counters() {
+    use super::bytes::{build, Component};
+    use super::{Counter, IntegrityIssue, RocksdbDatastore};
+    use crate::util::generate_temporary_path;
+    use crate::{
+        Datastore, EdgeDirection, EdgeKey, EdgeQueryExt, PropertyFilter, PropertyPredicate, RangeVertexQuery,
+        SpecificEdgeQuery, SpecificVertexQuery, Transaction, Type, VertexQueryExt,
+    };
+    use byteorder::{BigEndian, ByteOrder};
+    use serde_json::json;
+
+    let path = generate_temporary_path();
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    datastore.index_property("name").unwrap();
+    datastore.index_property("weight").unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let a = trans.create_vertex_from_type(t.clone()).unwrap();
+    let b = trans.create_vertex_from_type(t.clone()).unwrap();
+    let ab = EdgeKey::new(a, t.clone(), b);
+    trans.create_edge(&ab).unwrap();
+    trans
+        .set_vertex_properties(SpecificVertexQuery::single(a).property("name"), &json!("a"))
+        .unwrap();
+    trans
+        .set_edge_properties(SpecificEdgeQuery::single(ab.clone()).property("weight"), &json!(1))
+        .unwrap();
+
+    // Enough vertices to need more than one chunk of repairs
+    let many: Vec<_> = (0..1500)
+        .map(|_| trans.create_vertex_from_type(t.clone()).unwrap())
+        .collect();
+    trans
+        .set_vertex_properties(SpecificVertexQuery::new(many.clone()).property("name"), &json!("many"))
+        .unwrap();
+    trans.commit().unwrap();
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+    drop(datastore);
+
+    // Corrupt the database by deleting and adding property index entries,
+    // and by changing, adding and deleting counters
+    {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+        let db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+
+        let vertex_index = db.cf_handle("vertex_property_values:v1").unwrap();
+        let vertex_index_key = |name: &str, value: &str, id| {
+            build(&[
+                Component::SizedString(name),
+                Component::SizedString(value),
+                Component::Uuid(id),
+            ])
+        };
+        db.delete_cf(vertex_index, vertex_index_key("name", "\"a\"", a))
+            .unwrap();
+        db.put_cf(vertex_index, vertex_index_key("name", "\"b\"", b), b"")
+            .unwrap();
+        db.put_cf(vertex_index, vertex_index_key("other", "1", a), b"").unwrap();
+
+        for id in &many {
+            db.delete_cf(vertex_index, vertex_index_key("name", "\"many\"", *id))
+                .unwrap();
+        }
+
+        let edge_index = db.cf_handle("edge_property_values:v1").unwrap();
+        let edge_index_key = |value: &str| {
+            build(&[
+                Component::SizedString("weight"),
+                Component::SizedString(value),
+                Component::Uuid(a),
+                Component::Type(&t),
+                Component::Uuid(b),
+            ])
+        };
+        db.delete_cf(edge_index, edge_index_key("1")).unwrap();
+        db.put_cf(edge_index, edge_index_key("2"), b"").unwrap();
+
+        let counters = db.cf_handle("counters:v1").unwrap();
+        let mut value = [0; 8];
+        BigEndian::write_u64(&mut value, 5);
+        let key = build(&[Component::UnsizedString("o"), Component::Uuid(a), Component::Type(&t)]);
+        db.put_cf(counters, key, value).unwrap();
+        let key = build(&[Component::UnsizedString("i"), Component::Uuid(b), Component::Type(&t)]);
+        db.delete_cf(counters, key).unwrap();
+        let key = build(&[
+            Component::UnsizedString("t"),
+            Component::Type(&Type::new("bar").unwrap()),
+        ]);
+        db.put_cf(counters, key, value).unwrap();
+    }
+
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    let issues = datastore.check_integrity(false).unwrap();
+    assert_eq!(issues.len(), 1508);
+    assert!(issues.contains(&IntegrityIssue::MissingVertexPropertyIndex {
+        id: a,
+        name: "name".to_string(),
+        value: json!("a"),
+    }));
+    assert!(issues.contains(&IntegrityIssue::MissingVertexPropertyIndex {
+        id: many[0],
+        name: "name".to_string(),
+        value: json!("many"),
+    }));
+    assert!(issues.contains(&IntegrityIssue::DanglingVertexPropertyIndex {
+        id: b,
+        name: "name".to_string(),
+        value: json!("b"),
+    }));
+    assert!(issues.contains(&IntegrityIssue::DanglingVertexPropertyIndex {
+        id: a,
+        name: "other".to_string(),
+        value: json!(1),
+    }));
+    assert!(issues.contains(&IntegrityIssue::MissingEdgePropertyIndex {
+        key: ab.clone(),
+        name: "weight".to_string(),
+        value: json!(1),
+    }));
+    assert!(issues.contains(&IntegrityIssue::DanglingEdgePropertyIndex {
+        key: ab.clone(),
+        name: "weight".to_string(),
+        value: json!(2),
+    }));
+    assert!(issues.contains(&IntegrityIssue::WrongCounter {
+        counter: Counter::Edges {
+            id: a,
+            t: t.clone(),
+            direction: EdgeDirection::Outbound,
+        },
+        expected: 1,
+        actual: 5,
+    }));
+    assert!(issues.contains(&IntegrityIssue::WrongCounter {
+        counter: Counter::Edges {
+            id: b,
+            t: t.clone(),
+            direction: EdgeDirection::Inbound,
+        },
+        expected: 1,
+        actual: 0,
+    }));
+    assert!(issues.contains(&IntegrityIssue::WrongCounter {
+        counter: Counter::VerticesOfType(Type::new("bar").unwrap()),
+        expected: 0,
+        actual: 5,
+    }));
+
+    // Checking shouldn't have changed anything, but repairing should
+    assert_eq!(datastore.check_integrity(true).unwrap(), issues);
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+
+    let trans = datastore.transaction().unwrap();
+    let q = RangeVertexQuery::new(10).filter(PropertyFilter::new("name", PropertyPredicate::Equals(json!("a"))));
+    assert_eq!(trans.get_vertices(q).unwrap().len(), 1);
+    let q = RangeVertexQuery::new(10).filter(PropertyFilter::new("name", PropertyPredicate::Equals(json!("b"))));
+    assert_eq!(trans.get_vertices(q).unwrap().len(), 0);
+    let q = RangeVertexQuery::new(2000).filter(PropertyFilter::new("name", PropertyPredicate::Equals(json!("many"))));
+    assert_eq!(trans.get_vertices(q).unwrap().len(), 1500);
+    let edges = trans.get_edges_by_indexed_property("weight", &json!(1)).unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(
+        trans.get_edges_by_indexed_property("weight", &json!(2)).unwrap().len(),
+        0
+    );
+    assert_eq!(trans.get_edge_count(a, None, EdgeDirection::Outbound).unwrap(), 1);
+    assert_eq!(trans.get_edge_count(b, None, EdgeDirection::Inbound).unwrap(), 1);
+    assert_eq!(trans.get_vertex_count(Some(&Type::new("bar").unwrap())).unwrap(), 0);
+}
+
+#[test]
+fn should_version_the_database() {
+    use super::bytes::{build, Component};
+    use super::migrations::CURRENT_VERSION;
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::Error;
+    use byteorder::{BigEndian, ByteOrder};
+
+    fn get_version(path: &str) -> Option<u32> {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, path).unwrap();
+        let db = rocksdb::DB::open_cf(&opts, path, &cf_names).unwrap();
+        let cf = db.cf_handle("metadata:v1")?;
+        let value = db.get_cf(cf, build(&[Component::UnsizedString("version")])).unwrap()?;
+        Some(BigEndian::read_u32(&value))
+    }
+
+    // New databases should be at the current version
+    let path = generate_temporary_path();
+    drop(RocksdbDatastore::new(&path, Some(1), false).unwrap());
+    assert_eq!(get_version(&path), Some(CURRENT_VERSION));
+
+    // Databases that predate versioning should be migrated from the first
+    // version
+    {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+        let mut db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+        db.drop_cf("metadata:v1").unwrap();
+    }
+
+    assert_eq!(get_version(&path), None);
+    drop(RocksdbDatastore::new(&path, Some(1), false).unwrap());
+    assert_eq!(get_version(&path), Some(CURRENT_VERSION));
+
+    // Databases from future versions should be refused
+    {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+        let db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+        let cf = db.cf_handle("metadata:v1").unwrap();
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, CURRENT_VERSION + 1);
+        db.put_cf(cf, build(&[Component::UnsizedString("version")]), buf)
+            .unwrap();
+    }
+
+    match RocksdbDatastore::new(&path, Some(1), false) {
+        Err(Error::UnsupportedVersion { version }) => assert_eq!(version, CURRENT_VERSION + 1),
+        _ => panic!("expected an unsupported version error"),
+    }
+}
+
+#[test]
+fn should_encode_varints_in_sort_order() {
+    use super::bytes::{read_varint, write_varint};
+    use std::io::Cursor;
+
+    let values = [0, 1, 127, 128, 255, 256, 16_383, 16_384, 65_535, 0x1F_FFFF];
+    let mut last: Option<Vec<u8>> = None;
+
+    for value in &values {
+        let mut buf = Vec::new();
+        write_varint(&mut buf, *value).unwrap();
+        assert_eq!(read_varint(&mut Cursor::new(&buf)).unwrap(), *value);
+
+        if let Some(last) = last {
+            assert!(last < buf);
+        }
+
+        last = Some(buf);
+    }
+}
+
+#[test]
+fn should_migrate_long_types() {
+    use super::bytes::{build, Component};
+    use super::RocksdbDatastore;
+    use crate::util::{generate_temporary_path, generate_uuid_v1};
+    use crate::{Datastore, EdgeDirection, RangeVertexQuery, SpecificVertexQuery, Transaction, Type, VertexQueryExt};
+    use byteorder::{BigEndian, ByteOrder};
+    use chrono::offset::Utc;
+
+    let path = generate_temporary_path();
+    drop(RocksdbDatastore::new(&path, Some(1), false).unwrap());
+
+    // Write a vertex and an edge in the first version of the format, where
+    // types are prefixed by a single length byte
+    let t = Type::new("x".repeat(200)).unwrap();
+    let a = generate_uuid_v1();
+    let b = generate_uuid_v1();
+    let datetime = Utc::now();
+
+    {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+        let mut db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+        let mut old_t = vec![200u8];
+        old_t.extend(t.0.as_bytes());
+        let key = |parts: &[&[u8]]| parts.concat();
+
+        // Enough vertices to be migrated in more than one chunk
+        let vertices = db.cf_handle("vertices:v1").unwrap();
+        db.put_cf(vertices, a.as_bytes(), &old_t).unwrap();
+        db.put_cf(vertices, b.as_bytes(), &old_t).unwrap();
+
+        for _ in 0..1500 {
+            db.put_cf(vertices, generate_uuid_v1().as_bytes(), &old_t).unwrap();
+        }
+
+        let edges = db.cf_handle("edges:v1").unwrap();
+        let datetime_bytes = build(&[Component::DateTime(datetime)]);
+        db.put_cf(edges, key(&[a.as_bytes(), &old_t, b.as_bytes()]), &datetime_bytes)
+            .unwrap();
+        let edge_ranges = db.cf_handle("edge_ranges:v1").unwrap();
+        db.put_cf(
+            edge_ranges,
+            key(&[a.as_bytes(), &old_t, &datetime_bytes, b.as_bytes()]),
+            b"",
+        )
+        .unwrap();
+        let reversed_edge_ranges = db.cf_handle("reversed_edge_ranges:v1").unwrap();
+        db.put_cf(
+            reversed_edge_ranges,
+            key(&[b.as_bytes(), &old_t, &datetime_bytes, a.as_bytes()]),
+            b"",
+        )
+        .unwrap();
+
+        let metadata = db.cf_handle("metadata:v1").unwrap();
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, 1);
+        db.put_cf(metadata, build(&[Component::UnsizedString("version")]), buf)
+            .unwrap();
+        db.drop_cf("counters:v1").unwrap();
+    }
+
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+    let trans = datastore.transaction().unwrap();
+    let vertices = trans
+        .get_vertices(RangeVertexQuery::new(u32::max_value()).t(t.clone()))
+        .unwrap();
+    assert_eq!(vertices.len(), 1502);
+    let edges = trans
+        .get_edges(SpecificVertexQuery::single(b).inbound(10).t(t.clone()))
+        .unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key.outbound_id, a);
+    assert_eq!(trans.get_vertex_count(Some(&t)).unwrap(), 1502);
+    assert_eq!(trans.get_edge_count(a, Some(&t), EdgeDirection::Outbound).unwrap(), 1);
+}
+
+#[test]
+fn should_resume_interrupted_migration() {
+    use super::bytes::{build, write_varint, Component};
+    use super::RocksdbDatastore;
+    use crate::util::{generate_temporary_path, generate_uuid_v1};
+    use crate::{Datastore, EdgeDirection, SpecificVertexQuery, Transaction, Type, VertexQueryExt};
+    use byteorder::{BigEndian, ByteOrder};
+    use chrono::offset::Utc;
+
+    let path = generate_temporary_path();
+    drop(RocksdbDatastore::new(&path, Some(1), false).unwrap());
+    let t = Type::new("x".repeat(200)).unwrap();
+    let a = generate_uuid_v1();
+    let b = generate_uuid_v1();
+    let datetime = Utc::now();
+
+    // Leave the database as if the migration from the first version was
+    // interrupted while rewriting the edges: the vertices have been
+    // rewritten, the first edge has been moved to its new key but not
+    // written yet, and everything after it is still in the old format
+    {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+        let mut db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+        let mut old_t = vec![200u8];
+        old_t.extend(t.0.as_bytes());
+        let mut new_t = Vec::new();
+        write_varint(&mut new_t, 200).unwrap();
+        new_t.extend(t.0.as_bytes());
+        let key = |parts: &[&[u8]]| parts.concat();
+        let datetime_bytes = build(&[Component::DateTime(datetime)]);
+
+        let vertices = db.cf_handle("vertices:v1").unwrap();
+        db.put_cf(vertices, a.as_bytes(), &new_t).unwrap();
+        db.put_cf(vertices, b.as_bytes(), &new_t).unwrap();
+
+        let (first, second) = if a < b { ((a, b), (b, a)) } else { ((b, a), (a, b)) };
+        let edges = db.cf_handle("edges:v1").unwrap();
+        db.put_cf(
+            edges,
+            key(&[second.0.as_bytes(), &old_t, second.1.as_bytes()]),
+            &datetime_bytes,
+        )
+        .unwrap();
+
+        let edge_ranges = db.cf_handle("edge_ranges:v1").unwrap();
+        let reversed_edge_ranges = db.cf_handle("reversed_edge_ranges:v1").unwrap();
+
+        for (out_id, in_id) in &[first, second] {
+            db.put_cf(
+                edge_ranges,
+                key(&[out_id.as_bytes(), &old_t, &datetime_bytes, in_id.as_bytes()]),
+                b"",
+            )
+            .unwrap();
+            db.put_cf(
+                reversed_edge_ranges,
+                key(&[in_id.as_bytes(), &old_t, &datetime_bytes, out_id.as_bytes()]),
+                b"",
+            )
+            .unwrap();
+        }
+
+        let metadata = db.cf_handle("metadata:v1").unwrap();
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, 1);
+        db.put_cf(metadata, build(&[Component::UnsizedString("version")]), buf)
+            .unwrap();
+        let mut staged_key = b"migrated/".to_vec();
+        staged_key.extend(key(&[first.0.as_bytes(), &new_t, first.1.as_bytes()]));
+        db.put_cf(metadata, staged_key, &datetime_bytes).unwrap();
+        let mut progress = vec![0; 4];
+        BigEndian::write_u32(&mut progress, 2);
+        progress.extend(key(&[first.0.as_bytes(), &old_t, first.1.as_bytes()]));
+        db.put_cf(metadata, b"migration_progress", progress).unwrap();
+        db.drop_cf("counters:v1").unwrap();
+    }
+
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(Some(&t)).unwrap(), 2);
+
+    for (out_id, in_id) in &[(a, b), (b, a)] {
+        let edges = trans
+            .get_edges(SpecificVertexQuery::single(*in_id).inbound(10).t(t.clone()))
+            .unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].key.outbound_id, *out_id);
+        assert_eq!(
+            trans
+                .get_edge_count(*out_id, Some(&t), EdgeDirection::Outbound)
+                .unwrap(),
+            1
+        );
+    }
+
+    drop(trans);
+    drop(datastore);
+
+    // The progress and staged entries should be cleaned up
+    let opts = rocksdb::Options::default();
+    let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+    let db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+    let metadata = db.cf_handle("metadata:v1").unwrap();
+    assert!(db.get_cf(metadata, b"migration_progress").unwrap().is_none());
+    let keys: Vec<_> = db
+        .iterator_cf(metadata, rocksdb::IteratorMode::Start)
+        .unwrap()
+        .map(|(k, _)| k)
+        .collect();
+    assert!(!keys.iter().any(|k| k.starts_with(b"migrated/")));
+}
+
+#[test]
+fn should_reopen_named_graphs() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, NamedGraphs, Transaction, Type};
+
+    let path = generate_temporary_path();
+    let t = Type::new("foo").unwrap();
+
+    {
+        let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+        datastore.create_graph("tenant-a").unwrap();
+        let graph = datastore.graph("tenant-a").unwrap().unwrap();
+        graph.transaction().unwrap().create_vertex_from_type(t).unwrap();
+    }
+
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    assert_eq!(datastore.list_graphs().unwrap(), vec!["tenant-a".to_string()]);
+    assert_eq!(datastore.transaction().unwrap().get_vertex_count(None).unwrap(), 0);
+
+    let graph = datastore.graph("tenant-a").unwrap().unwrap();
+    assert_eq!(graph.transaction().unwrap().get_vertex_count(None).unwrap(), 1);
+}
+
+#[test]
+fn should_not_drop_graphs_in_use() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, Error, NamedGraphs};
+
+    let datastore = RocksdbDatastore::new(&generate_temporary_path(), Some(1), false).unwrap();
+    datastore.create_graph("tenant-a").unwrap();
+    let graph = datastore.graph("tenant-a").unwrap().unwrap();
+    let trans = graph.transaction().unwrap();
+    drop(graph);
+
+    match datastore.drop_graph("tenant-a") {
+        Err(Error::GraphInUse) => (),
+        other => panic!("expected a graph in use error, got {:?}", other),
+    }
+
+    drop(trans);
+    assert!(datastore.drop_graph("tenant-a").unwrap());
+    assert_eq!(datastore.list_graphs().unwrap(), Vec::<String>::new());
+}
+
+#[test]
+fn should_open_with_options() {
+    use super::{CompactionStyle, RocksdbDatastore, RocksdbOptions};
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, Transaction, Type, Vertex};
+
+    let path = generate_temporary_path();
+
+    let options = RocksdbOptions::default()
+        .max_open_files(Some(1))
+        .write_buffer_size(4_194_304)
+        .compaction_style(CompactionStyle::Universal)
+        .num_levels(2)
+        .block_cache_size(Some(8_388_608));
+
+    let datastore = RocksdbDatastore::new_with_options(&path, options.clone()).unwrap();
+    let vertex = Vertex::new(Type::new("foo").unwrap());
+    datastore.transaction().unwrap().create_vertex(&vertex).unwrap();
+    drop(datastore);
+
+    let datastore = RocksdbDatastore::new_with_options(&path, options).unwrap();
+    assert_eq!(datastore.transaction().unwrap().get_vertex_count(None).unwrap(), 1);
+}
+
+#[test]
+fn should_compact() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, EdgeDirection, EdgeKey, SpecificVertexQuery, Transaction, Type, Vertex};
+
+    let path = generate_temporary_path();
+    let datastore = RocksdbDatastore::new(&path, Some(1), true).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let outbound = Vertex::new(t.clone());
+    let inbound = Vertex::new(t.clone());
+    trans.create_vertex(&outbound).unwrap();
+    trans.create_vertex(&inbound).unwrap();
+    trans.create_edge(&EdgeKey::new(outbound.id, t, inbound.id)).unwrap();
+    trans.delete_vertices(SpecificVertexQuery::single(inbound.id)).unwrap();
+    trans.commit().unwrap();
+
+    datastore.compact();
+
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 1);
+    assert_eq!(
+        trans
+            .get_edge_count(outbound.id, None, EdgeDirection::Outbound)
+            .unwrap(),
+        0
+    );
+}
+
+#[test]
+fn should_query_with_prefix_extractors_and_bloom_filters() {
+    use super::{ColumnFamily, Compression, RocksdbDatastore, RocksdbOptions};
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, EdgeKey, EdgeQueryExt, SpecificVertexQuery, Transaction, Type, Vertex, VertexQueryExt};
+    use serde_json::Value as JsonValue;
+
+    let options = RocksdbOptions::default()
+        .max_open_files(Some(1))
+        .bloom_filter(ColumnFamily::Vertices, 10)
+        .bloom_filter(ColumnFamily::Edges, 10)
+        .compression_per_level(vec![
+            Compression::None,
+            Compression::None,
+            Compression::Lz4,
+            Compression::Zstd,
+        ])
+        .sync_writes(true);
+
+    let datastore = RocksdbDatastore::new_with_options(&generate_temporary_path(), options).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let vertices: Vec<Vertex> = (0..3).map(|_| Vertex::new(t.clone())).collect();
+
+    for vertex in &vertices {
+        trans.create_vertex(vertex).unwrap();
+    }
+
+    trans
+        .create_edge(&EdgeKey::new(vertices[0].id, t.clone(), vertices[1].id))
+        .unwrap();
+    trans
+        .create_edge(&EdgeKey::new(vertices[1].id, t.clone(), vertices[2].id))
+        .unwrap();
+    trans
+        .create_edge(&EdgeKey::new(vertices[2].id, t, vertices[0].id))
+        .unwrap();
+
+    // Edge ranges are only read from the queried vertex's prefix
+    let edges = trans
+        .get_edges(SpecificVertexQuery::single(vertices[1].id).outbound(10))
+        .unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key.inbound_id, vertices[2].id);
+    let edges = trans
+        .get_edges(SpecificVertexQuery::single(vertices[1].id).inbound(10))
+        .unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0].key.outbound_id, vertices[0].id);
+
+    // Properties are only read from the owner's prefix too
+    for vertex in &vertices {
+        trans
+            .set_vertex_properties(
+                SpecificVertexQuery::single(vertex.id).property("foo"),
+                &JsonValue::Bool(true),
+            )
+            .unwrap();
+    }
+    let properties = trans
+        .get_all_vertex_properties(SpecificVertexQuery::single(vertices[1].id))
+        .unwrap();
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].props.len(), 1);
+    trans
+        .set_edge_properties(
+            SpecificVertexQuery::single(vertices[1].id).outbound(10).property("bar"),
+            &JsonValue::Bool(true),
+        )
+        .unwrap();
+    let properties = trans
+        .get_edge_properties(
+            SpecificVertexQuery::new(vertices.iter().map(|v| v.id).collect())
+                .outbound(10)
+                .property("bar"),
+        )
+        .unwrap();
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].key.outbound_id, vertices[1].id);
+
+    // ... but full scans still see every key
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+}
+
+#[test]
+fn should_create_edge_with_datetime() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, Edge, EdgeKey, SpecificEdgeQuery, Transaction, Type, Vertex};
+    use chrono::{DateTime, Utc};
+    use uuid::Uuid;
+
+    let datastore = RocksdbDatastore::new(&generate_temporary_path(), Some(1), false).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let outbound_v = Vertex::new(t.clone());
+    let inbound_v = Vertex::new(t.clone());
+    trans.create_vertex(&outbound_v).unwrap();
+    trans.create_vertex(&inbound_v).unwrap();
+    let created_datetime = DateTime::parse_from_rfc3339("2000-01-01T12:34:56Z")
+        .unwrap()
+        .with_timezone(&Utc);
+
+    let key = EdgeKey::new(outbound_v.id, t.clone(), inbound_v.id);
+    assert!(trans.create_edge_with_datetime(&key, created_datetime).unwrap());
+    let edges = trans.get_edges(SpecificEdgeQuery::single(key.clone())).unwrap();
+    assert_eq!(edges, vec![Edge::new(key, created_datetime)]);
+
+    let missing_key = EdgeKey::new(outbound_v.id, t, Uuid::default());
+    assert!(!trans.create_edge_with_datetime(&missing_key, created_datetime).unwrap());
+}
+
+#[test]
+fn should_read_through_nested_batches() {
+    use super::batch::Batch;
+    use crate::util::generate_temporary_path;
+    use rocksdb::{Options, DB};
+    use std::sync::Mutex;
+
+    let mut opts = Options::default();
+    opts.create_if_missing(true);
+    opts.create_missing_column_families(true);
+    let db = DB::open_cf(&opts, generate_temporary_path(), ["vertices:v1"]).unwrap();
+    let write_lock = Mutex::new(());
+
+    let mut outer = Batch::new(&db, &write_lock);
+    outer.put_cf("vertices:v1", b"a", b"1").unwrap();
+    outer.put_cf("vertices:v1", b"b", b"1").unwrap();
+
+    let mut middle = Batch::staged(&db, outer.source());
+    middle.delete_cf("vertices:v1", b"a").unwrap();
+    middle.put_cf("vertices:v1", b"c", b"2").unwrap();
+
+    let mut inner = Batch::staged(&db, middle.source());
+    inner.put_cf("vertices:v1", b"c", b"3").unwrap();
+
+    assert_eq!(inner.get_cf("vertices:v1", b"a").unwrap(), None);
+    assert_eq!(inner.get_cf("vertices:v1", b"b").unwrap(), Some(b"1".to_vec()));
+    assert_eq!(inner.get_cf("vertices:v1", b"c").unwrap(), Some(b"3".to_vec()));
+    assert_eq!(middle.get_cf("vertices:v1", b"c").unwrap(), Some(b"2".to_vec()));
+}
+
+#[test]
+fn should_index_existing_values_in_chunks() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{
+        Datastore, PropertyFilter, PropertyPredicate, RangeVertexQuery, SpecificVertexQuery, Transaction, Type, Vertex,
+        VertexQueryExt,
+    };
+    use serde_json::json;
+
+    let datastore = RocksdbDatastore::new(&generate_temporary_path(), Some(1), false).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("user").unwrap();
+
+    // More values than are indexed per chunk
+    for i in 0..2500 {
+        let v = Vertex::new(t.clone());
+        trans.create_vertex(&v).unwrap();
+        trans
+            .set_vertex_properties(SpecificVertexQuery::single(v.id).property("parity"), &json!(i % 2))
+            .unwrap();
+    }
+
+    trans.commit().unwrap();
+    datastore.index_property("parity").unwrap();
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+
+    let trans = datastore.transaction().unwrap();
+    let q = RangeVertexQuery::new(5000).filter(PropertyFilter::new("parity", PropertyPredicate::Equals(json!(0))));
+    assert_eq!(trans.get_vertices(q).unwrap().len(), 1250);
+}
+
+#[test]
+fn should_skip_repairs_for_issues_that_are_gone() {
+    use super::batch::Batch;
+    use super::integrity::repair;
+    use super::{IntegrityIssue, RocksdbDatastore};
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, EdgeKey, SpecificEdgeQuery, SpecificVertexQuery, Transaction, Type, VertexQueryExt};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    let path = generate_temporary_path();
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let a = trans.create_vertex_from_type(t.clone()).unwrap();
+    let b = trans.create_vertex_from_type(t.clone()).unwrap();
+    let ab = EdgeKey::new(a, t, b);
+    trans.create_edge(&ab).unwrap();
+    trans
+        .set_vertex_properties(SpecificVertexQuery::single(a).property("name"), &json!("a"))
+        .unwrap();
+    trans.commit().unwrap();
+    drop(trans);
+    drop(datastore);
+
+    // Repair issues as if they'd been found before the vertices and edge
+    // were created
+    {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+        let db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+        let write_lock = Mutex::new(());
+        let mut batch = Batch::new(&db, &write_lock);
+        let issues = vec![
+            IntegrityIssue::DanglingEdge { key: ab.clone() },
+            IntegrityIssue::DanglingVertexProperty {
+                id: a,
+                name: "name".to_string(),
+            },
+        ];
+        repair(&db, &mut batch, &issues).unwrap();
+        batch.write().unwrap();
+    }
+
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_edges(SpecificEdgeQuery::single(ab.clone())).unwrap().len(), 1);
+    let properties = trans
+        .get_vertex_properties(SpecificVertexQuery::single(a).property("name"))
+        .unwrap();
+    assert_eq!(properties[0].value, json!("a"));
+}
+
+#[test]
+fn should_keep_iterating_after_the_snapshot_and_changes_are_replaced() {
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, RangeVertexQuery, Transaction, Type, Vertex};
+
+    let datastore = RocksdbDatastore::new(&generate_temporary_path(), Some(1), false).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let t = Type::new("foo").unwrap();
+    let committed_v = Vertex::new(t.clone());
+    trans.create_vertex(&committed_v).unwrap();
+    trans.commit().unwrap();
+    let staged_v = Vertex::new(t.clone());
+    trans.create_vertex(&staged_v).unwrap();
+
+    // The iterator holds the only references left to its snapshot and
+    // changes once the transaction has replaced both, and has to drop its
+    // inner iterator before them
+    let mut iter = trans.iter_vertices(RangeVertexQuery::new(10)).unwrap();
+    let first = iter.next().unwrap().unwrap();
+    trans.commit().unwrap();
+    trans.create_vertex(&Vertex::new(t)).unwrap();
+
+    let mut ids: Vec<_> = iter.map(|v| v.unwrap().id).collect();
+    ids.push(first.id);
+    ids.sort();
+    let mut expected = vec![committed_v.id, staged_v.id];
+    expected.sort();
+    assert_eq!(ids, expected);
+}
+
+#[test]
+fn should_resume_interrupted_counter_rebuild() {
+    use super::bytes::{build, Component};
+    use super::RocksdbDatastore;
+    use crate::util::generate_temporary_path;
+    use crate::{Datastore, EdgeDirection, EdgeKey, Transaction, Type, Vertex};
+    use byteorder::{BigEndian, ByteOrder};
+
+    let path = generate_temporary_path();
+    let t = Type::new("foo").unwrap();
+    let mut ids = Vec::new();
+
+    {
+        let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+        let trans = datastore.transaction().unwrap();
+
+        for _ in 0..4 {
+            let v = Vertex::new(t.clone());
+            trans.create_vertex(&v).unwrap();
+            ids.push(v.id);
+        }
+
+        trans.create_edge(&EdgeKey::new(ids[0], t.clone(), ids[1])).unwrap();
+        trans.create_edge(&EdgeKey::new(ids[0], t.clone(), ids[2])).unwrap();
+        trans.commit().unwrap();
+    }
+
+    let edges = [(ids[0], ids[1]), (ids[0], ids[2])];
+    ids.sort();
+
+    // Leave the database as if the migration from version 2 was interrupted
+    // after clearing the counters and counting the first two vertices
+    {
+        let opts = rocksdb::Options::default();
+        let cf_names = rocksdb::DB::list_cf(&opts, &path).unwrap();
+        let db = rocksdb::DB::open_cf(&opts, &path, &cf_names).unwrap();
+        let counters = db.cf_handle("counters:v1").unwrap();
+        let keys: Vec<_> = db
+            .iterator_cf(counters, rocksdb::IteratorMode::Start)
+            .unwrap()
+            .map(|(k, _)| k)
+            .collect();
+
+        for key in keys {
+            db.delete_cf(counters, key).unwrap();
+        }
+
+        let mut count = [0; 8];
+        BigEndian::write_u64(&mut count, 2);
+        db.put_cf(counters, build(&[Component::UnsizedString("v")]), count)
+            .unwrap();
+        db.put_cf(
+            counters,
+            build(&[Component::UnsizedString("t"), Component::Type(&t)]),
+            count,
+        )
+        .unwrap();
+
+        let metadata = db.cf_handle("metadata:v1").unwrap();
+        let mut buf = [0; 4];
+        BigEndian::write_u32(&mut buf, 2);
+        db.put_cf(metadata, build(&[Component::UnsizedString("version")]), buf)
+            .unwrap();
+        let mut progress = vec![0; 4];
+        BigEndian::write_u32(&mut progress, 1);
+        progress.extend(ids[1].as_bytes());
+        db.put_cf(metadata, b"migration_progress", progress).unwrap();
+    }
+
+    let datastore = RocksdbDatastore::new(&path, Some(1), false).unwrap();
+    assert_eq!(datastore.check_integrity(false).unwrap(), vec![]);
+    let trans = datastore.transaction().unwrap();
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 4);
+    assert_eq!(trans.get_vertex_count(Some(&t)).unwrap(), 4);
+    assert_eq!(
+        trans.get_edge_count(edges[0].0, None, EdgeDirection::Outbound).unwrap(),
+        2
+    );
+
+    for (_, in_id) in &edges {
+        assert_eq!(trans.get_edge_count(*in_id, None, EdgeDirection::Inbound).unwrap(), 1);
+    }
+}
@@ -0,0 +1,153 @@
+use super::super::{BulkInsertItem, Datastore, EdgeDirection, SpecificVertexQuery, Transaction, VertexQueryExt};
+use crate::algorithms::{degree_distribution, page_rank, run, triangle_counts, weakly_connected_components, Algorithm};
+use crate::models;
+use serde_json::json;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Creates the graph `a -> b -> c -> a`, plus `b -> a`, `c -> d`, `e -> f`
+/// and an isolated vertex `g`. Returns the IDs of `a` through `g`.
+fn create_graph<D: Datastore>(datastore: &mut D) -> [Uuid; 7] {
+    let trans = datastore.transaction().unwrap();
+    let vertex_t = models::Type::new("test_vertex_type").unwrap();
+    let mut ids = [Uuid::default(); 7];
+
+    for id in ids.iter_mut() {
+        *id = trans.create_vertex_from_type(vertex_t.clone()).unwrap();
+    }
+
+    let edge_t = models::Type::new("test_edge_type").unwrap();
+
+    for (outbound, inbound) in &[(0, 1), (1, 2), (2, 0), (1, 0), (2, 3), (4, 5)] {
+        let key = models::EdgeKey::new(ids[*outbound], edge_t.clone(), ids[*inbound]);
+        assert!(trans.create_edge(&key).unwrap());
+    }
+
+    ids
+}
+
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+}
+
+pub fn should_calculate_page_rank<D: Datastore>(datastore: &mut D) {
+    let [a, b, c, d, e, f, g] = create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+    let ranks = page_rank(&trans, None, 0.85, 50).unwrap();
+
+    assert_eq!(ranks.len(), 7);
+    assert_close(ranks.values().sum(), 1.0);
+
+    // Vertices without inbound edges only get the random jump share
+    assert_close(ranks[&e], ranks[&g]);
+    assert!(ranks[&f] > ranks[&e]);
+    assert!(ranks[&a] > ranks[&d]);
+    assert!(ranks[&b] > ranks[&c]);
+
+    let ranks = page_rank(
+        &trans,
+        Some(&models::Type::new("test_other_edge_type").unwrap()),
+        0.85,
+        50,
+    )
+    .unwrap();
+
+    for id in &[a, b, c, d, e, f, g] {
+        assert_close(ranks[id], 1.0 / 7.0);
+    }
+}
+
+pub fn should_calculate_page_rank_over_multiple_pages<D: Datastore>(datastore: &mut D) {
+    // Enough isolated vertices to span three pages, which should all get an
+    // equal share of the rank
+    let vertex_t = models::Type::new("test_vertex_type").unwrap();
+    let items = (0..2500).map(|_| BulkInsertItem::Vertex(models::Vertex::new(vertex_t.clone())));
+    datastore.bulk_insert(items).unwrap();
+    let trans = datastore.transaction().unwrap();
+    let ranks = page_rank(&trans, None, 0.85, 10).unwrap();
+
+    assert_eq!(ranks.len(), 2500);
+    assert_close(ranks.values().sum(), 1.0);
+
+    for rank in ranks.values() {
+        assert_close(*rank, 1.0 / 2500.0);
+    }
+}
+
+pub fn should_find_weakly_connected_components<D: Datastore>(datastore: &mut D) {
+    let [a, b, c, d, e, f, g] = create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+    let components = weakly_connected_components(&trans, None).unwrap();
+
+    assert_eq!(components.len(), 7);
+    let first = *[a, b, c, d].iter().min().unwrap();
+    let second = *[e, f].iter().min().unwrap();
+
+    for id in &[a, b, c, d] {
+        assert_eq!(components[id], first);
+    }
+
+    assert_eq!(components[&e], second);
+    assert_eq!(components[&f], second);
+    assert_eq!(components[&g], g);
+}
+
+pub fn should_count_triangles<D: Datastore>(datastore: &mut D) {
+    let [a, b, c, d, e, f, g] = create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+    let counts = triangle_counts(&trans, None).unwrap();
+
+    assert_eq!(counts.len(), 7);
+
+    for id in &[a, b, c] {
+        assert_eq!(counts[id], 1);
+    }
+
+    for id in &[d, e, f, g] {
+        assert_eq!(counts[id], 0);
+    }
+}
+
+pub fn should_get_degree_distribution<D: Datastore>(datastore: &mut D) {
+    create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+
+    let distribution = degree_distribution(&trans, None, EdgeDirection::Outbound).unwrap();
+    let expected: BTreeMap<u64, u64> = vec![(0, 3), (1, 2), (2, 2)].into_iter().collect();
+    assert_eq!(distribution, expected);
+
+    let distribution = degree_distribution(&trans, None, EdgeDirection::Inbound).unwrap();
+    let expected: BTreeMap<u64, u64> = vec![(0, 2), (1, 4), (2, 1)].into_iter().collect();
+    assert_eq!(distribution, expected);
+}
+
+pub fn should_run_algorithms<D: Datastore>(datastore: &mut D) {
+    let [a, _, _, _, _, _, g] = create_graph(datastore);
+
+    let count = run(datastore, &Algorithm::TriangleCount, None, "triangles").unwrap();
+    assert_eq!(count, 7);
+    let count = run(datastore, &Algorithm::WeaklyConnectedComponents, None, "component").unwrap();
+    assert_eq!(count, 7);
+
+    let trans = datastore.transaction().unwrap();
+    let triangles = trans
+        .get_vertex_properties(SpecificVertexQuery::single(a).property("triangles"))
+        .unwrap();
+    assert_eq!(triangles[0].value, json!(1));
+    let component = trans
+        .get_vertex_properties(SpecificVertexQuery::single(g).property("component"))
+        .unwrap();
+    assert_eq!(component[0].value, json!(g.to_string()));
+
+    let algorithm = Algorithm::PageRank {
+        damping_factor: 0.85,
+        iterations: 20,
+    };
+    let count = run(datastore, &algorithm, None, "rank").unwrap();
+    assert_eq!(count, 7);
+    let trans = datastore.transaction().unwrap();
+    let ranks = trans
+        .get_vertex_properties(SpecificVertexQuery::single(g).property("rank"))
+        .unwrap();
+    assert!(ranks[0].value.as_f64().unwrap() > 0.0);
+}
@@ -1,9 +1,9 @@
 use super::super::{
-    BulkInsertItem, Datastore, EdgeKey, EdgeQueryExt, SpecificEdgeQuery, SpecificVertexQuery, Transaction, Type,
+    BulkInsertItem, Datastore, Edge, EdgeKey, EdgeQueryExt, SpecificEdgeQuery, SpecificVertexQuery, Transaction, Type,
     Vertex, VertexQueryExt,
 };
 use chrono::offset::Utc;
-use chrono::Timelike;
+use chrono::{DateTime, Timelike};
 use serde_json::Value as JsonValue;
 
 pub fn should_bulk_insert<D: Datastore>(datastore: &mut D) {
@@ -86,6 +86,30 @@ pub fn should_bulk_insert<D: Datastore>(datastore: &mut D) {
     );
 }
 
+pub fn should_bulk_insert_an_edge_with_datetime<D: Datastore>(datastore: &mut D) {
+    let vertex_t = Type::new("test_vertex_type").unwrap();
+    let outbound_v = Vertex::new(vertex_t.clone());
+    let inbound_v = Vertex::new(vertex_t);
+    let key = EdgeKey::new(outbound_v.id, Type::new("test_edge_type").unwrap(), inbound_v.id);
+    // Use a whole second, since some implementations may not have
+    // sub-second accuracy.
+    let created_datetime = DateTime::parse_from_rfc3339("2000-01-01T12:34:56Z")
+        .unwrap()
+        .with_timezone(&Utc);
+
+    let items = vec![
+        BulkInsertItem::Vertex(outbound_v),
+        BulkInsertItem::Vertex(inbound_v),
+        BulkInsertItem::EdgeWithDatetime(Edge::new(key.clone(), created_datetime)),
+    ];
+
+    datastore.bulk_insert(items.into_iter()).unwrap();
+
+    let trans = datastore.transaction().unwrap();
+    let edges = trans.get_edges(SpecificEdgeQuery::single(key.clone())).unwrap();
+    assert_eq!(edges, vec![Edge::new(key, created_datetime)]);
+}
+
 // Bulk insert allows for redundant vertex insertion
 pub fn should_bulk_insert_a_redundant_vertex<D: Datastore>(datastore: &mut D) {
     let vertex_t = Type::new("test_vertex_type").unwrap();
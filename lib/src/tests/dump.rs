@@ -0,0 +1,99 @@
+use super::super::{
+    BulkInsertItem, Datastore, EdgeQueryExt, MemoryDatastore, RangeVertexQuery, SpecificEdgeQuery, SpecificVertexQuery,
+    Transaction, VertexQueryExt,
+};
+use super::util::create_edges;
+use crate::dump::{dump, restore};
+use crate::models;
+use serde_json::json;
+
+pub fn should_dump_and_restore<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, inbound_ids) = create_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    let key = models::EdgeKey::new(
+        outbound_id,
+        models::Type::new("test_edge_type").unwrap(),
+        inbound_ids[0],
+    );
+    trans
+        .set_vertex_properties(
+            SpecificVertexQuery::single(outbound_id).property("foo"),
+            &json!({"bar": [1, 2]}),
+        )
+        .unwrap();
+    trans
+        .set_edge_properties(SpecificEdgeQuery::single(key.clone()).property("baz"), &json!(true))
+        .unwrap();
+
+    let mut buf = Vec::new();
+    dump(&trans, &mut buf).unwrap();
+
+    // The dump is line-delimited: 6 vertices, 5 edges and 2 properties
+    assert_eq!(String::from_utf8(buf.clone()).unwrap().lines().count(), 13);
+
+    let restored = MemoryDatastore::default();
+    restore(&restored, &buf[..]).unwrap();
+    let restored_trans = restored.transaction().unwrap();
+
+    let mut vertices = trans.get_vertices(RangeVertexQuery::new(u32::MAX)).unwrap();
+    let mut restored_vertices = restored_trans.get_vertices(RangeVertexQuery::new(u32::MAX)).unwrap();
+    vertices.sort_by_key(|v| v.id);
+    restored_vertices.sort_by_key(|v| v.id);
+    assert_eq!(vertices, restored_vertices);
+
+    let q = SpecificVertexQuery::single(outbound_id).outbound(u32::MAX);
+    let mut edges = trans.get_edges(q.clone()).unwrap();
+    let mut restored_edges = restored_trans.get_edges(q).unwrap();
+    edges.sort_by(|a, b| a.key.cmp(&b.key));
+    restored_edges.sort_by(|a, b| a.key.cmp(&b.key));
+    assert_eq!(edges.len(), 5);
+    assert_eq!(edges, restored_edges);
+
+    let properties = restored_trans
+        .get_vertex_properties(SpecificVertexQuery::single(outbound_id).property("foo"))
+        .unwrap();
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, json!({"bar": [1, 2]}));
+
+    let properties = restored_trans
+        .get_edge_properties(SpecificEdgeQuery::single(key).property("baz"))
+        .unwrap();
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties[0].value, json!(true));
+}
+
+pub fn should_dump_and_restore_multiple_pages<D: Datastore>(datastore: &mut D) {
+    // Enough vertices to span three pages, linked into a chain by edges
+    let t = models::Type::new("test_vertex_type").unwrap();
+    let vertices: Vec<models::Vertex> = (0..2500).map(|_| models::Vertex::new(t.clone())).collect();
+    let mut items: Vec<BulkInsertItem> = vertices.iter().cloned().map(BulkInsertItem::Vertex).collect();
+
+    for pair in vertices.windows(2) {
+        let key = models::EdgeKey::new(pair[0].id, t.clone(), pair[1].id);
+        items.push(BulkInsertItem::Edge(key));
+    }
+
+    datastore.bulk_insert(items.into_iter()).unwrap();
+    let trans = datastore.transaction().unwrap();
+
+    let mut buf = Vec::new();
+    dump(&trans, &mut buf).unwrap();
+
+    // Each vertex and edge should be dumped exactly once
+    assert_eq!(String::from_utf8(buf.clone()).unwrap().lines().count(), 2500 + 2499);
+
+    let restored = MemoryDatastore::default();
+    restore(&restored, &buf[..]).unwrap();
+    let restored_trans = restored.transaction().unwrap();
+    assert_eq!(restored_trans.get_vertex_count(None).unwrap(), 2500);
+    let edge_count = restored_trans
+        .get_edges(RangeVertexQuery::new(u32::MAX).outbound(u32::MAX))
+        .unwrap()
+        .len();
+    assert_eq!(edge_count, 2499);
+}
+
+pub fn should_not_restore_an_invalid_dump<D: Datastore>(datastore: &mut D) {
+    let result = restore(datastore, &b"{\"type\":\"vertex\",\"id\":\"foo\"}\n"[..]);
+    assert!(result.is_err());
+}
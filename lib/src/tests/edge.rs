@@ -1,13 +1,16 @@
 use super::super::{
-    Datastore, EdgeDirection, EdgeKey, EdgeQueryExt, SpecificEdgeQuery, SpecificVertexQuery, Transaction,
-    VertexQueryExt,
+    Datastore, EdgeCursor, EdgeDirection, EdgeKey, EdgeQueryExt, PropertyFilter, PropertyPredicate, RangeVertexQuery,
+    SpecificEdgeQuery, SpecificVertexQuery, Transaction, VertexQueryExt,
 };
 use super::util::{create_edge_from, create_edges, create_time_range_queryable_edges};
 use crate::models;
 use chrono::offset::Utc;
 use chrono::Timelike;
+use serde_json::json;
 use serde_json::Value as JsonValue;
 use std::collections::HashSet;
+use std::thread;
+use std::time::Duration;
 use uuid::Uuid;
 
 pub fn should_get_a_valid_edge<D: Datastore>(datastore: &mut D) {
@@ -331,6 +334,194 @@ pub fn should_get_edges_piped<D: Datastore>(datastore: &mut D) {
     );
 }
 
+pub fn should_iter_edges<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, _, _, _) = create_time_range_queryable_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    let q = SpecificVertexQuery::single(outbound_id).outbound(u32::MAX);
+
+    let expected = trans.get_edges(q.clone()).unwrap();
+    assert_eq!(expected.len(), 15);
+
+    let edges: Vec<models::Edge> = trans.iter_edges(q.clone()).unwrap().map(|e| e.unwrap()).collect();
+    assert_eq!(edges, expected);
+
+    let edges: Vec<models::Edge> = trans.iter_edges(q).unwrap().take(3).map(|e| e.unwrap()).collect();
+    assert_eq!(&edges[..], &expected[..3]);
+}
+
+pub fn should_get_edges_with_property_filter<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, inbound_ids) = create_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    let t = models::Type::new("test_edge_type").unwrap();
+
+    for (i, inbound_id) in inbound_ids.iter().enumerate() {
+        let key = EdgeKey::new(outbound_id, t.clone(), *inbound_id);
+        trans
+            .set_edge_properties(SpecificEdgeQuery::single(key).property("weight"), &json!(i))
+            .unwrap();
+    }
+
+    let filter = PropertyFilter::new("weight", PropertyPredicate::LessThan(json!(2)));
+    let range = trans
+        .get_edges(
+            SpecificVertexQuery::single(outbound_id)
+                .outbound(10)
+                .filter(filter.clone()),
+        )
+        .unwrap();
+    let mut ids: Vec<Uuid> = range.iter().map(|e| e.key.inbound_id).collect();
+    ids.sort();
+    let mut expected_ids = vec![inbound_ids[0], inbound_ids[1]];
+    expected_ids.sort();
+    assert_eq!(ids, expected_ids);
+
+    // Filters should apply to inbound edges as well
+    let range = trans
+        .get_edges(SpecificVertexQuery::single(inbound_ids[0]).inbound(10).filter(filter))
+        .unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].key, EdgeKey::new(outbound_id, t, inbound_ids[0]));
+}
+
+pub fn should_page_through_edges_with_cursor<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, inbound_ids) = create_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    let t = models::Type::new("test_edge_type").unwrap();
+
+    let outbound_pages = page_through_edges(&trans, SpecificVertexQuery::single(outbound_id).outbound(2));
+    let inbound_pages = page_through_edges(&trans, SpecificVertexQuery::new(inbound_ids.to_vec()).inbound(2));
+    let typed_pages = page_through_edges(&trans, SpecificVertexQuery::single(outbound_id).outbound(2).t(t));
+
+    for pages in &[outbound_pages, inbound_pages, typed_pages] {
+        let lens: Vec<usize> = pages.iter().map(|page| page.len()).collect();
+        assert_eq!(lens, vec![2, 2, 1]);
+
+        let mut ids: Vec<Uuid> = pages.iter().flatten().map(|edge| edge.key.inbound_id).collect();
+        ids.sort();
+        let mut expected_ids = inbound_ids.to_vec();
+        expected_ids.sort();
+        assert_eq!(ids, expected_ids);
+    }
+}
+
+pub fn should_resume_cursor_after_deleted_vertex<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let outbound_vertex_t = models::Type::new("test_outbound_vertex_type").unwrap();
+    let mut outbound_ids = Vec::new();
+
+    for _ in 0..3 {
+        let outbound_v = models::Vertex::new(outbound_vertex_t.clone());
+        trans.create_vertex(&outbound_v).unwrap();
+        create_edge_from(&trans, outbound_v.id);
+        create_edge_from(&trans, outbound_v.id);
+        outbound_ids.push(outbound_v.id);
+    }
+
+    outbound_ids.sort();
+
+    // The first page ends part way through the second vertex's edges
+    let q = RangeVertexQuery::new(u32::MAX).t(outbound_vertex_t).outbound(3);
+    let page = trans.get_edges(q.clone()).unwrap();
+    assert_eq!(page.len(), 3);
+    let cursor = EdgeCursor::from(page.last().unwrap());
+    assert_eq!(cursor.key.outbound_id, outbound_ids[1]);
+
+    // Deleting the cursor's vertex should resume from the next one, rather
+    // than ending the paging early
+    trans
+        .delete_vertices(SpecificVertexQuery::single(outbound_ids[1]))
+        .unwrap();
+    let page = trans.get_edges(q.cursor(cursor)).unwrap();
+    assert_eq!(page.len(), 2);
+    assert!(page.iter().all(|edge| edge.key.outbound_id == outbound_ids[2]));
+}
+
+pub fn should_resume_unordered_cursor_by_vertex_id<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let outbound_vertex_t = models::Type::new("test_outbound_vertex_type").unwrap();
+    let mut outbound_ids = Vec::new();
+
+    for _ in 0..3 {
+        let outbound_v = models::Vertex::new(outbound_vertex_t.clone());
+        trans.create_vertex(&outbound_v).unwrap();
+        create_edge_from(&trans, outbound_v.id);
+        create_edge_from(&trans, outbound_v.id);
+        outbound_ids.push(outbound_v.id);
+    }
+
+    outbound_ids.sort();
+
+    // Edges are piped from vertices in order of ID, whatever order they're
+    // queried in
+    let mut query_ids = outbound_ids.clone();
+    query_ids.reverse();
+    let page = trans
+        .get_edges(SpecificVertexQuery::new(query_ids).outbound(3))
+        .unwrap();
+    let page_outbound_ids: Vec<Uuid> = page.iter().map(|edge| edge.key.outbound_id).collect();
+    assert_eq!(
+        page_outbound_ids,
+        vec![outbound_ids[0], outbound_ids[0], outbound_ids[1]]
+    );
+    let cursor = EdgeCursor::from(page.last().unwrap());
+
+    // The cursor's vertex still exists, but isn't piped from anymore, so
+    // this should resume from the next vertex that is
+    let q = SpecificVertexQuery::new(vec![outbound_ids[2], outbound_ids[0]]).outbound(3);
+    let page = trans.get_edges(q.cursor(cursor)).unwrap();
+    assert_eq!(page.len(), 2);
+    assert!(page.iter().all(|edge| edge.key.outbound_id == outbound_ids[2]));
+}
+
+pub fn should_order_piped_edges_by_type_then_newest_first<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let vertex_t = models::Type::new("test_vertex_type").unwrap();
+    let outbound_v = models::Vertex::new(vertex_t.clone());
+    trans.create_vertex(&outbound_v).unwrap();
+    let mut inbound_ids = Vec::new();
+
+    // Shorter type names come first, then edges are ordered newest first
+    for t in &["b", "a", "aa", "a"] {
+        let inbound_v = models::Vertex::new(vertex_t.clone());
+        trans.create_vertex(&inbound_v).unwrap();
+        let key = EdgeKey::new(outbound_v.id, models::Type::new(*t).unwrap(), inbound_v.id);
+        trans.create_edge(&key).unwrap();
+        inbound_ids.push(inbound_v.id);
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    let expected_ids = vec![inbound_ids[3], inbound_ids[1], inbound_ids[0], inbound_ids[2]];
+    let q = SpecificVertexQuery::single(outbound_v.id).outbound(10);
+    let ids: Vec<Uuid> = trans
+        .get_edges(q)
+        .unwrap()
+        .iter()
+        .map(|edge| edge.key.inbound_id)
+        .collect();
+    assert_eq!(ids, expected_ids);
+
+    let q = SpecificVertexQuery::single(outbound_v.id).outbound(1);
+    let ids: Vec<Uuid> = page_through_edges(&trans, q)
+        .iter()
+        .flatten()
+        .map(|edge| edge.key.inbound_id)
+        .collect();
+    assert_eq!(ids, expected_ids);
+}
+
+fn page_through_edges<T: Transaction>(trans: &T, q: models::PipeEdgeQuery) -> Vec<Vec<models::Edge>> {
+    let mut pages = Vec::new();
+    let mut page = trans.get_edges(q.clone()).unwrap();
+
+    while !page.is_empty() {
+        let cursor = EdgeCursor::from(page.last().unwrap());
+        pages.push(page);
+        page = trans.get_edges(q.clone().cursor(cursor)).unwrap();
+    }
+
+    pages
+}
+
 fn check_edge_range(range: &[models::Edge], expected_outbound_id: Uuid, expected_length: usize) {
     assert_eq!(range.len(), expected_length);
     let mut covered_ids: HashSet<Uuid> = HashSet::new();
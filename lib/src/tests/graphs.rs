@@ -0,0 +1,77 @@
+use super::super::{Error, NamedGraphs, Transaction};
+use crate::models;
+
+pub fn should_create_and_list_graphs<D: NamedGraphs>(datastore: &mut D) {
+    assert_eq!(datastore.list_graphs().unwrap(), Vec::<String>::new());
+    assert!(datastore.create_graph("tenant-b").unwrap());
+    assert!(datastore.create_graph("tenant-a").unwrap());
+    assert!(!datastore.create_graph("tenant-a").unwrap());
+    assert_eq!(
+        datastore.list_graphs().unwrap(),
+        vec!["tenant-a".to_string(), "tenant-b".to_string()]
+    );
+
+    assert!(datastore.graph("tenant-a").unwrap().is_some());
+    assert!(datastore.graph("tenant-c").unwrap().is_none());
+}
+
+pub fn should_not_create_graphs_with_invalid_names<D: NamedGraphs>(datastore: &mut D) {
+    let long_name = "a".repeat(65);
+
+    for name in &["", "../tenant", "tenant a", long_name.as_str()] {
+        match datastore.create_graph(name) {
+            Err(Error::InvalidGraphName) => (),
+            other => panic!("unexpected result for `{}`: {:?}", name, other),
+        }
+    }
+
+    assert_eq!(datastore.list_graphs().unwrap(), Vec::<String>::new());
+}
+
+pub fn should_isolate_graphs<D: NamedGraphs>(datastore: &mut D) {
+    let t = models::Type::new("test_vertex_type").unwrap();
+    datastore.create_graph("tenant-a").unwrap();
+    datastore.create_graph("tenant-b").unwrap();
+
+    let trans = datastore.transaction().unwrap();
+    let id = trans.create_vertex_from_type(t.clone()).unwrap();
+
+    {
+        let graph = datastore.graph("tenant-a").unwrap().unwrap();
+        let trans = graph.transaction().unwrap();
+        trans.create_vertex_from_type(t.clone()).unwrap();
+        trans.create_vertex_from_type(t.clone()).unwrap();
+        assert_eq!(trans.get_vertex_count(None).unwrap(), 2);
+        assert_eq!(
+            trans.get_vertices(models::SpecificVertexQuery::single(id)).unwrap(),
+            vec![]
+        );
+
+        // Graphs are looked up the same way from any graph
+        let sibling = graph.graph("tenant-b").unwrap().unwrap();
+        assert_eq!(sibling.transaction().unwrap().get_vertex_count(None).unwrap(), 0);
+    }
+
+    assert_eq!(trans.get_vertex_count(None).unwrap(), 1);
+}
+
+pub fn should_drop_graphs<D: NamedGraphs>(datastore: &mut D) {
+    let t = models::Type::new("test_vertex_type").unwrap();
+    datastore.create_graph("tenant-a").unwrap();
+    datastore.create_graph("tenant-b").unwrap();
+
+    {
+        let graph = datastore.graph("tenant-a").unwrap().unwrap();
+        graph.transaction().unwrap().create_vertex_from_type(t).unwrap();
+    }
+
+    assert!(datastore.drop_graph("tenant-a").unwrap());
+    assert!(!datastore.drop_graph("tenant-a").unwrap());
+    assert_eq!(datastore.list_graphs().unwrap(), vec!["tenant-b".to_string()]);
+    assert!(datastore.graph("tenant-a").unwrap().is_none());
+
+    // A graph created with the same name as a dropped one starts out empty
+    datastore.create_graph("tenant-a").unwrap();
+    let graph = datastore.graph("tenant-a").unwrap().unwrap();
+    assert_eq!(graph.transaction().unwrap().get_vertex_count(None).unwrap(), 0);
+}
@@ -0,0 +1,238 @@
+use super::super::{
+    BulkInsertItem, Datastore, EdgeQueryExt, MemoryDatastore, RangeVertexQuery, SpecificEdgeQuery, SpecificVertexQuery,
+    Transaction, VertexQueryExt,
+};
+use super::util::create_edges;
+use crate::interchange::csv::{export_csv, import_csv};
+use crate::interchange::graphml::{export_graphml, import_graphml};
+use crate::models;
+use serde_json::json;
+use uuid::Uuid;
+
+fn set_properties<T: Transaction>(trans: &T, outbound_id: Uuid, key: &models::EdgeKey) {
+    trans
+        .set_vertex_properties(SpecificVertexQuery::single(outbound_id).property("name"), &json!("123"))
+        .unwrap();
+    trans
+        .set_vertex_properties(
+            SpecificVertexQuery::single(outbound_id).property("foo"),
+            &json!({"bar": [1, 2]}),
+        )
+        .unwrap();
+    trans
+        .set_edge_properties(SpecificEdgeQuery::single(key.clone()).property("weight"), &json!(1.5))
+        .unwrap();
+    trans
+        .set_edge_properties(SpecificEdgeQuery::single(key.clone()).property("seen"), &json!(true))
+        .unwrap();
+}
+
+fn check_round_trip<T: Transaction, U: Transaction>(trans: &T, restored_trans: &U, outbound_id: Uuid) {
+    let mut vertices = trans.get_vertices(RangeVertexQuery::new(u32::MAX)).unwrap();
+    let mut restored_vertices = restored_trans.get_vertices(RangeVertexQuery::new(u32::MAX)).unwrap();
+    vertices.sort_by_key(|v| v.id);
+    restored_vertices.sort_by_key(|v| v.id);
+    assert_eq!(vertices, restored_vertices);
+
+    let q = SpecificVertexQuery::single(outbound_id).outbound(u32::MAX);
+    let mut keys: Vec<models::EdgeKey> = trans.get_edges(q.clone()).unwrap().into_iter().map(|e| e.key).collect();
+    let mut restored_keys: Vec<models::EdgeKey> = restored_trans
+        .get_edges(q)
+        .unwrap()
+        .into_iter()
+        .map(|e| e.key)
+        .collect();
+    keys.sort();
+    restored_keys.sort();
+    assert_eq!(keys.len(), 5);
+    assert_eq!(keys, restored_keys);
+
+    let q = SpecificVertexQuery::single(outbound_id);
+    let properties = trans.get_all_vertex_properties(q.clone()).unwrap();
+    let restored_properties = restored_trans.get_all_vertex_properties(q).unwrap();
+    assert_eq!(restored_properties.len(), 1);
+    assert_eq!(restored_properties[0].props.len(), 2);
+    assert_eq!(properties, restored_properties);
+
+    let q = SpecificVertexQuery::single(outbound_id).outbound(u32::MAX);
+    let mut properties: Vec<(models::EdgeKey, Vec<models::NamedProperty>)> = trans
+        .get_all_edge_properties(q.clone())
+        .unwrap()
+        .into_iter()
+        .map(|p| (p.edge.key, p.props))
+        .collect();
+    let mut restored_properties: Vec<(models::EdgeKey, Vec<models::NamedProperty>)> = restored_trans
+        .get_all_edge_properties(q)
+        .unwrap()
+        .into_iter()
+        .map(|p| (p.edge.key, p.props))
+        .collect();
+    properties.sort_by(|a, b| a.0.cmp(&b.0));
+    restored_properties.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(properties, restored_properties);
+}
+
+pub fn should_export_and_import_csv<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, inbound_ids) = create_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    let key = models::EdgeKey::new(
+        outbound_id,
+        models::Type::new("test_edge_type").unwrap(),
+        inbound_ids[0],
+    );
+    set_properties(&trans, outbound_id, &key);
+
+    let mut vertices_buf = Vec::new();
+    let mut edges_buf = Vec::new();
+    export_csv(&trans, &mut vertices_buf, &mut edges_buf).unwrap();
+
+    let vertices_csv = String::from_utf8(vertices_buf.clone()).unwrap();
+    assert!(vertices_csv.starts_with("id,label,foo,name\n"));
+    assert_eq!(vertices_csv.lines().count(), 7);
+    let edges_csv = String::from_utf8(edges_buf.clone()).unwrap();
+    assert!(edges_csv.starts_with("source,target,label,seen,weight\n"));
+    assert_eq!(edges_csv.lines().count(), 6);
+
+    let restored = MemoryDatastore::default();
+    import_csv(&restored, &vertices_buf[..], &edges_buf[..]).unwrap();
+    check_round_trip(&trans, &restored.transaction().unwrap(), outbound_id);
+}
+
+pub fn should_export_multiple_pages_of_csv<D: Datastore>(datastore: &mut D) {
+    // Enough vertices to span three pages, linked into a chain by edges
+    let t = models::Type::new("test_vertex_type").unwrap();
+    let vertices: Vec<models::Vertex> = (0..2500).map(|_| models::Vertex::new(t.clone())).collect();
+    let mut items: Vec<BulkInsertItem> = vertices.iter().cloned().map(BulkInsertItem::Vertex).collect();
+
+    for pair in vertices.windows(2) {
+        items.push(BulkInsertItem::Edge(models::EdgeKey::new(
+            pair[0].id,
+            t.clone(),
+            pair[1].id,
+        )));
+    }
+
+    datastore.bulk_insert(items.into_iter()).unwrap();
+    let trans = datastore.transaction().unwrap();
+
+    let mut vertices_buf = Vec::new();
+    let mut edges_buf = Vec::new();
+    export_csv(&trans, &mut vertices_buf, &mut edges_buf).unwrap();
+
+    // Each vertex and edge should be exported exactly once, after a header
+    let vertices_csv = String::from_utf8(vertices_buf.clone()).unwrap();
+    assert_eq!(vertices_csv.lines().count(), 1 + 2500);
+    let edges_csv = String::from_utf8(edges_buf.clone()).unwrap();
+    assert_eq!(edges_csv.lines().count(), 1 + 2499);
+
+    let restored = MemoryDatastore::default();
+    import_csv(&restored, &vertices_buf[..], &edges_buf[..]).unwrap();
+    assert_eq!(restored.transaction().unwrap().get_vertex_count(None).unwrap(), 2500);
+}
+
+pub fn should_import_csv_with_generated_ids<D: Datastore>(datastore: &mut D) {
+    let vertices = "id,label,name\na,person,alice\nb,person,\n";
+    let edges = "source,target,label,since\na,b,follows,2019\n";
+    import_csv(datastore, vertices.as_bytes(), edges.as_bytes()).unwrap();
+
+    let trans = datastore.transaction().unwrap();
+    let vertex_properties = trans
+        .get_all_vertex_properties(RangeVertexQuery::new(u32::MAX).t(models::Type::new("person").unwrap()))
+        .unwrap();
+    assert_eq!(vertex_properties.len(), 2);
+
+    let alice = vertex_properties.iter().find(|p| !p.props.is_empty()).unwrap();
+    assert_eq!(
+        alice.props,
+        vec![models::NamedProperty::new("name".to_string(), json!("alice"))]
+    );
+
+    let edge_properties = trans
+        .get_all_edge_properties(SpecificVertexQuery::single(alice.vertex.id).outbound(u32::MAX))
+        .unwrap();
+    assert_eq!(edge_properties.len(), 1);
+    assert_eq!(edge_properties[0].edge.key.t, models::Type::new("follows").unwrap());
+    assert_eq!(
+        edge_properties[0].props,
+        vec![models::NamedProperty::new("since".to_string(), json!(2019))]
+    );
+}
+
+pub fn should_not_import_csv_with_invalid_types<D: Datastore>(datastore: &mut D) {
+    let vertices = "id,label\na,not\ta type\n";
+    let result = import_csv(datastore, vertices.as_bytes(), "source,target,label\n".as_bytes());
+    assert!(result.is_err());
+}
+
+pub fn should_not_import_csv_with_unknown_vertices<D: Datastore>(datastore: &mut D) {
+    let vertices = "id,label\na,person\n";
+    let edges = "source,target,label\na,b,follows\n";
+    let result = import_csv(datastore, vertices.as_bytes(), edges.as_bytes());
+    assert!(result.is_err());
+}
+
+pub fn should_export_and_import_graphml<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, inbound_ids) = create_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    let key = models::EdgeKey::new(
+        outbound_id,
+        models::Type::new("test_edge_type").unwrap(),
+        inbound_ids[0],
+    );
+    set_properties(&trans, outbound_id, &key);
+
+    let mut buf = Vec::new();
+    export_graphml(&trans, &mut buf).unwrap();
+
+    let graphml = String::from_utf8(buf.clone()).unwrap();
+    assert!(graphml.contains(r#"<key id="e1" for="edge" attr.name="weight" attr.type="double" />"#));
+    assert_eq!(graphml.matches("<node ").count(), 6);
+    assert_eq!(graphml.matches("<edge ").count(), 5);
+
+    let restored = MemoryDatastore::default();
+    import_graphml(&restored, &buf[..], &models::Type::default()).unwrap();
+    check_round_trip(&trans, &restored.transaction().unwrap(), outbound_id);
+}
+
+pub fn should_import_graphml_with_default_types<D: Datastore>(datastore: &mut D) {
+    let graphml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+            <key id="d0" for="node" attr.name="age" attr.type="int"/>
+            <key id="d1" for="edge" attr.name="note" attr.type="string"/>
+            <graph edgedefault="directed">
+                <edge source="n0" target="n1"><data key="d1">hello</data></edge>
+                <node id="n0"><data key="d0">42</data></node>
+                <node id="n1"/>
+            </graph>
+        </graphml>"#;
+    let t = models::Type::new("imported").unwrap();
+    import_graphml(datastore, graphml.as_bytes(), &t).unwrap();
+
+    let trans = datastore.transaction().unwrap();
+    let vertex_properties = trans
+        .get_all_vertex_properties(RangeVertexQuery::new(u32::MAX).t(t.clone()))
+        .unwrap();
+    assert_eq!(vertex_properties.len(), 2);
+
+    let n0 = vertex_properties.iter().find(|p| !p.props.is_empty()).unwrap();
+    assert_eq!(n0.props, vec![models::NamedProperty::new("age".to_string(), json!(42))]);
+
+    let edge_properties = trans
+        .get_all_edge_properties(SpecificVertexQuery::single(n0.vertex.id).outbound(u32::MAX))
+        .unwrap();
+    assert_eq!(edge_properties.len(), 1);
+    assert_eq!(edge_properties[0].edge.key.t, t);
+    assert_eq!(
+        edge_properties[0].props,
+        vec![models::NamedProperty::new("note".to_string(), json!("hello"))]
+    );
+}
+
+pub fn should_not_import_graphml_with_invalid_values<D: Datastore>(datastore: &mut D) {
+    let graphml = r#"<graphml>
+            <key id="d0" for="node" attr.name="age" attr.type="int"/>
+            <graph><node id="n0"><data key="d0">forty-two</data></node></graph>
+        </graphml>"#;
+    let result = import_graphml(datastore, graphml.as_bytes(), &models::Type::new("imported").unwrap());
+    assert!(result.is_err());
+}
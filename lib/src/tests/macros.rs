@@ -16,6 +16,7 @@ macro_rules! full_test_impl {
     ($code:expr) => {
         // Bulk insert
         define_test!(should_bulk_insert, $code);
+        define_test!(should_bulk_insert_an_edge_with_datetime, $code);
         define_test!(should_bulk_insert_a_redundant_vertex, $code);
         define_test!(should_bulk_insert_an_invalid_edge, $code);
 
@@ -24,12 +25,20 @@ macro_rules! full_test_impl {
         define_test!(should_get_range_vertices, $code);
         define_test!(should_get_no_vertices_with_zero_limit, $code);
         define_test!(should_get_range_vertices_out_of_range, $code);
+        define_test!(should_get_range_vertices_from_inclusive_start_id, $code);
         define_test!(should_get_no_vertices_with_type_filter, $code);
         define_test!(should_get_single_vertex, $code);
         define_test!(should_get_single_vertex_nonexisting, $code);
         define_test!(should_get_vertices, $code);
         define_test!(should_get_vertices_piped, $code);
+        define_test!(should_get_range_vertices_with_property_filter, $code);
+        define_test!(should_get_piped_vertices_with_property_filter, $code);
+        define_test!(should_page_through_piped_vertices_with_cursor, $code);
+        define_test!(should_iter_vertices, $code);
         define_test!(should_get_a_vertex_count, $code);
+        define_test!(should_get_a_vertex_count_by_type, $code);
+        define_test!(should_get_a_vertex_count_for_query, $code);
+        define_test!(should_handle_long_and_unicode_types, $code);
         define_test!(should_delete_a_valid_outbound_vertex, $code);
         define_test!(should_delete_a_valid_inbound_vertex, $code);
         define_test!(should_not_delete_an_invalid_vertex, $code);
@@ -54,6 +63,30 @@ macro_rules! full_test_impl {
         define_test!(should_get_no_edges_for_reversed_time, $code);
         define_test!(should_get_edges, $code);
         define_test!(should_get_edges_piped, $code);
+        define_test!(should_iter_edges, $code);
+        define_test!(should_get_edges_with_property_filter, $code);
+        define_test!(should_page_through_edges_with_cursor, $code);
+        define_test!(should_resume_cursor_after_deleted_vertex, $code);
+        define_test!(should_resume_unordered_cursor_by_vertex_id, $code);
+        define_test!(should_order_piped_edges_by_type_then_newest_first, $code);
+
+        // Traversals
+        define_test!(should_traverse_outbound, $code);
+        define_test!(should_traverse_inbound, $code);
+        define_test!(should_traverse_with_depth_bounds, $code);
+        define_test!(should_traverse_with_limit, $code);
+        define_test!(should_not_traverse_from_missing_vertices, $code);
+        define_test!(should_find_shortest_path, $code);
+        define_test!(should_find_shortest_path_bidirectionally, $code);
+        define_test!(should_find_shortest_weighted_path, $code);
+
+        // Algorithms
+        define_test!(should_calculate_page_rank, $code);
+        define_test!(should_calculate_page_rank_over_multiple_pages, $code);
+        define_test!(should_find_weakly_connected_components, $code);
+        define_test!(should_count_triangles, $code);
+        define_test!(should_get_degree_distribution, $code);
+        define_test!(should_run_algorithms, $code);
 
         // Properties
         define_test!(should_handle_vertex_properties, $code);
@@ -64,5 +97,40 @@ macro_rules! full_test_impl {
         define_test!(should_not_set_invalid_edge_properties, $code);
         define_test!(should_not_delete_invalid_edge_properties, $code);
         define_test!(should_get_all_edge_properties, $code);
+
+        // Dump and restore
+        define_test!(should_dump_and_restore, $code);
+        define_test!(should_dump_and_restore_multiple_pages, $code);
+        define_test!(should_not_restore_an_invalid_dump, $code);
+
+        // Import and export
+        define_test!(should_export_and_import_csv, $code);
+        define_test!(should_export_multiple_pages_of_csv, $code);
+        define_test!(should_import_csv_with_generated_ids, $code);
+        define_test!(should_not_import_csv_with_invalid_types, $code);
+        define_test!(should_not_import_csv_with_unknown_vertices, $code);
+        define_test!(should_export_and_import_graphml, $code);
+        define_test!(should_import_graphml_with_default_types, $code);
+        define_test!(should_not_import_graphml_with_invalid_values, $code);
+
+        // Transactions
+        define_test!(should_rollback_created_items, $code);
+        define_test!(should_rollback_deleted_vertex, $code);
+        define_test!(should_rollback_property_changes, $code);
+        define_test!(should_not_rollback_committed_changes, $code);
+        define_test!(should_not_show_uncommitted_changes_to_other_transactions, $code);
+        define_test!(should_rollback_without_clobbering_other_transactions, $code);
+    };
+}
+
+/// Use this macro to enable the tests for datastores that implement
+/// `NamedGraphs`.
+#[macro_export]
+macro_rules! named_graphs_test_impl {
+    ($code:expr) => {
+        define_test!(should_create_and_list_graphs, $code);
+        define_test!(should_not_create_graphs_with_invalid_names, $code);
+        define_test!(should_isolate_graphs, $code);
+        define_test!(should_drop_graphs, $code);
     };
 }
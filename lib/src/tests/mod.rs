@@ -4,17 +4,29 @@
 //! `indradb` crate can reuse them. Generally you can use the convenience macro
 //! `full_test_impl`.
 
+mod algorithms;
 mod bulk_insert;
+mod dump;
 mod edge;
+mod graphs;
+mod interchange;
 #[macro_use]
 mod macros;
 mod properties;
+mod transaction;
+mod traversal;
 mod util;
 mod vertex;
 
+pub use self::algorithms::*;
 pub use self::bulk_insert::*;
+pub use self::dump::*;
 pub use self::edge::*;
+pub use self::graphs::*;
+pub use self::interchange::*;
 pub use self::macros::*;
 pub use self::properties::*;
+pub use self::transaction::*;
+pub use self::traversal::*;
 pub use self::util::*;
 pub use self::vertex::*;
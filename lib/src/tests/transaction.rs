@@ -0,0 +1,218 @@
+use super::super::{
+    Datastore, EdgeDirection, EdgeKey, EdgeQueryExt, SpecificEdgeQuery, SpecificVertexQuery, Transaction, Type, Vertex,
+    VertexQueryExt,
+};
+use super::util::create_edges;
+use serde_json::Value as JsonValue;
+
+pub fn should_rollback_created_items<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let vertex_t = Type::new("test_vertex_type").unwrap();
+    let outbound_v = Vertex::new(vertex_t.clone());
+    let inbound_v = Vertex::new(vertex_t);
+    trans.create_vertex(&outbound_v).unwrap();
+    trans.create_vertex(&inbound_v).unwrap();
+    let key = EdgeKey::new(outbound_v.id, Type::new("test_edge_type").unwrap(), inbound_v.id);
+    trans.create_edge(&key).unwrap();
+    trans
+        .set_vertex_properties(
+            SpecificVertexQuery::single(outbound_v.id).property("foo"),
+            &JsonValue::Bool(true),
+        )
+        .unwrap();
+    trans
+        .set_edge_properties(
+            SpecificEdgeQuery::single(key.clone()).property("bar"),
+            &JsonValue::Bool(true),
+        )
+        .unwrap();
+
+    trans.rollback().unwrap();
+
+    let vertices = trans
+        .get_vertices(SpecificVertexQuery::new(vec![outbound_v.id, inbound_v.id]))
+        .unwrap();
+    assert_eq!(vertices.len(), 0);
+    let edges = trans.get_edges(SpecificEdgeQuery::single(key.clone())).unwrap();
+    assert_eq!(edges.len(), 0);
+    let count = trans
+        .get_edge_count(inbound_v.id, None, EdgeDirection::Inbound)
+        .unwrap();
+    assert_eq!(count, 0);
+    let vertex_properties = trans
+        .get_vertex_properties(SpecificVertexQuery::single(outbound_v.id).property("foo"))
+        .unwrap();
+    assert_eq!(vertex_properties.len(), 0);
+    let edge_properties = trans
+        .get_edge_properties(SpecificEdgeQuery::single(key).property("bar"))
+        .unwrap();
+    assert_eq!(edge_properties.len(), 0);
+}
+
+pub fn should_rollback_deleted_vertex<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, inbound_ids) = create_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    let q = SpecificVertexQuery::single(outbound_id);
+    trans
+        .set_vertex_properties(q.clone().property("foo"), &JsonValue::Bool(true))
+        .unwrap();
+    trans
+        .set_edge_properties(q.clone().outbound(5).property("bar"), &JsonValue::Bool(true))
+        .unwrap();
+    trans.commit().unwrap();
+
+    let edges_before = trans.get_edges(q.clone().outbound(5)).unwrap();
+    trans.delete_vertices(q.clone()).unwrap();
+    assert_eq!(trans.get_vertices(q.clone()).unwrap().len(), 0);
+    trans.rollback().unwrap();
+
+    assert_eq!(trans.get_vertices(q.clone()).unwrap().len(), 1);
+    let edges_after = trans.get_edges(q.clone().outbound(5)).unwrap();
+    assert_eq!(edges_after, edges_before);
+
+    for inbound_id in inbound_ids.iter() {
+        let count = trans.get_edge_count(*inbound_id, None, EdgeDirection::Inbound).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    let vertex_properties = trans.get_vertex_properties(q.clone().property("foo")).unwrap();
+    assert_eq!(vertex_properties.len(), 1);
+    assert_eq!(vertex_properties[0].value, JsonValue::Bool(true));
+    let edge_properties = trans.get_edge_properties(q.outbound(5).property("bar")).unwrap();
+    assert_eq!(edge_properties.len(), 5);
+}
+
+pub fn should_rollback_property_changes<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, _) = create_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    let vertex_q = SpecificVertexQuery::single(outbound_id).property("foo");
+    let edge_q = SpecificVertexQuery::single(outbound_id).outbound(5).property("bar");
+    trans
+        .set_vertex_properties(vertex_q.clone(), &JsonValue::Bool(true))
+        .unwrap();
+    trans
+        .set_edge_properties(edge_q.clone(), &JsonValue::Bool(true))
+        .unwrap();
+    trans.commit().unwrap();
+
+    // Overwrite a property more than once, then delete the other, to make
+    // sure the originally committed values are the ones that get restored
+    trans
+        .set_vertex_properties(vertex_q.clone(), &JsonValue::Bool(false))
+        .unwrap();
+    trans.set_vertex_properties(vertex_q.clone(), &JsonValue::Null).unwrap();
+    trans.delete_edge_properties(edge_q.clone()).unwrap();
+    trans.rollback().unwrap();
+
+    let vertex_properties = trans.get_vertex_properties(vertex_q).unwrap();
+    assert_eq!(vertex_properties.len(), 1);
+    assert_eq!(vertex_properties[0].value, JsonValue::Bool(true));
+    let edge_properties = trans.get_edge_properties(edge_q).unwrap();
+    assert_eq!(edge_properties.len(), 5);
+
+    for edge_property in edge_properties {
+        assert_eq!(edge_property.value, JsonValue::Bool(true));
+    }
+}
+
+pub fn should_not_rollback_committed_changes<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let vertex_t = Type::new("test_vertex_type").unwrap();
+    let committed_v = Vertex::new(vertex_t.clone());
+    let uncommitted_v = Vertex::new(vertex_t);
+    trans.create_vertex(&committed_v).unwrap();
+    trans.commit().unwrap();
+    trans.create_vertex(&uncommitted_v).unwrap();
+    trans.rollback().unwrap();
+
+    let vertices = trans
+        .get_vertices(SpecificVertexQuery::new(vec![committed_v.id, uncommitted_v.id]))
+        .unwrap();
+    assert_eq!(vertices.len(), 1);
+    assert_eq!(vertices[0].id, committed_v.id);
+
+    // A second rollback should be a no-op
+    trans.rollback().unwrap();
+    let vertices = trans.get_vertices(SpecificVertexQuery::single(committed_v.id)).unwrap();
+    assert_eq!(vertices.len(), 1);
+}
+
+pub fn should_not_show_uncommitted_changes_to_other_transactions<D: Datastore>(datastore: &mut D) {
+    let writer = datastore.transaction().unwrap();
+    let v = Vertex::new(Type::new("test_vertex_type").unwrap());
+    writer.create_vertex(&v).unwrap();
+    writer
+        .set_vertex_properties(
+            SpecificVertexQuery::single(v.id).property("foo"),
+            &JsonValue::Bool(true),
+        )
+        .unwrap();
+
+    let reader = datastore.transaction().unwrap();
+    assert_eq!(reader.get_vertices(SpecificVertexQuery::single(v.id)).unwrap().len(), 0);
+    assert_eq!(reader.get_vertex_count(None).unwrap(), 0);
+
+    writer.commit().unwrap();
+
+    let reader = datastore.transaction().unwrap();
+    assert_eq!(reader.get_vertices(SpecificVertexQuery::single(v.id)).unwrap().len(), 1);
+    let vertex_properties = reader
+        .get_vertex_properties(SpecificVertexQuery::single(v.id).property("foo"))
+        .unwrap();
+    assert_eq!(vertex_properties.len(), 1);
+    assert_eq!(vertex_properties[0].value, JsonValue::Bool(true));
+}
+
+pub fn should_rollback_without_clobbering_other_transactions<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, inbound_ids) = create_edges(datastore);
+    let vertex_q = SpecificVertexQuery::single(outbound_id).property("foo");
+    let vertex_t = Type::new("test_vertex_type").unwrap();
+
+    let rolled_back = datastore.transaction().unwrap();
+    let committed = datastore.transaction().unwrap();
+
+    // Interleave writes to the same items from both transactions
+    let rolled_back_v = Vertex::new(vertex_t.clone());
+    rolled_back.create_vertex(&rolled_back_v).unwrap();
+    rolled_back
+        .set_vertex_properties(vertex_q.clone(), &JsonValue::from(1))
+        .unwrap();
+    let committed_v = Vertex::new(vertex_t);
+    committed.create_vertex(&committed_v).unwrap();
+    committed
+        .set_vertex_properties(vertex_q.clone(), &JsonValue::from(2))
+        .unwrap();
+    rolled_back
+        .delete_vertices(SpecificVertexQuery::single(inbound_ids[0]))
+        .unwrap();
+    committed
+        .create_edge(&EdgeKey::new(
+            committed_v.id,
+            Type::new("test_edge_type").unwrap(),
+            outbound_id,
+        ))
+        .unwrap();
+
+    committed.commit().unwrap();
+    rolled_back.rollback().unwrap();
+
+    let trans = datastore.transaction().unwrap();
+    let vertices = trans
+        .get_vertices(SpecificVertexQuery::new(vec![
+            rolled_back_v.id,
+            committed_v.id,
+            inbound_ids[0],
+        ]))
+        .unwrap();
+    assert_eq!(vertices.len(), 2);
+    assert!(vertices.iter().all(|vertex| vertex.id != rolled_back_v.id));
+    let vertex_properties = trans.get_vertex_properties(vertex_q).unwrap();
+    assert_eq!(vertex_properties.len(), 1);
+    assert_eq!(vertex_properties[0].value, JsonValue::from(2));
+    let count = trans.get_edge_count(outbound_id, None, EdgeDirection::Inbound).unwrap();
+    assert_eq!(count, 1);
+    let count = trans
+        .get_edge_count(outbound_id, None, EdgeDirection::Outbound)
+        .unwrap();
+    assert_eq!(count, 5);
+}
@@ -0,0 +1,168 @@
+use super::super::{
+    Datastore, EdgeDirection, EdgeQueryExt, ShortestPathAlgorithm, ShortestPathQuery, SpecificEdgeQuery,
+    SpecificVertexQuery, Transaction, VertexQueryExt,
+};
+use crate::models;
+use serde_json::json;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Creates the graph `a -> b -> c -> d -> a`, plus a shortcut `a -> c` and
+/// an edge of a different type `a -> e`. Returns the IDs of `a` through `e`.
+fn create_graph<D: Datastore>(datastore: &mut D) -> [Uuid; 5] {
+    let trans = datastore.transaction().unwrap();
+    let vertex_t = models::Type::new("test_vertex_type").unwrap();
+    let mut ids = [Uuid::default(); 5];
+
+    for id in ids.iter_mut() {
+        *id = trans.create_vertex_from_type(vertex_t.clone()).unwrap();
+    }
+
+    let edge_t = models::Type::new("test_edge_type").unwrap();
+
+    for (outbound, inbound) in &[(0, 1), (1, 2), (2, 3), (3, 0), (0, 2)] {
+        let key = models::EdgeKey::new(ids[*outbound], edge_t.clone(), ids[*inbound]);
+        assert!(trans.create_edge(&key).unwrap());
+    }
+
+    let key = models::EdgeKey::new(ids[0], models::Type::new("test_other_edge_type").unwrap(), ids[4]);
+    assert!(trans.create_edge(&key).unwrap());
+
+    ids
+}
+
+fn traversed_ids<T: Transaction>(trans: &T, q: models::TraversalQuery) -> Vec<Uuid> {
+    trans.traverse(q).unwrap().into_iter().map(|v| v.id).collect()
+}
+
+fn id_set(ids: &[Uuid]) -> HashSet<Uuid> {
+    ids.iter().cloned().collect()
+}
+
+pub fn should_traverse_outbound<D: Datastore>(datastore: &mut D) {
+    let [a, b, c, d, e] = create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+
+    let ids = traversed_ids(
+        &trans,
+        SpecificVertexQuery::single(a).traverse(EdgeDirection::Outbound, 1, u32::MAX),
+    );
+    assert_eq!(id_set(&ids), id_set(&[b, c, e]));
+
+    // `d` is two hops away, and `a` is only reachable via a cycle, so it's
+    // not returned again
+    let ids = traversed_ids(
+        &trans,
+        SpecificVertexQuery::single(a).traverse(EdgeDirection::Outbound, 10, u32::MAX),
+    );
+    assert_eq!(ids.len(), 4);
+    assert_eq!(id_set(&ids[..3]), id_set(&[b, c, e]));
+    assert_eq!(ids[3], d);
+}
+
+pub fn should_traverse_inbound<D: Datastore>(datastore: &mut D) {
+    let [a, b, c, d, _] = create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+
+    let ids = traversed_ids(
+        &trans,
+        SpecificVertexQuery::single(c).traverse(EdgeDirection::Inbound, 2, u32::MAX),
+    );
+    assert_eq!(id_set(&ids), id_set(&[a, b, d]));
+    assert_eq!(id_set(&ids[..2]), id_set(&[a, b]));
+}
+
+pub fn should_traverse_with_depth_bounds<D: Datastore>(datastore: &mut D) {
+    let [a, b, c, d, _] = create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+    let edge_t = models::Type::new("test_edge_type").unwrap();
+
+    let q = SpecificVertexQuery::single(a)
+        .traverse(EdgeDirection::Outbound, 2, u32::MAX)
+        .t(edge_t.clone())
+        .min_depth(2);
+    assert_eq!(traversed_ids(&trans, q), vec![d]);
+
+    let q = SpecificVertexQuery::single(a)
+        .traverse(EdgeDirection::Outbound, 1, u32::MAX)
+        .t(edge_t)
+        .min_depth(0);
+    let ids = traversed_ids(&trans, q);
+    assert_eq!(ids[0], a);
+    assert_eq!(id_set(&ids), id_set(&[a, b, c]));
+
+    let q = SpecificVertexQuery::single(a).traverse(EdgeDirection::Outbound, 0, u32::MAX);
+    assert!(traversed_ids(&trans, q).is_empty());
+}
+
+pub fn should_traverse_with_limit<D: Datastore>(datastore: &mut D) {
+    let [a, _, _, _, _] = create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+    let q = SpecificVertexQuery::single(a).traverse(EdgeDirection::Outbound, 10, 2);
+    assert_eq!(traversed_ids(&trans, q).len(), 2);
+}
+
+pub fn should_not_traverse_from_missing_vertices<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let q = SpecificVertexQuery::single(Uuid::default()).traverse(EdgeDirection::Outbound, 10, u32::MAX);
+    assert!(traversed_ids(&trans, q).is_empty());
+}
+
+fn edge_key(outbound_id: Uuid, inbound_id: Uuid) -> models::EdgeKey {
+    models::EdgeKey::new(outbound_id, models::Type::new("test_edge_type").unwrap(), inbound_id)
+}
+
+fn should_find_shortest_path_with_algorithm<D: Datastore>(datastore: &mut D, algorithm: ShortestPathAlgorithm) {
+    let [a, _, c, d, e] = create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+    let q = |start_id, end_id| ShortestPathQuery::new(start_id, end_id).algorithm(algorithm.clone());
+
+    assert_eq!(
+        trans.shortest_path(q(a, d)).unwrap(),
+        Some(vec![edge_key(a, c), edge_key(c, d)])
+    );
+    assert_eq!(
+        trans.shortest_path(q(d, c)).unwrap(),
+        Some(vec![edge_key(d, a), edge_key(a, c)])
+    );
+    assert_eq!(trans.shortest_path(q(a, a)).unwrap(), Some(vec![]));
+    assert_eq!(trans.shortest_path(q(e, a)).unwrap(), None);
+    assert_eq!(trans.shortest_path(q(a, Uuid::default())).unwrap(), None);
+    assert_eq!(trans.shortest_path(q(a, d).max_depth(1)).unwrap(), None);
+    assert_eq!(
+        trans
+            .shortest_path(q(a, e).t(models::Type::new("test_edge_type").unwrap()))
+            .unwrap(),
+        None
+    );
+}
+
+pub fn should_find_shortest_path<D: Datastore>(datastore: &mut D) {
+    should_find_shortest_path_with_algorithm(datastore, ShortestPathAlgorithm::BreadthFirst);
+}
+
+pub fn should_find_shortest_path_bidirectionally<D: Datastore>(datastore: &mut D) {
+    should_find_shortest_path_with_algorithm(datastore, ShortestPathAlgorithm::BidirectionalBreadthFirst);
+}
+
+pub fn should_find_shortest_weighted_path<D: Datastore>(datastore: &mut D) {
+    let [a, b, c, d, _] = create_graph(datastore);
+    let trans = datastore.transaction().unwrap();
+
+    for (outbound_id, inbound_id, weight) in &[(a, b, 1), (b, c, 1), (a, c, 5), (c, d, 1)] {
+        let q = SpecificEdgeQuery::single(edge_key(*outbound_id, *inbound_id)).property("weight");
+        trans.set_edge_properties(q, &json!(weight)).unwrap();
+    }
+
+    let algorithm = ShortestPathAlgorithm::Weighted("weight".to_string());
+    let path = trans
+        .shortest_path(ShortestPathQuery::new(a, d).algorithm(algorithm.clone()))
+        .unwrap();
+    assert_eq!(path, Some(vec![edge_key(a, b), edge_key(b, c), edge_key(c, d)]));
+
+    // The edge `d -> a` has no weight, so it isn't followed
+    let path = trans
+        .shortest_path(ShortestPathQuery::new(d, a).algorithm(algorithm))
+        .unwrap();
+    assert_eq!(path, None);
+}
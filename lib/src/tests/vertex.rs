@@ -1,6 +1,11 @@
-use super::super::{Datastore, EdgeQueryExt, RangeVertexQuery, SpecificVertexQuery, Transaction, VertexQueryExt};
+use super::super::{
+    Datastore, EdgeCursor, EdgeQueryExt, PropertyFilter, PropertyPredicate, RangeVertexQuery, SpecificVertexQuery,
+    Transaction, VertexQueryExt,
+};
 use super::util::{create_edge_from, create_edges};
 use crate::models;
+use crate::util::next_uuid;
+use serde_json::json;
 use serde_json::Value as JsonValue;
 use std::collections::HashSet;
 use std::u32;
@@ -51,6 +56,25 @@ pub fn should_get_range_vertices_out_of_range<D: Datastore>(datastore: &mut D) {
     assert_eq!(range.len(), 0);
 }
 
+pub fn should_get_range_vertices_from_inclusive_start_id<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let inserted_ids = create_vertices(&trans);
+
+    // The start ID itself is returned
+    let range = trans
+        .get_vertices(RangeVertexQuery::new(2).start_id(inserted_ids[1]))
+        .unwrap();
+    let ids: Vec<Uuid> = range.iter().map(|v| v.id).collect();
+    assert_eq!(ids, inserted_ids[1..3].to_vec());
+
+    // So the next page starts right after the last vertex
+    let range = trans
+        .get_vertices(RangeVertexQuery::new(2).start_id(next_uuid(inserted_ids[2]).unwrap()))
+        .unwrap();
+    let ids: Vec<Uuid> = range.iter().map(|v| v.id).collect();
+    assert_eq!(ids, inserted_ids[3..5].to_vec());
+}
+
 pub fn should_get_no_vertices_with_type_filter<D: Datastore>(datastore: &mut D) {
     let trans = datastore.transaction().unwrap();
     let type_filter = models::Type::new("foo").unwrap();
@@ -155,6 +179,91 @@ pub fn should_get_vertices_piped<D: Datastore>(datastore: &mut D) {
     assert_eq!(range[0], v);
 }
 
+pub fn should_get_range_vertices_with_property_filter<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let inserted_ids = create_vertices(&trans);
+
+    for (i, id) in inserted_ids.iter().enumerate() {
+        trans
+            .set_vertex_properties(SpecificVertexQuery::single(*id).property("age"), &json!(i))
+            .unwrap();
+    }
+
+    let filter = PropertyFilter::new("age", PropertyPredicate::GreaterThanOrEqual(json!(3)));
+    let range = trans
+        .get_vertices(RangeVertexQuery::new(u32::MAX).filter(filter.clone()))
+        .unwrap();
+    let ids: Vec<Uuid> = range.iter().map(|v| v.id).collect();
+    assert_eq!(ids, vec![inserted_ids[3], inserted_ids[4]]);
+
+    // The limit should apply to the filtered vertices
+    let range = trans
+        .get_vertices(RangeVertexQuery::new(1).filter(filter.clone()))
+        .unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].id, inserted_ids[3]);
+
+    // Vertices should have to match every filter
+    let range = trans
+        .get_vertices(
+            RangeVertexQuery::new(u32::MAX)
+                .filter(filter)
+                .filter(PropertyFilter::new("age", PropertyPredicate::Equals(json!(4)))),
+        )
+        .unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].id, inserted_ids[4]);
+
+    let range = trans
+        .get_vertices(RangeVertexQuery::new(u32::MAX).filter(PropertyFilter::new("foo", PropertyPredicate::Exists)))
+        .unwrap();
+    assert_eq!(range.len(), 0);
+}
+
+pub fn should_get_piped_vertices_with_property_filter<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, inbound_ids) = create_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    trans
+        .set_vertex_properties(
+            SpecificVertexQuery::single(inbound_ids[2]).property("country"),
+            &json!("NL"),
+        )
+        .unwrap();
+
+    let range = trans
+        .get_vertices(
+            SpecificVertexQuery::single(outbound_id)
+                .outbound(u32::MAX)
+                .inbound(u32::MAX)
+                .filter(PropertyFilter::new("country", PropertyPredicate::Equals(json!("NL")))),
+        )
+        .unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range[0].id, inbound_ids[2]);
+}
+
+pub fn should_page_through_piped_vertices_with_cursor<D: Datastore>(datastore: &mut D) {
+    let (outbound_id, inbound_ids) = create_edges(datastore);
+    let trans = datastore.transaction().unwrap();
+    let first_page = trans
+        .get_edges(SpecificVertexQuery::single(outbound_id).outbound(2))
+        .unwrap();
+    assert_eq!(first_page.len(), 2);
+
+    let cursor = EdgeCursor::from(&first_page[1]);
+    let q = SpecificVertexQuery::single(outbound_id)
+        .outbound(2)
+        .inbound(u32::MAX)
+        .cursor(cursor);
+    let second_page = trans.get_vertices(q).unwrap();
+    assert_eq!(second_page.len(), 2);
+
+    for vertex in second_page {
+        assert!(inbound_ids.contains(&vertex.id));
+        assert!(first_page.iter().all(|edge| edge.key.inbound_id != vertex.id));
+    }
+}
+
 pub fn should_delete_a_valid_outbound_vertex<D: Datastore>(datastore: &mut D) {
     let (outbound_id, _) = create_edges(datastore);
     let trans = datastore.transaction().unwrap();
@@ -194,15 +303,111 @@ pub fn should_not_delete_an_invalid_vertex<D: Datastore>(datastore: &mut D) {
         .unwrap();
 }
 
+pub fn should_iter_vertices<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let inserted_ids = create_vertices(&trans);
+
+    let q = SpecificVertexQuery::new(inserted_ids.clone());
+    let vertices: Vec<models::Vertex> = trans.iter_vertices(q).unwrap().map(|v| v.unwrap()).collect();
+    assert_eq!(vertices.len(), 5);
+    assert_eq!(vertices.iter().map(|v| v.id).collect::<Vec<Uuid>>(), inserted_ids);
+
+    let q = RangeVertexQuery::new(u32::MAX);
+    let expected = trans.get_vertices(q.clone()).unwrap();
+    let vertices: Vec<models::Vertex> = trans.iter_vertices(q).unwrap().map(|v| v.unwrap()).collect();
+    assert_eq!(vertices, expected);
+
+    // Iterators are lazy, so taking a few items shouldn't read the rest
+    let q = RangeVertexQuery::new(u32::MAX);
+    let vertices: Vec<models::Vertex> = trans.iter_vertices(q).unwrap().take(2).map(|v| v.unwrap()).collect();
+    assert_eq!(&vertices[..], &expected[..2]);
+}
+
 pub fn should_get_a_vertex_count<D: Datastore>(datastore: &mut D) {
     let trans = datastore.transaction().unwrap();
     let vertex_t = models::Type::new("test_vertex_type").unwrap();
     let v = models::Vertex::new(vertex_t);
     trans.create_vertex(&v).unwrap();
-    let count = trans.get_vertex_count().unwrap();
+    let count = trans.get_vertex_count(None).unwrap();
     assert!(count >= 1);
 }
 
+pub fn should_get_a_vertex_count_by_type<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let vertex_t = models::Type::new("test_counted_vertex_type").unwrap();
+    let other_t = models::Type::new("test_other_counted_vertex_type").unwrap();
+    trans.create_vertex_from_type(vertex_t.clone()).unwrap();
+    trans.create_vertex_from_type(vertex_t.clone()).unwrap();
+    let id = trans.create_vertex_from_type(other_t.clone()).unwrap();
+    assert_eq!(trans.get_vertex_count(Some(&vertex_t)).unwrap(), 2);
+    assert_eq!(trans.get_vertex_count(Some(&other_t)).unwrap(), 1);
+
+    trans.delete_vertices(SpecificVertexQuery::single(id)).unwrap();
+    assert_eq!(trans.get_vertex_count(Some(&vertex_t)).unwrap(), 2);
+    assert_eq!(trans.get_vertex_count(Some(&other_t)).unwrap(), 0);
+}
+
+pub fn should_get_a_vertex_count_for_query<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let vertex_t = models::Type::new("test_counted_vertex_type").unwrap();
+    let ids: Vec<Uuid> = (0..3)
+        .map(|_| trans.create_vertex_from_type(vertex_t.clone()).unwrap())
+        .collect();
+    let count = trans
+        .get_vertex_count_for_query(RangeVertexQuery::new(u32::MAX).t(vertex_t.clone()))
+        .unwrap();
+    assert_eq!(count, 3);
+    let count = trans
+        .get_vertex_count_for_query(RangeVertexQuery::new(2).t(vertex_t))
+        .unwrap();
+    assert_eq!(count, 2);
+    let count = trans
+        .get_vertex_count_for_query(SpecificVertexQuery::new(vec![ids[0], Uuid::default()]))
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+pub fn should_handle_long_and_unicode_types<D: Datastore>(datastore: &mut D) {
+    let trans = datastore.transaction().unwrap();
+    let long_t = models::Type::new("x".repeat(300)).unwrap();
+    let prefix_t = models::Type::new("x".repeat(299)).unwrap();
+    let unicode_t = models::Type::new("ns:Mitarbeiter_社員").unwrap();
+    let a = trans.create_vertex_from_type(long_t.clone()).unwrap();
+    let b = trans.create_vertex_from_type(unicode_t.clone()).unwrap();
+    trans.create_vertex_from_type(prefix_t.clone()).unwrap();
+    trans
+        .create_edge(&models::EdgeKey::new(a, unicode_t.clone(), b))
+        .unwrap();
+    trans.create_edge(&models::EdgeKey::new(a, long_t.clone(), b)).unwrap();
+    trans
+        .create_edge(&models::EdgeKey::new(a, prefix_t.clone(), b))
+        .unwrap();
+
+    let vertices = trans
+        .get_vertices(RangeVertexQuery::new(u32::MAX).t(long_t.clone()))
+        .unwrap();
+    assert_eq!(vertices.len(), 1);
+    assert_eq!(vertices[0].id, a);
+    assert_eq!(vertices[0].t, long_t);
+    let vertices = trans.get_vertices(SpecificVertexQuery::single(b)).unwrap();
+    assert_eq!(vertices[0].t, unicode_t);
+
+    // A type that's a prefix of another shouldn't match it
+    for t in &[&long_t, &prefix_t, &unicode_t] {
+        let edges = trans
+            .get_edges(SpecificVertexQuery::single(a).outbound(u32::MAX).t((*t).clone()))
+            .unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(&edges[0].key.t, *t);
+        assert_eq!(
+            trans
+                .get_edge_count(b, Some(t), models::EdgeDirection::Inbound)
+                .unwrap(),
+            1
+        );
+    }
+}
+
 fn create_vertices<T>(trans: &T) -> Vec<Uuid>
 where
     T: Transaction,
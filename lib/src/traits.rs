@@ -1,6 +1,8 @@
 use crate::errors::{Error, Result};
 use crate::models;
 use crate::models::{EdgeQueryExt, VertexQueryExt};
+use chrono::offset::Utc;
+use chrono::DateTime;
 use serde_json::value::Value as JsonValue;
 use std::vec::Vec;
 use uuid::Uuid;
@@ -18,6 +20,11 @@ pub trait Datastore {
 
     /// Bulk inserts many vertices, edges, and/or properties.
     ///
+    /// The default implementation inserts items one by one through a
+    /// transaction. `BulkInsertItem::EdgeWithDatetime` items go through
+    /// `Transaction::create_edge_with_datetime`, so they fail for datastores
+    /// that can't set an edge's creation datetime.
+    ///
     /// # Arguments
     /// * `items`: The items to insert.
     fn bulk_insert<I>(&self, items: I) -> Result<()>
@@ -34,6 +41,9 @@ pub trait Datastore {
                 models::BulkInsertItem::Edge(edge_key) => {
                     trans.create_edge(&edge_key)?;
                 }
+                models::BulkInsertItem::EdgeWithDatetime(edge) => {
+                    trans.create_edge_with_datetime(&edge.key, edge.created_datetime)?;
+                }
                 models::BulkInsertItem::VertexProperty(id, name, value) => {
                     let query = models::SpecificVertexQuery::single(id).property(name);
                     trans.set_vertex_properties(query, &value)?;
@@ -45,18 +55,66 @@ pub trait Datastore {
             }
         }
 
-        Ok(())
+        trans.commit()
     }
 }
 
+/// Specifies a datastore implementation that can hold separate, named graphs
+/// alongside its default one. Each named graph is itself a datastore, and
+/// shares no vertices, edges or properties with the default graph or with
+/// other named graphs.
+///
+/// Graph names live in a single namespace, so these methods behave the same
+/// regardless of which graph they're called on.
+///
+/// # Errors
+/// All methods return `Error::InvalidGraphName` if passed a name that isn't
+/// valid; see `util::validate_graph_name`.
+pub trait NamedGraphs: Datastore + Sized {
+    /// Creates a new, empty named graph. Returns whether the graph was
+    /// created - if this is false, it's because a graph with the same name
+    /// already exists.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the graph.
+    fn create_graph(&self, name: &str) -> Result<bool>;
+
+    /// Deletes a named graph, along with everything in it. Returns whether
+    /// the graph existed.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the graph.
+    fn drop_graph(&self, name: &str) -> Result<bool>;
+
+    /// Gets the names of all of the named graphs, in sorted order.
+    fn list_graphs(&self) -> Result<Vec<String>>;
+
+    /// Gets a named graph, or `None` if it doesn't exist.
+    ///
+    /// # Arguments
+    /// * `name`: The name of the graph.
+    fn graph(&self, name: &str) -> Result<Option<Self>>;
+}
+
 /// Specifies a transaction implementation, which are returned by datastores.
-/// All datastore manipulations are done through transactions. Despite the
-/// name, different datastore implementations carry different guarantees.
-/// Depending on the implementation, it may not be possible to rollback the
-/// changes on error. See the documentation of individual implementations for
-/// details. Transactions are automatically committed on drop. Transactions
-/// should be designed to not fail on commit; i.e. errors should occur when a
-/// method is actually called instead.
+/// All datastore manipulations are done through transactions.
+///
+/// Changes made through a transaction are only visible to that transaction
+/// until they're committed, at which point they're applied atomically.
+/// Different datastore implementations carry different guarantees beyond
+/// that, e.g. whether reads see changes committed by other transactions in
+/// the meantime. See the documentation of individual implementations for
+/// details.
+///
+/// Changes can be discarded with `rollback`, which drops everything done
+/// since the transaction was created, or since the last `commit` or
+/// `rollback`, without affecting other transactions.
+///
+/// Transactions are automatically committed on drop, but errors can't be
+/// returned from there; they're logged instead, and panic in debug builds.
+/// Call `commit` explicitly to handle them. Transactions should be designed
+/// to not fail on commit; i.e. errors should occur when a method is
+/// actually called instead.
 pub trait Transaction {
     /// Creates a new vertex. Returns whether the vertex was successfully
     /// created - if this is false, it's because a vertex with the same UUID
@@ -88,14 +146,53 @@ pub trait Transaction {
     /// * `q` - The query to run.
     fn get_vertices<Q: Into<models::VertexQuery>>(&self, q: Q) -> Result<Vec<models::Vertex>>;
 
+    /// Lazily iterates over the vertices specified by a query. Unlike
+    /// `get_vertices`, results are not buffered in memory, which makes this
+    /// suitable for walking over very large result sets. The default
+    /// implementation just wraps `get_vertices`.
+    ///
+    /// Datastores may hold locks for as long as the iterator is alive, so it
+    /// should be dropped before making changes through the same
+    /// transaction.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    fn iter_vertices<'a, Q: Into<models::VertexQuery>>(
+        &'a self,
+        q: Q,
+    ) -> Result<Box<dyn Iterator<Item = Result<models::Vertex>> + 'a>> {
+        let vertices = self.get_vertices(q)?;
+        Ok(Box::new(vertices.into_iter().map(Ok)))
+    }
+
     /// Deletes existing vertices specified by a query.
     ///
     /// # Arguments
     /// * `q` - The query to run.
     fn delete_vertices<Q: Into<models::VertexQuery>>(&self, q: Q) -> Result<()>;
 
-    /// Gets the number of vertices in the datastore..
-    fn get_vertex_count(&self) -> Result<u64>;
+    /// Gets the number of vertices in the datastore.
+    ///
+    /// # Arguments
+    /// * `t` - Only get the count for a specified vertex type.
+    fn get_vertex_count(&self, t: Option<&models::Type>) -> Result<u64>;
+
+    /// Gets the number of vertices specified by a query. Like
+    /// `get_vertices`, this is bounded by the query's limit. The default
+    /// implementation counts the results of `iter_vertices`.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    fn get_vertex_count_for_query<Q: Into<models::VertexQuery>>(&self, q: Q) -> Result<u64> {
+        let mut count = 0;
+
+        for vertex in self.iter_vertices(q)? {
+            vertex?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
 
     /// Creates a new edge. If the edge already exists, this will update it
     /// with a new update datetime. Returns whether the edge was successfully
@@ -106,12 +203,43 @@ pub trait Transaction {
     /// * `key`: The edge to create.
     fn create_edge(&self, key: &models::EdgeKey) -> Result<bool>;
 
+    /// Creates a new edge with a given creation datetime rather than the
+    /// current time, e.g. when restoring a dump. Otherwise, this behaves
+    /// like `create_edge`. The default implementation is for datastores
+    /// that can't set creation datetimes, and always returns
+    /// `Error::Unsupported`.
+    ///
+    /// # Arguments
+    /// * `key`: The edge to create.
+    /// * `datetime`: The edge's creation datetime.
+    fn create_edge_with_datetime(&self, _key: &models::EdgeKey, _datetime: DateTime<Utc>) -> Result<bool> {
+        Err(Error::Unsupported)
+    }
+
     /// Gets a range of edges specified by a query.
     ///
     /// # Arguments
     /// * `q` - The query to run.
     fn get_edges<Q: Into<models::EdgeQuery>>(&self, q: Q) -> Result<Vec<models::Edge>>;
 
+    /// Lazily iterates over the edges specified by a query. Unlike
+    /// `get_edges`, results are not buffered in memory. The default
+    /// implementation just wraps `get_edges`.
+    ///
+    /// Datastores may hold locks for as long as the iterator is alive, so it
+    /// should be dropped before making changes through the same
+    /// transaction.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    fn iter_edges<'a, Q: Into<models::EdgeQuery>>(
+        &'a self,
+        q: Q,
+    ) -> Result<Box<dyn Iterator<Item = Result<models::Edge>> + 'a>> {
+        let edges = self.get_edges(q)?;
+        Ok(Box::new(edges.into_iter().map(Ok)))
+    }
+
     /// Deletes a set of edges specified by a query.
     ///
     /// # Arguments
@@ -126,6 +254,22 @@ pub trait Transaction {
     /// * `direction`: The direction of edges to get.
     fn get_edge_count(&self, id: Uuid, t: Option<&models::Type>, direction: models::EdgeDirection) -> Result<u64>;
 
+    /// Gets the vertices reachable from a set of start vertices, via a
+    /// breadth-first traversal. Vertices are returned in order of depth,
+    /// each at most once.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    fn traverse(&self, q: models::TraversalQuery) -> Result<Vec<models::Vertex>>;
+
+    /// Finds the shortest directed path between two vertices. Returns the
+    /// edges along the path in order, or `None` if there is no path. The
+    /// path from a vertex to itself is empty.
+    ///
+    /// # Arguments
+    /// * `q` - The query to run.
+    fn shortest_path(&self, q: models::ShortestPathQuery) -> Result<Option<Vec<models::EdgeKey>>>;
+
     /// Gets vertex properties.
     ///
     /// # Arguments
@@ -167,6 +311,17 @@ pub trait Transaction {
     /// * `q` - The query to run.
     fn get_all_edge_properties<Q: Into<models::EdgeQuery>>(&self, q: Q) -> Result<Vec<models::EdgeProperties>>;
 
+    /// Gets the edges whose property is set to a given value, using the
+    /// property's index. The default implementation is for datastores that
+    /// don't support indexes, and always returns `Error::NotIndexed`.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the property, which must be indexed.
+    /// * `value` - The property value to look up.
+    fn get_edges_by_indexed_property(&self, _name: &str, _value: &JsonValue) -> Result<Vec<models::Edge>> {
+        Err(Error::NotIndexed)
+    }
+
     /// Sets edge properties.
     ///
     /// # Arguments
@@ -181,4 +336,14 @@ pub trait Transaction {
     /// * `q` - The query to run.
     /// * `name` - The property name.
     fn delete_edge_properties(&self, q: models::EdgePropertyQuery) -> Result<()>;
+
+    /// Commits the changes made so far, so that they can no longer be
+    /// rolled back. If committing fails, nothing is written, and the changes
+    /// are kept, so that the commit can be retried, or the changes rolled
+    /// back.
+    fn commit(&self) -> Result<()>;
+
+    /// Reverts all changes made since the transaction was created, or since
+    /// the last commit or rollback.
+    fn rollback(&self) -> Result<()>;
 }
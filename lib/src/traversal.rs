@@ -0,0 +1,335 @@
+//! Execution of traversal and shortest path queries, shared between
+//! datastore implementations. Datastores plug in their own adjacency and
+//! lookups by implementing `Graph`.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::errors::Result;
+use crate::{EdgeDirection, EdgeKey, ShortestPathAlgorithm, ShortestPathQuery, TraversalQuery, Type, Vertex};
+
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+/// The lookups that traversals are built on.
+pub trait Graph {
+    /// Gets the type of a vertex, or `None` if it doesn't exist.
+    fn vertex_type(&self, id: Uuid) -> Result<Option<Type>>;
+
+    /// Gets the keys of the edges adjacent to a set of vertices.
+    ///
+    /// # Arguments
+    /// * `ids` - The vertices.
+    /// * `direction` - Whether to get the outbound or inbound edges.
+    /// * `t` - Only gets edges of this type.
+    fn adjacent_edges(&self, ids: &[Uuid], direction: EdgeDirection, t: Option<&Type>) -> Result<Vec<EdgeKey>>;
+
+    /// Gets an edge property value, or `None` if it isn't set.
+    fn edge_property(&self, key: &EdgeKey, name: &str) -> Result<Option<JsonValue>>;
+}
+
+/// Gets the vertex at the end of an edge that's reached when following it
+/// in the given direction.
+fn far_end(key: &EdgeKey, direction: EdgeDirection) -> Uuid {
+    match direction {
+        EdgeDirection::Outbound => key.inbound_id,
+        EdgeDirection::Inbound => key.outbound_id,
+    }
+}
+
+/// Gets the vertex at the end of an edge that it's followed from.
+fn near_end(key: &EdgeKey, direction: EdgeDirection) -> Uuid {
+    match direction {
+        EdgeDirection::Outbound => key.outbound_id,
+        EdgeDirection::Inbound => key.inbound_id,
+    }
+}
+
+/// Walks the edges that a search followed to reach a vertex back to the
+/// search's root. Edges are returned in the order they were walked.
+fn walk_back(parents: &HashMap<Uuid, EdgeKey>, id: Uuid, direction: EdgeDirection) -> Vec<EdgeKey> {
+    let mut path = Vec::new();
+    let mut id = id;
+
+    while let Some(key) = parents.get(&id) {
+        id = near_end(key, direction);
+        path.push(key.clone());
+    }
+
+    path
+}
+
+/// Runs a breadth-first traversal.
+///
+/// # Arguments
+/// * `graph` - The graph to traverse.
+/// * `start` - The vertices to start from.
+/// * `q` - The traversal query.
+pub fn breadth_first<G: Graph>(graph: &G, start: Vec<(Uuid, Type)>, q: &TraversalQuery) -> Result<Vec<Vertex>> {
+    let limit = q.limit as usize;
+    let mut visited = HashSet::new();
+    let mut frontier = Vec::new();
+    let mut vertices = Vec::new();
+
+    for (id, t) in start {
+        if visited.insert(id) {
+            if q.min_depth == 0 {
+                vertices.push(Vertex::with_id(id, t));
+            }
+
+            frontier.push(id);
+        }
+    }
+
+    let mut depth = 0;
+
+    while depth < q.max_depth && !frontier.is_empty() && vertices.len() < limit {
+        depth += 1;
+        let mut next_frontier = Vec::new();
+
+        for key in graph.adjacent_edges(&frontier, q.direction, q.t.as_ref())? {
+            let id = far_end(&key, q.direction);
+
+            if !visited.insert(id) {
+                continue;
+            }
+
+            // Edges may reference vertices that don't exist, e.g. after a
+            // bulk insert, so those are skipped
+            if let Some(t) = graph.vertex_type(id)? {
+                if depth >= q.min_depth {
+                    vertices.push(Vertex::with_id(id, t));
+
+                    if vertices.len() >= limit {
+                        break;
+                    }
+                }
+
+                next_frontier.push(id);
+            }
+        }
+
+        frontier = next_frontier;
+    }
+
+    vertices.truncate(limit);
+    Ok(vertices)
+}
+
+/// One side of a breadth-first shortest path search.
+struct Search {
+    root: Uuid,
+    direction: EdgeDirection,
+    /// For each reached vertex, the edge it was reached through.
+    parents: HashMap<Uuid, EdgeKey>,
+    frontier: Vec<Uuid>,
+    depth: u32,
+}
+
+impl Search {
+    fn new(root: Uuid, direction: EdgeDirection) -> Self {
+        Self {
+            root,
+            direction,
+            parents: HashMap::new(),
+            frontier: vec![root],
+            depth: 0,
+        }
+    }
+
+    fn contains(&self, id: Uuid) -> bool {
+        id == self.root || self.parents.contains_key(&id)
+    }
+
+    /// Expands the search by one level. Returns the newly reached vertices.
+    fn expand<G: Graph>(&mut self, graph: &G, t: Option<&Type>) -> Result<Vec<Uuid>> {
+        let mut reached = Vec::new();
+
+        for key in graph.adjacent_edges(&self.frontier, self.direction, t)? {
+            let id = far_end(&key, self.direction);
+
+            if self.contains(id) || graph.vertex_type(id)?.is_none() {
+                continue;
+            }
+
+            self.parents.insert(id, key);
+            reached.push(id);
+        }
+
+        self.frontier = reached.clone();
+        self.depth += 1;
+        Ok(reached)
+    }
+
+    /// Gets the path between the root and a reached vertex, oriented in the
+    /// direction of the edges.
+    fn path(&self, id: Uuid) -> Vec<EdgeKey> {
+        let mut path = walk_back(&self.parents, id, self.direction);
+
+        if self.direction == EdgeDirection::Outbound {
+            path.reverse();
+        }
+
+        path
+    }
+}
+
+/// An entry in the priority queue of a weighted search, ordered so that
+/// the lowest cost is popped first from a max-heap.
+struct WeightedState {
+    cost: f64,
+    id: Uuid,
+}
+
+impl PartialEq for WeightedState {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for WeightedState {}
+
+impl PartialOrd for WeightedState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for WeightedState {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+fn breadth_first_path<G: Graph>(graph: &G, q: &ShortestPathQuery) -> Result<Option<Vec<EdgeKey>>> {
+    let mut search = Search::new(q.start_id, EdgeDirection::Outbound);
+
+    while search.depth < q.max_depth && !search.frontier.is_empty() {
+        search.expand(graph, q.t.as_ref())?;
+
+        if search.contains(q.end_id) {
+            return Ok(Some(search.path(q.end_id)));
+        }
+    }
+
+    Ok(None)
+}
+
+fn bidirectional_breadth_first_path<G: Graph>(graph: &G, q: &ShortestPathQuery) -> Result<Option<Vec<EdgeKey>>> {
+    let mut forward = Search::new(q.start_id, EdgeDirection::Outbound);
+    let mut backward = Search::new(q.end_id, EdgeDirection::Inbound);
+
+    while forward.depth + backward.depth < q.max_depth && !forward.frontier.is_empty() && !backward.frontier.is_empty()
+    {
+        // Expand whichever side has the smaller frontier
+        let (expanded, other) = if forward.frontier.len() <= backward.frontier.len() {
+            (&mut forward, &backward)
+        } else {
+            (&mut backward, &forward)
+        };
+
+        let meeting_ids: Vec<Uuid> = expanded
+            .expand(graph, q.t.as_ref())?
+            .into_iter()
+            .filter(|id| other.contains(*id))
+            .collect();
+
+        // The searches may meet at several vertices, at different depths of
+        // the other side, so pick the shortest combined path
+        let path = meeting_ids
+            .into_iter()
+            .map(|id| {
+                let mut path = forward.path(id);
+                path.extend(backward.path(id));
+                path
+            })
+            .min_by_key(|path| path.len());
+
+        if path.is_some() {
+            return Ok(path);
+        }
+    }
+
+    Ok(None)
+}
+
+fn weighted_path<G: Graph>(graph: &G, q: &ShortestPathQuery, name: &str) -> Result<Option<Vec<EdgeKey>>> {
+    let mut costs: HashMap<Uuid, f64> = HashMap::new();
+    let mut parents: HashMap<Uuid, EdgeKey> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    costs.insert(q.start_id, 0.0);
+    queue.push(WeightedState {
+        cost: 0.0,
+        id: q.start_id,
+    });
+
+    while let Some(WeightedState { cost, id }) = queue.pop() {
+        if id == q.end_id {
+            let mut path = walk_back(&parents, id, EdgeDirection::Outbound);
+            path.reverse();
+            return Ok(Some(path));
+        }
+
+        // Skip stale queue entries for vertices that were since reached
+        // more cheaply
+        if costs.get(&id).is_some_and(|best| cost > *best) {
+            continue;
+        }
+
+        for key in graph.adjacent_edges(&[id], EdgeDirection::Outbound, q.t.as_ref())? {
+            let weight = match graph.edge_property(&key, name)? {
+                Some(JsonValue::Number(weight)) => match weight.as_f64() {
+                    Some(weight) if weight >= 0.0 => weight,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+
+            let next_id = key.inbound_id;
+            let next_cost = cost + weight;
+
+            if costs.get(&next_id).is_some_and(|best| next_cost >= *best) {
+                continue;
+            }
+
+            if graph.vertex_type(next_id)?.is_none() {
+                continue;
+            }
+
+            costs.insert(next_id, next_cost);
+            parents.insert(next_id, key);
+            queue.push(WeightedState {
+                cost: next_cost,
+                id: next_id,
+            });
+        }
+    }
+
+    Ok(None)
+}
+
+/// Finds the shortest path between two vertices.
+///
+/// # Arguments
+/// * `graph` - The graph to search.
+/// * `q` - The shortest path query.
+pub fn shortest_path<G: Graph>(graph: &G, q: &ShortestPathQuery) -> Result<Option<Vec<EdgeKey>>> {
+    if graph.vertex_type(q.start_id)?.is_none() || graph.vertex_type(q.end_id)?.is_none() {
+        return Ok(None);
+    }
+
+    if q.start_id == q.end_id {
+        return Ok(Some(Vec::new()));
+    }
+
+    match q.algorithm {
+        ShortestPathAlgorithm::BreadthFirst => breadth_first_path(graph, q),
+        ShortestPathAlgorithm::BidirectionalBreadthFirst => bidirectional_breadth_first_path(graph, q),
+        ShortestPathAlgorithm::Weighted(ref name) => weighted_path(graph, q, name),
+    }
+}
@@ -1,10 +1,12 @@
 //! Utility functions.
 
-use crate::errors::{ValidationError, ValidationResult};
+use crate::errors::{Error, Result, ValidationError, ValidationResult};
+use crate::models::RangeVertexQuery;
 use chrono::offset::Utc;
 use chrono::DateTime;
 use rand::prelude::*;
 use rand::rngs::OsRng;
+use regex::Regex;
 use std::env;
 use uuid::v1::{Context, Timestamp};
 use uuid::Uuid;
@@ -14,6 +16,7 @@ const NODE_ID: [u8; 6] = [0, 0, 0, 0, 0, 0];
 
 lazy_static! {
     static ref CONTEXT: Context = Context::new(0);
+    static ref GRAPH_NAME_VALIDATOR: Regex = Regex::new(r"^[a-zA-Z0-9_-]{1,64}$").unwrap();
 }
 
 /// Gets the path to a file or directory within the temporary directory, in a
@@ -51,6 +54,23 @@ pub fn generate_random_secret(count: usize) -> String {
     String::from_utf8(chars).unwrap()
 }
 
+/// Checks that a graph name is valid. Names must be 1-64 characters long,
+/// and may only contain ASCII letters, digits, `_` and `-`, so that they can
+/// be safely used in file paths.
+///
+/// # Arguments
+/// * `name` - The graph name.
+///
+/// # Errors
+/// Returns `Error::InvalidGraphName` if the name is not valid.
+pub fn validate_graph_name(name: &str) -> Result<()> {
+    if GRAPH_NAME_VALIDATOR.is_match(name) {
+        Ok(())
+    } else {
+        Err(Error::InvalidGraphName)
+    }
+}
+
 /// Gets the next UUID that would occur after the given one.
 ///
 /// # Arguments
@@ -75,6 +95,45 @@ pub fn next_uuid(uuid: Uuid) -> ValidationResult<Uuid> {
     Err(ValidationError::CannotIncrementUuid)
 }
 
+/// Reads every vertex a page at a time through range queries, so that memory
+/// usage stays bounded regardless of the size of the graph.
+/// `RangeVertexQuery::start_id` is inclusive, so each page starts right after
+/// the last vertex of the previous one.
+///
+/// # Arguments
+/// * `page_size` - The number of vertices to read per page.
+/// * `get_page` - Runs a range query, e.g. through `Transaction::get_vertices`.
+/// * `vertex_id` - Gets the ID of the vertex that an item is for.
+/// * `f` - Called with every item, in vertex ID order.
+pub(crate) fn for_each_vertex_page<T, G, I, F>(page_size: u32, mut get_page: G, vertex_id: I, mut f: F) -> Result<()>
+where
+    G: FnMut(RangeVertexQuery) -> Result<Vec<T>>,
+    I: Fn(&T) -> Uuid,
+    F: FnMut(T) -> Result<()>,
+{
+    let mut q = RangeVertexQuery::new(page_size);
+
+    loop {
+        let page = get_page(q)?;
+
+        // A short page is the last one, as is one that ends with the maximum
+        // possible ID
+        let next_start_id = match page.last() {
+            Some(last) if page.len() == page_size as usize => next_uuid(vertex_id(last)).ok(),
+            _ => None,
+        };
+
+        for item in page {
+            f(item)?;
+        }
+
+        match next_start_id {
+            Some(start_id) => q = RangeVertexQuery::new(page_size).start_id(start_id),
+            None => return Ok(()),
+        }
+    }
+}
+
 /// Gets the number of nanoseconds since unix epoch for a given datetime.
 ///
 /// # Arguments